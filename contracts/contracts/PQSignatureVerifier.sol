@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+import "../interfaces/IPQVerifier.sol";
+
+/**
+ * @title PQSignatureVerifier
+ * @dev Accepts only calls carrying a valid Dilithium5 signature, the check a
+ *      quantum-safe wallet or bridge makes before acting on a signed message.
+ *      The node's end-to-end test deploys this contract's logic.
+ */
+contract PQSignatureVerifier {
+    /**
+     * @dev Revert unless `signature` is a valid Dilithium5 signature of `message`
+     * @param message Signed bytes
+     * @param signature Dilithium5 signature
+     * @param publicKey Dilithium5 public key of the signer
+     * @return Always true
+     */
+    function verify(bytes calldata message, bytes calldata signature, bytes calldata publicKey)
+        external
+        view
+        returns (bool)
+    {
+        require(PQVerifiers.verifyDilithium(message, signature, publicKey), "invalid signature");
+        return true;
+    }
+}
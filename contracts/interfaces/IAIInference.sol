@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+/**
+ * @title IAIInference
+ * @notice Interface of the node's AI inference precompiles
+ * @dev Numbers are 18-decimal fixed point (1e18 is one); products round toward
+ *      zero and overflow reverts. Each model has its own precompile (see
+ *      AIPrecompiles) and implements only its own function.
+ */
+interface IAIInference {
+    /**
+     * @notice ReLU network forward pass
+     * @param weights weights[layer][neuron][input]; every neuron has one weight per
+     *        input of its layer. At most 16 layers of at most 256 neurons and inputs
+     * @param input Input layer
+     * @return output Last layer's activations
+     */
+    function neuralNetworkInference(int256[][][] calldata weights, int256[] calldata input)
+        external
+        view
+        returns (int256[] memory output);
+
+    /// @notice slope * x + intercept
+    function linearRegression(int256 slope, int256 intercept, int256 x)
+        external
+        view
+        returns (int256 prediction);
+
+    /**
+     * @notice sigmoid(weights · features), at most 256 features
+     * @return probability Between 0 and 1e18
+     */
+    function logisticRegression(int256[] calldata weights, int256[] calldata features)
+        external
+        view
+        returns (int256 probability);
+}
+
+/**
+ * @title AIPrecompiles
+ * @notice Precompile addresses of the AI models
+ */
+library AIPrecompiles {
+    /// @notice Gas: 100,000 + 50,000 per layer (input layer included) + 20 per multiply
+    IAIInference internal constant NEURAL_NETWORK = IAIInference(0x0000000000000000000000000000000000001100);
+    /// @notice Gas: 10,000
+    IAIInference internal constant LINEAR_REGRESSION = IAIInference(0x0000000000000000000000000000000000001101);
+    /// @notice Gas: 20,000
+    IAIInference internal constant LOGISTIC_REGRESSION = IAIInference(0x0000000000000000000000000000000000001102);
+}
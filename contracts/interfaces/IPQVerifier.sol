@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+/**
+ * @title IPQVerifier
+ * @notice Interface of the node's post-quantum signature verification precompiles
+ * @dev Each scheme has its own precompile (see PQVerifiers). Gas is the scheme's
+ *      verification cost from the node's gas config plus 12 per 32-byte message word.
+ */
+interface IPQVerifier {
+    /**
+     * @notice Check a signature of `message`
+     * @param message Signed bytes, exactly as signed (the schemes hash it themselves)
+     * @param signature Dilithium5 or SPHINCS+ signature; for hybrids r | s | v (65 bytes)
+     *        followed by the Dilithium5 or SPHINCS+ signature
+     * @param publicKey Dilithium5 or SPHINCS+ public key; for hybrids the compressed
+     *        secp256k1 key (33 bytes) followed by the post-quantum key
+     * @return valid True if the signature is valid; malformed signatures and keys are false
+     */
+    function verify(bytes calldata message, bytes calldata signature, bytes calldata publicKey)
+        external
+        view
+        returns (bool valid);
+}
+
+/**
+ * @title PQVerifiers
+ * @notice Precompile addresses and helpers for post-quantum signature checks
+ */
+library PQVerifiers {
+    /// @notice Dilithium5
+    IPQVerifier internal constant DILITHIUM5 = IPQVerifier(0x0000000000000000000000000000000000000200);
+    /// @notice SPHINCS+-SHA2-256f-simple
+    IPQVerifier internal constant SPHINCS_PLUS = IPQVerifier(0x0000000000000000000000000000000000000201);
+    /// @notice ECDSA (secp256k1, SHA-256 message hash) plus Dilithium5 or SPHINCS+
+    IPQVerifier internal constant HYBRID = IPQVerifier(0x0000000000000000000000000000000000000202);
+
+    function verifyDilithium(bytes memory message, bytes memory signature, bytes memory publicKey)
+        internal
+        view
+        returns (bool)
+    {
+        return DILITHIUM5.verify(message, signature, publicKey);
+    }
+
+    function verifySphincs(bytes memory message, bytes memory signature, bytes memory publicKey)
+        internal
+        view
+        returns (bool)
+    {
+        return SPHINCS_PLUS.verify(message, signature, publicKey);
+    }
+
+    function verifyHybrid(bytes memory message, bytes memory signature, bytes memory publicKey)
+        internal
+        view
+        returns (bool)
+    {
+        return HYBRID.verify(message, signature, publicKey);
+    }
+}
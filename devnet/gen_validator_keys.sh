@@ -0,0 +1,23 @@
+#!/bin/bash
+# Generate devnet validator keys and the shared validator set
+# Run with: bash gen_validator_keys.sh [count]
+
+set -e
+
+COUNT=${1:-3}
+DEVNET_DIR="$(cd "$(dirname "$0")" && pwd)"
+OUT_DIR="$DEVNET_DIR/validators"
+
+mkdir -p "$OUT_DIR"
+rm -f "$OUT_DIR"/validator-*.json "$OUT_DIR/validators.json"
+
+cd "$DEVNET_DIR/../node"
+cargo build --release --bin ionova_node
+
+entries=()
+for i in $(seq 0 $((COUNT - 1))); do
+    entries+=("$(./target/release/ionova_node keygen --out "$OUT_DIR/validator-$i.json" --name "validator-$i")")
+done
+
+(IFS=,; echo "[${entries[*]}]") > "$OUT_DIR/validators.json"
+echo "Wrote $COUNT validator keys and validators.json to $OUT_DIR"
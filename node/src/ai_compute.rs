@@ -1,117 +1,181 @@
+use alloy_primitives::{Address, Bytes, I256};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use revm::precompile::PrecompileWithAddress;
+use revm::primitives::{Precompile, PrecompileError, PrecompileOutput, PrecompileResult};
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+
+use crate::evm_executor::precompile_address;
+
+/// One in the models' fixed-point numbers: every weight, input and output
+/// is an `int256` with 18 decimals, so results are the same on every CPU.
+/// Products are rounded toward zero.
+pub const FIXED_ONE: i128 = 1_000_000_000_000_000_000;
+
+/// Input size limits
+pub const MAX_LAYERS: usize = 16;
+pub const MAX_LAYER_WIDTH: usize = 256;
+pub const MAX_FEATURES: usize = 256;
+
+/// ln 2 in fixed point
+const LN_2: i128 = 693_147_180_559_945_309;
+/// Sigmoid inputs are clamped to ±64, where it is 0 or 1 in fixed point
+const SIGMOID_CLAMP: i128 = 64 * FIXED_ONE;
+
+sol! {
+    /// ReLU network: `weights[layer][neuron][input]`
+    function neuralNetworkInference(int256[][][] weights, int256[] input) external view returns (int256[] output);
+    /// `slope * x + intercept`
+    function linearRegression(int256 slope, int256 intercept, int256 x) external view returns (int256 prediction);
+    /// `sigmoid(weights · features)`
+    function logisticRegression(int256[] weights, int256[] features) external view returns (int256 probability);
+}
 
 /// AI computation precompiles for Ionova
 /// Enables on-chain AI inference with quantum-safe verification
 pub struct AIPrecompiles;
 
 impl AIPrecompiles {
-    /// Precompile addresses (starting at 0x1000)
-    pub const NEURAL_NETWORK_INFERENCE: &'static str = "0x1000";
-    pub const LINEAR_REGRESSION: &'static str = "0x1001";
-    pub const LOGISTIC_REGRESSION: &'static str = "0x1002";
-    pub const DECISION_TREE: &'static str = "0x1003";
-    pub const K_NEAREST_NEIGHBORS: &'static str = "0x1004";
-    pub const SUPPORT_VECTOR_MACHINE: &'static str = "0x1005";
-    pub const GRADIENT_BOOSTING: &'static str = "0x1006";
-    pub const RANDOM_FOREST: &'static str = "0x1007";
-
-    /// Execute AI precompile
-    pub fn execute(address: &str, input: Vec<u8>) -> Result<Vec<u8>> {
-        match address {
-            Self::NEURAL_NETWORK_INFERENCE => {
-                Self::neural_network_inference(input)
-            }
-            Self::LINEAR_REGRESSION => {
-                Self::linear_regression(input)
-            }
-            Self::LOGISTIC_REGRESSION => {
-                Self::logistic_regression(input)
-            }
-            _ => Err(anyhow::anyhow!("Unknown AI precompile")),
-        }
+    /// Precompile addresses (starting at 0x1100; 0x1000-0x1002 are the
+    /// cross-shard router, validator fee pool and treasury)
+    pub const NEURAL_NETWORK_INFERENCE: Address = precompile_address(0x1100);
+    pub const LINEAR_REGRESSION: Address = precompile_address(0x1101);
+    pub const LOGISTIC_REGRESSION: Address = precompile_address(0x1102);
+
+    /// The implemented models at their addresses
+    pub fn precompiles() -> Vec<PrecompileWithAddress> {
+        vec![
+            PrecompileWithAddress(Self::NEURAL_NETWORK_INFERENCE, Precompile::Standard(Self::neural_network_inference)),
+            PrecompileWithAddress(Self::LINEAR_REGRESSION, Precompile::Standard(Self::linear_regression)),
+            PrecompileWithAddress(Self::LOGISTIC_REGRESSION, Precompile::Standard(Self::logistic_regression)),
+        ]
     }
 
-    /// Neural network inference (optimized)
-    fn neural_network_inference(input: Vec<u8>) -> Result<Vec<u8>> {
-        // Parse input: model weights + input data
-        let params: NeuralNetworkParams = bincode::deserialize(&input)?;
-        
-        // Run inference
-        let output = Self::forward_pass(&params.weights, &params.input_data);
-        
-        // Serialize output
-        Ok(bincode::serialize(&output)?)
+    /// Neural network inference, priced by `AIGasPricing::calculate_nn_gas`
+    /// over the layer widths (input layer included)
+    fn neural_network_inference(input: &Bytes, gas_limit: u64) -> PrecompileResult {
+        let call = neuralNetworkInferenceCall::abi_decode(input, true).map_err(invalid)?;
+        let shape = nn_shape(&call.weights, &call.input).map_err(invalid)?;
+        charge(AIGasPricing::calculate_nn_gas(&shape), gas_limit, || {
+            let output = Self::forward_pass(&call.weights, &call.input)?;
+            Ok(neuralNetworkInferenceCall::abi_encode_returns(&(output,)))
+        })
     }
 
-    /// Forward pass through neural network
-    fn forward_pass(weights: &[Vec<Vec<f32>>], input: &[f32]) -> Vec<f32> {
+    /// Forward pass through neural network. `weights` must match the input
+    /// as `nn_shape` checks.
+    fn forward_pass(weights: &[Vec<Vec<I256>>], input: &[I256]) -> Result<Vec<I256>> {
         let mut activation = input.to_vec();
-        
+
         // For each layer
         for layer_weights in weights {
-            let mut next_activation = vec![0.0; layer_weights.len()];
-            
-            // Matrix multiplication
-            for (i, neuron_weights) in layer_weights.iter().enumerate() {
-                let mut sum = 0.0;
-                for (j, &weight) in neuron_weights.iter().enumerate() {
-                    sum += weight * activation.get(j).unwrap_or(&0.0);
-                }
+            activation = layer_weights
+                .iter()
                 // ReLU activation
-                next_activation[i] = sum.max(0.0);
-            }
-            
-            activation = next_activation;
+                .map(|neuron_weights| Ok(dot(neuron_weights, &activation)?.max(I256::ZERO)))
+                .collect::<Result<_>>()?;
         }
-        
-        activation
+
+        Ok(activation)
     }
 
     /// Linear regression
-    fn linear_regression(input: Vec<u8>) -> Result<Vec<u8>> {
-        let params: LinearRegressionParams = bincode::deserialize(&input)?;
-        
-        // y = mx + b
-        let prediction = params.slope * params.x + params.intercept;
-        
-        Ok(bincode::serialize(&prediction)?)
+    fn linear_regression(input: &Bytes, gas_limit: u64) -> PrecompileResult {
+        let call = linearRegressionCall::abi_decode(input, true).map_err(invalid)?;
+        charge(AIGasPricing::LINEAR_REGRESSION, gas_limit, || {
+            // y = mx + b
+            let prediction = dot(&[call.slope], &[call.x])?
+                .checked_add(call.intercept)
+                .ok_or_else(overflow)?;
+            Ok(linearRegressionCall::abi_encode_returns(&(prediction,)))
+        })
     }
 
     /// Logistic regression (binary classification)
-    fn logistic_regression(input: Vec<u8>) -> Result<Vec<u8>> {
-        let params: LogisticRegressionParams = bincode::deserialize(&input)?;
-        
-        // Sigmoid function
-        let z: f32 = params.weights.iter()
-            .zip(params.features.iter())
-            .map(|(w, x)| w * x)
-            .sum();
-        
-        let probability = 1.0 / (1.0 + (-z).exp());
-        
-        Ok(bincode::serialize(&probability)?)
+    fn logistic_regression(input: &Bytes, gas_limit: u64) -> PrecompileResult {
+        let call = logisticRegressionCall::abi_decode(input, true).map_err(invalid)?;
+        if call.weights.len() != call.features.len() {
+            return Err(invalid("weights and features differ in length").into());
+        }
+        if call.features.len() > MAX_FEATURES {
+            return Err(invalid(format!("more than {} features", MAX_FEATURES)).into());
+        }
+        charge(AIGasPricing::LOGISTIC_REGRESSION, gas_limit, || {
+            let probability = sigmoid(dot(&call.weights, &call.features)?);
+            Ok(logisticRegressionCall::abi_encode_returns(&(probability,)))
+        })
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct NeuralNetworkParams {
-    weights: Vec<Vec<Vec<f32>>>,
-    input_data: Vec<f32>,
+/// Layer widths of a network, input layer first, checking that every
+/// neuron has one weight per input and the size limits
+fn nn_shape(weights: &[Vec<Vec<I256>>], input: &[I256]) -> Result<Vec<usize>> {
+    if weights.len() > MAX_LAYERS {
+        return Err(anyhow!("more than {} layers", MAX_LAYERS));
+    }
+    let mut shape = vec![input.len()];
+    for (layer, layer_weights) in weights.iter().enumerate() {
+        let inputs = shape[shape.len() - 1];
+        if let Some(neuron) = layer_weights.iter().position(|neuron| neuron.len() != inputs) {
+            return Err(anyhow!("neuron {} of layer {} does not have {} weights", neuron, layer, inputs));
+        }
+        shape.push(layer_weights.len());
+    }
+    if shape.iter().any(|&width| width > MAX_LAYER_WIDTH) {
+        return Err(anyhow!("layer wider than {}", MAX_LAYER_WIDTH));
+    }
+    Ok(shape)
 }
 
-#[derive(Serialize, Deserialize)]
-struct LinearRegressionParams {
-    slope: f32,
-    intercept: f32,
-    x: f32,
+/// Fixed-point dot product, rounded once at the end
+fn dot(weights: &[I256], values: &[I256]) -> Result<I256> {
+    let sum = weights.iter().zip(values).try_fold(I256::ZERO, |sum, (w, x)| {
+        w.checked_mul(*x).and_then(|product| sum.checked_add(product))
+    });
+    Ok(sum.ok_or_else(overflow)? / I256::try_from(FIXED_ONE).unwrap())
 }
 
-#[derive(Serialize, Deserialize)]
-struct LogisticRegressionParams {
-    weights: Vec<f32>,
-    features: Vec<f32>,
+/// Fixed-point logistic function, `1 / (1 + e^-z)`
+fn sigmoid(z: I256) -> I256 {
+    let clamped = z.clamp(-I256::try_from(SIGMOID_CLAMP).unwrap(), I256::try_from(SIGMOID_CLAMP).unwrap());
+    let magnitude = i128::try_from(clamped).unwrap().abs();
+    let e = exp_negative(magnitude);
+    let p = FIXED_ONE * FIXED_ONE / (FIXED_ONE + e);
+    I256::try_from(if z.is_negative() { FIXED_ONE - p } else { p }).unwrap()
+}
+
+/// `e^-x` in fixed point for `0 <= x <= SIGMOID_CLAMP`: `e^-x = 2^-k * e^r`
+/// with `r` in `[0, ln 2)` summed as a Taylor series
+fn exp_negative(x: i128) -> i128 {
+    let k = (x + LN_2 - 1) / LN_2;
+    let r = k * LN_2 - x;
+    let (mut sum, mut term) = (FIXED_ONE, FIXED_ONE);
+    for n in 1.. {
+        term = term * r / (n * FIXED_ONE);
+        if term == 0 {
+            break;
+        }
+        sum += term;
+    }
+    sum >> k
+}
+
+/// Charge `gas`, then run the model
+fn charge(gas: u64, gas_limit: u64, run: impl FnOnce() -> Result<Vec<u8>>) -> PrecompileResult {
+    if gas > gas_limit {
+        return Err(PrecompileError::OutOfGas.into());
+    }
+    let output = run().map_err(invalid)?;
+    Ok(PrecompileOutput::new(gas, output.into()))
+}
+
+fn invalid(error: impl std::fmt::Display) -> PrecompileError {
+    PrecompileError::other(format!("Invalid AI precompile input: {}", error))
+}
+
+fn overflow() -> anyhow::Error {
+    anyhow!("fixed-point overflow")
 }
 
 /// AI model storage and retrieval
@@ -124,7 +188,7 @@ pub struct AIModelRegistry {
 pub struct AIModel {
     pub model_hash: String,
     pub model_type: AIModelType,
-    pub weights: Vec<u8>,
+    pub weights: Vec<u8>, // ABI-encoded, fixed point (see FIXED_ONE)
     pub metadata: ModelMetadata,
     pub storage_location: StorageLocation,
 }
@@ -182,18 +246,19 @@ impl AIModelRegistry {
     pub async fn run_inference(
         &self,
         model_hash: &str,
-        input_data: Vec<f32>,
-    ) -> Result<Vec<f32>> {
+        input_data: Vec<I256>,
+    ) -> Result<Vec<I256>> {
         let model = self.get_model(model_hash)
             .ok_or_else(|| anyhow::anyhow!("Model not found"))?;
 
         match &model.model_type {
             AIModelType::NeuralNetwork { .. } => {
-                // Deserialize weights
-                let weights: Vec<Vec<Vec<f32>>> = bincode::deserialize(&model.weights)?;
-                
+                // Weights are an ABI-encoded int256[][][], as the precompile takes them
+                let weights = Vec::<Vec<Vec<I256>>>::abi_decode(&model.weights, true)?;
+                nn_shape(&weights, &input_data)?;
+
                 // Run inference
-                Ok(AIPrecompiles::forward_pass(&weights, &input_data))
+                AIPrecompiles::forward_pass(&weights, &input_data)
             }
             _ => Err(anyhow::anyhow!("Model type not yet supported")),
         }
@@ -318,7 +383,7 @@ impl AIGasPricing {
     /// Gas costs for AI operations
     pub const NEURAL_NETWORK_BASE: u64 = 100_000;
     pub const NEURAL_NETWORK_PER_LAYER: u64 = 50_000;
-    pub const NEURAL_NETWORK_PER_NEURON: u64 = 1_000;
+    pub const NEURAL_NETWORK_PER_MULTIPLY: u64 = 20;
     
     pub const LINEAR_REGRESSION: u64 = 10_000;
     pub const LOGISTIC_REGRESSION: u64 = 20_000;
@@ -326,13 +391,14 @@ impl AIGasPricing {
     pub const MODEL_STORAGE_PER_KB: u64 = 5_000;
     pub const MODEL_RETRIEVAL: u64 = 2_000;
 
-    /// Calculate gas for neural network inference
+    /// Calculate gas for neural network inference over its layer widths,
+    /// input layer first: each layer's neurons multiply every input
     pub fn calculate_nn_gas(layers: &[usize]) -> u64 {
         let mut gas = Self::NEURAL_NETWORK_BASE;
         gas += Self::NEURAL_NETWORK_PER_LAYER * layers.len() as u64;
         
-        for &neurons in layers {
-            gas += Self::NEURAL_NETWORK_PER_NEURON * neurons as u64;
+        for pair in layers.windows(2) {
+            gas += Self::NEURAL_NETWORK_PER_MULTIPLY * (pair[0] * pair[1]) as u64;
         }
         
         gas
@@ -348,18 +414,72 @@ impl AIGasPricing {
 mod tests {
     use super::*;
 
+    /// `thousandths / 1000` in fixed point
+    fn fixed(thousandths: i64) -> I256 {
+        I256::try_from(thousandths as i128 * FIXED_ONE / 1000).unwrap()
+    }
+
+    fn run(address: Address, input: Vec<u8>, gas_limit: u64) -> PrecompileResult {
+        let precompile = AIPrecompiles::precompiles().into_iter().find(|p| p.0 == address).unwrap();
+        precompile.1.call_ref(&input.into(), gas_limit, &Default::default())
+    }
+
     #[test]
     fn test_neural_network_inference() {
         // Simple 2-layer network
         let weights = vec![
-            vec![vec![0.5, 0.3], vec![0.2, 0.7]],  // Layer 1
-            vec![vec![0.6, 0.4]],                    // Layer 2
+            vec![vec![fixed(500), fixed(300)], vec![fixed(200), fixed(700)]],  // Layer 1
+            vec![vec![fixed(600), fixed(-400)]],                               // Layer 2
         ];
-        
-        let input = vec![1.0, 2.0];
-        let output = AIPrecompiles::forward_pass(&weights, &input);
-        
-        assert!(!output.is_empty());
+        let input = vec![fixed(1_000), fixed(2_000)];
+        assert_eq!(AIPrecompiles::forward_pass(&weights, &input).unwrap(), vec![fixed(20)]);
+
+        let call = neuralNetworkInferenceCall { weights: weights.clone(), input: input.clone() }.abi_encode();
+        let gas = AIGasPricing::calculate_nn_gas(&[2, 2, 1]);
+        assert_eq!(gas, 100_000 + 3 * 50_000 + (2 * 2 + 2) * 20);
+        let output = run(AIPrecompiles::NEURAL_NETWORK_INFERENCE, call.clone(), gas).unwrap();
+        assert_eq!(output.gas_used, gas);
+        let returned = neuralNetworkInferenceCall::abi_decode_returns(&output.bytes, true).unwrap();
+        assert_eq!(returned.output, vec![fixed(20)]);
+        assert!(run(AIPrecompiles::NEURAL_NETWORK_INFERENCE, call, gas - 1).unwrap_err().to_string().contains("out of gas"));
+
+        // A neuron missing a weight, or a network over the size limits
+        let mut ragged = weights.clone();
+        ragged[1][0].pop();
+        let call = neuralNetworkInferenceCall { weights: ragged, input: input.clone() }.abi_encode();
+        assert!(run(AIPrecompiles::NEURAL_NETWORK_INFERENCE, call, u64::MAX).is_err());
+        let deep = vec![vec![vec![fixed(1_000)]]; MAX_LAYERS + 1];
+        assert!(nn_shape(&deep, &[fixed(1_000)]).is_err());
+        assert!(nn_shape(&[], &vec![fixed(1_000); MAX_LAYER_WIDTH + 1]).is_err());
+    }
+
+    #[test]
+    fn test_regressions() {
+        let call = linearRegressionCall { slope: fixed(1_500), intercept: fixed(-250), x: fixed(3_000) }.abi_encode();
+        let output = run(AIPrecompiles::LINEAR_REGRESSION, call, u64::MAX).unwrap();
+        assert_eq!(output.gas_used, AIGasPricing::LINEAR_REGRESSION);
+        assert_eq!(linearRegressionCall::abi_decode_returns(&output.bytes, true).unwrap().prediction, fixed(4_250));
+
+        let probability = |weights: Vec<I256>, features: Vec<I256>| {
+            let call = logisticRegressionCall { weights, features }.abi_encode();
+            let output = run(AIPrecompiles::LOGISTIC_REGRESSION, call, u64::MAX)?;
+            assert_eq!(output.gas_used, AIGasPricing::LOGISTIC_REGRESSION);
+            Ok::<_, revm::primitives::PrecompileErrors>(
+                logisticRegressionCall::abi_decode_returns(&output.bytes, true).unwrap().probability,
+            )
+        };
+        assert_eq!(probability(vec![fixed(1_000)], vec![fixed(0)]).unwrap(), fixed(500));
+        // sigmoid(1) = 0.731058578630004879...
+        let p = probability(vec![fixed(500), fixed(250)], vec![fixed(1_000), fixed(2_000)]).unwrap();
+        assert!((p - I256::try_from(731_058_578_630_004_879i128).unwrap()).abs() < I256::try_from(1_000).unwrap());
+        let q = probability(vec![fixed(-500), fixed(-250)], vec![fixed(1_000), fixed(2_000)]).unwrap();
+        assert_eq!(p + q, fixed(1_000));
+        // Saturates instead of overflowing
+        assert_eq!(probability(vec![fixed(1_000_000)], vec![fixed(1_000_000)]).unwrap(), fixed(1_000));
+        assert_eq!(probability(vec![fixed(-1_000_000)], vec![fixed(1_000_000)]).unwrap(), I256::ZERO);
+
+        assert!(probability(vec![fixed(1_000)], vec![]).is_err());
+        assert!(probability(vec![I256::MAX], vec![I256::MAX]).is_err());
     }
 
     #[test]
@@ -1,13 +1,16 @@
 use ethers::{
     contract::abigen,
-    core::types::{Address, U256, Signature},
+    core::types::{Address, U256},
     providers::{Provider, Http},
     signers::{LocalWallet, Signer},
 };
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use tokio::sync::RwLock;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+
+use crate::compact_qc::CompactQuorumCertificate;
+use crate::epoch::Epoch;
 
 /// Ethereum bridge for Ionova
 /// Native trustless bridge using light clients
@@ -21,21 +24,9 @@ pub struct EthereumBridge {
     /// Bridge contract on Ionova
     ionova_bridge_address: Address,
     
-    /// Validator set for bridge security
-    validators: RwLock<ValidatorSet>,
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-pub struct ValidatorSet {
-    pub validators: Vec<ValidatorInfo>,
-    pub threshold: u64, // Required signatures (2/3+)
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-pub struct ValidatorInfo {
-    pub address: Address,
-    pub pub_key: Vec<u8>,
-    pub stake: U256,
+    /// Current epoch of the consensus validator set; attestations need a
+    /// quorum of its stake
+    epoch: RwLock<Option<Epoch>>,
 }
 
 /// Bridge transfer types
@@ -98,8 +89,8 @@ pub struct BridgeProof {
     /// Transaction receipt
     pub receipt: Vec<u8>,
     
-    /// Validator signatures (threshold)
-    pub signatures: Vec<Signature>,
+    /// Commit QC of the epoch's validators over the base block holding the event
+    pub attestation: Option<CompactQuorumCertificate>,
 }
 
 impl EthereumBridge {
@@ -114,13 +105,23 @@ impl EthereumBridge {
             eth_provider: provider,
             eth_bridge_address: eth_bridge,
             ionova_bridge_address: ionova_bridge,
-            validators: RwLock::new(ValidatorSet {
-                validators: Vec::new(),
-                threshold: 0,
-            }),
+            epoch: RwLock::new(None),
         })
     }
 
+    /// Follow the validator set at each epoch boundary
+    pub async fn set_epoch(&self, epoch: Epoch) {
+        *self.epoch.write().await = Some(epoch);
+    }
+
+    /// Check a proof's attestation against the current epoch; returns the signed stake
+    pub async fn verify_attestation(&self, proof: &BridgeProof) -> Result<u64> {
+        let epoch = self.epoch.read().await;
+        let epoch = epoch.as_ref().ok_or_else(|| anyhow!("No validator set epoch known yet"))?;
+        let attestation = proof.attestation.as_ref().ok_or_else(|| anyhow!("Proof carries no attestation"))?;
+        attestation.verify(&epoch.validators)
+    }
+
     /// Lock tokens on Ethereum, mint on Ionova
     pub async fn bridge_to_ionova(
         &self,
@@ -217,7 +218,7 @@ impl EthereumBridge {
             block_header: vec![],
             merkle_proof: vec![],
             receipt,
-            signatures: vec![],
+            attestation: None,
         })
     }
 
@@ -227,7 +228,7 @@ impl EthereumBridge {
             block_header: vec![],
             merkle_proof: vec![],
             receipt,
-            signatures: vec![],
+            attestation: None,
         })
     }
 }
@@ -282,6 +283,13 @@ impl BridgeManager {
         })
     }
 
+    /// Hand a new epoch's validator set to every bridge
+    pub async fn set_epoch(&self, epoch: Epoch) {
+        self.eth_bridge.set_epoch(epoch.clone()).await;
+        self.bsc_bridge.set_epoch(epoch.clone()).await;
+        self.polygon_bridge.set_epoch(epoch).await;
+    }
+
     /// Bridge tokens from any chain to Ionova
     pub async fn bridge_in(
         &self,
@@ -300,7 +308,7 @@ impl BridgeManager {
             ChainId::Polygon => {
                 self.polygon_bridge.bridge_to_ionova(token, amount, recipient).await
             }
-            _ => Err(anyhow::anyhow!("Unsupported chain")),
+            _ => Err(anyhow!("Unsupported chain")),
         }
     }
 
@@ -322,7 +330,7 @@ impl BridgeManager {
             ChainId::Polygon => {
                 self.polygon_bridge.bridge_to_ethereum(token, amount, recipient).await
             }
-            _ => Err(anyhow::anyhow!("Unsupported chain")),
+            _ => Err(anyhow!("Unsupported chain")),
         }
     }
 
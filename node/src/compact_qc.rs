@@ -0,0 +1,351 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{KeyPair, Signature, SignatureAlgorithm};
+use crate::finality::{
+    vote_payload, QuorumCertificate, ValidatorInfo, ValidatorSet, ValidatorSignature, VotePhase,
+};
+
+/// Signers of a certificate as bits over `ValidatorSet::ordered_ids`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignerBitmap {
+    pub len: u32,
+    pub bits: Vec<u8>,
+}
+
+impl SignerBitmap {
+    pub fn new(len: usize) -> Self {
+        Self { len: len as u32, bits: vec![0; len.div_ceil(8)] }
+    }
+
+    pub fn set(&mut self, index: usize) {
+        self.bits[index / 8] |= 1 << (index % 8);
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        index < self.len as usize && self.bits[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    pub fn count(&self) -> usize {
+        self.indices().count()
+    }
+
+    /// Set bit positions in ascending order
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len as usize).filter(|&index| self.contains(index))
+    }
+
+    fn check(&self, set_len: usize) -> Result<()> {
+        if self.len as usize != set_len || self.bits.len() != set_len.div_ceil(8) {
+            bail!("Signer bitmap covers {} validators, the set has {}", self.len, set_len);
+        }
+        if !set_len.is_multiple_of(8) && self.bits[set_len / 8] >> (set_len % 8) != 0 {
+            bail!("Signer bitmap sets bits past the validator set");
+        }
+        Ok(())
+    }
+}
+
+/// Quorum certificate that names its signers by bitmap against a
+/// committed validator set instead of repeating ids and stakes. Keys and
+/// stakes live once in the set, so a QC carries only signatures.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactQuorumCertificate {
+    pub view: u64,
+    pub block_hash: String,
+    pub phase: VotePhase,
+    /// `ValidatorSet::commitment` of the set the bitmap indexes
+    pub validator_set_hash: String,
+    pub signers: SignerBitmap,
+    /// Signatures in bitmap order
+    pub signatures: Vec<Vec<u8>>,
+    pub aggregated_stake: u64,
+}
+
+impl CompactQuorumCertificate {
+    /// Encode a QC against the set its signers belong to
+    pub fn compress(qc: &QuorumCertificate, set: &ValidatorSet) -> Result<Self> {
+        let ids = set.ordered_ids();
+        let mut by_index = Vec::with_capacity(qc.signatures.len());
+        for signature in &qc.signatures {
+            let index = ids
+                .binary_search(&&signature.validator_id)
+                .map_err(|_| anyhow!("{} is not in the validator set", signature.validator_id))?;
+            by_index.push((index, &signature.signature));
+        }
+        by_index.sort_by_key(|(index, _)| *index);
+
+        let mut signers = SignerBitmap::new(ids.len());
+        for window in by_index.windows(2) {
+            if window[0].0 == window[1].0 {
+                bail!("Duplicate signature by {}", ids[window[0].0]);
+            }
+        }
+        for (index, _) in &by_index {
+            signers.set(*index);
+        }
+
+        Ok(Self {
+            view: qc.view,
+            block_hash: qc.block_hash.clone(),
+            phase: qc.phase,
+            validator_set_hash: hex::encode(set.commitment()),
+            signers,
+            signatures: by_index.into_iter().map(|(_, signature)| signature.clone()).collect(),
+            aggregated_stake: qc.aggregated_stake,
+        })
+    }
+
+    /// Rebuild the full QC the node gossips and stores
+    pub fn expand(&self, set: &ValidatorSet) -> Result<QuorumCertificate> {
+        self.check_set(set)?;
+        if self.signatures.len() != self.signers.count() {
+            bail!("QC carries {} signatures for {} signers", self.signatures.len(), self.signers.count());
+        }
+        let ids = set.ordered_ids();
+        let signatures = self
+            .signers
+            .indices()
+            .zip(&self.signatures)
+            .map(|(index, signature)| ValidatorSignature {
+                validator_id: ids[index].clone(),
+                signature: signature.clone(),
+                stake: set.validators[ids[index]].stake,
+            })
+            .collect();
+        Ok(QuorumCertificate {
+            view: self.view,
+            block_hash: self.block_hash.clone(),
+            phase: self.phase,
+            signatures,
+            aggregated_stake: self.aggregated_stake,
+        })
+    }
+
+    /// Verify every signature against `set`; returns the signed stake
+    pub fn verify(&self, set: &ValidatorSet) -> Result<u64> {
+        let qc = self.expand(set)?;
+        set.check_qc(&qc)?;
+        Ok(signer_stake(&self.signers, set))
+    }
+
+    fn check_set(&self, set: &ValidatorSet) -> Result<()> {
+        if self.validator_set_hash != hex::encode(set.commitment()) {
+            bail!("QC for view {} was made for another validator set", self.view);
+        }
+        self.signers.check(set.validators.len())
+    }
+
+    /// Binary encoding for bridges and light clients
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.view.to_be_bytes());
+        out.push(self.phase as u8);
+        put_bytes(&mut out, &hex::decode(&self.block_hash)?);
+        out.extend_from_slice(&hex::decode(&self.validator_set_hash)?);
+        out.extend_from_slice(&self.signers.len.to_be_bytes());
+        out.extend_from_slice(&self.signers.bits);
+        out.extend_from_slice(&self.aggregated_stake.to_be_bytes());
+        out.extend_from_slice(&(self.signatures.len() as u32).to_be_bytes());
+        for signature in &self.signatures {
+            put_bytes(&mut out, signature);
+        }
+        Ok(out)
+    }
+
+    /// Decode the binary encoding of `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader(bytes);
+        let view = u64::from_be_bytes(reader.take(8)?.try_into()?);
+        let phase = match reader.take(1)?[0] {
+            0 => VotePhase::Prepare,
+            1 => VotePhase::PreCommit,
+            2 => VotePhase::Commit,
+            other => bail!("Unknown vote phase {}", other),
+        };
+        let block_hash = hex::encode(reader.bytes()?);
+        let validator_set_hash = hex::encode(reader.take(32)?);
+        let len = u32::from_be_bytes(reader.take(4)?.try_into()?);
+        let bits = reader.take((len as usize).div_ceil(8))?.to_vec();
+        let aggregated_stake = u64::from_be_bytes(reader.take(8)?.try_into()?);
+        let count = u32::from_be_bytes(reader.take(4)?.try_into()?);
+        let signatures = (0..count).map(|_| reader.bytes().map(<[u8]>::to_vec)).collect::<Result<_>>()?;
+        if !reader.0.is_empty() {
+            bail!("{} trailing bytes after QC", reader.0.len());
+        }
+        Ok(Self {
+            view,
+            block_hash,
+            phase,
+            validator_set_hash,
+            signers: SignerBitmap { len, bits },
+            signatures,
+            aggregated_stake,
+        })
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("QC encoding truncated");
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    /// Length-prefixed bytes
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = u32::from_be_bytes(self.take(4)?.try_into()?);
+        self.take(len as usize)
+    }
+}
+
+/// Active stake of the bitmap's signers
+fn signer_stake(signers: &SignerBitmap, set: &ValidatorSet) -> u64 {
+    let ids = set.ordered_ids();
+    signers
+        .indices()
+        .map(|index| &set.validators[ids[index]])
+        .filter(|info| info.is_active)
+        .map(|info| info.stake)
+        .sum()
+}
+
+/// Encoded sizes of one commit QC signed by a bare quorum
+#[derive(Debug, Clone, Serialize)]
+pub struct QcSizes {
+    pub validators: usize,
+    pub signers: usize,
+    /// `QuorumCertificate` as JSON, as gossiped and stored today
+    pub full_json: usize,
+    /// Compact QC with signatures, binary
+    pub compact: usize,
+}
+
+/// Measure QC sizes for a set of `validators` equal-stake members
+pub fn measure_sizes(validators: usize, algorithm: SignatureAlgorithm) -> Result<QcSizes> {
+    let keys = (0..validators).map(|_| KeyPair::generate(algorithm)).collect::<Vec<_>>();
+    let mut set = ValidatorSet::new();
+    for key in &keys {
+        set.add_validator(
+            key.address().to_string(),
+            ValidatorInfo { public_key: key.public_key.clone(), stake: 100, is_active: true },
+        );
+    }
+
+    let block_hash = hex::encode([0xab; 32]);
+    let payload = vote_payload(VotePhase::Commit, 1, &block_hash);
+    let mut signatures = Vec::new();
+    for key in &keys {
+        if set.has_quorum(signatures.iter().map(|s: &ValidatorSignature| s.stake).sum()) {
+            break;
+        }
+        let signature = match key.sign(&payload)? {
+            Signature::Dilithium { data } | Signature::SPHINCSPlus { data } => data,
+            other => bail!("Validator keys must be post-quantum, got {:?}", other.algorithm()),
+        };
+        signatures.push(ValidatorSignature { validator_id: key.address().to_string(), signature, stake: 100 });
+    }
+    let qc = QuorumCertificate {
+        view: 1,
+        block_hash,
+        phase: VotePhase::Commit,
+        aggregated_stake: signatures.iter().map(|s| s.stake).sum(),
+        signatures,
+    };
+
+    // Measured as a light client receives it: encoded, decoded and verified
+    let compact = CompactQuorumCertificate::compress(&qc, &set)?.to_bytes()?;
+    CompactQuorumCertificate::from_bytes(&compact)?.verify(&set)?;
+
+    Ok(QcSizes {
+        validators,
+        signers: qc.signatures.len(),
+        full_json: serde_json::to_vec(&qc)?.len(),
+        compact: compact.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed_qc(count: usize, signers: usize) -> (ValidatorSet, QuorumCertificate) {
+        let keys: Vec<KeyPair> = (0..count).map(|_| KeyPair::generate(SignatureAlgorithm::Dilithium)).collect();
+        let mut set = ValidatorSet::new();
+        for key in &keys {
+            set.add_validator(
+                key.address().to_string(),
+                ValidatorInfo { public_key: key.public_key.clone(), stake: 100, is_active: true },
+            );
+        }
+        let block_hash = hex::encode([7u8; 32]);
+        let payload = vote_payload(VotePhase::Commit, 3, &block_hash);
+        let signatures: Vec<ValidatorSignature> = keys[..signers]
+            .iter()
+            .map(|key| {
+                let Signature::Dilithium { data } = key.sign(&payload).unwrap() else { unreachable!() };
+                ValidatorSignature { validator_id: key.address().to_string(), signature: data, stake: 100 }
+            })
+            .collect();
+        let qc = QuorumCertificate {
+            view: 3,
+            block_hash,
+            phase: VotePhase::Commit,
+            aggregated_stake: 100 * signers as u64,
+            signatures,
+        };
+        (set, qc)
+    }
+
+    #[test]
+    fn test_compact_qc_round_trip() {
+        let (set, qc) = signed_qc(7, 5);
+        let compact = CompactQuorumCertificate::compress(&qc, &set).unwrap();
+        assert_eq!(compact.signers.count(), 5);
+        assert_eq!(compact.verify(&set).unwrap(), 500);
+
+        let decoded = CompactQuorumCertificate::from_bytes(&compact.to_bytes().unwrap()).unwrap();
+        let expanded = decoded.expand(&set).unwrap();
+        set.check_qc(&expanded).unwrap();
+        assert_eq!(expanded.signatures.len(), 5);
+        assert!(compact.to_bytes().unwrap().len() < serde_json::to_vec(&qc).unwrap().len() / 2);
+    }
+
+    #[test]
+    fn test_compact_qc_rejects_tampering() {
+        let (set, qc) = signed_qc(4, 3);
+        let compact = CompactQuorumCertificate::compress(&qc, &set).unwrap();
+
+        // A bit moved to a validator that did not sign
+        let missing = (0..4).find(|i| !compact.signers.contains(*i)).unwrap();
+        let first = compact.signers.indices().next().unwrap();
+        let mut moved = compact.clone();
+        moved.signers.bits[first / 8] &= !(1 << (first % 8));
+        moved.signers.set(missing);
+        assert!(moved.verify(&set).is_err());
+
+        // Below quorum once a signature is dropped
+        let mut short = qc.clone();
+        short.signatures.pop();
+        assert!(CompactQuorumCertificate::compress(&short, &set).unwrap().verify(&set).is_err());
+
+        // Duplicate signers are refused outright
+        let mut duplicated = qc.clone();
+        duplicated.signatures.push(qc.signatures[0].clone());
+        assert!(CompactQuorumCertificate::compress(&duplicated, &set).is_err());
+
+        // Another set does not match the commitment
+        let (other, _) = signed_qc(4, 0);
+        assert!(compact.verify(&other).is_err());
+    }
+}
@@ -2,37 +2,282 @@
 
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::net::{IpAddr, Ipv4Addr};
 use anyhow::Result;
 
+use crate::cross_shard;
+use crate::epoch::EpochConfig;
+use crate::eth_tx;
+use crate::fee_model::FeeConfig;
+use crate::mempool::MempoolConfig;
+use crate::network_security::NetworkSecurityConfig;
+use crate::sequencer::SequencerConfig;
+use crate::crypto::SignatureAlgorithm;
+use crate::transaction::{Transaction, BASE_CHAIN_ID};
+
 /// Network configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkConfig {
     pub chain_id: u64,
+    /// Interface the RPC server binds to (loopback unless exposed on purpose)
+    #[serde(default = "default_rpc_host")]
+    pub rpc_host: IpAddr,
     pub rpc_port: u16,
     pub metrics_port: u16,
     pub shard_count: u8,
     pub max_block_size: u64,
 }
 
-/// Gas configuration
+/// Intrinsic gas per account of an EIP-2930 access list, as the EVM charges
+pub const ACCESS_LIST_ADDRESS_GAS: u64 = 2_400;
+
+/// Intrinsic gas per storage key of an EIP-2930 access list
+pub const ACCESS_LIST_STORAGE_KEY_GAS: u64 = 1_900;
+
+/// Gas configuration: the intrinsic gas of native transactions and the cost
+/// of the signature precompiles
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GasConfig {
     pub base_transaction: u64,
     pub ecdsa_signature: u64,
     pub dilithium_signature: u64,
     pub sphincs_signature: u64,
-    pub hybrid_signature: u64,
     pub data_per_byte: u64,
+    /// Discount post-quantum signature verification in transactions
     pub subsidy_enabled: bool,
     pub subsidy_rate: f64, // 0.5 = 50% subsidy
 }
 
+impl GasConfig {
+    /// Gas for verifying a transaction signature, on top of the base cost.
+    /// Hybrids pair ECDSA with Dilithium.
+    pub fn signature_verification(&self, algorithm: SignatureAlgorithm) -> u64 {
+        match algorithm {
+            SignatureAlgorithm::ECDSA => self.ecdsa_signature,
+            SignatureAlgorithm::Dilithium => self.subsidized(self.dilithium_signature),
+            SignatureAlgorithm::SPHINCSPlus => self.subsidized(self.sphincs_signature),
+            SignatureAlgorithm::Hybrid => self.ecdsa_signature + self.subsidized(self.dilithium_signature),
+        }
+    }
+
+    /// Gas for verifying a transaction's signature. Ethereum envelopes are
+    /// ECDSA-recovered, which the base cost already covers as on Ethereum.
+    pub fn signature_gas(&self, tx: &Transaction) -> u64 {
+        match tx.eth_envelope {
+            Some(_) => 0,
+            None => self.signature_verification(tx.signature.algorithm()),
+        }
+    }
+
+    /// Intrinsic gas of a native transaction: base cost, signature
+    /// verification, calldata and an Ethereum envelope's access list
+    pub fn intrinsic_gas(&self, tx: &Transaction) -> Result<u64> {
+        // SECURITY FIX M-1: Protected data and total gas calculation
+        let data_gas = (tx.data.len() as u64)
+            .checked_mul(self.data_per_byte)
+            .ok_or(anyhow::anyhow!("Data size causes gas overflow"))?;
+        let access_list_gas = match &tx.eth_envelope {
+            Some(envelope) => eth_tx::access_list(envelope)?
+                .iter()
+                .map(|item| ACCESS_LIST_ADDRESS_GAS + ACCESS_LIST_STORAGE_KEY_GAS * item.storage_keys.len() as u64)
+                .sum(),
+            None => 0,
+        };
+        self.base_transaction
+            .checked_add(self.signature_gas(tx))
+            .and_then(|sum| sum.checked_add(data_gas))
+            .and_then(|sum| sum.checked_add(access_list_gas))
+            .ok_or(anyhow::anyhow!("Total gas cost overflow"))
+    }
+
+    /// Post-quantum verification cost after the migration subsidy
+    fn subsidized(&self, gas: u64) -> u64 {
+        if !self.subsidy_enabled {
+            return gas;
+        }
+        (gas as f64 * (1.0 - self.subsidy_rate)).round() as u64
+    }
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            base_transaction: 21_000,
+            ecdsa_signature: 3_000,
+            dilithium_signature: 50_000,
+            sphincs_signature: 70_000,
+            data_per_byte: 16,
+            subsidy_enabled: true,
+            subsidy_rate: 0.5,
+        }
+    }
+}
+
 /// Rate limiting configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RateLimitConfig {
     pub global_requests_per_second: u32,
     pub per_ip_requests_per_second: u32,
     pub max_tracked_ips: usize,
+    /// Largest accepted HTTP body or WebSocket message
+    #[serde(default = "default_max_request_bytes")]
+    pub max_request_bytes: u64,
+    /// Concurrent WebSocket connections per IP
+    #[serde(default = "default_max_connections_per_ip")]
+    pub max_connections_per_ip: usize,
+    /// How long an IP flooding far above its quota stays banned
+    #[serde(default = "default_ban_duration_secs")]
+    pub ban_duration_secs: u64,
+    /// If non-empty, only these IPs may use the RPC
+    #[serde(default)]
+    pub allow_list: Vec<IpAddr>,
+    /// IPs always refused
+    #[serde(default)]
+    pub deny_list: Vec<IpAddr>,
+    /// IPs allowed to call the admin_* methods; none by default
+    #[serde(default)]
+    pub admin_allow_list: Vec<IpAddr>,
+    /// Reverse proxies (nginx, the gateway) trusted to name the client in
+    /// X-Forwarded-For; limits, bans and lists then apply to that client
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            global_requests_per_second: 100,
+            per_ip_requests_per_second: 10,
+            max_tracked_ips: 10_000,
+            max_request_bytes: default_max_request_bytes(),
+            max_connections_per_ip: default_max_connections_per_ip(),
+            ban_duration_secs: default_ban_duration_secs(),
+            allow_list: Vec::new(),
+            deny_list: Vec::new(),
+            admin_allow_list: Vec::new(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Connection and ban policy for the RPC server. Clients bursting to ten
+    /// times their per-IP quota within a second are banned outright.
+    pub fn network_security(&self) -> NetworkSecurityConfig {
+        NetworkSecurityConfig {
+            max_connections_per_ip: self.max_connections_per_ip,
+            max_requests_per_sec: self.per_ip_requests_per_second.saturating_mul(10),
+            ban_duration: self.ban_duration_secs,
+            enable_whitelist: !self.allow_list.is_empty(),
+            enable_blacklist: true,
+            ..NetworkSecurityConfig::default()
+        }
+    }
+}
+
+fn default_rpc_host() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+fn default_max_request_bytes() -> u64 {
+    // Room for a batch of SPHINCS+-signed transactions
+    5 * 1024 * 1024
+}
+
+fn default_max_connections_per_ip() -> usize {
+    10
+}
+
+fn default_ban_duration_secs() -> u64 {
+    3600
+}
+
+/// Sequencer block production, fee and mempool settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SequencerSettings {
+    pub micro_block_interval_ms: u64,
+    pub batch_interval_ms: u64,
+    pub max_batch_size: usize,
+    /// Base blocks a cross-shard transfer stays claimable before it is refunded
+    pub cross_shard_timeout_blocks: u64,
+    /// Micro-blocks of history (and their state) kept; 0 keeps everything
+    pub history_blocks: u64,
+    pub fee: FeeConfig,
+    pub mempool: MempoolConfig,
+}
+
+impl Default for SequencerSettings {
+    fn default() -> Self {
+        Self {
+            micro_block_interval_ms: 200,
+            batch_interval_ms: 1000,
+            max_batch_size: 1000,
+            cross_shard_timeout_blocks: cross_shard::DEFAULT_TIMEOUT_BLOCKS,
+            history_blocks: 100_000,
+            fee: FeeConfig::default(),
+            mempool: MempoolConfig::default(),
+        }
+    }
+}
+
+/// Validator networking and finality settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsensusSettings {
+    /// libp2p listen port for consensus gossip
+    pub p2p_port: u16,
+    /// Multiaddrs dialed at startup (mDNS finds peers on the local network)
+    pub bootstrap_peers: Vec<String>,
+    /// JSON file listing validator public keys and stakes
+    pub validator_set: Option<PathBuf>,
+    /// JSON file listing each shard's sequencer key; validators require it
+    /// and drop batches signed by any other key
+    pub sequencer_set: Option<PathBuf>,
+    /// Timeout of a view; doubled after each view that times out
+    pub view_timeout_ms: u64,
+    /// Cap on the backed-off view timeout
+    pub max_view_timeout_ms: u64,
+    /// Validator set rotation
+    pub epoch: EpochConfig,
+}
+
+impl Default for ConsensusSettings {
+    fn default() -> Self {
+        Self {
+            p2p_port: 26656,
+            bootstrap_peers: Vec::new(),
+            validator_set: None,
+            sequencer_set: None,
+            view_timeout_ms: 1000,
+            max_view_timeout_ms: 30_000,
+            epoch: EpochConfig::default(),
+        }
+    }
+}
+
+/// Shard-aware JSON-RPC gateway settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GatewaySettings {
+    /// Port the gateway listens on (bound to network.rpc_host)
+    pub rpc_port: u16,
+    /// JSON-RPC URL of each shard's sequencer, indexed by shard; when empty,
+    /// every shard is expected on the local host at its default RPC port
+    pub shard_endpoints: Vec<String>,
+    /// Time a sequencer has to answer a forwarded call
+    pub request_timeout_ms: u64,
+}
+
+impl Default for GatewaySettings {
+    fn default() -> Self {
+        Self {
+            rpc_port: 8545,
+            shard_endpoints: Vec::new(),
+            request_timeout_ms: 10_000,
+        }
+    }
 }
 
 /// Complete configuration
@@ -41,54 +286,95 @@ pub struct Config {
     pub network: NetworkConfig,
     pub gas: GasConfig,
     pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub sequencer: SequencerSettings,
+    #[serde(default)]
+    pub consensus: ConsensusSettings,
+    #[serde(default)]
+    pub gateway: GatewaySettings,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             network: NetworkConfig {
-                chain_id: 31337,
+                chain_id: BASE_CHAIN_ID,
+                rpc_host: default_rpc_host(),
                 rpc_port: 27000,
                 metrics_port: 9100,
                 shard_count: 100,
                 max_block_size: 10_000_000,
             },
-            gas: GasConfig {
-                base_transaction: 21_000,
-                ecdsa_signature: 3_000,
-                dilithium_signature: 50_000,
-                sphincs_signature: 70_000,
-                hybrid_signature: 28_000,
-                data_per_byte: 16,
-                subsidy_enabled: true,
-                subsidy_rate: 0.5,
-            },
-            rate_limit: RateLimitConfig {
-                global_requests_per_second: 100,
-                per_ip_requests_per_second: 10,
-                max_tracked_ips: 10_000,
-            },
+            gas: GasConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            sequencer: SequencerSettings::default(),
+            consensus: ConsensusSettings::default(),
+            gateway: GatewaySettings::default(),
         }
     }
 }
 
 impl Config {
     /// Load configuration from file
-    pub fn load_from_file(path: &str) -> Result<Self> {
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self> {
         let content = fs::read_to_string(path)?;
         let config: Config = toml::from_str(&content)?;
         Ok(config)
     }
     
     /// Save configuration to file
-    pub fn save_to_file(&self, path: &str) -> Result<()> {
+    pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
         let content = toml::to_string_pretty(self)?;
         fs::write(path, content)?;
         Ok(())
     }
     
+    /// Sequencer settings for one shard
+    pub fn sequencer_config(&self, shard_id: u8) -> SequencerConfig {
+        SequencerConfig {
+            shard_id,
+            shard_count: self.network.shard_count,
+            base_chain_id: self.network.chain_id,
+            micro_block_interval_ms: self.sequencer.micro_block_interval_ms,
+            batch_interval_ms: self.sequencer.batch_interval_ms,
+            max_batch_size: self.sequencer.max_batch_size,
+            fee_config: self.sequencer.fee.clone(),
+            mempool_config: self.sequencer.mempool.clone(),
+            cross_shard_timeout_blocks: self.sequencer.cross_shard_timeout_blocks,
+            history_blocks: self.sequencer.history_blocks,
+            gas: self.gas.clone(),
+        }
+    }
+
+    /// Chain ID a shard reports through eth_chainId: shard 0 uses
+    /// `network.chain_id`, the others count up from it
+    pub fn chain_id_for(&self, shard_id: u8) -> u64 {
+        self.network.chain_id + shard_id as u64
+    }
+
+    /// Default RPC port of a shard's sequencer: shards are 100 ports apart
+    pub fn rpc_port_for(&self, shard_id: u8) -> Result<u16> {
+        u16::try_from(self.network.rpc_port as u32 + 100 * shard_id as u32)
+            .map_err(|_| anyhow::anyhow!("No default RPC port for shard {}", shard_id))
+    }
+
+    /// Sequencer endpoint of every shard, as the gateway reaches them
+    pub fn gateway_endpoints(&self) -> Result<Vec<String>> {
+        if !self.gateway.shard_endpoints.is_empty() {
+            return Ok(self.gateway.shard_endpoints.clone());
+        }
+        (0..self.network.shard_count)
+            .map(|shard| Ok(format!("http://127.0.0.1:{}", self.rpc_port_for(shard)?)))
+            .collect()
+    }
+
     /// Validate configuration
     pub fn validate(&self) -> Result<()> {
+        // Shard N signs for chain ID chain_id + N, which must not wrap
+        if self.network.chain_id == 0 || self.network.chain_id.checked_add(u8::MAX as u64).is_none() {
+            return Err(anyhow::anyhow!("Unsupported chain ID {}", self.network.chain_id));
+        }
+
         if self.network.shard_count == 0 {
             return Err(anyhow::anyhow!("Shard count must be > 0"));
         }
@@ -100,6 +386,52 @@ impl Config {
         if self.rate_limit.global_requests_per_second == 0 {
             return Err(anyhow::anyhow!("Global rate limit must be > 0"));
         }
+
+        if self.rate_limit.per_ip_requests_per_second == 0 {
+            return Err(anyhow::anyhow!("Per-IP rate limit must be > 0"));
+        }
+
+        if self.sequencer.micro_block_interval_ms == 0 || self.sequencer.batch_interval_ms == 0 {
+            return Err(anyhow::anyhow!("Sequencer intervals must be > 0"));
+        }
+
+        for peer in &self.consensus.bootstrap_peers {
+            peer.parse::<libp2p::Multiaddr>()
+                .map_err(|e| anyhow::anyhow!("Invalid bootstrap peer {}: {}", peer, e))?;
+        }
+
+        if self.sequencer.max_batch_size == 0 {
+            return Err(anyhow::anyhow!("Max batch size must be > 0"));
+        }
+
+        if !self.sequencer.fee.split.is_valid() {
+            return Err(anyhow::anyhow!("Fee split shares must be non-negative and add up to 1"));
+        }
+
+        if self.sequencer.cross_shard_timeout_blocks == 0 {
+            return Err(anyhow::anyhow!("Cross-shard timeout must be > 0"));
+        }
+
+        if self.consensus.view_timeout_ms == 0 || self.consensus.max_view_timeout_ms < self.consensus.view_timeout_ms {
+            return Err(anyhow::anyhow!("View timeout must be > 0 and at most the max view timeout"));
+        }
+
+        if self.consensus.epoch.epoch_length == 0 || self.consensus.epoch.max_validators == 0 {
+            return Err(anyhow::anyhow!("Epoch length and max validators must be > 0"));
+        }
+
+        let endpoints = self.gateway.shard_endpoints.len();
+        if endpoints != 0 && endpoints != self.network.shard_count as usize {
+            return Err(anyhow::anyhow!(
+                "Gateway lists {} shard endpoints for {} shards",
+                endpoints,
+                self.network.shard_count
+            ));
+        }
+
+        if self.gateway.request_timeout_ms == 0 {
+            return Err(anyhow::anyhow!("Gateway request timeout must be > 0"));
+        }
         
         Ok(())
     }
@@ -108,6 +440,8 @@ impl Config {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::{Address, PublicKeyData, Signature};
+    use rust_decimal_macros::dec;
     
     #[test]
     fn test_default_config() {
@@ -117,10 +451,154 @@ mod tests {
         assert_eq!(config.gas.subsidy_rate, 0.5);
     }
     
+    #[test]
+    fn test_gas_cost_ecdsa() {
+        let tx = Transaction {
+            chain_id: 31337,
+            shard_id: 0,
+            nonce: 1,
+            from: Address::EVM([0u8; 20]),
+            to: Some(Address::EVM([1u8; 20])),
+            value: dec!(100),
+            gas_limit: 21_000,
+            gas_price: dec!(0.000001),
+            tip: dec!(0),
+            data: vec![],
+            signature: Signature::ECDSA {
+                r: [0u8; 32],
+                s: [0u8; 32],
+                v: 0,
+            },
+            public_key: PublicKeyData::ECDSA { bytes: [0u8; 33] },
+            expiry: None,
+            eth_envelope: None,
+        };
+        
+        let gas = GasConfig::default();
+        assert_eq!(gas.intrinsic_gas(&tx).unwrap(), 24_000); // 21k base + 3k sig
+
+        // Ethereum envelopes are ECDSA-recovered within the base cost
+        let envelope = Transaction { eth_envelope: Some(vec![]), ..tx.clone() };
+        assert_eq!(gas.intrinsic_gas(&envelope).unwrap(), 21_000);
+
+        // The [gas] settings price every part
+        let gas = GasConfig { base_transaction: 30_000, ecdsa_signature: 5_000, data_per_byte: 4, ..gas };
+        let with_data = Transaction { data: vec![0u8; 10], ..tx };
+        assert_eq!(gas.intrinsic_gas(&with_data).unwrap(), 30_000 + 5_000 + 40);
+    }
+    
+    #[test]
+    fn test_gas_cost_dilithium_subsidized() {
+        let tx = Transaction {
+            chain_id: 31337,
+            shard_id: 0,
+            nonce: 1,
+            from: Address::EVM([0u8; 20]),
+            to: Some(Address::EVM([1u8; 20])),
+            value: dec!(100),
+            gas_limit: 50_000,
+            gas_price: dec!(0.000001),
+            tip: dec!(0),
+            data: vec![],
+            signature: Signature::Dilithium { data: vec![0u8; 2420] },
+            public_key: PublicKeyData::Dilithium { bytes: vec![0u8; 2592] },
+            expiry: None,
+            eth_envelope: None,
+        };
+        
+        // 21k base + 25k subsidized (50k / 2)
+        let gas = GasConfig::default();
+        assert_eq!(gas.intrinsic_gas(&tx).unwrap(), 46_000);
+
+        let unsubsidized = GasConfig { subsidy_enabled: false, ..gas.clone() };
+        assert_eq!(unsubsidized.intrinsic_gas(&tx).unwrap(), 71_000);
+        let quarter = GasConfig { subsidy_rate: 0.25, ..gas };
+        assert_eq!(quarter.intrinsic_gas(&tx).unwrap(), 21_000 + 37_500);
+    }
+    
     #[test]
     fn test_invalid_subsidy_rate() {
         let mut config = Config::default();
         config.gas.subsidy_rate = 1.5; // Invalid
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let config = Config::default();
+        let parsed: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert!(parsed.validate().is_ok());
+        assert_eq!(parsed.sequencer.micro_block_interval_ms, 200);
+        assert_eq!(parsed.sequencer.fee.base_tx_fee, config.sequencer.fee.base_tx_fee);
+    }
+
+    #[test]
+    fn test_partial_file_uses_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            [network]
+            chain_id = 31337
+            rpc_host = "0.0.0.0"
+            rpc_port = 27100
+            metrics_port = 9101
+            shard_count = 8
+            max_block_size = 10000000
+
+            [gas]
+            base_transaction = 21000
+            ecdsa_signature = 3000
+            dilithium_signature = 50000
+            sphincs_signature = 70000
+            data_per_byte = 16
+            subsidy_enabled = true
+            subsidy_rate = 0.5
+
+            [rate_limit]
+            global_requests_per_second = 500
+            per_ip_requests_per_second = 50
+            max_tracked_ips = 10000
+
+            [sequencer]
+            micro_block_interval_ms = 100
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.validate().is_ok());
+        assert_eq!(config.network.rpc_port, 27100);
+        assert_eq!(config.rate_limit.max_request_bytes, RateLimitConfig::default().max_request_bytes);
+
+        let sequencer = config.sequencer_config(1);
+        assert_eq!(sequencer.shard_id, 1);
+        assert_eq!(sequencer.micro_block_interval_ms, 100);
+        assert_eq!(sequencer.max_batch_size, 1000);
+        assert_eq!(sequencer.shard_count, 8);
+
+        // Shards serve RPC 100 ports apart unless the gateway is told otherwise
+        let endpoints = config.gateway_endpoints().unwrap();
+        assert_eq!(endpoints.len(), 8);
+        assert_eq!(endpoints[1], "http://127.0.0.1:27200");
+    }
+
+    #[test]
+    fn test_gateway_endpoints_match_shards() {
+        let mut config = Config::default();
+        config.network.shard_count = 2;
+        config.gateway.shard_endpoints = vec!["http://10.0.0.1:27000".to_string()];
+        assert!(config.validate().is_err());
+        config.gateway.shard_endpoints.push("http://10.0.0.2:27000".to_string());
+        assert!(config.validate().is_ok());
+        assert_eq!(config.gateway_endpoints().unwrap(), config.gateway.shard_endpoints);
+    }
+
+    #[test]
+    fn test_unsupported_chain_id() {
+        let mut config = Config::default();
+        config.network.chain_id = 0;
+        assert!(config.validate().is_err());
+        config.network.chain_id = u64::MAX - 1;
+        assert!(config.validate().is_err());
+        config.network.chain_id = 1;
+        assert!(config.validate().is_ok());
+    }
 }
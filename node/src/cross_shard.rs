@@ -0,0 +1,363 @@
+// Cross-shard transfers: receipts, inclusion proofs and per-shard bookkeeping
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::crypto::{keccak256, Address};
+use crate::finality::BatchHeader;
+use crate::state_trie;
+
+/// System address cross-shard transfers are sent to. The transaction's value
+/// leaves the source shard and `data` names the destination (`parse_transfer`).
+/// Its storage holds the status of every receipt the shard sent or claimed.
+pub const CROSS_SHARD_ROUTER: Address = Address::EVM([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0x00,
+]);
+
+/// Base blocks a receipt stays claimable for by default
+pub const DEFAULT_TIMEOUT_BLOCKS: u64 = 100;
+
+/// Status of a receipt in router storage, keyed by receipt ID. A receipt ID
+/// with any status is never sent, claimed or settled again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReceiptStatus {
+    /// Source: sender debited, waiting for delivery
+    Sent = 1,
+    /// Source: the destination's claim was finalized before expiry
+    Delivered = 2,
+    /// Source: expired undelivered, sender refunded
+    Refunded = 3,
+    /// Destination: claim waiting for base-layer finality
+    Claimed = 4,
+    /// Destination: recipient credited
+    Credited = 5,
+    /// Destination: claim finalized after expiry, the source refunds instead
+    Expired = 6,
+}
+
+impl ReceiptStatus {
+    /// Storage word of the status
+    pub fn to_word(self) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[31] = self as u8;
+        word
+    }
+
+    pub fn from_word(word: &[u8; 32]) -> Option<Self> {
+        if word[..31].iter().any(|b| *b != 0) {
+            return None;
+        }
+        match word[31] {
+            1 => Some(Self::Sent),
+            2 => Some(Self::Delivered),
+            3 => Some(Self::Refunded),
+            4 => Some(Self::Claimed),
+            5 => Some(Self::Credited),
+            6 => Some(Self::Expired),
+            _ => None,
+        }
+    }
+}
+
+/// Value in flight from one shard to another, emitted by the source shard
+/// into its micro-block and committed to by its batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrossShardReceipt {
+    pub source_shard: u8,
+    pub destination_shard: u8,
+    /// Per-source-shard counter, so equal transfers get distinct IDs
+    pub nonce: u64,
+    pub sender: Address,
+    pub recipient: Address,
+    /// Amount in wei
+    pub amount: u128,
+    /// Transaction that sent it
+    pub source_tx: [u8; 32],
+    /// Base height from which the receipt can no longer be claimed; if no
+    /// claim is final below it, the source refunds the sender
+    pub expires_at: u64,
+}
+
+impl CrossShardReceipt {
+    /// Canonical encoding (integers big-endian, addresses tagged as in transactions)
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(160);
+        buf.push(self.source_shard);
+        buf.push(self.destination_shard);
+        buf.extend_from_slice(&self.nonce.to_be_bytes());
+        encode_address(&mut buf, &self.sender);
+        encode_address(&mut buf, &self.recipient);
+        buf.extend_from_slice(&self.amount.to_be_bytes());
+        buf.extend_from_slice(&self.source_tx);
+        buf.extend_from_slice(&self.expires_at.to_be_bytes());
+        buf
+    }
+
+    /// Receipt ID: keccak256 of the encoding
+    pub fn id(&self) -> [u8; 32] {
+        keccak256(&self.encode())
+    }
+}
+
+/// Transaction data of a transfer to `recipient` on `destination_shard`
+#[cfg(test)]
+pub fn transfer_data(destination_shard: u8, recipient: &Address) -> Vec<u8> {
+    let mut data = vec![destination_shard];
+    encode_address(&mut data, recipient);
+    data
+}
+
+/// Destination shard and recipient of a router transaction's data
+pub fn parse_transfer(data: &[u8]) -> Result<(u8, Address)> {
+    let (&shard, rest) = data.split_first().ok_or_else(|| anyhow!("Missing destination shard"))?;
+    let recipient = match rest {
+        [0, bytes @ ..] if bytes.len() == 20 => Address::EVM(bytes.try_into()?),
+        [1, bytes @ ..] if bytes.len() == 32 => Address::Native(bytes.try_into()?),
+        _ => bail!("Invalid cross-shard recipient"),
+    };
+    Ok((shard, recipient))
+}
+
+/// Root a batch commits its outgoing receipts under (ordered trie, like
+/// Ethereum receipt roots)
+pub fn receipts_root(receipts: &[CrossShardReceipt]) -> Result<[u8; 32]> {
+    let items: Vec<Vec<u8>> = receipts.iter().map(CrossShardReceipt::encode).collect();
+    state_trie::ordered_trie_root(&items)
+}
+
+/// A receipt with the trie proof that its source batch committed to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptProof {
+    pub receipt: CrossShardReceipt,
+    /// Source batch holding the receipt
+    pub batch_sequence: u64,
+    /// Position among the batch's receipts
+    pub index: usize,
+    pub proof: Vec<Vec<u8>>,
+}
+
+impl ReceiptProof {
+    pub fn build(receipts: &[CrossShardReceipt], index: usize, batch_sequence: u64) -> Result<Self> {
+        let receipt = receipts.get(index).ok_or_else(|| anyhow!("No receipt at index {}", index))?.clone();
+        let items: Vec<Vec<u8>> = receipts.iter().map(CrossShardReceipt::encode).collect();
+        Ok(Self {
+            receipt,
+            batch_sequence,
+            index,
+            proof: state_trie::ordered_trie_proof(&items, index)?,
+        })
+    }
+
+    /// Check the proof against the source batch's header from a finalized base block
+    pub fn verify(&self, header: &BatchHeader) -> Result<()> {
+        if header.shard_id != self.receipt.source_shard || header.batch_sequence != self.batch_sequence {
+            bail!("Receipt proof is for another batch");
+        }
+        let proven = state_trie::verify_proof(&header.receipts_root, &alloy_rlp::encode(self.index), &self.proof)?;
+        if proven.as_deref() != Some(self.receipt.encode().as_slice()) {
+            bail!("Receipt is not committed to by batch {} of shard {}", header.batch_sequence, header.shard_id);
+        }
+        Ok(())
+    }
+}
+
+/// State change a finalized base block makes due on a shard
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Settlement {
+    /// Destination: our claim is final in time, credit the recipient
+    Credit(CrossShardReceipt),
+    /// Destination: our claim came too late
+    Expire([u8; 32]),
+    /// Source: the destination's claim is final
+    Deliver([u8; 32]),
+    /// Source: expired without a final claim, refund the sender
+    Refund(CrossShardReceipt),
+}
+
+/// Open cross-shard items of one shard, persisted with its chain head.
+///
+/// The base layer decides each receipt's fate: it is delivered if the
+/// destination's claim is finalized in a base block below `expires_at`,
+/// otherwise the source refunds it. Both shards follow the same finalized
+/// blocks, so they settle the same way.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrossShardLedger {
+    /// Nonce of the next outgoing receipt
+    pub next_nonce: u64,
+    /// Height of the last finalized base block seen
+    pub base_height: Option<u64>,
+    /// Sent receipts neither delivered nor refunded
+    pub outbound: Vec<CrossShardReceipt>,
+    /// Proven incoming receipts waiting to be claimed
+    pub inbox: Vec<ReceiptProof>,
+    /// Claimed receipts waiting for the claim to be final
+    pub claimed: Vec<CrossShardReceipt>,
+    /// Due in the next micro-block
+    pub settlements: Vec<Settlement>,
+}
+
+impl CrossShardLedger {
+    /// Expiry height for a receipt sent now
+    pub fn expiry(&self, timeout_blocks: u64) -> u64 {
+        self.base_height.map_or(0, |h| h + 1) + timeout_blocks
+    }
+
+    /// Whether a micro-block is needed to claim or settle receipts
+    pub fn has_work(&self) -> bool {
+        !self.inbox.is_empty() || !self.settlements.is_empty()
+    }
+
+    /// Queue a proven receipt for `shard_id` unless it is already known
+    pub fn receive(&mut self, shard_id: u8, proof: ReceiptProof) {
+        let id = proof.receipt.id();
+        if proof.receipt.destination_shard != shard_id
+            || self.inbox.iter().any(|p| p.receipt.id() == id)
+            || self.claimed.iter().any(|r| r.id() == id)
+        {
+            return;
+        }
+        self.inbox.push(proof);
+    }
+
+    /// Follow a finalized base block: settle claims it finalized and refund
+    /// receipts that expired with it
+    pub fn on_finalized(&mut self, shard_id: u8, height: u64, headers: &[BatchHeader]) {
+        if self.base_height.is_some_and(|h| height <= h) {
+            return;
+        }
+        for header in headers {
+            for id in &header.claims {
+                if header.shard_id == shard_id {
+                    let Some(position) = self.claimed.iter().position(|r| r.id() == *id) else {
+                        continue;
+                    };
+                    let receipt = self.claimed.remove(position);
+                    if height < receipt.expires_at {
+                        self.settlements.push(Settlement::Credit(receipt));
+                    } else {
+                        warn!("Claim of receipt 0x{} was final after its expiry", hex::encode(id));
+                        self.settlements.push(Settlement::Expire(*id));
+                    }
+                } else if let Some(position) = self.outbound.iter().position(|r| r.id() == *id) {
+                    let receipt = &self.outbound[position];
+                    if receipt.destination_shard == header.shard_id && height < receipt.expires_at {
+                        self.outbound.remove(position);
+                        self.settlements.push(Settlement::Deliver(*id));
+                    }
+                }
+            }
+        }
+
+        let (expired, open): (Vec<_>, Vec<_>) = self.outbound.drain(..).partition(|r| r.expires_at <= height);
+        self.outbound = open;
+        for receipt in expired {
+            info!("Receipt 0x{} expired undelivered, refunding {}", hex::encode(receipt.id()), receipt.sender);
+            self.settlements.push(Settlement::Refund(receipt));
+        }
+        self.inbox.retain(|proof| proof.receipt.expires_at > height);
+        self.base_height = Some(height);
+    }
+}
+
+fn encode_address(buf: &mut Vec<u8>, address: &Address) {
+    match address {
+        Address::EVM(bytes) => {
+            buf.push(0);
+            buf.extend_from_slice(bytes);
+        }
+        Address::Native(bytes) => {
+            buf.push(1);
+            buf.extend_from_slice(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(nonce: u64, expires_at: u64) -> CrossShardReceipt {
+        CrossShardReceipt {
+            source_shard: 0,
+            destination_shard: 3,
+            nonce,
+            sender: Address::EVM([1; 20]),
+            recipient: Address::Native([2; 32]),
+            amount: 5_000,
+            source_tx: [nonce as u8; 32],
+            expires_at,
+        }
+    }
+
+    fn header(shard_id: u8, receipts: &[CrossShardReceipt], claims: Vec<[u8; 32]>) -> BatchHeader {
+        BatchHeader {
+            shard_id,
+            batch_sequence: 4,
+            state_root: String::new(),
+            parent_state_root: String::new(),
+            hash: [0; 32],
+            receipts_root: receipts_root(receipts).unwrap(),
+            claims,
+            staking: vec![],
+        }
+    }
+
+    #[test]
+    fn test_transfer_data_roundtrip() {
+        let recipient = Address::Native([9; 32]);
+        assert_eq!(parse_transfer(&transfer_data(7, &recipient)).unwrap(), (7, recipient));
+        assert!(parse_transfer(&[7, 0, 1, 2]).is_err());
+        assert!(parse_transfer(&[]).is_err());
+    }
+
+    #[test]
+    fn test_receipt_proof() {
+        let receipts: Vec<_> = (0..5).map(|n| receipt(n, 50)).collect();
+        let header = header(0, &receipts, vec![]);
+
+        let proof = ReceiptProof::build(&receipts, 3, 4).unwrap();
+        proof.verify(&header).unwrap();
+
+        let mut forged = proof.clone();
+        forged.receipt.amount += 1;
+        assert!(forged.verify(&header).is_err());
+
+        let mut moved = proof.clone();
+        moved.index = 2;
+        assert!(moved.verify(&header).is_err());
+
+        // The root of another batch does not prove it
+        let other = self::header(0, &receipts[..3], vec![]);
+        assert!(proof.verify(&other).is_err());
+    }
+
+    #[test]
+    fn test_ledger_settles_by_base_order() {
+        let delivered = receipt(0, 10);
+        let late = receipt(1, 10);
+
+        // Source shard 0: one claim is final in time, the other never arrives
+        let mut source = CrossShardLedger {
+            outbound: vec![delivered.clone(), late.clone()],
+            ..CrossShardLedger::default()
+        };
+        source.on_finalized(0, 8, &[header(3, &[], vec![delivered.id()])]);
+        assert_eq!(source.settlements, vec![Settlement::Deliver(delivered.id())]);
+        source.on_finalized(0, 10, &[header(3, &[], vec![late.id()])]);
+        assert_eq!(source.settlements[1], Settlement::Refund(late.clone()));
+        assert!(source.outbound.is_empty());
+
+        // Destination shard 3 sees the same blocks
+        let mut destination = CrossShardLedger {
+            claimed: vec![delivered.clone(), late.clone()],
+            ..CrossShardLedger::default()
+        };
+        destination.on_finalized(3, 8, &[header(3, &[], vec![delivered.id()])]);
+        destination.on_finalized(3, 10, &[header(3, &[], vec![late.id()])]);
+        assert_eq!(
+            destination.settlements,
+            vec![Settlement::Credit(delivered), Settlement::Expire(late.id())]
+        );
+    }
+}
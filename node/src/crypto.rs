@@ -4,16 +4,27 @@
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use sha3::Keccak256;
 use tracing::{debug, info, warn, error}; // SECURITY FIX L-2
 
 // Post-quantum crypto imports
 use pqcrypto_dilithium::dilithium5;
-use pqcrypto_sphincsplus::sphincssha2256256frobust;
-use pqcrypto_traits::sign::{PublicKey as PQPublicKey, SecretKey as PQSecretKey, SignedMessage};
+use rand::Rng;
+// SPHINCS+-SHA2-256f-simple: pqcrypto-sphincsplus 0.7 no longer ships the
+// "robust" parameter sets, so sha2256256frobust is not available
+use pqcrypto_sphincsplus::sphincssha2256fsimple;
+use pqcrypto_traits::sign::{
+    DetachedSignature as PQDetachedSignature, PublicKey as PQPublicKey, SecretKey as PQSecretKey,
+};
 
 // ECDSA imports
 use secp256k1::{ecdsa::Signature as ECDSASignature, Message, PublicKey, Secp256k1, SecretKey};
 
+/// Dilithium5 public key size in bytes
+pub const DILITHIUM5_PK_SIZE: usize = 2592;
+/// Dilithium5 signature size in bytes
+pub const DILITHIUM5_SIG_SIZE: usize = 4627;
+
 /// Supported signature algorithms
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SignatureAlgorithm {
@@ -35,7 +46,7 @@ pub enum SignatureAlgorithm {
 }
 
 /// Multi-algorithm signature container
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Signature {
     ECDSA {
         r: [u8; 32],
@@ -43,10 +54,10 @@ pub enum Signature {
         v: u8,
     },
     Dilithium {
-        data: Vec<u8>, // ~4,595 bytes
+        data: Vec<u8>, // 4,627 bytes (Dilithium5)
     },
     SPHINCSPlus {
-        data: Vec<u8>, // ~2,048 bytes
+        data: Vec<u8>, // 49,856 bytes (SPHINCS+-SHA2-256f)
     },
     Hybrid {
         ecdsa: Box<Signature>,
@@ -142,13 +153,14 @@ impl Signature {
 }
 
 /// Public key for any supported algorithm
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PublicKeyData {
     ECDSA {
+        #[serde(with = "compressed_key")]
         bytes: [u8; 33], // Compressed public key
     },
     Dilithium {
-        bytes: Vec<u8>, // 2,528 bytes (Dilithium5)
+        bytes: Vec<u8>, // 2,592 bytes (Dilithium5)
     },
     SPHINCSPlus {
         bytes: Vec<u8>, // 64 bytes
@@ -164,8 +176,18 @@ impl PublicKeyData {
     pub fn to_address(&self) -> Address {
         match self {
             PublicKeyData::ECDSA { bytes } => {
-                // Ethereum-style address (last 20 bytes of keccak256)
-                let hash = Sha256::digest(bytes);
+                // Ethereum-style address (last 20 bytes of keccak256 of the
+                // uncompressed key without its 0x04 prefix)
+                let uncompressed = match PublicKey::from_slice(bytes) {
+                    Ok(pk) => pk.serialize_uncompressed(),
+                    // Invalid keys never verify; still give them a stable address
+                    Err(_) => {
+                        let mut padded = [0u8; 65];
+                        padded[32..].copy_from_slice(bytes);
+                        padded
+                    }
+                };
+                let hash = keccak256(&uncompressed[1..]);
                 let mut addr = [0u8; 20];
                 addr.copy_from_slice(&hash[12..32]);
                 Address::EVM(addr)
@@ -186,6 +208,164 @@ impl PublicKeyData {
     }
 }
 
+/// Keccak-256 (Ethereum's hash function)
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&Keccak256::digest(data));
+    out
+}
+
+/// Serde support for 33-byte compressed keys (serde only derives arrays up to 32)
+mod compressed_key {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8; 33], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 33], D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        bytes
+            .try_into()
+            .map_err(|_| serde::de::Error::custom("compressed public key must be 33 bytes"))
+    }
+}
+
+/// Secret key for any supported algorithm
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SecretKeyData {
+    ECDSA { bytes: [u8; 32] },
+    Dilithium { bytes: Vec<u8> },
+    SPHINCSPlus { bytes: Vec<u8> },
+    Hybrid {
+        ecdsa: Box<SecretKeyData>,
+        pq: Box<SecretKeyData>,
+    },
+}
+
+impl std::fmt::Debug for SecretKeyData {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Never print key material
+        write!(f, "SecretKeyData::{:?}", self.algorithm())
+    }
+}
+
+impl SecretKeyData {
+    pub fn algorithm(&self) -> SignatureAlgorithm {
+        match self {
+            SecretKeyData::ECDSA { .. } => SignatureAlgorithm::ECDSA,
+            SecretKeyData::Dilithium { .. } => SignatureAlgorithm::Dilithium,
+            SecretKeyData::SPHINCSPlus { .. } => SignatureAlgorithm::SPHINCSPlus,
+            SecretKeyData::Hybrid { .. } => SignatureAlgorithm::Hybrid,
+        }
+    }
+
+    /// Sign a message (the counterpart of `Signature::verify`)
+    pub fn sign(&self, message: &[u8]) -> Result<Signature> {
+        match self {
+            SecretKeyData::ECDSA { bytes } => {
+                let secp = Secp256k1::new();
+                let sk = SecretKey::from_slice(bytes)
+                    .map_err(|e| anyhow!("Invalid ECDSA secret key: {}", e))?;
+                let msg_hash = Sha256::digest(message);
+                let msg = Message::from_digest_slice(&msg_hash)
+                    .map_err(|e| anyhow!("Invalid message: {}", e))?;
+
+                let (recovery_id, compact) = secp
+                    .sign_ecdsa_recoverable(&msg, &sk)
+                    .serialize_compact();
+                let mut r = [0u8; 32];
+                let mut s = [0u8; 32];
+                r.copy_from_slice(&compact[..32]);
+                s.copy_from_slice(&compact[32..]);
+                Ok(Signature::ECDSA { r, s, v: recovery_id.to_i32() as u8 })
+            }
+            SecretKeyData::Dilithium { bytes } => {
+                let sk = dilithium5::SecretKey::from_bytes(bytes)
+                    .map_err(|e| anyhow!("Invalid Dilithium secret key: {:?}", e))?;
+                let sig = dilithium5::detached_sign(message, &sk);
+                Ok(Signature::Dilithium { data: sig.as_bytes().to_vec() })
+            }
+            SecretKeyData::SPHINCSPlus { bytes } => {
+                let sk = sphincssha2256fsimple::SecretKey::from_bytes(bytes)
+                    .map_err(|e| anyhow!("Invalid SPHINCS+ secret key: {:?}", e))?;
+                let sig = sphincssha2256fsimple::detached_sign(message, &sk);
+                Ok(Signature::SPHINCSPlus { data: sig.as_bytes().to_vec() })
+            }
+            SecretKeyData::Hybrid { ecdsa, pq } => Ok(Signature::Hybrid {
+                ecdsa: Box::new(ecdsa.sign(message)?),
+                pq: Box::new(pq.sign(message)?),
+            }),
+        }
+    }
+}
+
+/// Signing key pair for any supported algorithm (serializable for key files)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyPair {
+    pub public_key: PublicKeyData,
+    pub secret_key: SecretKeyData,
+}
+
+impl KeyPair {
+    /// Generate a fresh key pair (Hybrid pairs ECDSA with Dilithium)
+    pub fn generate(algorithm: SignatureAlgorithm) -> Self {
+        match algorithm {
+            SignatureAlgorithm::ECDSA => {
+                let secp = Secp256k1::new();
+                let mut rng = rand::thread_rng();
+                let sk = loop {
+                    // Out-of-range scalars are astronomically rare; just redraw
+                    if let Ok(sk) = SecretKey::from_slice(&rng.gen::<[u8; 32]>()) {
+                        break sk;
+                    }
+                };
+                let pk = PublicKey::from_secret_key(&secp, &sk);
+                KeyPair {
+                    public_key: PublicKeyData::ECDSA { bytes: pk.serialize() },
+                    secret_key: SecretKeyData::ECDSA { bytes: sk.secret_bytes() },
+                }
+            }
+            SignatureAlgorithm::Dilithium => {
+                let (pk, sk) = dilithium5::keypair();
+                KeyPair {
+                    public_key: PublicKeyData::Dilithium { bytes: pk.as_bytes().to_vec() },
+                    secret_key: SecretKeyData::Dilithium { bytes: sk.as_bytes().to_vec() },
+                }
+            }
+            SignatureAlgorithm::SPHINCSPlus => {
+                let (pk, sk) = sphincssha2256fsimple::keypair();
+                KeyPair {
+                    public_key: PublicKeyData::SPHINCSPlus { bytes: pk.as_bytes().to_vec() },
+                    secret_key: SecretKeyData::SPHINCSPlus { bytes: sk.as_bytes().to_vec() },
+                }
+            }
+            SignatureAlgorithm::Hybrid => {
+                let ecdsa = Self::generate(SignatureAlgorithm::ECDSA);
+                let pq = Self::generate(SignatureAlgorithm::Dilithium);
+                KeyPair {
+                    public_key: PublicKeyData::Hybrid {
+                        ecdsa: Box::new(ecdsa.public_key),
+                        pq: Box::new(pq.public_key),
+                    },
+                    secret_key: SecretKeyData::Hybrid {
+                        ecdsa: Box::new(ecdsa.secret_key),
+                        pq: Box::new(pq.secret_key),
+                    },
+                }
+            }
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.public_key.to_address()
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Result<Signature> {
+        self.secret_key.sign(message)
+    }
+}
+
 /// Address format
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Address {
@@ -195,6 +375,59 @@ pub enum Address {
     Native([u8; 32]),
 }
 
+impl Address {
+    /// Raw address bytes (20 for EVM, 32 for native)
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Address::EVM(bytes) => bytes,
+            Address::Native(bytes) => bytes,
+        }
+    }
+
+    /// 20-byte account used inside the EVM (native addresses map to their
+    /// last 20 bytes)
+    pub fn evm_bytes(&self) -> [u8; 20] {
+        match self {
+            Address::EVM(bytes) => *bytes,
+            Address::Native(bytes) => {
+                let mut addr = [0u8; 20];
+                addr.copy_from_slice(&bytes[12..]);
+                addr
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "0x{}", hex::encode(self.as_bytes()))
+    }
+}
+
+impl std::str::FromStr for Address {
+    type Err = anyhow::Error;
+
+    /// Parse a 0x-prefixed hex address (40 hex chars = EVM, 64 = native)
+    fn from_str(s: &str) -> Result<Self> {
+        let hex_part = s.strip_prefix("0x").unwrap_or(s);
+        let bytes = hex::decode(hex_part).map_err(|e| anyhow!("Invalid address hex: {}", e))?;
+
+        match bytes.len() {
+            20 => {
+                let mut addr = [0u8; 20];
+                addr.copy_from_slice(&bytes);
+                Ok(Address::EVM(addr))
+            }
+            32 => {
+                let mut addr = [0u8; 32];
+                addr.copy_from_slice(&bytes);
+                Ok(Address::Native(addr))
+            }
+            n => Err(anyhow!("Invalid address length: {} bytes", n)),
+        }
+    }
+}
+
 /// Verify ECDSA signature (secp256k1)
 fn verify_ecdsa(
     message: &[u8],
@@ -240,9 +473,6 @@ fn verify_dilithium(
     };
 
     // SECURITY FIX M-7: Validate expected sizes
-    const DILITHIUM5_PK_SIZE: usize = 2528;
-    const DILITHIUM5_SIG_SIZE: usize = 4595;
-    
     if pk_bytes.len() != DILITHIUM5_PK_SIZE {
         return Err(anyhow!(
             "Invalid Dilithium public key size: expected {}, got {}",
@@ -263,16 +493,11 @@ fn verify_dilithium(
     let pk = dilithium5::PublicKey::from_bytes(pk_bytes)
         .map_err(|e| anyhow!("Invalid Dilithium public key: {:?}", e))?;
 
-    // Combine message and signature for verification
-    let mut signed_msg = Vec::with_capacity(message.len() + signature.len());
-    signed_msg.extend_from_slice(signature);
-    signed_msg.extend_from_slice(message);
+    let sig = dilithium5::DetachedSignature::from_bytes(signature)
+        .map_err(|e| anyhow!("Invalid Dilithium signature: {:?}", e))?;
 
     // Verify
-    match dilithium5::open(&signed_msg, &pk) {
-        Ok(verified_msg) => Ok(verified_msg == message),
-        Err(_) => Ok(false),
-    }
+    Ok(dilithium5::verify_detached_signature(&sig, message, &pk).is_ok())
 }
 
 /// Verify SPHINCS+ signature
@@ -286,19 +511,13 @@ fn verify_sphincs(
     };
 
     // Parse public key
-    let pk = sphincssha2256256frobust::PublicKey::from_bytes(pk_bytes)
+    let pk = sphincssha2256fsimple::PublicKey::from_bytes(pk_bytes)
         .map_err(|e| anyhow!("Invalid SPHINCS+ public key: {:?}", e))?;
-
-    // Combine message and signature
-    let mut signed_msg = Vec::with_capacity(message.len() + signature.len());
-    signed_msg.extend_from_slice(signature);
-    signed_msg.extend_from_slice(message);
+    let sig = sphincssha2256fsimple::DetachedSignature::from_bytes(signature)
+        .map_err(|e| anyhow!("Invalid SPHINCS+ signature: {:?}", e))?;
 
     // Verify
-    match sphincssha2256256frobust::open(&signed_msg, &pk) {
-        Ok(verified_msg) => Ok(verified_msg == message),
-        Err(_) => Ok(false),
-    }
+    Ok(sphincssha2256fsimple::verify_detached_signature(&sig, message, &pk).is_ok())
 }
 
 // SECURITY FIX H-3: Falcon removed until properly implemented
@@ -331,4 +550,28 @@ mod tests {
         };
         assert_eq!(sig.algorithm(), SignatureAlgorithm::ECDSA);
     }
+
+    #[test]
+    fn test_sign_and_verify_roundtrip() {
+        for algorithm in [
+            SignatureAlgorithm::ECDSA,
+            SignatureAlgorithm::Dilithium,
+            SignatureAlgorithm::Hybrid,
+        ] {
+            let keypair = KeyPair::generate(algorithm);
+            let sig = keypair.sign(b"ionova").unwrap();
+            assert_eq!(sig.algorithm(), algorithm);
+            assert!(sig.verify(b"ionova", &keypair.public_key).unwrap());
+            assert!(!sig.verify(b"tampered", &keypair.public_key).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_dilithium_sizes() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let sig = keypair.sign(b"ionova").unwrap();
+        assert_eq!(sig.size(), DILITHIUM5_SIG_SIZE);
+        let PublicKeyData::Dilithium { bytes } = &keypair.public_key else { panic!() };
+        assert_eq!(bytes.len(), DILITHIUM5_PK_SIZE);
+    }
 }
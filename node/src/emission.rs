@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 /// IONX Token Emission System
 /// Total Supply: 10,000,000,000 IONX (10 billion)
-/// Emission Period: 15 years with annual halving
+/// Emission Period: 20 years with block rewards halving every 2 years
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmissionSchedule {
     /// Total maximum supply (10 billion IONX)
@@ -19,8 +19,14 @@ pub struct EmissionSchedule {
     /// Halving interval in days (1 year = 365 days)
     pub halving_interval_days: u64,
     
-    /// Number of halvings (15 total over 15 years)
+    /// Number of halvings (10 total over 20 years)
     pub total_halvings: u32,
+
+    /// Initial block reward (79.35 IONX)
+    pub initial_block_reward: Decimal,
+
+    /// Halving interval in blocks (2 years ≈ 63M blocks at 1s)
+    pub halving_interval: u64,
     
     /// Current circulating supply
     pub circulating_supply: Decimal,
@@ -42,7 +48,9 @@ impl Default for EmissionSchedule {
             genesis_allocation: dec!(2_100_000),        // 2.1M for fractions
             initial_daily_emission: dec!(9_600_000),    // 9.6M IONX per day
             halving_interval_days: 365,                 // 1 year
-            total_halvings: 15,                         // 15 years
+            total_halvings: 10,                         // 20 years
+            initial_block_reward: dec!(79.35),
+            halving_interval: 63_072_000,               // 2 years at 1s blocks
             circulating_supply: dec!(2_100_000),        // Starts with genesis
             total_minted: Decimal::ZERO,
             total_burned: Decimal::ZERO,
@@ -74,24 +82,37 @@ impl EmissionSchedule {
         self.initial_block_reward / divisor
     }
     
-    /// Get current epoch (0-14, where 0 is year 0-2, 14 is year 28-30)
+    /// Get current epoch (0-9, where 0 is year 0-2, 9 is year 18-20)
     pub fn current_epoch(&self, block_height: u64) -> u32 {
         let blocks_since_genesis = block_height.saturating_sub(self.genesis_height);
         let epoch = blocks_since_genesis / self.halving_interval;
-        epoch.min(self.total_halvings as u64) as u32
+        epoch.min(self.total_halvings.saturating_sub(1) as u64) as u32
     }
     
-    /// Calculate total IONX that will be minted in a given epoch
+    /// Calculate total IONX that will be minted in a given epoch. The last
+    /// epoch is cut short where the supply reaches its maximum, as minting is.
     pub fn epoch_total_emission(&self, epoch: u32) -> Decimal {
         if epoch >= self.total_halvings {
             return Decimal::ZERO;
         }
-        
+
+        let earlier: Decimal = (0..epoch).map(|earlier| self.full_epoch_emission(earlier)).sum();
+        let available = (self.emission_cap() - earlier).max(Decimal::ZERO);
+        self.full_epoch_emission(epoch).min(available)
+    }
+
+    /// Block rewards of a whole epoch, regardless of the supply cap
+    fn full_epoch_emission(&self, epoch: u32) -> Decimal {
         let epoch_reward = self.initial_block_reward / Decimal::from(2u64.pow(epoch));
         let blocks_in_epoch = Decimal::from(self.halving_interval);
         
         epoch_reward * blocks_in_epoch
     }
+
+    /// Most IONX block rewards can mint on top of the genesis allocation
+    fn emission_cap(&self) -> Decimal {
+        self.max_supply - self.genesis_allocation
+    }
     
     /// Calculate total IONX minted up to a given block height
     pub fn total_minted_at_height(&self, block_height: u64) -> Decimal {
@@ -113,7 +134,7 @@ impl EmissionSchedule {
             total += current_reward * Decimal::from(remaining_blocks);
         }
         
-        total
+        total.min(self.emission_cap())
     }
     
     /// Mint new IONX for a block (updates circulating supply)
@@ -283,11 +304,11 @@ mod tests {
         
         // After 1 complete epoch
         let minted_epoch1 = emission.total_minted_at_height(63_072_000);
-        assert_eq!(minted_epoch1, dec!(79.3) * dec!(63_072_000));
+        assert_eq!(minted_epoch1, dec!(79.35) * dec!(63_072_000));
         
         // After 2 complete epochs
         let minted_epoch2 = emission.total_minted_at_height(63_072_000 * 2);
-        let expected = (dec!(79.3) + dec!(39.65)) * dec!(63_072_000);
+        let expected = (dec!(79.35) + dec!(39.675)) * dec!(63_072_000);
         assert_eq!(minted_epoch2, expected);
     }
 
@@ -300,13 +321,13 @@ mod tests {
         
         // Mint first block reward
         let reward = emission.mint_block_reward(0);
-        assert_eq!(reward, dec!(79.3));
-        assert_eq!(emission.circulating_supply, dec!(2_100_000) + dec!(79.3));
-        assert_eq!(emission.total_minted, dec!(79.3));
+        assert_eq!(reward, dec!(79.35));
+        assert_eq!(emission.circulating_supply, dec!(2_100_000) + dec!(79.35));
+        assert_eq!(emission.total_minted, dec!(79.35));
         
         // Burn some tokens
         emission.burn(dec!(10));
-        assert_eq!(emission.circulating_supply, dec!(2_100_000) + dec!(79.3) - dec!(10));
+        assert_eq!(emission.circulating_supply, dec!(2_100_000) + dec!(79.35) - dec!(10));
         assert_eq!(emission.total_burned, dec!(10));
     }
 
@@ -350,12 +371,14 @@ mod tests {
             total += emission.epoch_total_emission(epoch);
         }
         
-        // Should be close to 10B - genesis allocation
+        // Should be close to 10B - genesis allocation; the last epoch is cut at the cap
         let expected = dec!(10_000_000_000) - dec!(2_100_000);
         
         // Allow small rounding difference
         let diff = (total - expected).abs();
         assert!(diff < dec!(1_000_000)); // Within 1M IONX
+        assert_eq!(total, expected);
+        assert_eq!(emission.total_minted_at_height(63_072_000 * 12), expected);
     }
 
     #[test]
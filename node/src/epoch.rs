@@ -0,0 +1,502 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{bail, Result};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::crypto::PublicKeyData;
+use crate::emission::EmissionSchedule;
+use crate::finality::{EquivocationEvidence, ValidatorInfo, ValidatorSet};
+use crate::staking::{
+    BlockRewardConfig, BlockRewardDistribution, SlashOutcome, SlashingConfig, StakingAction, StakingOp, StakingRewards,
+    ValidatorStake,
+};
+
+/// Validator set rotation rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EpochConfig {
+    /// Base blocks per epoch
+    pub epoch_length: u64,
+    /// Most validators active at once
+    pub max_validators: usize,
+    /// Least total stake a validator needs to be selected
+    pub min_stake: u64,
+    /// Validators joining per epoch; the rest wait in the activation queue
+    pub max_activations_per_epoch: usize,
+    /// Validators leaving per epoch; the rest wait in the exit queue
+    pub max_exits_per_epoch: usize,
+    /// Epochs unbonded stake stays slashable before it is released
+    pub unbonding_epochs: u64,
+    /// Penalties for double signing
+    pub slashing: SlashingConfig,
+}
+
+impl Default for EpochConfig {
+    fn default() -> Self {
+        Self {
+            epoch_length: 600,
+            max_validators: 100,
+            min_stake: 1,
+            max_activations_per_epoch: 4,
+            max_exits_per_epoch: 4,
+            unbonding_epochs: 7,
+            slashing: SlashingConfig::default(),
+        }
+    }
+}
+
+/// Validators and voting stake of one epoch
+#[derive(Debug, Clone)]
+pub struct Epoch {
+    pub number: u64,
+    /// First base block height of the epoch
+    pub start_height: u64,
+    pub validators: ValidatorSet,
+}
+
+/// Single source of who validates: selects each epoch's set from the
+/// staking ledger at the epoch boundary. The finality gadget and the reward
+/// distributor read the current epoch, so stake changes become consensus
+/// weight at the next boundary.
+pub struct EpochManager {
+    config: EpochConfig,
+    staking: StakingRewards,
+    /// Consensus keys of registered validators
+    keys: HashMap<String, PublicKeyData>,
+    current: Epoch,
+    activation_queue: VecDeque<String>,
+    exit_queue: VecDeque<String>,
+    /// Validators that asked to leave the set
+    exit_requests: HashSet<String>,
+}
+
+impl EpochManager {
+    /// Genesis epoch from a validator set file: each entry's stake becomes
+    /// its self-stake, and the best-staked entries up to `max_validators`
+    /// form epoch 0 without waiting in the activation queue.
+    pub fn new(config: EpochConfig, genesis: &ValidatorSet) -> Result<Self> {
+        if config.epoch_length == 0 || config.max_validators == 0 {
+            bail!("Epoch length and max validators must be > 0");
+        }
+        let mut staking =
+            StakingRewards::new(BlockRewardConfig::default(), 0).with_slashing(config.slashing.clone());
+        let mut keys = HashMap::new();
+        for (id, info) in &genesis.validators {
+            staking.add_validator(ValidatorStake {
+                operator: id.clone(),
+                self_stake: Decimal::from(info.stake),
+                delegated_stake: Decimal::ZERO,
+                commission_rate: Decimal::ZERO,
+                delegators: HashMap::new(),
+                jailed: !info.is_active,
+            });
+            keys.insert(id.clone(), info.public_key.clone());
+        }
+
+        let mut manager = Self {
+            config,
+            staking,
+            keys,
+            current: Epoch { number: 0, start_height: 0, validators: ValidatorSet::new() },
+            activation_queue: VecDeque::new(),
+            exit_queue: VecDeque::new(),
+            exit_requests: HashSet::new(),
+        };
+        let selected = manager.eligible();
+        manager.install(0, 0, selected.into_iter().collect());
+        if manager.current.validators.validators.is_empty() {
+            bail!("No validator meets the minimum stake of {}", manager.config.min_stake);
+        }
+        Ok(manager)
+    }
+
+    #[cfg(test)]
+    pub fn current(&self) -> &Epoch {
+        &self.current
+    }
+
+    /// Voting set of the current epoch
+    pub fn validators(&self) -> &ValidatorSet {
+        &self.current.validators
+    }
+
+    pub fn staking(&self) -> &StakingRewards {
+        &self.staking
+    }
+
+    /// Stake still unbonding
+    #[cfg(test)]
+    pub fn unbonding(&self) -> &[crate::staking::UnbondingEntry] {
+        self.staking.unbonding()
+    }
+
+    #[cfg(test)]
+    pub fn activation_queue(&self) -> impl Iterator<Item = &String> {
+        self.activation_queue.iter()
+    }
+
+    #[cfg(test)]
+    pub fn exit_queue(&self) -> impl Iterator<Item = &String> {
+        self.exit_queue.iter()
+    }
+
+    /// Register a validator candidate; it queues for activation at the next boundary
+    pub fn register(&mut self, stake: ValidatorStake, public_key: PublicKeyData) -> Result<()> {
+        if !matches!(public_key, PublicKeyData::Dilithium { .. } | PublicKeyData::SPHINCSPlus { .. }) {
+            bail!("Validator {} must use a Dilithium or SPHINCS+ key", stake.operator);
+        }
+        if public_key.to_address().to_string() != stake.operator {
+            bail!("Validator {} does not match its key", stake.operator);
+        }
+        if self.keys.contains_key(&stake.operator) {
+            bail!("Validator {} is already registered", stake.operator);
+        }
+        self.keys.insert(stake.operator.clone(), public_key);
+        self.staking.add_validator(stake);
+        Ok(())
+    }
+
+    /// Leave the set at a coming boundary; the stake then unbonds
+    pub fn request_exit(&mut self, validator: &str) -> Result<()> {
+        if !self.keys.contains_key(validator) {
+            bail!("Validator {} is not registered", validator);
+        }
+        self.exit_requests.insert(validator.to_string());
+        Ok(())
+    }
+
+    /// Withdraw stake; it stops counting at the next boundary and is
+    /// released `unbonding_epochs` after the current epoch
+    pub fn unbond(&mut self, validator: &str, delegator: Option<&str>, amount: Decimal) -> Result<()> {
+        let release_epoch = self.current.number + self.config.unbonding_epochs;
+        self.staking
+            .unbond(validator, delegator, amount, release_epoch)
+            .map_err(anyhow::Error::msg)
+    }
+
+    /// Apply a staking transaction of a finalized batch; like every stake
+    /// change it becomes voting weight at the next boundary
+    pub fn apply(&mut self, action: &StakingAction) -> Result<()> {
+        let staker = action.staker.to_string();
+        match &action.op {
+            StakingOp::Register { public_key, commission_rate } => self.register(
+                ValidatorStake {
+                    operator: staker,
+                    self_stake: action.amount,
+                    delegated_stake: Decimal::ZERO,
+                    commission_rate: *commission_rate,
+                    delegators: HashMap::new(),
+                    jailed: false,
+                },
+                public_key.clone(),
+            ),
+            StakingOp::Delegate { validator } => self
+                .staking
+                .delegate(&validator.to_string(), staker, action.amount)
+                .map_err(anyhow::Error::msg),
+            StakingOp::Unbond { validator, amount } => {
+                let validator = validator.to_string();
+                let delegator = (validator != staker).then_some(staker.as_str());
+                self.unbond(&validator, delegator, *amount)
+            }
+            StakingOp::Exit => self.request_exit(&staker),
+            StakingOp::Unjail => self.staking.unjail(&staker).map_err(anyhow::Error::msg),
+        }
+    }
+
+    /// Slash verified double-sign evidence. A jailed offender stops voting
+    /// at once rather than at the boundary.
+    pub fn slash_double_sign(
+        &mut self,
+        evidence: &EquivocationEvidence,
+        emission: &mut EmissionSchedule,
+    ) -> Result<SlashOutcome> {
+        let outcome = self.staking.slash_double_sign(evidence, emission).map_err(anyhow::Error::msg)?;
+        let stake = self.staking.validator(&outcome.validator).map_or(Decimal::ZERO, |v| v.total_stake());
+        self.current
+            .validators
+            .update_validator(&outcome.validator, voting_stake(stake), !outcome.jailed);
+        Ok(outcome)
+    }
+
+    /// Pay a block reward to the current epoch's validators and the given
+    /// sequencers; it accrues in the staking ledger
+    pub fn distribute_block_reward(&mut self, height: u64, sequencers: &[String]) -> BlockRewardDistribution {
+        self.staking.distribute_block_reward(height, sequencers)
+    }
+
+    /// Base block height the next epoch starts at
+    pub fn next_boundary(&self) -> u64 {
+        self.current.start_height + self.config.epoch_length
+    }
+
+    /// Called once a base block is final; opens the next epoch after its
+    /// last block. Returns whether the set changed epoch.
+    pub fn on_finalized(&mut self, height: u64) -> bool {
+        if height + 1 < self.next_boundary() {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    fn advance(&mut self) {
+        let number = self.current.number + 1;
+        let start_height = self.next_boundary();
+
+        for entry in self.staking.release_unbonded(number) {
+            info!("Released {} unbonded from {}", entry.amount, entry.validator);
+        }
+
+        let eligible = self.eligible();
+        let mut active: HashSet<String> = self
+            .current
+            .validators
+            .validators
+            .iter()
+            .filter(|(_, info)| info.is_active)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        // Queues hold only validators whose status still needs to change
+        self.exit_queue.retain(|id| active.contains(id) && !eligible.contains(id));
+        self.activation_queue.retain(|id| !active.contains(id) && eligible.contains(id));
+        // Lowest stake leaves first
+        let stakes = &self.current.validators.validators;
+        let mut leaving: Vec<&String> = active.iter().filter(|id| !eligible.contains(*id)).collect();
+        leaving.sort_by_key(|id| (stakes[*id].stake, *id));
+        for id in leaving {
+            if !self.exit_queue.contains(id) {
+                self.exit_queue.push_back(id.clone());
+            }
+        }
+        for id in self.ranked().into_iter().filter(|id| eligible.contains(id)) {
+            if !active.contains(&id) && !self.activation_queue.contains(&id) {
+                self.activation_queue.push_back(id);
+            }
+        }
+
+        for _ in 0..self.config.max_exits_per_epoch {
+            let Some(id) = self.exit_queue.pop_front() else { break };
+            active.remove(&id);
+            if self.exit_requests.remove(&id) {
+                self.unbond_all(&id, number);
+            }
+            info!("Validator {} leaves the set in epoch {}", id, number);
+        }
+        for _ in 0..self.config.max_activations_per_epoch {
+            if active.len() >= self.config.max_validators {
+                break;
+            }
+            let Some(id) = self.activation_queue.pop_front() else { break };
+            info!("Validator {} joins the set in epoch {}", id, number);
+            active.insert(id);
+        }
+
+        self.install(number, start_height, active);
+    }
+
+    /// Unbond a leaving validator's self-stake and delegations
+    fn unbond_all(&mut self, id: &str, epoch: u64) {
+        let Some(validator) = self.staking.validator(id) else { return };
+        let self_stake = validator.self_stake;
+        let delegations: Vec<(String, Decimal)> =
+            validator.delegators.iter().map(|(d, amount)| (d.clone(), *amount)).collect();
+        let release_epoch = epoch + self.config.unbonding_epochs;
+        if self_stake > Decimal::ZERO {
+            let _ = self.staking.unbond(id, None, self_stake, release_epoch);
+        }
+        for (delegator, amount) in delegations {
+            let _ = self.staking.unbond(id, Some(&delegator), amount, release_epoch);
+        }
+    }
+
+    /// Registered validators by total stake, highest first, ties by id
+    fn ranked(&self) -> Vec<String> {
+        let mut candidates: Vec<&ValidatorStake> = self
+            .staking
+            .validators()
+            .filter(|v| !v.jailed && self.keys.contains_key(&v.operator))
+            .filter(|v| !self.exit_requests.contains(&v.operator))
+            .filter(|v| v.total_stake() >= Decimal::from(self.config.min_stake))
+            .collect();
+        candidates.sort_by(|a, b| b.total_stake().cmp(&a.total_stake()).then_with(|| a.operator.cmp(&b.operator)));
+        candidates.into_iter().map(|v| v.operator.clone()).collect()
+    }
+
+    /// Validators the stake ranking puts in the set
+    fn eligible(&self) -> HashSet<String> {
+        self.ranked().into_iter().take(self.config.max_validators).collect()
+    }
+
+    fn install(&mut self, number: u64, start_height: u64, active: HashSet<String>) {
+        let mut validators = ValidatorSet::new();
+        let mut ids: Vec<&String> = active.iter().collect();
+        ids.sort();
+        for id in ids {
+            let stake = self.staking.validator(id).map_or(Decimal::ZERO, |v| v.total_stake());
+            validators.add_validator(
+                id.clone(),
+                ValidatorInfo { public_key: self.keys[id].clone(), stake: voting_stake(stake), is_active: true },
+            );
+        }
+        info!(
+            "Epoch {} from height {}: {} validators, {} total stake",
+            number,
+            start_height,
+            validators.validators.len(),
+            validators.total_stake
+        );
+        self.staking.set_active_validators(active);
+        self.current = Epoch { number, start_height, validators };
+    }
+}
+
+/// Whole-token voting weight of a stake
+fn voting_stake(stake: Decimal) -> u64 {
+    stake.trunc().to_u64().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{Address, KeyPair, SignatureAlgorithm};
+    use rust_decimal_macros::dec;
+
+    fn genesis(stakes: &[u64]) -> (ValidatorSet, Vec<String>) {
+        let mut set = ValidatorSet::new();
+        let mut ids = Vec::new();
+        for stake in stakes {
+            let key = KeyPair::generate(SignatureAlgorithm::Dilithium);
+            let id = key.address().to_string();
+            set.add_validator(id.clone(), ValidatorInfo { public_key: key.public_key, stake: *stake, is_active: true });
+            ids.push(id);
+        }
+        (set, ids)
+    }
+
+    fn config() -> EpochConfig {
+        EpochConfig {
+            epoch_length: 10,
+            max_validators: 3,
+            min_stake: 10,
+            max_activations_per_epoch: 1,
+            max_exits_per_epoch: 1,
+            unbonding_epochs: 2,
+            ..EpochConfig::default()
+        }
+    }
+
+    fn candidate(stake: Decimal) -> (ValidatorStake, PublicKeyData) {
+        let key = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let stake = ValidatorStake {
+            operator: key.address().to_string(),
+            self_stake: stake,
+            delegated_stake: Decimal::ZERO,
+            commission_rate: Decimal::ZERO,
+            delegators: HashMap::new(),
+            jailed: false,
+        };
+        (stake, key.public_key)
+    }
+
+    #[test]
+    fn test_genesis_selects_top_stake() {
+        let (set, ids) = genesis(&[100, 50, 5, 80, 70]);
+        let epochs = EpochManager::new(config(), &set).unwrap();
+        let validators = &epochs.validators().validators;
+        assert_eq!(validators.len(), 3);
+        assert!(validators.contains_key(&ids[0]) && validators.contains_key(&ids[3]) && validators.contains_key(&ids[4]));
+        assert_eq!(epochs.validators().total_stake, 250);
+    }
+
+    #[test]
+    fn test_stake_changes_weight_at_boundary() {
+        let (set, ids) = genesis(&[100, 100, 100]);
+        let mut epochs = EpochManager::new(config(), &set).unwrap();
+        let (first, second): (Address, Address) = (ids[0].parse().unwrap(), ids[1].parse().unwrap());
+        let delegate = StakingOp::Delegate { validator: first };
+        epochs.apply(&StakingAction { staker: Address::EVM([0xa1; 20]), op: delegate, amount: dec!(200) }).unwrap();
+        // An operator unbonds its self-stake
+        let unbond = StakingOp::Unbond { validator: second, amount: dec!(40) };
+        epochs.apply(&StakingAction { staker: second, op: unbond, amount: Decimal::ZERO }).unwrap();
+
+        // Nothing changes mid-epoch
+        assert!(!epochs.on_finalized(8));
+        assert_eq!(epochs.validators().validators[&ids[0]].stake, 100);
+
+        assert!(epochs.on_finalized(9));
+        assert_eq!(epochs.current().number, 1);
+        assert_eq!(epochs.current().start_height, 10);
+        assert_eq!(epochs.validators().validators[&ids[0]].stake, 300);
+        assert_eq!(epochs.validators().validators[&ids[1]].stake, 60);
+        assert_eq!(epochs.validators().total_stake, 460);
+
+        // The unbonded stake is released two epochs after it was withdrawn
+        assert_eq!(epochs.unbonding().len(), 1);
+        epochs.on_finalized(19);
+        assert_eq!(epochs.unbonding().len(), 0);
+    }
+
+    #[test]
+    fn test_activation_and_exit_queues_limit_churn() {
+        let (set, ids) = genesis(&[100, 90, 80]);
+        let mut epochs = EpochManager::new(config(), &set).unwrap();
+
+        // Two stronger candidates: one swap per epoch
+        let (first, first_key) = candidate(dec!(500));
+        let (second, second_key) = candidate(dec!(400));
+        let (first_id, second_id) = (first.operator.clone(), second.operator.clone());
+        epochs.register(first, first_key).unwrap();
+        epochs.register(second, second_key).unwrap();
+
+        epochs.on_finalized(9);
+        let validators = &epochs.validators().validators;
+        assert!(validators.contains_key(&first_id) && !validators.contains_key(&second_id));
+        assert!(!validators.contains_key(&ids[2]));
+        assert_eq!(epochs.activation_queue().collect::<Vec<_>>(), vec![&second_id]);
+        assert_eq!(epochs.exit_queue().collect::<Vec<_>>(), vec![&ids[1]]);
+
+        epochs.on_finalized(19);
+        let validators = &epochs.validators().validators;
+        assert_eq!(validators.len(), 3);
+        assert!(validators.contains_key(&second_id) && validators.contains_key(&ids[0]));
+        assert_eq!(epochs.activation_queue().count() + epochs.exit_queue().count(), 0);
+    }
+
+    #[test]
+    fn test_exit_request_unbonds_stake() {
+        let (set, ids) = genesis(&[100, 100, 100]);
+        let mut epochs = EpochManager::new(config(), &set).unwrap();
+        epochs.request_exit(&ids[2]).unwrap();
+
+        epochs.on_finalized(9);
+        assert!(!epochs.validators().validators.contains_key(&ids[2]));
+        assert_eq!(epochs.staking().validator(&ids[2]).unwrap().total_stake(), Decimal::ZERO);
+        assert_eq!(epochs.unbonding()[0].release_epoch, 3);
+    }
+
+    #[test]
+    fn test_slashed_validator_leaves_at_once() {
+        let (set, ids) = genesis(&[100, 100, 100]);
+        let mut epochs = EpochManager::new(config(), &set).unwrap();
+        let vote = |block_hash: &str| crate::finality::Vote::Prepare {
+            view: 1,
+            block_hash: block_hash.to_string(),
+            validator_id: ids[0].clone(),
+            signature: vec![],
+        };
+        let evidence = EquivocationEvidence { first: vote("aa"), second: vote("bb") };
+        epochs.slash_double_sign(&evidence, &mut EmissionSchedule::default()).unwrap();
+        assert!(!epochs.validators().validators[&ids[0]].is_active);
+        assert_eq!(epochs.validators().total_stake, 200);
+
+        // Jailed validators are not selected again, nor paid meanwhile
+        assert!(!epochs.distribute_block_reward(0, &[]).validator_rewards.contains_key(&ids[0]));
+        epochs.on_finalized(9);
+        assert!(!epochs.validators().validators.contains_key(&ids[0]));
+    }
+}
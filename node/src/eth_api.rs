@@ -0,0 +1,1670 @@
+// Ethereum JSON-RPC read API served from the sequencer's applied state
+// Block numbers are micro-block sequence numbers; account queries read the
+// state trie at the requested block's state root.
+
+use alloy_primitives::{Bloom, BloomInput};
+use alloy_rlp::Header;
+use parking_lot::Mutex;
+use rand::Rng;
+use revm::db::CacheDB;
+use revm::{Database, DatabaseRef};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::GasConfig;
+use crate::cross_shard::CROSS_SHARD_ROUTER;
+use crate::crypto::{Address, Signature, SignatureAlgorithm};
+use crate::evm_executor::{BlockContext, EvmExecutionResult, EvmExecutor, EvmTransaction, StateView};
+use crate::eth_tx;
+use crate::sequencer::{Log, MicroBlock, TxReceipt};
+use crate::staking::STAKING_ACCOUNT;
+use crate::state_db::StateStore;
+use crate::state_trie::{self, AccountLeaf, EMPTY_CODE_HASH};
+use crate::tracer::{CallTracer, CallTracerConfig, StructLogger, StructLoggerConfig};
+use crate::transaction::Transaction;
+
+/// Gas limit reported for micro-blocks (they are bounded by transaction
+/// count, not by gas)
+pub const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// keccak256(rlp([])): hash of an empty uncle list
+const EMPTY_UNCLES_HASH: &str = "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347";
+
+/// Intrinsic gas of a plain call; no transaction can succeed with less
+const TX_BASE_GAS: u64 = 21_000;
+
+/// Most blocks one eth_getLogs or eth_getFilterLogs query may span
+pub const MAX_LOG_RANGE: u64 = 10_000;
+
+/// Most filters installed at once
+const MAX_FILTERS: usize = 1_000;
+
+/// Filters not polled for this long are uninstalled (as geth)
+const FILTER_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Most storage slots one eth_getProof request may prove
+const MAX_PROOF_KEYS: usize = 1_000;
+
+/// Error returned to JSON-RPC clients
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: i32,
+    pub message: String,
+    /// Extra error data (revert output for failed calls)
+    pub data: Option<Value>,
+}
+
+impl ApiError {
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self { code: -32602, message: message.into(), data: None }
+    }
+
+    fn filter_not_found() -> Self {
+        Self { code: -32000, message: "filter not found".to_string(), data: None }
+    }
+
+    /// Failed EVM execution: reverts carry their output (code 3, as geth)
+    fn execution(result: &EvmExecutionResult) -> Self {
+        match &result.halt_reason {
+            Some(reason) => Self {
+                code: -32000,
+                message: format!("execution halted: {}", reason),
+                data: None,
+            },
+            None => Self {
+                code: 3,
+                message: match &result.revert_reason {
+                    Some(reason) => format!("execution reverted: {}", reason),
+                    None => "execution reverted".to_string(),
+                },
+                data: Some(json!(data(&result.output))),
+            },
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self { code: -32603, message: e.to_string(), data: None }
+    }
+}
+
+pub type ApiResult = std::result::Result<Value, ApiError>;
+
+/// Block selector of a JSON-RPC block parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockTag {
+    Latest,
+    Number(u64),
+}
+
+impl BlockTag {
+    fn number(self, latest: u64) -> u64 {
+        match self {
+            BlockTag::Latest => latest,
+            BlockTag::Number(n) => n,
+        }
+    }
+}
+
+/// Filter installed by eth_newFilter or eth_newBlockFilter and polled with
+/// eth_getFilterChanges
+struct InstalledFilter {
+    kind: FilterKind,
+    /// First block whose changes were not reported yet
+    next_block: u64,
+    last_poll: Instant,
+}
+
+enum FilterKind {
+    Logs { from: BlockTag, to: BlockTag, filter: LogFilter },
+    Blocks,
+}
+
+/// Read-only Ethereum API over a state store, plus the poll filters
+/// installed through it
+#[derive(Clone)]
+pub struct EthApi {
+    state: Arc<dyn StateStore>,
+    /// Chain ID of the shard served, as configured
+    chain_id: u64,
+    filters: Arc<Mutex<HashMap<String, InstalledFilter>>>,
+    /// Precompile prices, as the sequencer charges them
+    gas: GasConfig,
+}
+
+impl EthApi {
+    pub fn new(state: Arc<dyn StateStore>, chain_id: u64) -> Self {
+        Self { state, chain_id, filters: Arc::default(), gas: GasConfig::default() }
+    }
+
+    pub fn with_gas_config(mut self, gas: GasConfig) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// eth_blockNumber: sequence of the latest micro-block
+    pub fn block_number(&self) -> ApiResult {
+        Ok(json!(quantity(self.latest_number()?)))
+    }
+
+    /// eth_getBalance(address, block): balance in wei
+    pub fn get_balance(&self, params: &[Value]) -> ApiResult {
+        let account = self.account_at(params)?;
+        Ok(json!(quantity(account.balance)))
+    }
+
+    /// eth_getTransactionCount(address, block): account nonce
+    pub fn get_transaction_count(&self, params: &[Value]) -> ApiResult {
+        let account = self.account_at(params)?;
+        Ok(json!(quantity(account.nonce)))
+    }
+
+    /// eth_getCode(address, block)
+    pub fn get_code(&self, params: &[Value]) -> ApiResult {
+        let account = self.account_at(params)?;
+        if account.code_hash == EMPTY_CODE_HASH {
+            return Ok(json!("0x"));
+        }
+        // Contract code is stored in the node table under its code hash
+        let code = self.state.trie_node(&account.code_hash)?.unwrap_or_default();
+        Ok(json!(data(&code)))
+    }
+
+    /// eth_getStorageAt(address, slot, block)
+    pub fn get_storage_at(&self, params: &[Value]) -> ApiResult {
+        let address = param_address(params, 0)?;
+        let slot = param_word(params, 1)?;
+        let root = self.state_root(param_block_tag(params.get(2))?)?;
+        let account = state_trie::get_account(self.state.as_ref(), root, &address)?
+            .unwrap_or_else(AccountLeaf::empty);
+        let value = state_trie::get_storage(self.state.as_ref(), account.storage_root, &slot)?;
+        Ok(json!(data(&value)))
+    }
+
+    /// eth_getProof(address, storageKeys, block): EIP-1186 account and
+    /// storage proofs. The nodes are this chain's trie nodes, whose children
+    /// are always referenced by hash and whose storage values are the slot
+    /// word without leading zeros; `state_trie::verify_proof` checks them.
+    pub fn get_proof(&self, params: &[Value]) -> ApiResult {
+        let address = param_address(params, 0)?;
+        let keys = params
+            .get(1)
+            .and_then(Value::as_array)
+            .ok_or_else(|| ApiError::invalid_params("Missing storage keys"))?;
+        if keys.len() > MAX_PROOF_KEYS {
+            return Err(ApiError::invalid_params(format!("At most {} storage keys per proof", MAX_PROOF_KEYS)));
+        }
+        let root = self.state_root(param_block_tag(params.get(2))?)?;
+
+        let proof = state_trie::prove_account(self.state.as_ref(), root, &address)?;
+        let account = proof.account.unwrap_or_else(AccountLeaf::empty);
+        let mut storage_proof = Vec::with_capacity(keys.len());
+        for (index, key) in keys.iter().enumerate() {
+            let slot = param_word(keys, index)?;
+            let value = state_trie::get_storage(self.state.as_ref(), account.storage_root, &slot)?;
+            let nodes = state_trie::prove_storage(self.state.as_ref(), account.storage_root, &slot)?;
+            storage_proof.push(json!({
+                "key": key,
+                "value": format!("{:#x}", alloy_primitives::U256::from_be_bytes(value)),
+                "proof": nodes.iter().map(|node| data(node)).collect::<Vec<_>>(),
+            }));
+        }
+
+        Ok(json!({
+            "address": address.to_string(),
+            "accountProof": proof.proof.iter().map(|node| data(node)).collect::<Vec<_>>(),
+            "balance": quantity(account.balance),
+            "nonce": quantity(account.nonce),
+            "codeHash": data(&account.code_hash),
+            "storageHash": data(&account.storage_root),
+            "storageProof": storage_proof,
+        }))
+    }
+
+    /// eth_getBlockByNumber(block, fullTransactions)
+    pub fn get_block_by_number(&self, params: &[Value]) -> ApiResult {
+        let number = param_block_tag(params.first())?.number(self.latest_number()?);
+        let full = params.get(1).and_then(Value::as_bool).unwrap_or(false);
+        match self.state.micro_block(number)? {
+            Some(block) => Ok(block_json(&block, full)),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// eth_getBlockByHash(hash, fullTransactions)
+    pub fn get_block_by_hash(&self, params: &[Value]) -> ApiResult {
+        let hash = param_hash(params, 0)?;
+        let full = params.get(1).and_then(Value::as_bool).unwrap_or(false);
+        match self.state.micro_block_by_hash(&hash)? {
+            Some(block) => Ok(block_json(&block, full)),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// eth_getTransactionByHash(hash)
+    pub fn get_transaction_by_hash(&self, params: &[Value]) -> ApiResult {
+        let hash = param_hash(params, 0)?;
+        match self.locate(&hash)? {
+            Some((block, index)) => Ok(transaction_json(&block.transactions[index], &block, index)),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// eth_getTransactionReceipt(hash)
+    pub fn get_transaction_receipt(&self, params: &[Value]) -> ApiResult {
+        let hash = param_hash(params, 0)?;
+        let Some((block, index)) = self.locate(&hash)? else {
+            return Ok(Value::Null);
+        };
+        match block.receipts.get(index) {
+            Some(receipt) => Ok(receipt_json(receipt, &block.transactions[index], &block, index)),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// eth_getLogs(filter): logs of one block (`blockHash`) or of a block
+    /// range matching the address and topic criteria
+    pub fn get_logs(&self, params: &[Value]) -> ApiResult {
+        let query = LogQuery::parse(params.first())?;
+        if let Some(hash) = query.block_hash {
+            let block = self
+                .state
+                .micro_block_by_hash(&hash)?
+                .ok_or_else(|| ApiError::invalid_params(format!("Unknown block {}", data(&hash))))?;
+            return Ok(json!(block_logs(&block, &query.filter)));
+        }
+        let latest = self.latest_number()?;
+        let (from, to) = (query.from.number(latest), query.to.number(latest));
+        check_log_range(from, to)?;
+        Ok(json!(self.logs_in_range(from, to, &query.filter)?))
+    }
+
+    /// eth_newFilter(filter): install a log filter, returning its ID
+    pub fn new_filter(&self, params: &[Value]) -> ApiResult {
+        let query = LogQuery::parse(params.first())?;
+        if query.block_hash.is_some() {
+            return Err(ApiError::invalid_params("blockHash is only supported by eth_getLogs"));
+        }
+        self.install(FilterKind::Logs { from: query.from, to: query.to, filter: query.filter })
+    }
+
+    /// eth_newBlockFilter: install a filter reporting new block hashes
+    pub fn new_block_filter(&self) -> ApiResult {
+        self.install(FilterKind::Blocks)
+    }
+
+    /// eth_getFilterChanges(id): logs or block hashes since the last poll.
+    /// A log filter fails once more than `MAX_LOG_RANGE` blocks went
+    /// unpolled; a block filter reports at most that many per poll.
+    pub fn get_filter_changes(&self, params: &[Value]) -> ApiResult {
+        let id = param_filter_id(params)?;
+        // Blocks below `head` exist; report those not seen yet
+        let head = self.next_sequence()?;
+
+        // Claim the unseen range under the lock, read the blocks after
+        let (range, filter) = {
+            let mut filters = self.filters.lock();
+            let installed = filters.get_mut(&id).ok_or_else(ApiError::filter_not_found)?;
+            let next = installed.next_block;
+            installed.last_poll = Instant::now();
+            if next >= head {
+                return Ok(json!([]));
+            }
+            let latest = head - 1;
+            match &installed.kind {
+                FilterKind::Logs { from: start, to: end, filter } => {
+                    let from = next.max(start.number(latest));
+                    let to = end.number(latest).min(latest);
+                    // Past a too-wide range even on error, or every later poll fails too
+                    installed.next_block = head;
+                    if from <= to {
+                        check_log_range(from, to)?;
+                    }
+                    ((from, to), Some(filter.clone()))
+                }
+                FilterKind::Blocks => {
+                    let to = latest.min(next + MAX_LOG_RANGE - 1);
+                    installed.next_block = to + 1;
+                    ((next, to), None)
+                }
+            }
+        };
+
+        let (from, to) = range;
+        let changes = match filter {
+            _ if from > to => Vec::new(),
+            Some(filter) => self.logs_in_range(from, to, &filter)?,
+            None => {
+                let mut hashes = Vec::new();
+                for sequence in from..=to {
+                    if let Some(block) = self.state.micro_block(sequence)? {
+                        hashes.push(json!(data(&block.hash())));
+                    }
+                }
+                hashes
+            }
+        };
+        Ok(json!(changes))
+    }
+
+    /// eth_getFilterLogs(id): every log matching a log filter's full range
+    pub fn get_filter_logs(&self, params: &[Value]) -> ApiResult {
+        let id = param_filter_id(params)?;
+        let latest = self.latest_number()?;
+        let (from, to, filter) = {
+            let mut filters = self.filters.lock();
+            let installed = filters.get_mut(&id).ok_or_else(ApiError::filter_not_found)?;
+            installed.last_poll = Instant::now();
+            match &installed.kind {
+                FilterKind::Logs { from, to, filter } => (from.number(latest), to.number(latest), filter.clone()),
+                FilterKind::Blocks => return Err(ApiError::filter_not_found()),
+            }
+        };
+        check_log_range(from, to)?;
+        Ok(json!(self.logs_in_range(from, to, &filter)?))
+    }
+
+    /// eth_uninstallFilter(id): whether the filter existed
+    pub fn uninstall_filter(&self, params: &[Value]) -> ApiResult {
+        let id = param_filter_id(params)?;
+        Ok(json!(self.filters.lock().remove(&id).is_some()))
+    }
+
+    /// eth_call(call, block): execute without committing and return the output
+    pub fn call(&self, params: &[Value]) -> ApiResult {
+        let request = CallRequest::parse(params.first())?;
+        let tag = param_block_tag(params.get(1))?;
+        let result = self.simulate(&request, tag, request.gas_limit())?;
+        if !result.success {
+            return Err(ApiError::execution(&result));
+        }
+        Ok(json!(data(&result.output)))
+    }
+
+    /// eth_estimateGas(call, block): smallest gas limit the call succeeds
+    /// with, found by binary search. Native (PQ-signed) transactions also pay
+    /// for signature verification, which the EVM does not see.
+    pub fn estimate_gas(&self, params: &[Value]) -> ApiResult {
+        let request = CallRequest::parse(params.first())?;
+        let tag = param_block_tag(params.get(1))?;
+        let cap = request.gas_limit();
+
+        let result = self.simulate(&request, tag, cap)?;
+        if !result.success {
+            return Err(ApiError::execution(&result));
+        }
+
+        // Invariant: `lo` fails, `hi` succeeds. Refunds make gas_used a
+        // lower bound only, so the search starts just below it.
+        let mut lo = result.gas_used.max(TX_BASE_GAS) - 1;
+        let mut hi = cap;
+        while lo + 1 < hi {
+            let mid = lo + (hi - lo) / 2;
+            // Too little gas can also fail validation outright
+            let succeeded = self
+                .simulate(&request, tag, mid)
+                .map(|r| r.success)
+                .unwrap_or(false);
+            if succeeded {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+
+        let signature_gas = request
+            .signature_algorithm()
+            .map_or(0, |algorithm| self.gas.signature_verification(algorithm));
+        Ok(json!(quantity(hi + signature_gas)))
+    }
+
+    /// debug_traceTransaction(hash, options): re-execute an included
+    /// transaction on the state it ran against, after the earlier
+    /// transactions of its micro-block, and trace it
+    pub fn trace_transaction(&self, params: &[Value]) -> ApiResult {
+        let hash = param_hash(params, 0)?;
+        let options = TraceOptions::parse(params.get(1))?;
+        let Some((block, index)) = self.locate(&hash)? else {
+            return Err(ApiError { code: -32000, message: "transaction not found".to_string(), data: None });
+        };
+
+        // Senders, and native recipients, are seen at their 20-byte form
+        let mut view = StateView::new(self.state.as_ref(), self.parent_root(block.sequence)?);
+        for tx in &block.transactions[..=index] {
+            view = view.with_account(tx.from);
+            if let Some(to @ Address::Native(_)) = tx.to {
+                view = view.with_account(to);
+            }
+        }
+        let mut db = CacheDB::new(view);
+        let executor = self.executor();
+        let context = BlockContext { number: block.sequence, timestamp: block.timestamp, ..BlockContext::default() };
+        for (tx, receipt) in block.transactions[..index].iter().zip(&block.receipts) {
+            replay_transaction(&executor, &mut db, &context, tx, receipt)?;
+        }
+
+        let tx = &block.transactions[index];
+        trace(&executor, &mut db, &context, &executor.transaction(tx)?, options, self.gas.signature_gas(tx))
+    }
+
+    /// debug_traceCall(call, block, options): trace a call on a block's
+    /// state as the sequencer would run it, without committing
+    pub fn trace_call(&self, params: &[Value]) -> ApiResult {
+        let request = CallRequest::parse(params.first())?;
+        let tag = param_block_tag(params.get(1))?;
+        let options = TraceOptions::parse(params.get(2))?;
+
+        let view = self.call_view(&request, tag)?;
+        let mut tx = request.to_evm(request.gas_limit());
+        tx.nonce = view.basic_ref(tx.from)?.map_or(0, |account| account.nonce);
+        let executor = self.executor();
+        trace(&executor, CacheDB::new(view), &self.block_context(tag)?, &tx, options, 0)
+    }
+
+    /// Run a call request against a block's state without committing
+    fn simulate(&self, request: &CallRequest, tag: BlockTag, gas_limit: u64) -> Result<EvmExecutionResult, ApiError> {
+        let block = self.block_context(tag)?;
+        let view = self.call_view(request, tag)?;
+        let executor = self.executor();
+        Ok(executor.simulate(view, &block, &request.to_evm(gas_limit))?)
+    }
+
+    fn executor(&self) -> EvmExecutor {
+        EvmExecutor::new(self.chain_id()).with_gas_config(self.gas.clone())
+    }
+
+    /// Block a call request executes in
+    fn block_context(&self, tag: BlockTag) -> Result<BlockContext, ApiError> {
+        let number = tag.number(self.latest_number()?);
+        Ok(BlockContext {
+            number,
+            timestamp: self.state.micro_block(number)?.map_or(0, |b| b.timestamp),
+            ..BlockContext::default()
+        })
+    }
+
+    /// State a call request reads: a native sender is seen at its 20-byte
+    /// form, as the sequencer does
+    fn call_view(&self, request: &CallRequest, tag: BlockTag) -> Result<StateView<'_>, ApiError> {
+        let view = StateView::new(self.state.as_ref(), self.state_root(tag)?);
+        Ok(match request.from {
+            Some(from) => view.with_account(from),
+            None => view,
+        })
+    }
+
+    /// Included transaction: its micro-block and index within it
+    fn locate(&self, tx_hash: &[u8; 32]) -> Result<Option<(MicroBlock, usize)>, ApiError> {
+        let Some((sequence, index)) = self.state.transaction_location(tx_hash)? else {
+            return Ok(None);
+        };
+        Ok(self
+            .state
+            .micro_block(sequence)?
+            .filter(|block| index < block.transactions.len())
+            .map(|block| (block, index)))
+    }
+
+    /// Register a filter reporting changes after the latest block. Filters
+    /// not polled within the timeout are dropped first.
+    fn install(&self, kind: FilterKind) -> ApiResult {
+        let next_block = self.next_sequence()?;
+        let mut filters = self.filters.lock();
+        filters.retain(|_, installed| installed.last_poll.elapsed() < FILTER_TIMEOUT);
+        if filters.len() >= MAX_FILTERS {
+            return Err(ApiError { code: -32005, message: "Too many filters installed".to_string(), data: None });
+        }
+        let id = format!("0x{}", hex::encode(rand::thread_rng().gen::<[u8; 16]>()));
+        filters.insert(id.clone(), InstalledFilter { kind, next_block, last_poll: Instant::now() });
+        Ok(json!(id))
+    }
+
+    /// Matching logs of `from..=to`, reading only blocks whose bloom may match
+    fn logs_in_range(&self, from: u64, to: u64, filter: &LogFilter) -> Result<Vec<Value>, ApiError> {
+        let mut logs = Vec::new();
+        for (sequence, bloom) in self.state.log_blooms(from, to)? {
+            if !filter.may_match(&bloom) {
+                continue;
+            }
+            if let Some(block) = self.state.micro_block(sequence)? {
+                logs.extend(block_logs(&block, filter));
+            }
+        }
+        Ok(logs)
+    }
+
+    /// Sequence the next micro-block will get
+    fn next_sequence(&self) -> Result<u64, ApiError> {
+        Ok(self.state.head()?.unwrap_or_default().next_sequence)
+    }
+
+    fn latest_number(&self) -> Result<u64, ApiError> {
+        Ok(self.next_sequence()?.saturating_sub(1))
+    }
+
+    /// State root after the given block
+    fn state_root(&self, tag: BlockTag) -> Result<[u8; 32], ApiError> {
+        let root = match tag {
+            BlockTag::Latest => self.state.head()?.unwrap_or_default().state_root,
+            BlockTag::Number(number) => {
+                let head = self.state.head()?.unwrap_or_default();
+                // Before the first micro-block the chain is at its genesis state
+                if number == 0 && head.next_sequence == 0 {
+                    head.state_root
+                } else {
+                    self.state
+                        .micro_block(number)?
+                        .ok_or_else(|| ApiError::invalid_params(format!("Unknown block {}", number)))?
+                        .state_root
+                }
+            }
+        };
+        Ok(state_trie::parse_root(&root)?)
+    }
+
+    /// State root a micro-block was applied to: the previous block's, or
+    /// the genesis state's for the first one
+    fn parent_root(&self, sequence: u64) -> Result<[u8; 32], ApiError> {
+        match sequence.checked_sub(1) {
+            Some(parent) => self.state_root(BlockTag::Number(parent)),
+            None => match self.state.head()? {
+                Some(head) if !head.genesis_root.is_empty() => Ok(state_trie::parse_root(&head.genesis_root)?),
+                _ => Err(ApiError::invalid_params("No genesis state root recorded")),
+            },
+        }
+    }
+
+    /// Account leaf for (address, block) parameters; missing accounts are empty
+    fn account_at(&self, params: &[Value]) -> Result<AccountLeaf, ApiError> {
+        let address = param_address(params, 0)?;
+        let root = self.state_root(param_block_tag(params.get(1))?)?;
+        Ok(state_trie::get_account(self.state.as_ref(), root, &address)?
+            .unwrap_or_else(AccountLeaf::empty))
+    }
+}
+
+/// Gas an EVM-backed method may burn, as batches budget it: the call's gas
+/// limit, or a block's for a replayed transaction. eth_estimateGas searches
+/// below that limit. Other methods reserve none.
+pub fn reserved_gas(method: &str, params: &[Value]) -> u64 {
+    match method {
+        "eth_call" | "eth_estimateGas" | "debug_traceCall" => {
+            CallRequest::parse(params.first()).map_or(BLOCK_GAS_LIMIT, |r| r.gas_limit())
+        }
+        "debug_traceTransaction" => BLOCK_GAS_LIMIT,
+        _ => 0,
+    }
+}
+
+/// Call object of eth_call / eth_estimateGas
+#[derive(Debug, Clone, Default)]
+struct CallRequest {
+    from: Option<Address>,
+    to: Option<Address>,
+    gas: Option<u64>,
+    gas_price: u128,
+    value: u128,
+    data: Vec<u8>,
+    /// Non-standard: signature scheme of the native transaction being estimated
+    signature: Option<SignatureAlgorithm>,
+}
+
+impl CallRequest {
+    fn parse(param: Option<&Value>) -> Result<Self, ApiError> {
+        let Some(Value::Object(call)) = param else {
+            return Err(ApiError::invalid_params("Missing call object"));
+        };
+
+        let address = |key: &str| -> Result<Option<Address>, ApiError> {
+            match call.get(key).and_then(Value::as_str) {
+                Some(raw) => Address::from_str(raw)
+                    .map(Some)
+                    .map_err(|e| ApiError::invalid_params(format!("Invalid {}: {}", key, e))),
+                None => Ok(None),
+            }
+        };
+        let number = |key: &str| -> Result<Option<u128>, ApiError> {
+            match call.get(key).and_then(Value::as_str) {
+                Some(raw) => parse_quantity_u128(raw).map(Some),
+                None => Ok(None),
+            }
+        };
+
+        let data = match call.get("input").or_else(|| call.get("data")).and_then(Value::as_str) {
+            Some(raw) => hex::decode(raw.strip_prefix("0x").unwrap_or(raw))
+                .map_err(|e| ApiError::invalid_params(format!("Invalid input: {}", e)))?,
+            None => Vec::new(),
+        };
+
+        let signature = match call.get("signatureAlgorithm") {
+            Some(algorithm) => Some(
+                serde_json::from_value(algorithm.clone())
+                    .map_err(|e| ApiError::invalid_params(format!("Invalid signatureAlgorithm: {}", e)))?,
+            ),
+            None => None,
+        };
+
+        let gas = number("gas")?
+            .map(|gas| u64::try_from(gas).map_err(|_| ApiError::invalid_params("Gas out of range")))
+            .transpose()?;
+
+        Ok(Self {
+            from: address("from")?,
+            to: address("to")?,
+            gas,
+            gas_price: number("gasPrice")?.or(number("maxFeePerGas")?).unwrap_or(0),
+            value: number("value")?.unwrap_or(0),
+            data,
+            signature,
+        })
+    }
+
+    /// Gas limit to run with: the requested one, at most the block gas limit
+    fn gas_limit(&self) -> u64 {
+        self.gas.map_or(BLOCK_GAS_LIMIT, |gas| gas.min(BLOCK_GAS_LIMIT))
+    }
+
+    /// Signature scheme to charge verification gas for: explicit, or the
+    /// costliest single-key PQ scheme for native senders so the estimate is
+    /// never too low. Plain EVM calls are not charged.
+    fn signature_algorithm(&self) -> Option<SignatureAlgorithm> {
+        match (self.signature, self.from) {
+            (Some(algorithm), _) => Some(algorithm),
+            (None, Some(Address::Native(_))) => Some(SignatureAlgorithm::SPHINCSPlus),
+            _ => None,
+        }
+    }
+
+    fn to_evm(&self, gas_limit: u64) -> EvmTransaction {
+        EvmTransaction {
+            from: self.from.map(|a| a.evm_bytes()).unwrap_or_default().into(),
+            to: self.to.map(|a| a.evm_bytes().into()),
+            value: alloy_primitives::U256::from(self.value),
+            data: self.data.clone().into(),
+            gas_limit,
+            gas_price: alloy_primitives::U256::from(self.gas_price),
+            nonce: 0,
+            access_list: Vec::new(),
+        }
+    }
+}
+
+/// Tracer selected by the options of debug_traceTransaction / debug_traceCall
+enum TraceOptions {
+    StructLogs(StructLoggerConfig),
+    Calls(CallTracerConfig),
+}
+
+impl TraceOptions {
+    /// Without a `tracer` the struct logger runs, configured by the
+    /// top-level fields; `callTracer` reads `tracerConfig`
+    fn parse(param: Option<&Value>) -> Result<Self, ApiError> {
+        let invalid = |e: serde_json::Error| ApiError::invalid_params(format!("Invalid trace options: {}", e));
+        let options = match param {
+            None | Some(Value::Null) => return Ok(Self::StructLogs(StructLoggerConfig::default())),
+            Some(options @ Value::Object(_)) => options,
+            Some(_) => return Err(ApiError::invalid_params("Invalid trace options")),
+        };
+        match options.get("tracer").and_then(Value::as_str) {
+            None => Ok(Self::StructLogs(serde_json::from_value(options.clone()).map_err(invalid)?)),
+            Some("callTracer") => match options.get("tracerConfig") {
+                None | Some(Value::Null) => Ok(Self::Calls(CallTracerConfig::default())),
+                Some(config) => Ok(Self::Calls(serde_json::from_value(config.clone()).map_err(invalid)?)),
+            },
+            Some(tracer) => Err(ApiError::invalid_params(format!("Unsupported tracer: {}", tracer))),
+        }
+    }
+}
+
+/// Execute `tx` under the selected tracer. `signature_gas` is what the
+/// sequencer charged on top of the EVM, reported in the totals.
+fn trace<DB>(
+    executor: &EvmExecutor,
+    db: DB,
+    block: &BlockContext,
+    tx: &EvmTransaction,
+    options: TraceOptions,
+    signature_gas: u64,
+) -> ApiResult
+where
+    DB: Database,
+    DB::Error: std::fmt::Debug,
+{
+    match options {
+        TraceOptions::StructLogs(config) => {
+            let mut logger = StructLogger::new(config);
+            let mut result = executor.inspect(db, block, tx, &mut logger)?;
+            result.gas_used += signature_gas;
+            Ok(json!(logger.finish(&result)))
+        }
+        TraceOptions::Calls(config) => {
+            let mut tracer = CallTracer::new(config);
+            let mut result = executor.inspect(db, block, tx, &mut tracer)?;
+            result.gas_used += signature_gas;
+            Ok(json!(tracer.finish(&result, tx.gas_limit + signature_gas)))
+        }
+    }
+}
+
+/// Re-apply an earlier transaction of a micro-block to `db`: contract
+/// transactions run on the EVM, native transfers move their value. The
+/// sender then pays the fee from its receipt. Fee shares and cross-shard
+/// claims are not replayed.
+fn replay_transaction(
+    executor: &EvmExecutor,
+    db: &mut CacheDB<StateView>,
+    block: &BlockContext,
+    tx: &Transaction,
+    receipt: &TxReceipt,
+) -> Result<(), ApiError> {
+    // Transactions that were not valid changed nothing
+    if receipt.gas_used == 0 {
+        return Ok(());
+    }
+    let from = tx.from.evm_bytes().into();
+    let runs_on_evm = match tx.to {
+        None => true,
+        Some(CROSS_SHARD_ROUTER | STAKING_ACCOUNT) => false,
+        Some(to) => {
+            !tx.data.is_empty() || db.basic(to.evm_bytes().into())?.is_some_and(|account| !account.is_empty_code_hash())
+        }
+    };
+
+    if runs_on_evm {
+        executor.replay(db, block, &executor.transaction(tx)?)?;
+    } else {
+        let value = alloy_primitives::U256::from(tx.value_wei()?);
+        let mut sender = db.basic(from)?.unwrap_or_default();
+        sender.nonce += 1;
+        if receipt.success {
+            sender.balance = sender.balance.saturating_sub(value);
+        }
+        db.insert_account_info(from, sender);
+        if let (true, Some(to)) = (receipt.success, tx.to.filter(|to| *to != CROSS_SHARD_ROUTER)) {
+            let to = to.evm_bytes().into();
+            let mut recipient = db.basic(to)?.unwrap_or_default();
+            recipient.balance = recipient.balance.saturating_add(value);
+            db.insert_account_info(to, recipient);
+        }
+    }
+
+    let mut sender = db.basic(from)?.unwrap_or_default();
+    sender.balance = sender.balance.saturating_sub(alloy_primitives::U256::from(receipt.fee));
+    db.insert_account_info(from, sender);
+    Ok(())
+}
+
+/// Block range and criteria of eth_getLogs and eth_newFilter
+struct LogQuery {
+    from: BlockTag,
+    to: BlockTag,
+    block_hash: Option<[u8; 32]>,
+    filter: LogFilter,
+}
+
+impl LogQuery {
+    /// `fromBlock`/`toBlock` (default latest) or `blockHash`, plus the
+    /// address and topic criteria
+    fn parse(param: Option<&Value>) -> Result<Self, ApiError> {
+        let filter = LogFilter::parse(param)?;
+        let Some(Value::Object(query)) = param else {
+            return Ok(Self { from: BlockTag::Latest, to: BlockTag::Latest, block_hash: None, filter });
+        };
+        let from = param_block_tag(query.get("fromBlock"))?;
+        let to = param_block_tag(query.get("toBlock"))?;
+        let block_hash = match query.get("blockHash") {
+            None | Some(Value::Null) => None,
+            Some(hash) => {
+                if query.contains_key("fromBlock") || query.contains_key("toBlock") {
+                    return Err(ApiError::invalid_params("blockHash cannot be combined with fromBlock/toBlock"));
+                }
+                Some(param_hash(std::slice::from_ref(hash), 0)?)
+            }
+        };
+        Ok(Self { from, to, block_hash, filter })
+    }
+}
+
+/// Range limits of a user-supplied log query
+fn check_log_range(from: u64, to: u64) -> Result<(), ApiError> {
+    if from > to {
+        return Err(ApiError::invalid_params("fromBlock is after toBlock"));
+    }
+    if to - from >= MAX_LOG_RANGE {
+        return Err(ApiError::invalid_params(format!("Block range exceeds {} blocks", MAX_LOG_RANGE)));
+    }
+    Ok(())
+}
+
+fn param_filter_id(params: &[Value]) -> Result<String, ApiError> {
+    params
+        .first()
+        .and_then(Value::as_str)
+        .map(str::to_lowercase)
+        .ok_or_else(|| ApiError::invalid_params("Missing filter ID"))
+}
+
+/// Parse a block parameter; a missing parameter means "latest"
+pub fn param_block_tag(param: Option<&Value>) -> Result<BlockTag, ApiError> {
+    match param {
+        None | Some(Value::Null) => Ok(BlockTag::Latest),
+        Some(Value::String(tag)) => match tag.as_str() {
+            "latest" | "pending" | "safe" | "finalized" => Ok(BlockTag::Latest),
+            "earliest" => Ok(BlockTag::Number(0)),
+            number => parse_quantity(number).map(BlockTag::Number),
+        },
+        Some(other) => Err(ApiError::invalid_params(format!("Invalid block parameter: {}", other))),
+    }
+}
+
+pub fn param_address(params: &[Value], index: usize) -> Result<Address, ApiError> {
+    let raw = params
+        .get(index)
+        .and_then(Value::as_str)
+        .ok_or_else(|| ApiError::invalid_params("Missing address"))?;
+    Address::from_str(raw).map_err(|e| ApiError::invalid_params(e.to_string()))
+}
+
+/// 32-byte hash parameter
+pub fn param_hash(params: &[Value], index: usize) -> Result<[u8; 32], ApiError> {
+    let raw = params
+        .get(index)
+        .and_then(Value::as_str)
+        .ok_or_else(|| ApiError::invalid_params("Missing hash"))?;
+    let bytes = hex::decode(raw.strip_prefix("0x").unwrap_or(raw))
+        .map_err(|e| ApiError::invalid_params(format!("Invalid hash: {}", e)))?;
+    bytes
+        .try_into()
+        .map_err(|_| ApiError::invalid_params("Hash must be 32 bytes"))
+}
+
+/// Storage slot parameter: a quantity of up to 32 bytes, left-padded
+fn param_word(params: &[Value], index: usize) -> Result<[u8; 32], ApiError> {
+    let raw = params
+        .get(index)
+        .and_then(Value::as_str)
+        .ok_or_else(|| ApiError::invalid_params("Missing storage slot"))?;
+    let mut digits = raw.strip_prefix("0x").unwrap_or(raw).to_string();
+    if digits.len() % 2 == 1 {
+        digits.insert(0, '0');
+    }
+    let bytes = hex::decode(&digits)
+        .map_err(|e| ApiError::invalid_params(format!("Invalid storage slot: {}", e)))?;
+    if bytes.len() > 32 {
+        return Err(ApiError::invalid_params("Storage slot longer than 32 bytes"));
+    }
+    let mut word = [0u8; 32];
+    word[32 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(word)
+}
+
+fn parse_quantity_u128(raw: &str) -> Result<u128, ApiError> {
+    let digits = raw
+        .strip_prefix("0x")
+        .ok_or_else(|| ApiError::invalid_params(format!("Invalid quantity: {}", raw)))?;
+    u128::from_str_radix(digits, 16)
+        .map_err(|_| ApiError::invalid_params(format!("Invalid quantity: {}", raw)))
+}
+
+fn parse_quantity(raw: &str) -> Result<u64, ApiError> {
+    u64::try_from(parse_quantity_u128(raw)?)
+        .map_err(|_| ApiError::invalid_params(format!("Quantity out of range: {}", raw)))
+}
+
+pub fn quantity(value: impl Into<u128>) -> String {
+    format!("0x{:x}", value.into())
+}
+
+pub fn data(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// EIP-2718 type of a transaction (native transactions report as legacy)
+fn tx_type(tx: &Transaction) -> u8 {
+    match tx.eth_envelope.as_deref().and_then(|e| e.first()) {
+        Some(&ty) if ty < 0x80 => ty,
+        _ => 0,
+    }
+}
+
+/// Raw bytes committed to by the block's transactions root
+fn tx_bytes(tx: &Transaction) -> Vec<u8> {
+    tx.eth_envelope.clone().unwrap_or_else(|| tx.encode())
+}
+
+/// Consensus encoding of a receipt (typed receipts carry the tx type prefix)
+fn receipt_bytes(receipt: &TxReceipt, tx: &Transaction) -> Vec<u8> {
+    let logs: Vec<Vec<u8>> = receipt
+        .logs
+        .iter()
+        .map(|log| {
+            let topics: Vec<Vec<u8>> = log.topics.iter().map(|t| alloy_rlp::encode(&t[..])).collect();
+            rlp_list(&[
+                alloy_rlp::encode(&log.address.evm_bytes()[..]),
+                rlp_list(&topics),
+                alloy_rlp::encode(&log.data[..]),
+            ])
+        })
+        .collect();
+
+    let mut out = Vec::new();
+    match tx_type(tx) {
+        0 => {}
+        ty => out.push(ty),
+    }
+    out.extend(rlp_list(&[
+        alloy_rlp::encode(receipt.success as u8),
+        alloy_rlp::encode(receipt.cumulative_gas_used),
+        alloy_rlp::encode(receipt.logs_bloom().as_slice()),
+        rlp_list(&logs),
+    ]));
+    out
+}
+
+/// RLP list of already-encoded items
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload_length = items.iter().map(Vec::len).sum();
+    let mut out = Vec::with_capacity(payload_length + 9);
+    Header { list: true, payload_length }.encode(&mut out);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+/// Block header as pushed to newHeads subscribers
+pub fn header_json(block: &MicroBlock) -> Value {
+    let mut header = block_json(block, false);
+    if let Some(obj) = header.as_object_mut() {
+        obj.remove("transactions");
+        obj.remove("uncles");
+    }
+    header
+}
+
+pub fn block_json(block: &MicroBlock, full: bool) -> Value {
+    let transactions: Vec<Value> = block
+        .transactions
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            if full {
+                transaction_json(tx, block, index)
+            } else {
+                json!(data(&tx.hash()))
+            }
+        })
+        .collect();
+
+    let tx_items: Vec<Vec<u8>> = block.transactions.iter().map(tx_bytes).collect();
+    let receipt_items: Vec<Vec<u8>> = block
+        .receipts
+        .iter()
+        .zip(&block.transactions)
+        .map(|(receipt, tx)| receipt_bytes(receipt, tx))
+        .collect();
+    let size: usize = tx_items.iter().map(Vec::len).sum();
+
+    json!({
+        "number": quantity(block.sequence),
+        "hash": data(&block.hash()),
+        "parentHash": data(&block.parent_hash),
+        "nonce": "0x0000000000000000",
+        "sha3Uncles": EMPTY_UNCLES_HASH,
+        "logsBloom": data(block.logs_bloom().as_slice()),
+        "transactionsRoot": data(&state_trie::ethereum_ordered_root(&tx_items)),
+        "stateRoot": format!("0x{}", block.state_root),
+        "receiptsRoot": data(&state_trie::ethereum_ordered_root(&receipt_items)),
+        "miner": data(&[0u8; 20]),
+        "difficulty": "0x0",
+        "totalDifficulty": "0x0",
+        "extraData": "0x",
+        "size": quantity(size as u64),
+        "gasLimit": quantity(BLOCK_GAS_LIMIT),
+        "gasUsed": quantity(block.gas_used()),
+        "timestamp": quantity(block.timestamp),
+        "transactions": transactions,
+        "uncles": [],
+    })
+}
+
+fn transaction_json(tx: &Transaction, block: &MicroBlock, index: usize) -> Value {
+    let ty = tx_type(tx);
+    let mut obj = json!({
+        "hash": data(&tx.hash()),
+        "type": quantity(ty),
+        "chainId": quantity(tx.chain_id),
+        "nonce": quantity(tx.nonce),
+        "blockHash": data(&block.hash()),
+        "blockNumber": quantity(block.sequence),
+        "transactionIndex": quantity(index as u64),
+        "from": tx.from.to_string(),
+        "to": tx.to.map(|to| to.to_string()),
+        // Out-of-range amounts only appear on rejected transactions
+        "value": tx.value_wei().ok().map(quantity),
+        "gas": quantity(tx.gas_limit),
+        "gasPrice": tx.gas_price_wei().ok().map(quantity),
+        "input": data(&tx.data),
+    });
+
+    if let Signature::ECDSA { r, s, v } = &tx.signature {
+        // Legacy Ethereum transactions report the EIP-155 v value
+        let v = match (&tx.eth_envelope, ty) {
+            (Some(_), 0) => *v as u64 + 35 + 2 * tx.chain_id,
+            _ => *v as u64,
+        };
+        obj["r"] = json!(data(r));
+        obj["s"] = json!(data(s));
+        obj["v"] = json!(quantity(v));
+        if ty != 0 {
+            obj["yParity"] = json!(quantity(v));
+        }
+    }
+    if ty == 2 {
+        // The tip is the priority fee over the whole gas limit
+        let priority = tx.tip_wei().ok().and_then(|tip| tip.checked_div(u128::from(tx.gas_limit)));
+        obj["maxPriorityFeePerGas"] = json!(priority.map(quantity));
+        obj["maxFeePerGas"] = json!(tx.gas_price_wei().ok().map(quantity));
+    }
+    if let Some(envelope) = tx.eth_envelope.as_ref().filter(|_| ty != 0) {
+        let access_list = eth_tx::access_list(envelope).unwrap_or_default();
+        obj["accessList"] = access_list
+            .iter()
+            .map(|item| {
+                let keys: Vec<_> = item.storage_keys.iter().map(|key| data(key)).collect();
+                json!({ "address": data(&item.address), "storageKeys": keys })
+            })
+            .collect();
+    }
+    obj
+}
+
+fn receipt_json(receipt: &TxReceipt, tx: &Transaction, block: &MicroBlock, index: usize) -> Value {
+    let first_log_index: usize = block.receipts[..index].iter().map(|r| r.logs.len()).sum();
+    let logs: Vec<Value> = receipt
+        .logs
+        .iter()
+        .enumerate()
+        .map(|(i, log)| log_json(log, block, index, first_log_index + i))
+        .collect();
+
+    json!({
+        "transactionHash": data(&receipt.tx_hash),
+        "transactionIndex": quantity(index as u64),
+        "blockHash": data(&block.hash()),
+        "blockNumber": quantity(block.sequence),
+        "from": tx.from.to_string(),
+        "to": tx.to.map(|to| to.to_string()),
+        "cumulativeGasUsed": quantity(receipt.cumulative_gas_used),
+        "gasUsed": quantity(receipt.gas_used),
+        "effectiveGasPrice": quantity(effective_gas_price(receipt)),
+        "contractAddress": receipt.contract_address.map(|a| a.to_string()),
+        "logs": logs,
+        "logsBloom": data(receipt.logs_bloom().as_slice()),
+        "type": quantity(tx_type(tx)),
+        "status": if receipt.success { "0x1" } else { "0x0" },
+    })
+}
+
+/// Fee actually charged per gas unit, flat fee and tip included
+fn effective_gas_price(receipt: &TxReceipt) -> u128 {
+    receipt.fee.checked_div(u128::from(receipt.gas_used)).unwrap_or(0)
+}
+
+fn log_json(log: &Log, block: &MicroBlock, tx_index: usize, log_index: usize) -> Value {
+    json!({
+        "address": log.address.to_string(),
+        "topics": log.topics.iter().map(|t| data(t)).collect::<Vec<_>>(),
+        "data": data(&log.data),
+        "blockNumber": quantity(block.sequence),
+        "blockHash": data(&block.hash()),
+        "transactionHash": data(&block.transactions[tx_index].hash()),
+        "transactionIndex": quantity(tx_index as u64),
+        "logIndex": quantity(log_index as u64),
+        "removed": false,
+    })
+}
+
+/// Address and topic criteria for log subscriptions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    /// Emitting contracts; empty matches any
+    pub addresses: Vec<Address>,
+    /// Per topic position: None matches anything, otherwise any of the listed
+    pub topics: Vec<Option<Vec<[u8; 32]>>>,
+}
+
+impl LogFilter {
+    /// Parse `{ "address": a | [a..], "topics": [t | [t..] | null, ..] }`
+    pub fn parse(param: Option<&Value>) -> Result<Self, ApiError> {
+        let Some(Value::Object(filter)) = param else {
+            return Ok(Self::default());
+        };
+
+        let addresses = match filter.get("address") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::String(_)) => vec![param_address(std::slice::from_ref(&filter["address"]), 0)?],
+            Some(Value::Array(list)) => (0..list.len())
+                .map(|i| param_address(list, i))
+                .collect::<Result<_, _>>()?,
+            Some(other) => return Err(ApiError::invalid_params(format!("Invalid address filter: {}", other))),
+        };
+
+        let topics = match filter.get("topics") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(positions)) => positions
+                .iter()
+                .map(|position| match position {
+                    Value::Null => Ok(None),
+                    Value::String(_) => Ok(Some(vec![param_hash(std::slice::from_ref(position), 0)?])),
+                    Value::Array(options) => (0..options.len())
+                        .map(|i| param_hash(options, i))
+                        .collect::<Result<_, _>>()
+                        .map(Some),
+                    other => Err(ApiError::invalid_params(format!("Invalid topic filter: {}", other))),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(other) => return Err(ApiError::invalid_params(format!("Invalid topics filter: {}", other))),
+        };
+
+        Ok(Self { addresses, topics })
+    }
+
+    /// Whether a block or receipt with this bloom may hold matching logs
+    pub fn may_match(&self, bloom: &Bloom) -> bool {
+        let contains = |bytes: &[u8]| bloom.contains_input(BloomInput::Raw(bytes));
+        (self.addresses.is_empty() || self.addresses.iter().any(|address| contains(&address.evm_bytes())))
+            && self.topics.iter().all(|position| match position {
+                None => true,
+                Some(options) => options.iter().any(|topic| contains(topic)),
+            })
+    }
+
+    pub fn matches(&self, log: &Log) -> bool {
+        if !self.addresses.is_empty() && !self.addresses.contains(&log.address) {
+            return false;
+        }
+        self.topics.iter().enumerate().all(|(i, position)| match position {
+            None => true,
+            Some(options) => log.topics.get(i).is_some_and(|topic| options.contains(topic)),
+        })
+    }
+}
+
+/// Logs of a micro-block matching `filter`, in block order
+pub fn block_logs(block: &MicroBlock, filter: &LogFilter) -> Vec<Value> {
+    let mut log_index = 0;
+    let mut matched = Vec::new();
+    for (tx_index, receipt) in block.receipts.iter().enumerate() {
+        for log in &receipt.logs {
+            if filter.matches(log) {
+                matched.push(log_json(log, block, tx_index, log_index));
+            }
+            log_index += 1;
+        }
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_sol_types::{Revert, SolError};
+    use crate::crypto::{KeyPair, SignatureAlgorithm};
+    use crate::genesis::Account;
+    use crate::sequencer::TxReceipt;
+    use crate::state_db::{ChainHead, InMemoryStateStore, StateChanges};
+    use crate::transaction::{TransactionBuilder, BASE_CHAIN_ID};
+    use rust_decimal_macros::dec;
+
+    /// Store with one funded account and one micro-block containing a transfer
+    fn test_chain() -> (EthApi, Transaction, Address) {
+        let state: Arc<dyn StateStore> = Arc::new(InMemoryStateStore::new());
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let sender = keypair.address();
+        let contract = Address::EVM([0xcc; 20]);
+
+        // Genesis: funded sender and a contract with one storage slot
+        let mut changes = StateChanges::new();
+        changes.set_account(sender, Account::new(100));
+        // Returns 42: PUSH1 0x2A PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 RETURN
+        changes.set_code(contract, hex::decode("602a60005260206000f3").unwrap());
+        // Always reverts with empty data: PUSH1 0x00 PUSH1 0x00 REVERT
+        changes.set_code(Address::EVM([0xdd; 20]), hex::decode("60006000fd").unwrap());
+        changes.set_storage(contract, [0u8; 32], [0x2a; 32]);
+        // Counter that calls the first contract: SLOAD 0, add 1, SSTORE 0,
+        // CALL 0xcc..cc and return its output
+        let counter = format!("6000546001016000556020600060006000600073{}5af15060206000f3", "cc".repeat(20));
+        changes.set_code(Address::EVM([0xee; 20]), hex::decode(counter).unwrap());
+        // Reverts with Error("nope"): CODECOPY the payload after the code, REVERT
+        let mut reverting = hex::decode("6064600c60003960646000fd").unwrap();
+        reverting.extend(Revert::from("nope").abi_encode());
+        changes.set_code(Address::EVM([0xdf; 20]), reverting);
+        let genesis_root =
+            state_trie::apply_changes(state.as_ref(), state_trie::EMPTY_ROOT, &mut changes).unwrap();
+        changes.set_head(ChainHead {
+            state_root: hex::encode(genesis_root),
+            genesis_root: hex::encode(genesis_root),
+            ..ChainHead::default()
+        });
+        state.commit(changes).unwrap();
+
+        // Micro-block 0: sender's nonce advances
+        let tx = TransactionBuilder::new()
+            .to(Address::EVM([0x35; 20]))
+            .value(dec!(1.5))
+            .unwrap()
+            .gas_limit(100_000)
+            .unwrap()
+            .sign(&keypair)
+            .unwrap();
+        let mut changes = StateChanges::new();
+        changes.set_account(sender, Account { balance: Account::new(100).balance, nonce: 1 });
+        let root = state_trie::apply_changes(state.as_ref(), genesis_root, &mut changes).unwrap();
+        let block = MicroBlock {
+            shard_id: 0,
+            sequence: 0,
+            parent_hash: [0u8; 32],
+            timestamp: 1_700_000_000,
+            transactions: vec![tx.clone()],
+            receipts: vec![TxReceipt {
+                tx_hash: tx.hash(),
+                success: true,
+                gas_used: 24_000,
+                cumulative_gas_used: 24_000,
+                contract_address: None,
+                logs: Vec::new(),
+                fee: 0,
+                error: None,
+            }],
+            state_root: hex::encode(root),
+            sent_receipts: vec![],
+            claimed_receipts: vec![],
+        };
+        changes.put_micro_block(block);
+        changes.set_head(ChainHead {
+            next_sequence: 1,
+            state_root: hex::encode(root),
+            genesis_root: hex::encode(genesis_root),
+            ..ChainHead::default()
+        });
+        state.commit(changes).unwrap();
+
+        (EthApi::new(state, BASE_CHAIN_ID), tx, sender)
+    }
+
+    #[test]
+    fn test_account_queries() {
+        let (api, _, sender) = test_chain();
+        let addr = json!(sender.to_string());
+
+        assert_eq!(api.block_number().unwrap(), json!("0x0"));
+        assert_eq!(
+            api.get_balance(&[addr.clone(), json!("latest")]).unwrap(),
+            json!(quantity(Account::new(100).balance))
+        );
+        assert_eq!(api.get_transaction_count(&[addr.clone(), json!("latest")]).unwrap(), json!("0x1"));
+
+        let contract = json!(Address::EVM([0xcc; 20]).to_string());
+        assert_eq!(api.get_code(&[contract.clone(), json!("latest")]).unwrap(), json!("0x602a60005260206000f3"));
+        assert_eq!(
+            api.get_storage_at(&[contract, json!("0x0"), json!("latest")]).unwrap(),
+            json!(data(&[0x2a; 32]))
+        );
+
+        // Unknown accounts are empty
+        let unknown = json!(Address::EVM([9; 20]).to_string());
+        assert_eq!(api.get_balance(&[unknown, json!("latest")]).unwrap(), json!("0x0"));
+    }
+
+    #[test]
+    fn test_blocks_transactions_and_receipts() {
+        let (api, tx, sender) = test_chain();
+        let tx_hash = json!(data(&tx.hash()));
+
+        let block = api.get_block_by_number(&[json!("0x0"), json!(false)]).unwrap();
+        assert_eq!(block["transactions"], json!([tx_hash.clone()]));
+        assert_eq!(block["gasUsed"], json!("0x5dc0"));
+        let by_hash = api.get_block_by_hash(&[block["hash"].clone(), json!(true)]).unwrap();
+        assert_eq!(by_hash["transactions"][0]["hash"], tx_hash);
+        assert_eq!(api.get_block_by_number(&[json!("0x5")]).unwrap(), Value::Null);
+
+        let found = api.get_transaction_by_hash(std::slice::from_ref(&tx_hash)).unwrap();
+        assert_eq!(found["from"], json!(sender.to_string()));
+        assert_eq!(found["value"], json!(quantity(1_500_000_000_000_000_000u128)));
+        assert_eq!(found["blockHash"], block["hash"]);
+
+        let receipt = api.get_transaction_receipt(std::slice::from_ref(&tx_hash)).unwrap();
+        assert_eq!(receipt["status"], json!("0x1"));
+        assert_eq!(receipt["blockNumber"], json!("0x0"));
+
+        let missing = json!(data(&[0u8; 32]));
+        assert_eq!(api.get_transaction_receipt(&[missing]).unwrap(), Value::Null);
+
+        // The first block replays on the recorded genesis root
+        assert_eq!(api.trace_transaction(&[tx_hash]).unwrap()["failed"], json!(false));
+    }
+
+    #[test]
+    fn test_historical_state() {
+        let (api, _, sender) = test_chain();
+        let addr = json!(sender.to_string());
+
+        // Block 0 includes the nonce bump; an unknown block is an error
+        assert_eq!(api.get_transaction_count(&[addr.clone(), json!("0x0")]).unwrap(), json!("0x1"));
+        assert!(api.get_transaction_count(&[addr, json!("0x7")]).is_err());
+    }
+
+    #[test]
+    fn test_eth_call() {
+        let (api, _, _) = test_chain();
+        let call = json!({ "to": Address::EVM([0xcc; 20]).to_string() });
+
+        let mut expected = [0u8; 32];
+        expected[31] = 42;
+        assert_eq!(api.call(&[call, json!("latest")]).unwrap(), json!(data(&expected)));
+
+        let reverting = json!({ "to": Address::EVM([0xdd; 20]).to_string() });
+        let err = api.call(&[reverting]).unwrap_err();
+        assert_eq!(err.code, 3);
+        assert_eq!(err.data, Some(json!("0x")));
+    }
+
+    #[test]
+    fn test_revert_reason() {
+        let (api, _, _) = test_chain();
+        let call = json!({ "to": Address::EVM([0xdf; 20]).to_string() });
+        let err = api.call(std::slice::from_ref(&call)).unwrap_err();
+        assert_eq!(err.code, 3);
+        assert_eq!(err.message, "execution reverted: nope");
+
+        let trace = api.trace_call(&[call, json!("latest"), json!({ "tracer": "callTracer" })]).unwrap();
+        assert_eq!(trace["error"], json!("execution reverted"));
+        assert_eq!(trace["revertReason"], json!("nope"));
+    }
+
+    /// Micro-block 1: two calls of the counter by the test chain's sender
+    fn trace_chain() -> (EthApi, Transaction) {
+        let (api, _, _) = test_chain();
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let counter = Address::EVM([0xee; 20]);
+        let call = |nonce| {
+            TransactionBuilder::new()
+                .nonce(nonce)
+                .to(counter)
+                .data(vec![1])
+                .unwrap()
+                .gas_limit(200_000)
+                .unwrap()
+                .sign(&keypair)
+                .unwrap()
+        };
+        let transactions = vec![call(0), call(1)];
+        let receipts = transactions
+            .iter()
+            .map(|tx| TxReceipt {
+                tx_hash: tx.hash(),
+                success: true,
+                gas_used: 50_000,
+                cumulative_gas_used: 0,
+                contract_address: None,
+                logs: Vec::new(),
+                fee: 0,
+                error: None,
+            })
+            .collect();
+        let state_root = api.state.micro_block(0).unwrap().unwrap().state_root;
+        let mut changes = StateChanges::new();
+        changes.put_micro_block(MicroBlock {
+            shard_id: 0,
+            sequence: 1,
+            parent_hash: [0u8; 32],
+            timestamp: 1_700_000_001,
+            transactions: transactions.clone(),
+            receipts,
+            state_root: state_root.clone(),
+            sent_receipts: vec![],
+            claimed_receipts: vec![],
+        });
+        changes.set_head(ChainHead { next_sequence: 2, state_root, ..ChainHead::default() });
+        api.state.commit(changes).unwrap();
+        (api, transactions[1].clone())
+    }
+
+    #[test]
+    fn test_trace_transaction_struct_logs() {
+        let (api, tx) = trace_chain();
+        let trace = api.trace_transaction(&[json!(data(&tx.hash()))]).unwrap();
+        assert_eq!(trace["failed"], json!(false));
+        let mut expected = [0u8; 32];
+        expected[31] = 42;
+        assert_eq!(trace["returnValue"], json!(data(&expected)));
+
+        let logs = trace["structLogs"].as_array().unwrap();
+        assert_eq!(logs[0]["op"], json!("PUSH1"));
+        assert_eq!(logs[0]["depth"], json!(1));
+        // The earlier call of the block was replayed: the counter reads 1
+        let word = |n: u8| hex::encode([[0u8; 31].as_slice(), &[n]].concat());
+        let sload = logs.iter().find(|log| log["op"] == "SLOAD").unwrap();
+        assert_eq!(sload["storage"][word(0)], json!(word(1)));
+        let sstore = logs.iter().find(|log| log["op"] == "SSTORE").unwrap();
+        assert_eq!(sstore["storage"][word(0)], json!(word(2)));
+        assert_eq!(sstore["stack"], json!(["0x2", "0x0"]));
+        assert!(logs.iter().any(|log| log["depth"] == json!(2)));
+        assert!(logs.iter().all(|log| log.get("memory").is_none()));
+
+        let limited = api
+            .trace_transaction(&[json!(data(&tx.hash())), json!({ "disableStack": true, "enableMemory": true, "limit": 5 })])
+            .unwrap();
+        let logs = limited["structLogs"].as_array().unwrap();
+        assert_eq!(logs.len(), 5);
+        assert!(logs[0].get("stack").is_none());
+        assert_eq!(logs[0]["memory"], json!([]));
+
+        let err = api.trace_transaction(&[json!(data(&tx.hash())), json!({ "tracer": "prestateTracer" })]).unwrap_err();
+        assert_eq!(err.message, "Unsupported tracer: prestateTracer");
+        assert!(api.trace_transaction(&[json!(data(&[0u8; 32]))]).is_err());
+    }
+
+    #[test]
+    fn test_trace_transaction_call_tracer() {
+        let (api, tx) = trace_chain();
+        let hash = json!(data(&tx.hash()));
+        let trace = api.trace_transaction(&[hash.clone(), json!({ "tracer": "callTracer" })]).unwrap();
+        assert_eq!(trace["type"], json!("CALL"));
+        assert_eq!(trace["from"], json!(data(&tx.from.evm_bytes())));
+        assert_eq!(trace["to"], json!(data(&[0xee; 20])));
+        assert_eq!(trace["gas"], json!(quantity(200_000u64)));
+        assert_eq!(trace["input"], json!("0x01"));
+        assert!(trace.get("error").is_none());
+        let inner = &trace["calls"][0];
+        assert_eq!(inner["type"], json!("CALL"));
+        assert_eq!(inner["from"], json!(data(&[0xee; 20])));
+        assert_eq!(inner["to"], json!(data(&[0xcc; 20])));
+        assert_eq!(inner["output"], trace["output"]);
+
+        let options = json!({ "tracer": "callTracer", "tracerConfig": { "onlyTopCall": true } });
+        let top = api.trace_transaction(&[hash, options]).unwrap();
+        assert!(top.get("calls").is_none());
+        assert_eq!(top["gasUsed"], trace["gasUsed"]);
+    }
+
+    #[test]
+    fn test_estimate_gas() {
+        let (api, _, sender) = test_chain();
+
+        let transfer = json!({ "from": sender.to_string(), "to": Address::EVM([0x35; 20]).to_string(), "value": "0x1" });
+        assert_eq!(api.estimate_gas(&[transfer]).unwrap(), json!(quantity(21_000u64)));
+
+        // Contract call costs more than a plain transfer
+        let call = json!({ "to": Address::EVM([0xcc; 20]).to_string() });
+        let estimate = api.estimate_gas(&[call]).unwrap();
+        let gas = u64::from_str_radix(estimate.as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+        assert!(gas > 21_000);
+
+        // PQ-signed native transactions include signature verification
+        let native = json!({
+            "from": Address::Native([7; 32]).to_string(),
+            "to": Address::EVM([0x35; 20]).to_string(),
+            "signatureAlgorithm": "Dilithium",
+        });
+        let expected = 21_000 + GasConfig::default().signature_verification(SignatureAlgorithm::Dilithium);
+        assert_eq!(api.estimate_gas(&[native]).unwrap(), json!(quantity(expected)));
+
+        let reverting = json!({ "to": Address::EVM([0xdd; 20]).to_string() });
+        assert_eq!(api.estimate_gas(&[reverting]).unwrap_err().code, 3);
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let token = Address::EVM([0xaa; 20]);
+        let log = Log {
+            address: token,
+            topics: vec![[1u8; 32], [2u8; 32]],
+            data: vec![],
+        };
+
+        assert!(LogFilter::default().matches(&log));
+
+        let filter = LogFilter::parse(Some(&json!({
+            "address": token.to_string(),
+            "topics": [data(&[1u8; 32]), null, [data(&[3u8; 32]), data(&[2u8; 32])]],
+        })))
+        .unwrap();
+        // Third position is absent from the log
+        assert!(!filter.matches(&log));
+
+        let filter = LogFilter::parse(Some(&json!({
+            "address": [Address::EVM([0xbb; 20]).to_string(), token.to_string()],
+            "topics": [null, [data(&[3u8; 32]), data(&[2u8; 32])]],
+        })))
+        .unwrap();
+        assert!(filter.matches(&log));
+
+        let other = LogFilter::parse(Some(&json!({ "address": Address::EVM([0xbb; 20]).to_string() }))).unwrap();
+        assert!(!other.matches(&log));
+    }
+
+    /// Micro-block with one transaction emitting `logs`
+    fn log_block(sequence: u64, logs: Vec<Log>) -> MicroBlock {
+        let tx = TransactionBuilder::new()
+            .nonce(sequence)
+            .to(Address::EVM([0x35; 20]))
+            .sign(&KeyPair::generate(SignatureAlgorithm::ECDSA))
+            .unwrap();
+        MicroBlock {
+            shard_id: 0,
+            sequence,
+            parent_hash: [0u8; 32],
+            timestamp: 1_700_000_000 + sequence,
+            receipts: vec![TxReceipt {
+                tx_hash: tx.hash(),
+                success: true,
+                gas_used: 30_000,
+                cumulative_gas_used: 30_000,
+                contract_address: None,
+                logs,
+                fee: 0,
+                error: None,
+            }],
+            transactions: vec![tx],
+            state_root: hex::encode(state_trie::EMPTY_ROOT),
+            sent_receipts: vec![],
+            claimed_receipts: vec![],
+        }
+    }
+
+    fn append(state: &dyn StateStore, block: MicroBlock) {
+        let mut changes = StateChanges::new();
+        changes.set_head(ChainHead { next_sequence: block.sequence + 1, ..ChainHead::default() });
+        changes.put_micro_block(block);
+        state.commit(changes).unwrap();
+    }
+
+    #[test]
+    fn test_get_logs_and_filters() {
+        let state: Arc<dyn StateStore> = Arc::new(InMemoryStateStore::new());
+        let api = EthApi::new(state.clone(), BASE_CHAIN_ID);
+        let token = Address::EVM([0xaa; 20]);
+        let log = |address, topic: u8| Log { address, topics: vec![[topic; 32]], data: vec![topic] };
+
+        append(state.as_ref(), log_block(0, vec![log(token, 1)]));
+        append(state.as_ref(), log_block(1, vec![]));
+        append(state.as_ref(), log_block(2, vec![log(Address::EVM([0xbb; 20]), 2), log(token, 2)]));
+
+        // Blooms cover the logged addresses and topics
+        let block = state.micro_block(2).unwrap().unwrap();
+        let bloom = block.receipts[0].logs_bloom();
+        assert_eq!(block.logs_bloom(), bloom);
+        assert_ne!(block_json(&block, false)["logsBloom"], json!(data(&[0u8; 256])));
+        assert!(LogFilter { addresses: vec![token], topics: vec![Some(vec![[2u8; 32]])] }.may_match(&bloom));
+        assert!(!LogFilter { addresses: vec![token], topics: vec![Some(vec![[1u8; 32]])] }.may_match(&bloom));
+
+        let logs = api
+            .get_logs(&[json!({ "fromBlock": "earliest", "address": token.to_string() })])
+            .unwrap();
+        let numbers: Vec<&Value> = logs.as_array().unwrap().iter().map(|l| &l["blockNumber"]).collect();
+        assert_eq!(numbers, vec!["0x0", "0x2"]);
+        assert_eq!(logs[1]["logIndex"], "0x1");
+
+        let logs = api
+            .get_logs(&[json!({ "fromBlock": "0x1", "toBlock": "0x2", "topics": [data(&[2u8; 32])] })])
+            .unwrap();
+        assert_eq!(logs.as_array().unwrap().len(), 2);
+        let logs = api
+            .get_logs(&[json!({ "blockHash": data(&block.hash()), "address": token.to_string() })])
+            .unwrap();
+        assert_eq!(logs.as_array().unwrap().len(), 1);
+
+        assert!(api.get_logs(&[json!({ "fromBlock": "0x2", "toBlock": "0x1" })]).is_err());
+        assert!(api.get_logs(&[json!({ "fromBlock": "0x0", "toBlock": quantity(MAX_LOG_RANGE) })]).is_err());
+
+        // Filters report what arrives after they are installed
+        let logs_id = [api.new_filter(&[json!({ "address": token.to_string() })]).unwrap()];
+        let blocks_id = [api.new_block_filter().unwrap()];
+        assert_eq!(api.get_filter_changes(&logs_id).unwrap(), json!([]));
+
+        append(state.as_ref(), log_block(3, vec![log(token, 3), log(Address::EVM([0xbb; 20]), 3)]));
+        let changes = api.get_filter_changes(&logs_id).unwrap();
+        assert_eq!(changes.as_array().unwrap().len(), 1);
+        assert_eq!(changes[0]["blockNumber"], "0x3");
+        assert_eq!(api.get_filter_changes(&logs_id).unwrap(), json!([]));
+        let hash = data(&state.micro_block(3).unwrap().unwrap().hash());
+        assert_eq!(api.get_filter_changes(&blocks_id).unwrap(), json!([hash]));
+
+        // The filter's full range is still latest..latest
+        assert_eq!(api.get_filter_logs(&logs_id).unwrap().as_array().unwrap().len(), 1);
+
+        // Polls read a bounded range: too many unpolled blocks fail one poll
+        // of a log filter, and a block filter catches up over several polls
+        let open_id = [api.new_filter(&[json!({ "fromBlock": "earliest" })]).unwrap()];
+        let mut changes = StateChanges::new();
+        changes.set_head(ChainHead { next_sequence: 5 + MAX_LOG_RANGE, ..ChainHead::default() });
+        state.commit(changes).unwrap();
+        assert!(api.get_filter_changes(&open_id).is_err());
+        assert_eq!(api.get_filter_changes(&logs_id).unwrap(), json!([]));
+        assert_eq!(api.get_filter_changes(&blocks_id).unwrap(), json!([]));
+        assert_eq!(api.filters.lock()[blocks_id[0].as_str().unwrap()].next_block, 4 + MAX_LOG_RANGE);
+        assert_eq!(api.get_filter_changes(&blocks_id).unwrap(), json!([]));
+        assert_eq!(api.get_filter_changes(&blocks_id).unwrap(), json!([]));
+
+        // The failed poll skipped the range, so the log filter reads new blocks again
+        append(state.as_ref(), log_block(5 + MAX_LOG_RANGE, vec![log(Address::EVM([0xcc; 20]), 4)]));
+        let changes = api.get_filter_changes(&open_id).unwrap();
+        assert_eq!(changes.as_array().unwrap().len(), 1);
+        assert_eq!(changes[0]["blockNumber"], json!(quantity(5 + MAX_LOG_RANGE)));
+
+        assert_eq!(api.uninstall_filter(&logs_id).unwrap(), json!(true));
+        assert_eq!(api.uninstall_filter(&logs_id).unwrap(), json!(false));
+        assert_eq!(api.get_filter_changes(&logs_id).unwrap_err().code, -32000);
+    }
+
+}
@@ -0,0 +1,537 @@
+// Ethereum transaction envelopes: RLP decoding and secp256k1 sender recovery
+// Supports legacy (EIP-155), EIP-2930 and EIP-1559 signed transactions
+
+use alloy_rlp::{Decodable, Header};
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, Secp256k1};
+
+use crate::crypto::{keccak256, Address, PublicKeyData, Signature};
+use crate::transaction::Transaction;
+
+/// secp256k1 curve order / 2; larger `s` values are malleable (EIP-2)
+const SECP256K1_HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// EIP-2718 transaction type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EthTxType {
+    /// Legacy RLP transaction (EIP-155 replay protected)
+    Legacy,
+    /// EIP-2930 access list transaction (type 0x01)
+    AccessList,
+    /// EIP-1559 dynamic fee transaction (type 0x02)
+    DynamicFee,
+}
+
+/// EIP-2930 access list entry: an account and the storage slots it declares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: [u8; 20],
+    pub storage_keys: Vec<[u8; 32]>,
+}
+
+/// Decoded and signature-checked Ethereum transaction
+#[derive(Debug, Clone)]
+pub struct EthTransaction {
+    pub tx_type: EthTxType,
+    pub chain_id: u64,
+    pub nonce: u64,
+    /// Gas price in wei (maxFeePerGas for EIP-1559)
+    pub gas_price: u128,
+    /// maxPriorityFeePerGas in wei, EIP-1559 only
+    pub max_priority_fee_per_gas: Option<u128>,
+    pub gas_limit: u64,
+    pub to: Option<[u8; 20]>,
+    /// Value in wei
+    pub value: u128,
+    pub data: Vec<u8>,
+    /// Sender recovered from the signature
+    pub from: Address,
+    pub public_key: PublicKeyData,
+    pub signature: Signature,
+}
+
+impl EthTransaction {
+    /// Map onto the node's transaction model for `shard_id`, the shard whose
+    /// chain ID it was signed for; amounts are converted from wei to IONX.
+    /// The gas price is maxFeePerGas, and an EIP-1559 priority fee becomes
+    /// the tip at its most: the priority fee over the whole gas limit. The
+    /// sequencer charges the priority fee the base fee leaves room for, on
+    /// the gas used.
+    pub fn into_transaction(self, envelope: Vec<u8>, shard_id: u8) -> Result<Transaction> {
+        let tip = match self.tx_type {
+            EthTxType::DynamicFee => {
+                let priority = self.max_priority_fee_per_gas.unwrap_or_default();
+                if priority > self.gas_price {
+                    return Err(anyhow!("maxPriorityFeePerGas {} exceeds maxFeePerGas {}", priority, self.gas_price));
+                }
+                priority
+                    .checked_mul(u128::from(self.gas_limit))
+                    .ok_or_else(|| anyhow!("Priority fee too large"))?
+            }
+            EthTxType::Legacy | EthTxType::AccessList => 0,
+        };
+        Ok(Transaction {
+            chain_id: self.chain_id,
+            shard_id,
+            nonce: self.nonce,
+            from: self.from,
+            to: self.to.map(Address::EVM),
+            value: wei_to_ionx(self.value)?,
+            gas_limit: self.gas_limit,
+            gas_price: wei_to_ionx(self.gas_price)?,
+            tip: wei_to_ionx(tip)?,
+            data: self.data,
+            signature: self.signature,
+            public_key: self.public_key,
+            expiry: None,
+            eth_envelope: Some(envelope),
+        })
+    }
+}
+
+/// Access list of a signed envelope (empty for legacy transactions).
+/// Reads the fields without recovering the signer.
+pub fn access_list(raw: &[u8]) -> Result<Vec<AccessListItem>> {
+    let fee_fields = match raw.first() {
+        Some(0x01) => 1,
+        Some(0x02) => 2,
+        _ => return Ok(Vec::new()),
+    };
+    let items = list_items(&raw[1..])?;
+    let item = items.get(2 + fee_fields + 4).ok_or_else(|| anyhow!("Transaction has no access list"))?;
+    decode_access_list(item)
+}
+
+/// Decode a raw signed transaction submitted through `eth_sendRawTransaction`
+/// and check it targets `chain_id`, the chain ID of shard `shard_id`
+pub fn decode_transaction(raw: &[u8], chain_id: u64, shard_id: u8) -> Result<Transaction> {
+    let eth_tx = decode(raw)?;
+    if eth_tx.chain_id != chain_id {
+        return Err(anyhow!(
+            "Wrong chain ID: expected {}, got {}",
+            chain_id,
+            eth_tx.chain_id
+        ));
+    }
+    eth_tx.into_transaction(raw.to_vec(), shard_id)
+}
+
+/// Decode a signed EIP-2718 envelope (or legacy RLP) and recover its sender
+pub fn decode(raw: &[u8]) -> Result<EthTransaction> {
+    let first = *raw.first().ok_or_else(|| anyhow!("Empty transaction"))?;
+    match first {
+        0x01 => decode_typed(EthTxType::AccessList, raw),
+        0x02 => decode_typed(EthTxType::DynamicFee, raw),
+        0xc0..=0xff => decode_legacy(raw),
+        other => Err(anyhow!("Unsupported transaction type 0x{:02x}", other)),
+    }
+}
+
+fn decode_legacy(raw: &[u8]) -> Result<EthTransaction> {
+    let items = list_items(raw)?;
+    if items.len() != 9 {
+        return Err(anyhow!("Legacy transaction must have 9 fields, got {}", items.len()));
+    }
+
+    let v: u64 = field(items[6])?;
+    if v == 27 || v == 28 {
+        return Err(anyhow!("Transactions without EIP-155 replay protection are not accepted"));
+    }
+    if v < 35 {
+        return Err(anyhow!("Invalid signature v value {}", v));
+    }
+    let chain_id = (v - 35) / 2;
+    let parity = ((v - 35) % 2) as u8;
+
+    // EIP-155 signing payload: rlp([nonce, gasPrice, gas, to, value, data, chainId, 0, 0])
+    let chain_id_rlp = alloy_rlp::encode(chain_id);
+    let mut signing_items: Vec<&[u8]> = items[..6].to_vec();
+    signing_items.extend_from_slice(&[&chain_id_rlp, &[0x80], &[0x80]]);
+    let signing_hash = keccak256(&encode_list(&signing_items));
+
+    let (from, public_key, signature) =
+        recover(signing_hash, word(items[7])?, word(items[8])?, parity)?;
+
+    Ok(EthTransaction {
+        tx_type: EthTxType::Legacy,
+        chain_id,
+        nonce: field(items[0])?,
+        gas_price: field(items[1])?,
+        max_priority_fee_per_gas: None,
+        gas_limit: field(items[2])?,
+        to: recipient(items[3])?,
+        value: field(items[4])?,
+        data: bytes(items[5])?.to_vec(),
+        from,
+        public_key,
+        signature,
+    })
+}
+
+fn decode_typed(tx_type: EthTxType, raw: &[u8]) -> Result<EthTransaction> {
+    let items = list_items(&raw[1..])?;
+
+    // Field positions after chainId/nonce differ only by the 1559 fee pair
+    let (fee_fields, expected) = match tx_type {
+        EthTxType::AccessList => (1, 11),
+        EthTxType::DynamicFee => (2, 12),
+        EthTxType::Legacy => unreachable!("legacy transactions are not typed"),
+    };
+    if items.len() != expected {
+        return Err(anyhow!(
+            "Type 0x{:02x} transaction must have {} fields, got {}",
+            raw[0],
+            expected,
+            items.len()
+        ));
+    }
+
+    let gas_index = 2 + fee_fields;
+    decode_access_list(items[gas_index + 4])?;
+
+    let parity: u8 = field(items[expected - 3])?;
+    if parity > 1 {
+        return Err(anyhow!("Invalid signature y parity {}", parity));
+    }
+
+    // Signing payload: type || rlp(all fields except the signature)
+    let mut payload = vec![raw[0]];
+    payload.extend_from_slice(&encode_list(&items[..expected - 3]));
+    let signing_hash = keccak256(&payload);
+
+    let (from, public_key, signature) = recover(
+        signing_hash,
+        word(items[expected - 2])?,
+        word(items[expected - 1])?,
+        parity,
+    )?;
+
+    let (gas_price, max_priority_fee_per_gas) = match tx_type {
+        EthTxType::DynamicFee => (field(items[3])?, Some(field(items[2])?)),
+        _ => (field(items[2])?, None),
+    };
+
+    Ok(EthTransaction {
+        tx_type,
+        chain_id: field(items[0])?,
+        nonce: field(items[1])?,
+        gas_price,
+        max_priority_fee_per_gas,
+        gas_limit: field(items[gas_index])?,
+        to: recipient(items[gas_index + 1])?,
+        value: field(items[gas_index + 2])?,
+        data: bytes(items[gas_index + 3])?.to_vec(),
+        from,
+        public_key,
+        signature,
+    })
+}
+
+/// Recover the signer of `hash` from an (r, s, y-parity) signature
+fn recover(
+    hash: [u8; 32],
+    r: [u8; 32],
+    s: [u8; 32],
+    parity: u8,
+) -> Result<(Address, PublicKeyData, Signature)> {
+    if s > SECP256K1_HALF_ORDER {
+        return Err(anyhow!("Signature s value is not canonical (EIP-2)"));
+    }
+
+    let mut compact = [0u8; 64];
+    compact[..32].copy_from_slice(&r);
+    compact[32..].copy_from_slice(&s);
+    let recovery_id = RecoveryId::from_i32(parity as i32)
+        .map_err(|e| anyhow!("Invalid recovery id: {}", e))?;
+    let sig = RecoverableSignature::from_compact(&compact, recovery_id)
+        .map_err(|e| anyhow!("Invalid signature: {}", e))?;
+
+    let public_key = Secp256k1::verification_only()
+        .recover_ecdsa(&Message::from_digest(hash), &sig)
+        .map_err(|e| anyhow!("Sender recovery failed: {}", e))?;
+
+    let public_key = PublicKeyData::ECDSA { bytes: public_key.serialize() };
+    Ok((public_key.to_address(), public_key, Signature::ECDSA { r, s, v: parity }))
+}
+
+/// Convert a wei amount to IONX (18 decimals)
+pub fn wei_to_ionx(wei: u128) -> Result<Decimal> {
+    let wei = i128::try_from(wei).map_err(|_| anyhow!("Amount too large"))?;
+    Decimal::try_from_i128_with_scale(wei, 18)
+        .map(|d| d.normalize())
+        .map_err(|_| anyhow!("Amount too large"))
+}
+
+/// Split an RLP list into its raw (still encoded) items
+fn list_items(buf: &[u8]) -> Result<Vec<&[u8]>> {
+    let mut rest = buf;
+    let header = Header::decode(&mut rest).map_err(rlp_error)?;
+    if !header.list {
+        return Err(anyhow!("Transaction must be an RLP list"));
+    }
+    if rest.len() != header.payload_length {
+        return Err(anyhow!("Transaction length does not match its RLP header"));
+    }
+
+    let mut items = Vec::new();
+    while !rest.is_empty() {
+        let mut cursor = rest;
+        let item = Header::decode(&mut cursor).map_err(rlp_error)?;
+        let len = (rest.len() - cursor.len()) + item.payload_length;
+        if len > rest.len() {
+            return Err(anyhow!("Truncated RLP item"));
+        }
+        items.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    Ok(items)
+}
+
+/// RLP list of already encoded items
+pub fn encode_list(items: &[&[u8]]) -> Vec<u8> {
+    let payload_length = items.iter().map(|item| item.len()).sum();
+    let mut out = Vec::with_capacity(payload_length + 9);
+    Header { list: true, payload_length }.encode(&mut out);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+fn field<T: Decodable>(item: &[u8]) -> Result<T> {
+    let mut buf = item;
+    let value = T::decode(&mut buf).map_err(rlp_error)?;
+    if !buf.is_empty() {
+        return Err(anyhow!("Trailing bytes in RLP field"));
+    }
+    Ok(value)
+}
+
+fn bytes(item: &[u8]) -> Result<&[u8]> {
+    let mut buf = item;
+    Header::decode_bytes(&mut buf, false).map_err(rlp_error)
+}
+
+fn recipient(item: &[u8]) -> Result<Option<[u8; 20]>> {
+    let to = bytes(item)?;
+    match to.len() {
+        0 => Ok(None),
+        20 => Ok(Some(to.try_into().expect("length checked"))),
+        n => Err(anyhow!("Invalid recipient length {}", n)),
+    }
+}
+
+/// rlp([[address, [storageKey, ...]], ...])
+fn decode_access_list(item: &[u8]) -> Result<Vec<AccessListItem>> {
+    if item.first().is_none_or(|b| *b < 0xc0) {
+        return Err(anyhow!("Access list must be an RLP list"));
+    }
+    list_items(item)?
+        .into_iter()
+        .map(|entry| {
+            let fields = list_items(entry)?;
+            let [address, keys] = fields[..] else {
+                return Err(anyhow!("Access list entry must have 2 fields, got {}", fields.len()));
+            };
+            let address = bytes(address)?
+                .try_into()
+                .map_err(|_| anyhow!("Access list address must be 20 bytes"))?;
+            let storage_keys = list_items(keys)?
+                .into_iter()
+                .map(|key| bytes(key)?.try_into().map_err(|_| anyhow!("Storage key must be 32 bytes")))
+                .collect::<Result<_>>()?;
+            Ok(AccessListItem { address, storage_keys })
+        })
+        .collect()
+}
+
+/// 32-byte big-endian signature scalar
+fn word(item: &[u8]) -> Result<[u8; 32]> {
+    let value = bytes(item)?;
+    if value.len() > 32 {
+        return Err(anyhow!("Signature value longer than 32 bytes"));
+    }
+    let mut out = [0u8; 32];
+    out[32 - value.len()..].copy_from_slice(value);
+    Ok(out)
+}
+
+fn rlp_error(e: alloy_rlp::Error) -> anyhow::Error {
+    anyhow!("Invalid RLP: {}", e)
+}
+
+/// Sign the unsigned fields of an EIP-1559 transaction with `secret_key`
+#[cfg(test)]
+pub fn sign_1559(secret_key: &[u8; 32], fields: Vec<Vec<u8>>) -> Vec<u8> {
+    let refs: Vec<&[u8]> = fields.iter().map(|f| f.as_slice()).collect();
+    let mut payload = vec![0x02];
+    payload.extend(encode_list(&refs));
+    let sk = secp256k1::SecretKey::from_slice(secret_key).expect("valid secret key");
+    let (recovery_id, compact) = Secp256k1::new()
+        .sign_ecdsa_recoverable(&Message::from_digest(keccak256(&payload)), &sk)
+        .serialize_compact();
+
+    let mut signed = fields;
+    signed.push(alloy_rlp::encode(recovery_id.to_i32() as u8));
+    signed.push(alloy_rlp::encode(&compact[..32]));
+    signed.push(alloy_rlp::encode(&compact[32..]));
+    let refs: Vec<&[u8]> = signed.iter().map(|f| f.as_slice()).collect();
+    let mut raw = vec![0x02];
+    raw.extend(encode_list(&refs));
+    raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::BASE_CHAIN_ID;
+
+    const TEST_KEY: [u8; 32] = [0x46; 32];
+
+    /// Build a signed EIP-1559 transaction to 0x3535..35
+    fn signed_1559(chain_id: u64, nonce: u64, value: u128) -> Vec<u8> {
+        sign_1559(vec![
+            alloy_rlp::encode(chain_id),
+            alloy_rlp::encode(nonce),
+            alloy_rlp::encode(1_000_000_000u64),
+            alloy_rlp::encode(2_000_000_000u64),
+            alloy_rlp::encode(21_000u64),
+            alloy_rlp::encode([0x35u8; 20]),
+            alloy_rlp::encode(value),
+            alloy_rlp::encode(&[0xabu8, 0xcd][..]),
+            vec![0xc0],
+        ])
+    }
+
+    fn sign_1559(fields: Vec<Vec<u8>>) -> Vec<u8> {
+        super::sign_1559(&TEST_KEY, fields)
+    }
+
+    fn test_sender() -> Address {
+        Address::EVM(hex::decode("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f").unwrap().try_into().unwrap())
+    }
+
+    #[test]
+    fn test_eip155_reference_vector() {
+        // Example from the EIP-155 specification
+        let raw = hex::decode(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        )
+        .unwrap();
+
+        let tx = decode(&raw).unwrap();
+        assert_eq!(tx.tx_type, EthTxType::Legacy);
+        assert_eq!(tx.chain_id, 1);
+        assert_eq!(tx.nonce, 9);
+        assert_eq!(tx.gas_price, 20_000_000_000);
+        assert_eq!(tx.gas_limit, 21_000);
+        assert_eq!(tx.to, Some([0x35; 20]));
+        assert_eq!(tx.value, 1_000_000_000_000_000_000);
+        assert_eq!(tx.from, test_sender());
+    }
+
+    #[test]
+    fn test_eip1559_maps_to_transaction() {
+        let chain_id = BASE_CHAIN_ID + 1;
+        let raw = signed_1559(chain_id, 3, 1_500_000_000_000_000_000);
+
+        let tx = decode_transaction(&raw, chain_id, 1).unwrap();
+        assert_eq!(tx.shard_id, 1);
+        assert_eq!(tx.nonce, 3);
+        assert_eq!(tx.from, test_sender());
+        assert_eq!(tx.to, Some(Address::EVM([0x35; 20])));
+        assert_eq!(tx.value, Decimal::new(15, 1));
+        // 2 gwei max fee, 1 gwei of it priority over 21000 gas at most
+        assert_eq!(tx.gas_price, Decimal::new(2, 9));
+        assert_eq!(tx.tip, Decimal::new(21, 6));
+        assert_eq!(tx.data, vec![0xab, 0xcd]);
+        assert_eq!(tx.hash(), keccak256(&raw));
+        assert!(tx.verify_signature().unwrap());
+
+        // Survives the canonical encoding
+        let decoded = Transaction::decode(&tx.encode()).unwrap();
+        assert_eq!(decoded, tx);
+        assert!(decoded.verify_signature().unwrap());
+    }
+
+    #[test]
+    fn test_rejects_wrong_chain_id() {
+        let raw = signed_1559(1, 0, 1);
+        assert!(decode_transaction(&raw, BASE_CHAIN_ID, 0).is_err());
+    }
+
+    #[test]
+    fn test_rejects_unprotected_legacy() {
+        // Same vector as above with v = 27 (pre-EIP-155)
+        let raw = hex::decode(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000801ba028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        )
+        .unwrap();
+        assert!(decode(&raw).is_err());
+    }
+
+    #[test]
+    fn test_tampered_fields_fail_verification() {
+        let chain_id = BASE_CHAIN_ID;
+        let mut tx = decode_transaction(&signed_1559(chain_id, 0, 10), chain_id, 0).unwrap();
+        tx.value = Decimal::from(1_000);
+        assert!(!tx.verify_signature().unwrap());
+    }
+
+    #[test]
+    fn test_rejects_malformed_envelopes() {
+        let chain_id = BASE_CHAIN_ID;
+        let raw = signed_1559(chain_id, 0, 10);
+
+        assert!(decode(&raw[..raw.len() - 1]).is_err());
+        let mut trailing = raw.clone();
+        trailing.push(0);
+        assert!(decode(&trailing).is_err());
+        assert!(decode(&[0x03, 0xc0]).is_err());
+        assert!(decode(&[]).is_err());
+    }
+
+    #[test]
+    fn test_fee_caps_and_access_lists() {
+        let chain_id = BASE_CHAIN_ID;
+        let fields = |priority: u64, access_list: Vec<u8>| {
+            vec![
+                alloy_rlp::encode(chain_id),
+                alloy_rlp::encode(0u64),
+                alloy_rlp::encode(priority),
+                alloy_rlp::encode(2_000_000_000u64),
+                alloy_rlp::encode(21_000u64),
+                alloy_rlp::encode([0x35u8; 20]),
+                alloy_rlp::encode(1u64),
+                alloy_rlp::encode(&[][..] as &[u8]),
+                access_list,
+            ]
+        };
+
+        let overpriced = sign_1559(fields(3_000_000_000, vec![0xc0]));
+        let e = decode_transaction(&overpriced, chain_id, 0).unwrap_err().to_string();
+        assert!(e.contains("exceeds maxFeePerGas"), "{}", e);
+
+        // [[0x35..35, [0x01..01]], [0x36..36, []]]
+        let key = alloy_rlp::encode([0x01u8; 32]);
+        let first = encode_list(&[&alloy_rlp::encode([0x35u8; 20]), &encode_list(&[&key])]);
+        let second = encode_list(&[&alloy_rlp::encode([0x36u8; 20]), &[0xc0]]);
+        let raw = sign_1559(fields(1_000_000_000, encode_list(&[&first, &second])));
+        let expected = vec![
+            AccessListItem { address: [0x35; 20], storage_keys: vec![[0x01; 32]] },
+            AccessListItem { address: [0x36; 20], storage_keys: vec![] },
+        ];
+        assert!(decode(&raw).is_ok());
+        assert_eq!(access_list(&raw).unwrap(), expected);
+
+        // Keys must be 32 bytes
+        let short_key = encode_list(&[&alloy_rlp::encode([0x35u8; 20]), &encode_list(&[&alloy_rlp::encode(1u64)])]);
+        let e = decode(&sign_1559(fields(1_000_000_000, encode_list(&[&short_key])))).unwrap_err().to_string();
+        assert!(e.contains("Storage key must be 32 bytes"), "{}", e);
+    }
+}
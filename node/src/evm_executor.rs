@@ -1,14 +1,27 @@
-use alloy_primitives::{Address, U256, Bytes};
+use alloy_primitives::{Address, B256, U256, Bytes};
+use alloy_sol_types::{Panic, Revert, SolError};
 use anyhow::{Result, anyhow};
 use revm::{
+    db::CacheDB,
+    handler::register::HandleRegisterBox,
     primitives::{
-        ExecutionResult, Output, TransactTo, TxEnv, Env, BlockEnv, CfgEnv,
+        AccessListItem, AccountInfo, Bytecode, EvmState, ExecutionResult, Output, TransactTo, TxEnv, Env, BlockEnv,
     },
-    Database, Evm, InMemoryDB,
+    Database, DatabaseRef, Evm, GetInspector, InMemoryDB,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tracing::info;
+use std::sync::Arc;
+
+use crate::ai_compute::AIPrecompiles;
+use crate::config::GasConfig;
+use crate::crypto::Address as NodeAddress;
+use crate::eth_tx;
+use crate::pq_precompiles;
+use crate::sequencer::Log;
+use crate::state_db::StateStore;
+use crate::state_trie::{self, AccountLeaf, EMPTY_CODE_HASH};
+use crate::transaction::Transaction;
 
 /// EVM transaction for Solidity contracts
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +33,44 @@ pub struct EvmTransaction {
     pub gas_limit: u64,
     pub gas_price: U256,
     pub nonce: u64,
+    /// Accounts and slots warmed before execution
+    pub access_list: Vec<AccessListItem>,
+}
+
+impl EvmTransaction {
+    /// EVM side of a node transaction. Signature verification is paid for
+    /// outside the EVM, so its gas is taken off the limit; the fee model
+    /// charges for gas, so the EVM runs at a zero gas price.
+    pub fn from_transaction(tx: &Transaction, gas: &GasConfig) -> Result<Self> {
+        let access_list = match &tx.eth_envelope {
+            Some(envelope) => eth_tx::access_list(envelope)?,
+            None => Vec::new(),
+        };
+        Ok(Self {
+            from: tx.from.evm_bytes().into(),
+            to: tx.to.map(|to| to.evm_bytes().into()),
+            value: U256::from(tx.value_wei()?),
+            data: Bytes::from(tx.data.clone()),
+            gas_limit: tx.gas_limit.saturating_sub(gas.signature_gas(tx)),
+            gas_price: U256::ZERO,
+            nonce: tx.nonce,
+            access_list: access_list
+                .into_iter()
+                .map(|item| AccessListItem {
+                    address: item.address.into(),
+                    storage_keys: item.storage_keys.into_iter().map(B256::from).collect(),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Address of the node's precompile `id`
+pub const fn precompile_address(id: u16) -> Address {
+    let mut bytes = [0u8; 20];
+    bytes[18] = (id >> 8) as u8;
+    bytes[19] = id as u8;
+    Address::new(bytes)
 }
 
 /// EVM execution result
@@ -29,13 +80,106 @@ pub struct EvmExecutionResult {
     pub gas_used: u64,
     pub output: Vec<u8>,
     pub contract_address: Option<Address>,
-    pub logs: Vec<String>,
+    pub logs: Vec<Log>,
+    /// Why execution halted (out of gas, invalid opcode, ...), if it did
+    #[serde(default)]
+    pub halt_reason: Option<String>,
+    /// Decoded `Error(string)` or `Panic(uint256)` of a revert
+    #[serde(default)]
+    pub revert_reason: Option<String>,
+}
+
+/// Block a transaction executes in
+#[derive(Debug, Clone, Default)]
+pub struct BlockContext {
+    pub number: u64,
+    pub timestamp: u64,
+    /// Block producer (COINBASE)
+    pub coinbase: Address,
+    /// Base fee per gas in wei (BASEFEE)
+    pub basefee: U256,
+}
+
+/// Read-only EVM view of node state under a state root
+pub struct StateView<'a> {
+    store: &'a dyn StateStore,
+    root: [u8; 32],
+    /// Native accounts the EVM sees under their 20-byte form
+    aliases: HashMap<Address, NodeAddress>,
+}
+
+impl<'a> StateView<'a> {
+    pub fn new(store: &'a dyn StateStore, root: [u8; 32]) -> Self {
+        Self { store, root, aliases: HashMap::new() }
+    }
+
+    /// Show `account` to the EVM at its 20-byte form, as the sequencer does
+    /// for the sender of a transaction
+    pub fn with_account(mut self, account: NodeAddress) -> Self {
+        self.aliases.insert(account.evm_bytes().into(), account);
+        self
+    }
+
+    fn account(&self, address: Address) -> Result<Option<AccountLeaf>> {
+        let account = match self.aliases.get(&address) {
+            Some(account) => *account,
+            None => NodeAddress::EVM(address.into_array()),
+        };
+        state_trie::get_account(self.store, self.root, &account)
+    }
+}
+
+impl DatabaseRef for StateView<'_> {
+    type Error = anyhow::Error;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>> {
+        let Some(leaf) = self.account(address)? else {
+            return Ok(None);
+        };
+        let code_hash = B256::from(leaf.code_hash);
+        Ok(Some(AccountInfo::new(
+            U256::from(leaf.balance),
+            leaf.nonce,
+            code_hash,
+            self.code_by_hash_ref(code_hash)?,
+        )))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode> {
+        if code_hash.0 == EMPTY_CODE_HASH {
+            return Ok(Bytecode::default());
+        }
+        // Contract code lives in the node table under its hash
+        let code = self
+            .store
+            .trie_node(&code_hash.0)?
+            .ok_or_else(|| anyhow!("Missing contract code {}", code_hash))?;
+        Ok(Bytecode::new_raw(Bytes::from(code)))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256> {
+        let Some(leaf) = self.account(address)? else {
+            return Ok(U256::ZERO);
+        };
+        let value = state_trie::get_storage(self.store, leaf.storage_root, &index.to_be_bytes::<32>())?;
+        Ok(U256::from_be_bytes(value))
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256> {
+        Ok(self
+            .store
+            .micro_block(number)?
+            .map(|block| B256::from(block.hash()))
+            .unwrap_or_default())
+    }
 }
 
 /// EVM executor for Solidity smart contracts
 pub struct EvmExecutor {
     db: InMemoryDB,
     chain_id: u64,
+    /// Prices of signature verification, in and out of the precompiles
+    gas: GasConfig,
 }
 
 impl EvmExecutor {
@@ -43,20 +187,160 @@ impl EvmExecutor {
         Self {
             db: InMemoryDB::default(),
             chain_id,
+            gas: GasConfig::default(),
         }
     }
 
-    /// Execute an EVM transaction
+    pub fn with_gas_config(mut self, gas: GasConfig) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    /// EVM side of a node transaction, priced by this executor's gas config
+    pub fn transaction(&self, tx: &Transaction) -> Result<EvmTransaction> {
+        EvmTransaction::from_transaction(tx, &self.gas)
+    }
+
+    /// Execute an EVM transaction and commit its state changes
     pub fn execute(&mut self, tx: EvmTransaction) -> Result<EvmExecutionResult> {
-        // Set up environment
+        let block = BlockContext { number: 1, ..BlockContext::default() };
+        let env = self.env(&block, &tx);
+
+        // Create and execute EVM
+        let precompiles = self.precompiles();
+        let mut evm = Evm::builder()
+            .with_env(Box::new(env))
+            .with_db(&mut self.db)
+            .append_handler_register_box(precompiles)
+            .build();
+
+        let result = evm.transact_commit().map_err(|e| anyhow!("EVM execution failed: {:?}", e))?;
+        Ok(Self::into_result(result))
+    }
+
+    /// Run a transaction against `db` without committing anything.
+    /// Nonces are not checked, as for `eth_call`.
+    pub fn simulate<DB>(&self, db: DB, block: &BlockContext, tx: &EvmTransaction) -> Result<EvmExecutionResult>
+    where
+        DB: DatabaseRef,
+        DB::Error: std::fmt::Debug,
+    {
+        let mut env = self.env(block, tx);
+        env.tx.nonce = None;
+
+        let mut evm = Evm::builder()
+            .with_env(Box::new(env))
+            .with_ref_db(db)
+            .append_handler_register_box(self.precompiles())
+            .build();
+
+        let result = evm.transact().map_err(|e| anyhow!("EVM execution failed: {:?}", e))?;
+        Ok(Self::into_result(result.result))
+    }
+
+    /// Read-only call against the executor's own state
+    pub fn call(&self, tx: &EvmTransaction) -> Result<EvmExecutionResult> {
+        self.simulate(&self.db, &BlockContext { number: 1, ..BlockContext::default() }, tx)
+    }
+
+    /// Execute a transaction against `db` and return the state it changed
+    /// for the caller to apply. Gas is metered but not paid for here: the
+    /// node's fee model charges for the gas used, so the EVM runs at a zero
+    /// gas price and skips the base fee check.
+    pub fn transact<DB>(
+        &self,
+        db: DB,
+        block: &BlockContext,
+        tx: &EvmTransaction,
+    ) -> Result<(EvmExecutionResult, EvmState)>
+    where
+        DB: DatabaseRef,
+        DB::Error: std::fmt::Debug,
+    {
+        let mut evm = Evm::builder()
+            .with_env(Box::new(self.sequencer_env(block, tx)))
+            .with_ref_db(db)
+            .append_handler_register_box(self.precompiles())
+            .build();
+
+        let result = evm.transact().map_err(|e| anyhow!("EVM execution failed: {:?}", e))?;
+        Ok((Self::into_result(result.result), result.state))
+    }
+
+    /// Execute a transaction as the sequencer did and commit it to `db`,
+    /// to rebuild the state a later transaction of the block ran against
+    pub fn replay<DB>(&self, db: &mut CacheDB<DB>, block: &BlockContext, tx: &EvmTransaction) -> Result<EvmExecutionResult>
+    where
+        DB: DatabaseRef,
+        DB::Error: std::fmt::Debug,
+    {
+        let mut evm = Evm::builder()
+            .with_env(Box::new(self.sequencer_env(block, tx)))
+            .with_db(db)
+            .append_handler_register_box(self.precompiles())
+            .build();
+
+        let result = evm.transact_commit().map_err(|e| anyhow!("EVM execution failed: {:?}", e))?;
+        Ok(Self::into_result(result))
+    }
+
+    /// Execute a transaction as the sequencer does, reporting each step to
+    /// `inspector`. Nothing is committed.
+    pub fn inspect<DB, I>(&self, db: DB, block: &BlockContext, tx: &EvmTransaction, inspector: I) -> Result<EvmExecutionResult>
+    where
+        DB: Database,
+        DB::Error: std::fmt::Debug,
+        I: GetInspector<DB>,
+    {
+        let mut evm = Evm::builder()
+            .with_env(Box::new(self.sequencer_env(block, tx)))
+            .with_db(db)
+            .with_external_context(inspector)
+            .append_handler_register(revm::inspector_handle_register)
+            .append_handler_register_box(self.precompiles())
+            .build();
+
+        let result = evm.transact().map_err(|e| anyhow!("EVM execution failed: {:?}", e))?;
+        Ok(Self::into_result(result.result))
+    }
+
+    /// Handler register adding the node's precompiles (see `pq_precompiles`
+    /// and `ai_compute`) to Ethereum's
+    fn precompiles<'a, EXT, DB: Database>(&self) -> HandleRegisterBox<'a, EXT, DB> {
+        let mut extra = pq_precompiles::precompiles(&self.gas);
+        extra.extend(AIPrecompiles::precompiles());
+        Box::new(move |handler| {
+            let precompiles = handler.pre_execution.load_precompiles();
+            let extra = extra.clone();
+            handler.pre_execution.load_precompiles = Arc::new(move || {
+                let mut precompiles = precompiles.clone();
+                precompiles.extend(extra.clone());
+                precompiles
+            });
+        })
+    }
+
+    /// Environment of sequencer execution: gas is metered but not paid for
+    /// in the EVM, see `transact`
+    fn sequencer_env(&self, block: &BlockContext, tx: &EvmTransaction) -> Env {
+        let mut env = self.env(block, tx);
+        env.cfg.disable_base_fee = true;
+        env.tx.gas_price = U256::ZERO;
+        env
+    }
+
+    fn env(&self, block: &BlockContext, tx: &EvmTransaction) -> Env {
         let mut env = Env::default();
-        
+
         // Configure chain
         env.cfg.chain_id = self.chain_id;
 
         // Configure block
         env.block = BlockEnv {
-            number: U256::from(1),
+            number: U256::from(block.number),
+            timestamp: U256::from(block.timestamp),
+            coinbase: block.coinbase,
+            basefee: block.basefee,
             ..Default::default()
         };
 
@@ -72,42 +356,47 @@ impl EvmExecutor {
             value: tx.value,
             data: tx.data.clone(),
             nonce: Some(tx.nonce),
+            access_list: tx.access_list.clone(),
             ..Default::default()
         };
 
-        // Create and execute EVM
-        let mut evm = Evm::builder()
-            .with_env(Box::new(env))
-            .with_db(&mut self.db)
-            .build();
-
-        let result = evm.transact().map_err(|e| anyhow!("EVM execution failed: {:?}", e))?;
+        env
+    }
 
-        // Extract results
-        let execution_result = result.result;
-        
-        let (success, gas_used, output, contract_address) = match execution_result {
-            ExecutionResult::Success { gas_used, output, .. } => {
+    fn into_result(execution_result: ExecutionResult) -> EvmExecutionResult {
+        let (success, gas_used, output, contract_address, logs, halt_reason) = match execution_result {
+            ExecutionResult::Success { gas_used, output, logs, .. } => {
+                let logs = logs
+                    .into_iter()
+                    .map(|log| Log {
+                        address: NodeAddress::EVM(log.address.into_array()),
+                        topics: log.data.topics().iter().map(|topic| topic.0).collect(),
+                        data: log.data.data.to_vec(),
+                    })
+                    .collect();
                 match output {
-                    Output::Call(bytes) => (true, gas_used, bytes.to_vec(), None),
-                    Output::Create(bytes, addr) => (true, gas_used, bytes.to_vec(), addr),
+                    Output::Call(bytes) => (true, gas_used, bytes.to_vec(), None, logs, None),
+                    Output::Create(bytes, addr) => (true, gas_used, bytes.to_vec(), addr, logs, None),
                 }
             }
             ExecutionResult::Revert { gas_used, output } => {
-                (false, gas_used, output.to_vec(), None)
+                (false, gas_used, output.to_vec(), None, vec![], None)
             }
-            ExecutionResult::Halt { gas_used, .. } => {
-                (false, gas_used, vec![], None)
+            ExecutionResult::Halt { gas_used, reason } => {
+                (false, gas_used, vec![], None, vec![], Some(format!("{:?}", reason)))
             }
         };
 
-        Ok(EvmExecutionResult {
+        let revert_reason = if success || halt_reason.is_some() { None } else { revert_reason(&output) };
+        EvmExecutionResult {
             success,
             gas_used,
             output,
             contract_address,
-            logs: vec![], // Simplified - would extract from result
-        })
+            logs,
+            halt_reason,
+            revert_reason,
+        }
     }
 
     /// Set account balance (for testing)
@@ -132,6 +421,15 @@ impl EvmExecutor {
     }
 }
 
+/// Decode revert data raised by Solidity's `require`/`revert` (`Error(string)`)
+/// or by a failed check such as an assertion or overflow (`Panic(uint256)`)
+pub fn revert_reason(output: &[u8]) -> Option<String> {
+    if let Ok(revert) = Revert::abi_decode(output, true) {
+        return Some(revert.reason);
+    }
+    Panic::abi_decode(output, true).ok().map(|panic| panic.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,6 +453,7 @@ mod tests {
             gas_limit: 21000,
             gas_price: U256::from(1),
             nonce: 0,
+            access_list: Vec::new(),
         };
 
         let result = executor.execute(tx).unwrap();
@@ -182,6 +481,7 @@ mod tests {
             gas_limit: 100000,
             gas_price: U256::from(1),
             nonce: 0,
+            access_list: Vec::new(),
         };
 
         let result = executor.execute(tx).unwrap();
@@ -189,4 +489,93 @@ mod tests {
         assert!(result.success);
         assert!(result.contract_address.is_some());
     }
+
+    #[test]
+    fn test_call_does_not_commit() {
+        let mut executor = EvmExecutor::new(1);
+
+        let from = Address::from([1u8; 20]);
+        let to = Address::from([2u8; 20]);
+        executor.set_balance(from, U256::from(1_000));
+
+        let tx = EvmTransaction {
+            from,
+            to: Some(to),
+            value: U256::from(100),
+            data: Bytes::new(),
+            gas_limit: 21000,
+            gas_price: U256::ZERO,
+            nonce: 0,
+            access_list: Vec::new(),
+        };
+
+        let result = executor.call(&tx).unwrap();
+        assert!(result.success);
+        assert_eq!(executor.get_balance(from), U256::from(1_000));
+        assert_eq!(executor.get_balance(to), U256::ZERO);
+
+        // The same transaction executed for real moves the funds
+        executor.execute(tx).unwrap();
+        assert_eq!(executor.get_balance(to), U256::from(100));
+    }
+
+    #[test]
+    fn test_ai_precompile_call() {
+        use crate::ai_compute::{linearRegressionCall, FIXED_ONE};
+        use alloy_primitives::I256;
+        use alloy_sol_types::SolCall;
+
+        let executor = EvmExecutor::new(1);
+        let fixed = |x: i128| I256::try_from(x * FIXED_ONE).unwrap();
+        let call = |gas_limit: u64| EvmTransaction {
+            from: Address::from([1u8; 20]),
+            to: Some(AIPrecompiles::LINEAR_REGRESSION),
+            value: U256::ZERO,
+            data: linearRegressionCall { slope: fixed(2), intercept: fixed(1), x: fixed(3) }.abi_encode().into(),
+            gas_limit,
+            gas_price: U256::ZERO,
+            nonce: 0,
+            access_list: Vec::new(),
+        };
+
+        let result = executor.call(&call(100_000)).unwrap();
+        assert!(result.success);
+        let prediction = linearRegressionCall::abi_decode_returns(&result.output, true).unwrap().prediction;
+        assert_eq!(prediction, fixed(7));
+
+        // Not enough gas left for the model after the intrinsic cost
+        assert!(!executor.call(&call(result.gas_used - 1)).unwrap().success);
+    }
+
+    #[test]
+    fn test_precompile_addresses_distinct() {
+        use crate::cross_shard::CROSS_SHARD_ROUTER;
+        use crate::fee_model::{TREASURY, VALIDATOR_FEE_POOL};
+        use revm::precompile::{PrecompileSpecId, Precompiles};
+
+        let executor = EvmExecutor::new(1);
+        let mut node: Vec<Address> = pq_precompiles::precompiles(&executor.gas).iter().map(|p| p.0).collect();
+        node.extend(AIPrecompiles::precompiles().iter().map(|p| p.0));
+        let mut taken: Vec<Address> = Precompiles::new(PrecompileSpecId::LATEST).addresses().copied().collect();
+        taken.extend([CROSS_SHARD_ROUTER, VALIDATOR_FEE_POOL, TREASURY].map(|system| Address::from(system.evm_bytes())));
+
+        for address in node {
+            assert!(!taken.contains(&address), "{} is used twice", address);
+            taken.push(address);
+        }
+    }
+
+    #[test]
+    fn test_revert_reason() {
+        let error = Revert::from("insufficient allowance").abi_encode();
+        assert_eq!(revert_reason(&error).as_deref(), Some("insufficient allowance"));
+
+        // Panic(0x11): arithmetic overflow
+        let panic = Panic::from(U256::from(0x11)).abi_encode();
+        assert_eq!(revert_reason(&panic).as_deref(), Some("panic: arithmetic underflow or overflow (0x11)"));
+
+        // Custom errors and bare reverts are left undecoded
+        assert_eq!(revert_reason(&[]), None);
+        assert_eq!(revert_reason(&hex::decode("deadbeef").unwrap()), None);
+    }
 }
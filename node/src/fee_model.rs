@@ -1,7 +1,21 @@
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
 
+use crate::crypto::Address;
+use crate::transaction::IONX_WEI;
+
+/// System account collecting the validators' share of fees on each shard
+pub const VALIDATOR_FEE_POOL: Address = Address::EVM([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0x01,
+]);
+
+/// Protocol treasury account
+pub const TREASURY: Address = Address::EVM([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0x02,
+]);
+
 /// Fee model configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FeeConfig {
@@ -13,6 +27,44 @@ pub struct FeeConfig {
     pub target_utilization: Decimal,
     /// Adjustment factor for dynamic fee (e.g., 0.125 = 12.5%)
     pub adjustment_factor: Decimal,
+    /// How base and gas fees are shared out
+    #[serde(default)]
+    pub split: FeeSplit,
+}
+
+/// Shares of the base and gas fees (the tip goes to the sequencer)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeSplit {
+    pub validators: Decimal,
+    pub treasury: Decimal,
+    pub burn: Decimal,
+}
+
+impl Default for FeeSplit {
+    fn default() -> Self {
+        Self {
+            validators: dec!(0.6),
+            treasury: dec!(0.2),
+            burn: dec!(0.2),
+        }
+    }
+}
+
+impl FeeSplit {
+    /// Shares must be non-negative and add up to one
+    pub fn is_valid(&self) -> bool {
+        let shares = [self.validators, self.treasury, self.burn];
+        shares.iter().all(|share| !share.is_sign_negative()) && shares.iter().sum::<Decimal>() == Decimal::ONE
+    }
+}
+
+/// Where a charged fee goes, in wei
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeShares {
+    pub validators: u128,
+    pub treasury: u128,
+    pub burned: u128,
+    pub sequencer: u128,
 }
 
 impl Default for FeeConfig {
@@ -22,6 +74,7 @@ impl Default for FeeConfig {
             base_fee_per_gas: dec!(0.000001),
             target_utilization: dec!(0.8),
             adjustment_factor: dec!(0.125),
+            split: FeeSplit::default(),
         }
     }
 }
@@ -52,6 +105,31 @@ impl TransactionFee {
             total,
         }
     }
+
+    /// Total fee in wei
+    pub fn total_wei(&self) -> u128 {
+        to_wei(self.total)
+    }
+
+    /// Share the fee out: the tip to the sequencer, the rest per `split`.
+    /// Rounding leftovers are burned, so the shares add up to the total.
+    pub fn shares(&self, split: &FeeSplit) -> FeeShares {
+        let sequencer = to_wei(self.tip);
+        let shared = self.total_wei().saturating_sub(sequencer);
+        let validators = to_wei((self.total - self.tip) * split.validators);
+        let treasury = to_wei((self.total - self.tip) * split.treasury);
+        FeeShares {
+            validators,
+            treasury,
+            burned: shared.saturating_sub(validators + treasury),
+            sequencer,
+        }
+    }
+}
+
+/// IONX amount in wei, truncated
+pub fn to_wei(amount: Decimal) -> u128 {
+    (amount * Decimal::from(IONX_WEI)).trunc().to_u128().unwrap_or(0)
 }
 
 /// Dynamic fee adjustment based on block utilization
@@ -93,6 +171,25 @@ mod tests {
         assert_eq!(fee.total, dec!(0.0051));
     }
 
+    #[test]
+    fn test_fee_shares_add_up() {
+        let config = FeeConfig::default();
+        let fee = TransactionFee::calculate(&config, 24_000, dec!(0.001));
+        let shares = fee.shares(&config.split);
+
+        assert_eq!(shares.sequencer, 1_000_000_000_000_000);
+        // (0.0001 + 0.024) IONX shared 60/20/20
+        assert_eq!(shares.validators, 14_460_000_000_000_000);
+        assert_eq!(shares.treasury, 4_820_000_000_000_000);
+        assert_eq!(shares.burned, 4_820_000_000_000_000);
+        assert_eq!(
+            shares.validators + shares.treasury + shares.burned + shares.sequencer,
+            fee.total_wei()
+        );
+        assert!(config.split.is_valid());
+        assert!(!FeeSplit { burn: dec!(0.3), ..FeeSplit::default() }.is_valid());
+    }
+
     #[test]
     fn test_fee_adjustment_over_target() {
         let current = dec!(0.000001);
@@ -1,9 +1,16 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+
+use crate::crypto::{keccak256, PublicKeyData, Signature};
+use crate::sequencer::BatchCommitment;
+use crate::staking::StakingAction;
+
+/// Votes keyed by (view, phase, validator)
+type CastVotes = HashMap<(u64, VotePhase, String), Vote>;
 
 /// HotStuff-based finality gadget for Ionova
 /// Provides BFT consensus with 3-phase commit
@@ -17,8 +24,17 @@ pub struct FinalityGadget {
     /// Proposal history
     proposals: Arc<RwLock<HashMap<u64, Proposal>>>,
     
-    /// Vote storage
-    votes: Arc<RwLock<HashMap<u64, VoteCollection>>>,
+    /// Vote storage by view and block hash
+    votes: Arc<RwLock<HashMap<(u64, String), VoteCollection>>>,
+
+    /// First vote of each validator, by view and phase
+    cast_votes: Arc<RwLock<CastVotes>>,
+
+    /// Timeout votes by view and validator
+    timeouts: Arc<RwLock<HashMap<u64, HashMap<String, TimeoutVote>>>>,
+
+    /// Commit QC of the last finalized block
+    finalized_qc: Arc<RwLock<Option<QuorumCertificate>>>,
     
     /// Configuration
     config: FinalityConfig,
@@ -28,6 +44,9 @@ pub struct FinalityGadget {
 pub struct FinalityConfig {
     /// View timeout (ms)
     pub view_timeout_ms: u64,
+
+    /// Upper bound of the backed-off view timeout (ms)
+    pub max_view_timeout_ms: u64,
     
     /// Minimum validators needed (Byzantine fault tolerance)
     pub min_validators: usize,
@@ -40,6 +59,7 @@ impl Default for FinalityConfig {
     fn default() -> Self {
         Self {
             view_timeout_ms: 1000,
+            max_view_timeout_ms: 30_000,
             min_validators: 4,
             pipelined: true,
         }
@@ -54,7 +74,8 @@ pub struct ValidatorSet {
 
 #[derive(Debug, Clone)]
 pub struct ValidatorInfo {
-    pub pub_key: Vec<u8>,
+    /// Post-quantum (Dilithium or SPHINCS+) key votes and proposals are signed with
+    pub public_key: PublicKeyData,
     pub stake: u64,
     pub is_active: bool,
 }
@@ -68,8 +89,49 @@ impl ValidatorSet {
     }
 
     pub fn add_validator(&mut self, id: String, info: ValidatorInfo) {
-        self.total_stake += info.stake;
         self.validators.insert(id, info);
+        self.recount_stake();
+    }
+
+    /// Change a validator's stake and status, e.g. after slashing
+    pub fn update_validator(&mut self, id: &str, stake: u64, is_active: bool) {
+        if let Some(info) = self.validators.get_mut(id) {
+            info.stake = stake;
+            info.is_active = is_active;
+        }
+        self.recount_stake();
+    }
+
+    /// Only active validators count towards the total stake
+    fn recount_stake(&mut self) {
+        self.total_stake = self.validators.values().filter(|info| info.is_active).map(|info| info.stake).sum();
+    }
+
+    /// Stake of an active validator, `None` for anyone else
+    pub fn active_stake(&self, id: &str) -> Option<u64> {
+        self.validators.get(id).filter(|info| info.is_active).map(|info| info.stake)
+    }
+
+    /// Member ids in the canonical order signer bitmaps index
+    pub fn ordered_ids(&self) -> Vec<&String> {
+        let mut ids: Vec<&String> = self.validators.keys().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Hash binding every member's id, key, stake and activity, in
+    /// `ordered_ids` order
+    pub fn commitment(&self) -> [u8; 32] {
+        let mut data = b"ionova-validator-set".to_vec();
+        for id in self.ordered_ids() {
+            let info = &self.validators[id];
+            let key = serde_json::to_vec(&info.public_key).unwrap_or_default();
+            data.extend_from_slice(id.as_bytes());
+            data.extend_from_slice(&keccak256(&key));
+            data.extend_from_slice(&info.stake.to_be_bytes());
+            data.push(info.is_active as u8);
+        }
+        keccak256(&data)
     }
 
     /// Calculate 2/3+ threshold for BFT
@@ -80,6 +142,220 @@ impl ValidatorSet {
     pub fn has_quorum(&self, stake: u64) -> bool {
         stake >= self.quorum_threshold()
     }
+
+    /// Leader of a view, drawn from the active validators with probability
+    /// proportional to stake, deterministically from the view number
+    pub fn leader(&self, view: u64) -> Option<&String> {
+        let mut active: Vec<(&String, u64)> = self
+            .validators
+            .iter()
+            .filter(|(_, info)| info.is_active && info.stake > 0)
+            .map(|(id, info)| (id, info.stake))
+            .collect();
+        active.sort();
+        let total: u64 = active.iter().map(|(_, stake)| stake).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let seed = keccak256(&view.to_be_bytes());
+        let mut target = u64::from_be_bytes(seed[..8].try_into().unwrap()) % total;
+        for (id, stake) in active {
+            if target < stake {
+                return Some(id);
+            }
+            target -= stake;
+        }
+        None
+    }
+
+    /// Check the signatures and quorum of a QC
+    pub fn check_qc(&self, qc: &QuorumCertificate) -> Result<()> {
+        let payload = vote_payload(qc.phase, qc.view, &qc.block_hash);
+        let stake = self.signed_stake(&qc.signatures, &payload)?;
+        if !self.has_quorum(stake) {
+            bail!("QC for view {} lacks quorum ({} stake)", qc.view, stake);
+        }
+        Ok(())
+    }
+
+    /// Check the signatures and quorum of a TC and the prepare QC it carries
+    pub fn check_timeout_certificate(&self, tc: &TimeoutCertificate) -> Result<()> {
+        let stake = self.signed_stake(&tc.signatures, &timeout_payload(tc.view))?;
+        if !self.has_quorum(stake) {
+            bail!("TC for view {} lacks quorum ({} stake)", tc.view, stake);
+        }
+        if let Some(qc) = &tc.high_qc {
+            if qc.phase != VotePhase::Prepare || qc.view > tc.view {
+                bail!("TC for view {} carries an invalid high QC", tc.view);
+            }
+            self.check_qc(qc)?;
+        }
+        Ok(())
+    }
+
+    /// Check that evidence holds two validly signed, conflicting votes
+    pub fn check_evidence(&self, evidence: &EquivocationEvidence) -> Result<()> {
+        let (first, second) = (&evidence.first, &evidence.second);
+        if first.validator_id() != second.validator_id()
+            || first.view() != second.view()
+            || first.phase() != second.phase()
+            || first.block_hash() == second.block_hash()
+        {
+            bail!("Evidence votes do not conflict");
+        }
+        for vote in [first, second] {
+            self.verify_signature(vote.validator_id(), &vote.signing_payload(), vote.signature())?;
+        }
+        Ok(())
+    }
+
+    /// Stake of the distinct validators whose signatures over `payload` verify
+    fn signed_stake(&self, signatures: &[ValidatorSignature], payload: &[u8]) -> Result<u64> {
+        let mut signers = HashSet::new();
+        let mut stake = 0;
+        for signature in signatures {
+            if !signers.insert(&signature.validator_id) {
+                bail!("Duplicate signature by {}", signature.validator_id);
+            }
+            stake += self.verify_signature(&signature.validator_id, payload, &signature.signature)?.stake;
+        }
+        Ok(stake)
+    }
+
+    /// Check that a proposal comes from the leader of its view, carries its
+    /// signature, and is justified by a commit QC or TC of the previous view.
+    /// A QC matching `finalized`, already verified when its block finalized,
+    /// is not checked again: it was signed by the set before any slashing
+    /// that block applied.
+    pub fn check_proposal(
+        &self,
+        proposal: &Proposal,
+        signature: &[u8],
+        finalized: Option<&QuorumCertificate>,
+    ) -> Result<()> {
+        if self.leader(proposal.view) != Some(&proposal.proposer) {
+            bail!("{} is not the leader of view {}", proposal.proposer, proposal.view);
+        }
+        self.verify_signature(&proposal.proposer, &proposal.signing_payload(), signature)?;
+
+        if let Some(qc) = &proposal.qc {
+            if qc.phase != VotePhase::Commit || qc.view >= proposal.view {
+                bail!("Proposal for view {} carries an invalid QC", proposal.view);
+            }
+            let known = finalized
+                .is_some_and(|f| f.phase == qc.phase && f.view == qc.view && f.block_hash == qc.block_hash);
+            if !known {
+                self.check_qc(qc)?;
+            }
+        }
+        if let Some(tc) = &proposal.tc {
+            if tc.view >= proposal.view {
+                bail!("Proposal for view {} carries an invalid TC", proposal.view);
+            }
+            self.check_timeout_certificate(tc)?;
+        }
+        let justified = proposal.view == 0
+            || proposal.qc.as_ref().is_some_and(|qc| qc.view + 1 == proposal.view)
+            || proposal.tc.as_ref().is_some_and(|tc| tc.view + 1 == proposal.view);
+        if !justified {
+            bail!("Proposal for view {} is not justified by the previous view", proposal.view);
+        }
+        Ok(())
+    }
+
+    /// Check a raw post-quantum signature by an active validator over `payload`
+    pub fn verify_signature(&self, validator_id: &str, payload: &[u8], signature: &[u8]) -> Result<&ValidatorInfo> {
+        let info = self
+            .validators
+            .get(validator_id)
+            .filter(|info| info.is_active)
+            .ok_or_else(|| anyhow!("{} is not an active validator", validator_id))?;
+        let signature = match &info.public_key {
+            PublicKeyData::Dilithium { .. } => Signature::Dilithium { data: signature.to_vec() },
+            PublicKeyData::SPHINCSPlus { .. } => Signature::SPHINCSPlus { data: signature.to_vec() },
+            _ => bail!("Validator {} has no post-quantum key", validator_id),
+        };
+        if !signature.verify(payload, &info.public_key)? {
+            bail!("Invalid signature from validator {}", validator_id);
+        }
+        Ok(info)
+    }
+}
+
+/// Batch commitment as referenced from a base block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchHeader {
+    pub shard_id: u8,
+    pub batch_sequence: u64,
+    pub state_root: String,
+    /// State root of the shard's previous batch
+    #[serde(default)]
+    pub parent_state_root: String,
+    pub hash: [u8; 32],
+    /// Root of the cross-shard receipts the batch sends
+    #[serde(default)]
+    pub receipts_root: [u8; 32],
+    /// Incoming cross-shard receipts the batch claims
+    #[serde(default)]
+    pub claims: Vec<[u8; 32]>,
+    /// Staking transactions the batch included
+    #[serde(default)]
+    pub staking: Vec<StakingAction>,
+}
+
+impl From<&BatchCommitment> for BatchHeader {
+    fn from(batch: &BatchCommitment) -> Self {
+        Self {
+            shard_id: batch.shard_id,
+            batch_sequence: batch.batch_sequence,
+            state_root: batch.state_root.clone(),
+            parent_state_root: batch.parent_state_root.clone(),
+            hash: batch.hash(),
+            receipts_root: batch.receipts_root,
+            claims: batch.claims(),
+            staking: batch.staking_actions(),
+        }
+    }
+}
+
+/// Base-layer block ordering shard batch commitments
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaseBlock {
+    pub height: u64,
+    /// Hash of the previous finalized base block (zero for the first one)
+    pub parent_hash: [u8; 32],
+    pub proposer: String,
+    pub timestamp: u64,
+    pub batches: Vec<BatchHeader>,
+    /// Double-sign evidence submitted for slashing
+    #[serde(default)]
+    pub evidence: Vec<EquivocationEvidence>,
+}
+
+impl BaseBlock {
+    /// Block hash: keccak256 over the header fields and batch hashes
+    pub fn hash(&self) -> [u8; 32] {
+        let mut buf = Vec::with_capacity(96 + 32 * self.batches.len());
+        buf.extend_from_slice(&self.height.to_be_bytes());
+        buf.extend_from_slice(&self.parent_hash);
+        buf.extend_from_slice(self.proposer.as_bytes());
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+        for batch in &self.batches {
+            buf.extend_from_slice(&batch.hash);
+        }
+        for evidence in &self.evidence {
+            buf.extend_from_slice(&evidence.hash());
+        }
+        keccak256(&buf)
+    }
+}
+
+/// Base block together with the commit QC that finalized it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinalizedBlock {
+    pub block: BaseBlock,
+    pub qc: QuorumCertificate,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -89,17 +365,79 @@ pub struct Proposal {
     pub parent_hash: String,
     pub proposer: String,
     pub timestamp: u64,
+    /// Commit QC of the last finalized block
     pub qc: Option<QuorumCertificate>,
+    /// Timeout certificate of the previous view, when it failed
+    #[serde(default)]
+    pub tc: Option<TimeoutCertificate>,
+}
+
+impl Proposal {
+    /// Bytes the proposer signs
+    pub fn signing_payload(&self) -> Vec<u8> {
+        format!("ionova-proposal:{}:{}:{}", self.view, self.block_hash, self.parent_hash).into_bytes()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuorumCertificate {
     pub view: u64,
     pub block_hash: String,
+    /// Phase of the votes the signatures were made for
+    #[serde(default)]
+    pub phase: VotePhase,
     pub signatures: Vec<ValidatorSignature>,
     pub aggregated_stake: u64,
 }
 
+/// Phases of the 3-phase commit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VotePhase {
+    Prepare,
+    PreCommit,
+    #[default]
+    Commit,
+}
+
+impl VotePhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VotePhase::Prepare => "prepare",
+            VotePhase::PreCommit => "precommit",
+            VotePhase::Commit => "commit",
+        }
+    }
+}
+
+/// Bytes a vote signature covers: phase, view and block hash
+pub fn vote_payload(phase: VotePhase, view: u64, block_hash: &str) -> Vec<u8> {
+    format!("ionova-vote:{}:{}:{}", phase.as_str(), view, block_hash).into_bytes()
+}
+
+/// Bytes a timeout signature covers
+pub fn timeout_payload(view: u64) -> Vec<u8> {
+    format!("ionova-timeout:{}", view).into_bytes()
+}
+
+/// A validator giving up on a view, with the highest prepare QC it holds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeoutVote {
+    pub view: u64,
+    pub validator_id: String,
+    pub high_qc: Option<QuorumCertificate>,
+    pub signature: Vec<u8>,
+}
+
+/// Quorum of timeout votes for a view; lets the next leader propose
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeoutCertificate {
+    pub view: u64,
+    pub signatures: Vec<ValidatorSignature>,
+    pub aggregated_stake: u64,
+    /// Highest prepare QC among the timeout votes
+    pub high_qc: Option<QuorumCertificate>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidatorSignature {
     pub validator_id: String,
@@ -107,7 +445,7 @@ pub struct ValidatorSignature {
     pub stake: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Vote {
     Prepare {
         view: u64,
@@ -129,6 +467,87 @@ pub enum Vote {
     },
 }
 
+impl Vote {
+    pub fn view(&self) -> u64 {
+        match self {
+            Vote::Prepare { view, .. } | Vote::PreCommit { view, .. } | Vote::Commit { view, .. } => *view,
+        }
+    }
+
+    pub fn block_hash(&self) -> &str {
+        match self {
+            Vote::Prepare { block_hash, .. }
+            | Vote::PreCommit { block_hash, .. }
+            | Vote::Commit { block_hash, .. } => block_hash,
+        }
+    }
+
+    pub fn validator_id(&self) -> &str {
+        match self {
+            Vote::Prepare { validator_id, .. }
+            | Vote::PreCommit { validator_id, .. }
+            | Vote::Commit { validator_id, .. } => validator_id,
+        }
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        match self {
+            Vote::Prepare { signature, .. } | Vote::PreCommit { signature, .. } | Vote::Commit { signature, .. } => {
+                signature
+            }
+        }
+    }
+
+    pub fn phase(&self) -> VotePhase {
+        match self {
+            Vote::Prepare { .. } => VotePhase::Prepare,
+            Vote::PreCommit { .. } => VotePhase::PreCommit,
+            Vote::Commit { .. } => VotePhase::Commit,
+        }
+    }
+
+    /// Bytes the voter signs: phase, view and block hash
+    pub fn signing_payload(&self) -> Vec<u8> {
+        vote_payload(self.phase(), self.view(), self.block_hash())
+    }
+}
+
+/// Two votes by one validator for different blocks in the same view and
+/// phase; gossiped, then included in a base block to slash the signer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EquivocationEvidence {
+    pub first: Vote,
+    pub second: Vote,
+}
+
+impl EquivocationEvidence {
+    pub fn offender(&self) -> &str {
+        self.first.validator_id()
+    }
+
+    /// Identifies the offence, so it is punished only once
+    pub fn offence(&self) -> (String, u64, VotePhase) {
+        (self.offender().to_string(), self.first.view(), self.first.phase())
+    }
+
+    /// Commitment to the offence and both block hashes, for base block hashing
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hashes = [self.first.block_hash(), self.second.block_hash()];
+        hashes.sort();
+        keccak256(
+            format!(
+                "ionova-equivocation:{}:{}:{}:{}:{}",
+                self.offender(),
+                self.first.view(),
+                self.first.phase().as_str(),
+                hashes[0],
+                hashes[1]
+            )
+            .as_bytes(),
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VoteCollection {
     pub prepare_votes: HashMap<String, ValidatorSignature>,
@@ -165,116 +584,232 @@ impl FinalityGadget {
             validators: Arc::new(RwLock::new(ValidatorSet::new())),
             proposals: Arc::new(RwLock::new(HashMap::new())),
             votes: Arc::new(RwLock::new(HashMap::new())),
+            cast_votes: Arc::new(RwLock::new(HashMap::new())),
+            timeouts: Arc::new(RwLock::new(HashMap::new())),
+            finalized_qc: Arc::new(RwLock::new(None)),
             config,
         }
     }
 
     /// Initialize validator set
+    ///
+    /// Stored votes and timeouts are re-weighed with the new stakes; those
+    /// of validators no longer active are dropped.
     pub async fn init_validators(&self, validators: ValidatorSet) {
         let mut v = self.validators.write().await;
+
+        for collection in self.votes.write().await.values_mut() {
+            for phase_votes in [
+                &mut collection.prepare_votes,
+                &mut collection.precommit_votes,
+                &mut collection.commit_votes,
+            ] {
+                phase_votes.retain(|id, signature| match validators.active_stake(id) {
+                    Some(stake) => {
+                        signature.stake = stake;
+                        true
+                    }
+                    None => false,
+                });
+            }
+        }
+        self.cast_votes.write().await.retain(|(_, _, id), _| validators.active_stake(id).is_some());
+        for votes in self.timeouts.write().await.values_mut() {
+            votes.retain(|id, _| validators.active_stake(id).is_some());
+        }
+
         *v = validators;
     }
 
-    /// Submit new proposal
-    pub async fn propose_block(&self, proposal: Proposal) -> Result<()> {
+    /// Record the commit QC of the block just finalized
+    pub async fn set_finalized_qc(&self, qc: QuorumCertificate) {
+        *self.finalized_qc.write().await = Some(qc);
+    }
+
+    /// Submit new proposal, signed by the leader of its view
+    pub async fn propose_block(&self, proposal: Proposal, signature: &[u8]) -> Result<()> {
+        let current_view = *self.current_view.read().await;
+        if proposal.view < current_view {
+            bail!("Proposal for past view {} (current view {})", proposal.view, current_view);
+        }
+
+        let finalized = self.finalized_qc.read().await.clone();
+        self.validators.read().await.check_proposal(&proposal, signature, finalized.as_ref())?;
+
         let mut proposals = self.proposals.write().await;
-        proposals.insert(proposal.view, proposal.clone());
-        
-        // Initialize vote collection for this view
-        let mut votes = self.votes.write().await;
-        votes.insert(proposal.view, VoteCollection::new());
-        
+        if let Some(existing) = proposals.get(&proposal.view) {
+            if existing.block_hash != proposal.block_hash {
+                bail!("Conflicting proposal for view {}", proposal.view);
+            }
+            return Ok(());
+        }
+        proposals.insert(proposal.view, proposal);
+
         Ok(())
     }
 
     /// Process vote (3-phase commit)
+    ///
+    /// The vote must be signed by an active validator and match the view's
+    /// proposal once known. A vote conflicting with an earlier vote by the
+    /// same validator in the same phase yields equivocation evidence. Quorum
+    /// counts only votes for one block hash, so no two hashes can reach
+    /// quorum in a view.
     pub async fn process_vote(&self, vote: Vote) -> Result<ConsensusResult> {
+        let view = vote.view();
+        if view < *self.current_view.read().await {
+            return Ok(ConsensusResult::Pending);
+        }
+
         let validators = self.validators.read().await;
+        let validator = validators.verify_signature(vote.validator_id(), &vote.signing_payload(), vote.signature())?;
+
+        if let Some(proposal) = self.proposals.read().await.get(&view) {
+            if proposal.block_hash != vote.block_hash() {
+                bail!("Vote by {} does not match the proposal of view {}", vote.validator_id(), view);
+            }
+        }
+
+        let mut cast_votes = self.cast_votes.write().await;
+        let key = (view, vote.phase(), vote.validator_id().to_string());
+        if let Some(first) = cast_votes.get(&key) {
+            if first.block_hash() != vote.block_hash() {
+                // Not counted: the signer is reported instead
+                return Ok(ConsensusResult::Equivocation(EquivocationEvidence { first: first.clone(), second: vote }));
+            }
+        } else {
+            cast_votes.insert(key, vote.clone());
+        }
+
+        let stake = validator.stake;
         let mut votes = self.votes.write().await;
-        
+        let collection = votes
+            .entry((view, vote.block_hash().to_string()))
+            .or_insert_with(VoteCollection::new);
+
         match vote {
             Vote::Prepare { view, block_hash, validator_id, signature } => {
-                let collection = votes.entry(view).or_insert_with(VoteCollection::new);
-                
-                if let Some(validator) = validators.validators.get(&validator_id) {
-                    collection.prepare_votes.insert(
-                        validator_id.clone(),
-                        ValidatorSignature {
-                            validator_id: validator_id.clone(),
-                            signature,
-                            stake: validator.stake,
-                        },
-                    );
-                    
-                    // Check if we have quorum for PREPARE phase
-                    if validators.has_quorum(collection.total_prepare_stake()) {
-                        return Ok(ConsensusResult::PrepareQuorum { view, block_hash });
-                    }
+                collection.prepare_votes.insert(
+                    validator_id.clone(),
+                    ValidatorSignature { validator_id, signature, stake },
+                );
+
+                // Check if we have quorum for PREPARE phase
+                if validators.has_quorum(collection.total_prepare_stake()) {
+                    let qc = Self::create_qc(view, block_hash.clone(), VotePhase::Prepare, collection);
+                    return Ok(ConsensusResult::PrepareQuorum { view, block_hash, qc });
                 }
             }
             Vote::PreCommit { view, block_hash, validator_id, signature } => {
-                let collection = votes.entry(view).or_insert_with(VoteCollection::new);
-                
-                if let Some(validator) = validators.validators.get(&validator_id) {
-                    collection.precommit_votes.insert(
-                        validator_id.clone(),
-                        ValidatorSignature {
-                            validator_id: validator_id.clone(),
-                            signature,
-                            stake: validator.stake,
-                        },
-                    );
-                    
-                    // Check if we have quorum for PRE-COMMIT phase
-                    if validators.has_quorum(collection.total_precommit_stake()) {
-                        return Ok(ConsensusResult::PreCommitQuorum { view, block_hash });
-                    }
+                collection.precommit_votes.insert(
+                    validator_id.clone(),
+                    ValidatorSignature { validator_id, signature, stake },
+                );
+
+                // Check if we have quorum for PRE-COMMIT phase
+                if validators.has_quorum(collection.total_precommit_stake()) {
+                    return Ok(ConsensusResult::PreCommitQuorum { view, block_hash });
                 }
             }
             Vote::Commit { view, block_hash, validator_id, signature } => {
-                let collection = votes.entry(view).or_insert_with(VoteCollection::new);
-                
-                if let Some(validator) = validators.validators.get(&validator_id) {
-                    collection.commit_votes.insert(
-                        validator_id.clone(),
-                        ValidatorSignature {
-                            validator_id: validator_id.clone(),
-                            signature,
-                            stake: validator.stake,
-                        },
-                    );
-                    
-                    // Check if we have quorum for COMMIT phase
-                    if validators.has_quorum(collection.total_commit_stake()) {
-                        // FINALIZED!
-                        return Ok(ConsensusResult::Finalized {
-                            view,
-                            block_hash: block_hash.clone(),
-                            qc: self.create_qc(view, block_hash, &collection).await,
-                        });
-                    }
+                collection.commit_votes.insert(
+                    validator_id.clone(),
+                    ValidatorSignature { validator_id, signature, stake },
+                );
+
+                // Check if we have quorum for COMMIT phase
+                if validators.has_quorum(collection.total_commit_stake()) {
+                    // FINALIZED!
+                    return Ok(ConsensusResult::Finalized {
+                        view,
+                        block_hash: block_hash.clone(),
+                        qc: Self::create_qc(view, block_hash, VotePhase::Commit, collection),
+                    });
                 }
             }
         }
-        
+
         Ok(ConsensusResult::Pending)
     }
 
-    /// Create Quorum Certificate
-    async fn create_qc(&self, view: u64, block_hash: String, votes: &VoteCollection) -> QuorumCertificate {
+    /// Create Quorum Certificate from the votes of one phase
+    fn create_qc(view: u64, block_hash: String, phase: VotePhase, votes: &VoteCollection) -> QuorumCertificate {
+        let phase_votes = match phase {
+            VotePhase::Prepare => &votes.prepare_votes,
+            VotePhase::PreCommit => &votes.precommit_votes,
+            VotePhase::Commit => &votes.commit_votes,
+        };
         QuorumCertificate {
             view,
             block_hash,
-            signatures: votes.commit_votes.values().cloned().collect(),
-            aggregated_stake: votes.total_commit_stake(),
+            phase,
+            signatures: phase_votes.values().cloned().collect(),
+            aggregated_stake: phase_votes.values().map(|v| v.stake).sum(),
         }
     }
 
-    /// Advance to next view
+    /// Process a timeout vote; returns the view's TC once timeouts reach quorum
+    pub async fn process_timeout(&self, vote: TimeoutVote) -> Result<Option<TimeoutCertificate>> {
+        if vote.view < *self.current_view.read().await {
+            return Ok(None);
+        }
+
+        let validators = self.validators.read().await;
+        validators.verify_signature(&vote.validator_id, &timeout_payload(vote.view), &vote.signature)?;
+        if let Some(qc) = &vote.high_qc {
+            if qc.phase != VotePhase::Prepare || qc.view > vote.view {
+                bail!("Timeout by {} carries an invalid high QC", vote.validator_id);
+            }
+            validators.check_qc(qc)?;
+        }
+
+        let view = vote.view;
+        let mut timeouts = self.timeouts.write().await;
+        let votes = timeouts.entry(view).or_default();
+        votes.insert(vote.validator_id.clone(), vote);
+
+        let signatures: Vec<ValidatorSignature> = votes
+            .values()
+            .filter_map(|vote| {
+                Some(ValidatorSignature {
+                    validator_id: vote.validator_id.clone(),
+                    signature: vote.signature.clone(),
+                    stake: validators.active_stake(&vote.validator_id)?,
+                })
+            })
+            .collect();
+        let aggregated_stake = signatures.iter().map(|s| s.stake).sum();
+        if !validators.has_quorum(aggregated_stake) {
+            return Ok(None);
+        }
+
+        let high_qc = votes
+            .values()
+            .filter_map(|vote| vote.high_qc.as_ref())
+            .max_by_key(|qc| qc.view)
+            .cloned();
+        Ok(Some(TimeoutCertificate { view, signatures, aggregated_stake, high_qc }))
+    }
+
+    /// Advance to next view, dropping proposals and votes of past views
     pub async fn advance_view(&self) -> u64 {
+        let view = *self.current_view.read().await + 1;
+        self.advance_to(view).await;
+        view
+    }
+
+    /// Move to `view` if it is ahead of the current one
+    pub async fn advance_to(&self, view: u64) {
         let mut current_view = self.current_view.write().await;
-        *current_view += 1;
-        *current_view
+        if view <= *current_view {
+            return;
+        }
+        *current_view = view;
+
+        self.proposals.write().await.retain(|v, _| *v >= view);
+        self.votes.write().await.retain(|(v, _), _| *v >= view);
+        self.cast_votes.write().await.retain(|(v, _, _), _| *v >= view);
+        self.timeouts.write().await.retain(|v, _| *v >= view);
     }
 
     /// Get current view
@@ -283,10 +818,309 @@ impl FinalityGadget {
     }
 }
 
+/// Per-view timeouts, doubled for each consecutive view that fails
+#[derive(Debug, Clone)]
+pub struct Pacemaker {
+    base_timeout_ms: u64,
+    max_timeout_ms: u64,
+    consecutive_timeouts: u32,
+}
+
+impl Pacemaker {
+    pub fn new(config: &FinalityConfig) -> Self {
+        Self {
+            base_timeout_ms: config.view_timeout_ms,
+            max_timeout_ms: config.max_view_timeout_ms.max(config.view_timeout_ms),
+            consecutive_timeouts: 0,
+        }
+    }
+
+    /// How long the current view may take
+    pub fn view_timeout(&self) -> Duration {
+        let backoff = 1u64 << self.consecutive_timeouts.min(20);
+        Duration::from_millis(self.base_timeout_ms.saturating_mul(backoff).min(self.max_timeout_ms))
+    }
+
+    /// A view ended without finalizing
+    pub fn on_timeout(&mut self) {
+        self.consecutive_timeouts = self.consecutive_timeouts.saturating_add(1);
+    }
+
+    /// A block was finalized
+    pub fn on_progress(&mut self) {
+        self.consecutive_timeouts = 0;
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ConsensusResult {
     Pending,
-    PrepareQuorum { view: u64, block_hash: String },
+    PrepareQuorum { view: u64, block_hash: String, qc: QuorumCertificate },
     PreCommitQuorum { view: u64, block_hash: String },
     Finalized { view: u64, block_hash: String, qc: QuorumCertificate },
+    Equivocation(EquivocationEvidence),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{KeyPair, SignatureAlgorithm};
+
+    fn raw_signature(key: &KeyPair, payload: &[u8]) -> Vec<u8> {
+        match key.sign(payload).unwrap() {
+            Signature::Dilithium { data } => data,
+            other => panic!("unexpected signature {:?}", other.algorithm()),
+        }
+    }
+
+    /// Gadget with four Dilithium validators of equal stake
+    async fn setup() -> (FinalityGadget, Vec<KeyPair>) {
+        let keys: Vec<KeyPair> = (0..4).map(|_| KeyPair::generate(SignatureAlgorithm::Dilithium)).collect();
+        let mut set = ValidatorSet::new();
+        for key in &keys {
+            set.add_validator(
+                key.address().to_string(),
+                ValidatorInfo { public_key: key.public_key.clone(), stake: 100, is_active: true },
+            );
+        }
+        let gadget = FinalityGadget::new(FinalityConfig::default());
+        gadget.init_validators(set).await;
+        (gadget, keys)
+    }
+
+    fn prepare(key: &KeyPair, view: u64, block_hash: &str) -> Vote {
+        let mut vote = Vote::Prepare {
+            view,
+            block_hash: block_hash.to_string(),
+            validator_id: key.address().to_string(),
+            signature: vec![],
+        };
+        let payload = vote.signing_payload();
+        if let Vote::Prepare { signature, .. } = &mut vote {
+            *signature = raw_signature(key, &payload);
+        }
+        vote
+    }
+
+    fn proposal(key: &KeyPair, view: u64, block_hash: &str) -> (Proposal, Vec<u8>) {
+        let proposal = Proposal {
+            view,
+            block_hash: block_hash.to_string(),
+            parent_hash: "00".repeat(32),
+            proposer: key.address().to_string(),
+            timestamp: 1_700_000_000,
+            qc: None,
+            tc: None,
+        };
+        let signature = raw_signature(key, &proposal.signing_payload());
+        (proposal, signature)
+    }
+
+    #[tokio::test]
+    async fn test_vote_signatures_verified() {
+        let (gadget, keys) = setup().await;
+
+        let mut forged = prepare(&keys[0], 0, "aa");
+        if let Vote::Prepare { validator_id, .. } = &mut forged {
+            *validator_id = keys[1].address().to_string();
+        }
+        assert!(gadget.process_vote(forged).await.is_err());
+
+        let outsider = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        assert!(gadget.process_vote(prepare(&outsider, 0, "aa")).await.is_err());
+
+        for key in &keys[..2] {
+            assert!(matches!(gadget.process_vote(prepare(key, 0, "aa")).await.unwrap(), ConsensusResult::Pending));
+        }
+        let result = gadget.process_vote(prepare(&keys[2], 0, "aa")).await.unwrap();
+        assert!(matches!(result, ConsensusResult::PrepareQuorum { view: 0, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_proposals_only_from_leader() {
+        let (gadget, keys) = setup().await;
+        let leader_id = gadget.validators.read().await.leader(0).unwrap().clone();
+        let leader = keys.iter().find(|key| key.address().to_string() == leader_id).unwrap();
+        let other = keys.iter().find(|key| key.address().to_string() != leader_id).unwrap();
+
+        let (wrong, signature) = proposal(other, 0, "aa");
+        assert!(gadget.propose_block(wrong, &signature).await.is_err());
+
+        let (right, _) = proposal(leader, 0, "aa");
+        let (_, bad_signature) = proposal(leader, 0, "bb");
+        assert!(gadget.propose_block(right.clone(), &bad_signature).await.is_err());
+
+        let signature = raw_signature(leader, &right.signing_payload());
+        gadget.propose_block(right, &signature).await.unwrap();
+
+        let (conflicting, signature) = proposal(leader, 0, "bb");
+        assert!(gadget.propose_block(conflicting, &signature).await.is_err());
+
+        // Votes must follow the accepted proposal
+        assert!(gadget.process_vote(prepare(&keys[0], 0, "bb")).await.is_err());
+        gadget.process_vote(prepare(&keys[0], 0, "aa")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_votes_cannot_form_two_quorums() {
+        let (gadget, keys) = setup().await;
+
+        // Before any proposal arrives, each validator may back one hash only
+        gadget.process_vote(prepare(&keys[0], 0, "aa")).await.unwrap();
+        let ConsensusResult::Equivocation(evidence) = gadget.process_vote(prepare(&keys[0], 0, "bb")).await.unwrap()
+        else {
+            panic!("conflicting vote not reported");
+        };
+        assert_eq!(evidence.offender(), keys[0].address().to_string());
+        gadget.validators.read().await.check_evidence(&evidence).unwrap();
+
+        gadget.process_vote(prepare(&keys[1], 0, "aa")).await.unwrap();
+        gadget.process_vote(prepare(&keys[2], 0, "bb")).await.unwrap();
+        let result = gadget.process_vote(prepare(&keys[3], 0, "bb")).await.unwrap();
+        assert!(matches!(result, ConsensusResult::Pending));
+
+        // A repeated vote is not counted twice
+        let result = gadget.process_vote(prepare(&keys[3], 0, "bb")).await.unwrap();
+        assert!(matches!(result, ConsensusResult::Pending));
+    }
+
+    fn timeout(key: &KeyPair, view: u64, high_qc: Option<QuorumCertificate>) -> TimeoutVote {
+        TimeoutVote {
+            view,
+            validator_id: key.address().to_string(),
+            high_qc,
+            signature: raw_signature(key, &timeout_payload(view)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout_certificate() {
+        let (gadget, keys) = setup().await;
+
+        let mut qc = None;
+        for key in &keys[..3] {
+            if let ConsensusResult::PrepareQuorum { qc: prepare_qc, .. } =
+                gadget.process_vote(prepare(key, 0, "aa")).await.unwrap()
+            {
+                qc = Some(prepare_qc);
+            }
+        }
+        let qc = qc.expect("no prepare quorum");
+        assert_eq!(qc.phase, VotePhase::Prepare);
+
+        assert!(gadget.process_timeout(timeout(&keys[0], 0, None)).await.unwrap().is_none());
+        assert!(gadget.process_timeout(timeout(&keys[1], 0, Some(qc.clone()))).await.unwrap().is_none());
+        let tc = gadget.process_timeout(timeout(&keys[2], 0, None)).await.unwrap().expect("no TC");
+        assert_eq!(tc.aggregated_stake, 300);
+        assert_eq!(tc.high_qc.as_ref().unwrap().block_hash, "aa");
+
+        let validators = gadget.validators.read().await;
+        validators.check_timeout_certificate(&tc).unwrap();
+
+        let mut short = tc.clone();
+        short.signatures.pop();
+        assert!(validators.check_timeout_certificate(&short).is_err());
+
+        let mut duplicated = tc.clone();
+        duplicated.signatures[1] = duplicated.signatures[0].clone();
+        assert!(validators.check_timeout_certificate(&duplicated).is_err());
+
+        // A prepare QC cannot pass as a commit QC
+        let mut relabeled = qc;
+        relabeled.phase = VotePhase::Commit;
+        assert!(validators.check_qc(&relabeled).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validator_set_change_reweighs_stored_votes() {
+        let (gadget, keys) = setup().await;
+
+        // Votes and a future-view timeout stored before the set changes
+        gadget.process_vote(prepare(&keys[1], 0, "aa")).await.unwrap();
+        gadget.process_vote(prepare(&keys[3], 0, "aa")).await.unwrap();
+        gadget.process_timeout(timeout(&keys[3], 5, None)).await.unwrap();
+
+        let mut set = ValidatorSet::new();
+        for (i, key) in keys[..3].iter().enumerate() {
+            set.add_validator(
+                key.address().to_string(),
+                ValidatorInfo { public_key: key.public_key.clone(), stake: 100 * (i as u64 + 1), is_active: true },
+            );
+        }
+        set.add_validator(
+            "standby".to_string(),
+            ValidatorInfo { public_key: keys[3].public_key.clone(), stake: 1_000, is_active: false },
+        );
+        assert_eq!(set.total_stake, 600);
+        gadget.init_validators(set).await;
+
+        // keys[3] left the set: its timeout no longer counts and nothing panics
+        assert!(gadget.process_timeout(timeout(&keys[1], 5, None)).await.unwrap().is_none());
+        let tc = gadget.process_timeout(timeout(&keys[2], 5, None)).await.unwrap().expect("no TC");
+        assert_eq!(tc.aggregated_stake, 500);
+        assert_eq!(tc.signatures.len(), 2);
+
+        // keys[1]'s prepare vote now weighs 200 of the 600 total; keys[2] adds 300
+        let result = gadget.process_vote(prepare(&keys[2], 0, "aa")).await.unwrap();
+        let ConsensusResult::PrepareQuorum { qc, .. } = result else {
+            panic!("no prepare quorum");
+        };
+        assert_eq!(qc.aggregated_stake, 500);
+        assert_eq!(qc.signatures.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_forged_evidence_rejected() {
+        let (gadget, keys) = setup().await;
+        let validators = gadget.validators.read().await;
+
+        let same_hash = EquivocationEvidence { first: prepare(&keys[0], 0, "aa"), second: prepare(&keys[0], 0, "aa") };
+        assert!(validators.check_evidence(&same_hash).is_err());
+
+        let two_signers = EquivocationEvidence { first: prepare(&keys[0], 0, "aa"), second: prepare(&keys[1], 0, "bb") };
+        assert!(validators.check_evidence(&two_signers).is_err());
+
+        // Another validator cannot frame keys[0]
+        let mut framed = prepare(&keys[1], 0, "bb");
+        if let Vote::Prepare { validator_id, .. } = &mut framed {
+            *validator_id = keys[0].address().to_string();
+        }
+        let forged = EquivocationEvidence { first: prepare(&keys[0], 0, "aa"), second: framed };
+        assert!(validators.check_evidence(&forged).is_err());
+    }
+
+    #[test]
+    fn test_leader_weighted_by_stake() {
+        let mut set = ValidatorSet::new();
+        for (i, stake) in [100u64, 200, 300, 400].into_iter().enumerate() {
+            let key = KeyPair::generate(SignatureAlgorithm::Dilithium);
+            set.add_validator(format!("v{}", i), ValidatorInfo { public_key: key.public_key, stake, is_active: true });
+        }
+        set.validators.get_mut("v0").unwrap().is_active = false;
+
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for view in 0..9_000 {
+            *counts.entry(set.leader(view).unwrap().clone()).or_default() += 1;
+        }
+        assert_eq!(set.leader(42), set.leader(42));
+        assert!(!counts.contains_key("v0"));
+        // Expected 2000, 3000 and 4000 of 9000 views
+        assert!((1700..2300).contains(&counts["v1"]));
+        assert!((2700..3300).contains(&counts["v2"]));
+        assert!((3700..4300).contains(&counts["v3"]));
+    }
+
+    #[test]
+    fn test_pacemaker_backoff() {
+        let config = FinalityConfig { view_timeout_ms: 1000, max_view_timeout_ms: 5000, ..FinalityConfig::default() };
+        let mut pacemaker = Pacemaker::new(&config);
+        assert_eq!(pacemaker.view_timeout(), Duration::from_millis(1000));
+        pacemaker.on_timeout();
+        assert_eq!(pacemaker.view_timeout(), Duration::from_millis(2000));
+        pacemaker.on_timeout();
+        pacemaker.on_timeout();
+        assert_eq!(pacemaker.view_timeout(), Duration::from_millis(5000));
+        pacemaker.on_progress();
+        assert_eq!(pacemaker.view_timeout(), Duration::from_millis(1000));
+    }
 }
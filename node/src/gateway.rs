@@ -0,0 +1,706 @@
+// Shard-aware JSON-RPC gateway: one endpoint in front of every shard's sequencer
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use warp::Filter;
+
+use crate::config::RateLimitConfig;
+use crate::crypto::Address;
+use crate::eth_tx;
+use crate::network_security;
+use crate::rate_limit::RpcRateLimiter;
+use crate::rpc::MAX_BATCH_SIZE;
+use crate::transaction::{self, Transaction, TX_ENCODING_VERSION};
+
+/// Gateway settings
+#[derive(Debug, Clone)]
+pub struct GatewayConfig {
+    /// Address to listen on
+    pub bind: SocketAddr,
+    /// JSON-RPC URL of each shard's sequencer, indexed by shard
+    pub endpoints: Vec<String>,
+    /// Time a sequencer has to answer a forwarded call
+    pub request_timeout: Duration,
+    /// Largest accepted HTTP body
+    pub max_request_bytes: u64,
+    /// Quotas charged per shard call, by client IP
+    pub rate_limit: RateLimitConfig,
+    /// Chain ID of shard 0; shard N's is this + N
+    pub base_chain_id: u64,
+}
+
+/// Most shard calls one HTTP body may cause, counting each shard asked by a
+/// lookup by hash
+const MAX_FAN_OUT: usize = MAX_BATCH_SIZE;
+
+const LIMIT_EXCEEDED: i32 = -32005;
+
+/// How often idle per-IP limiters are dropped
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long an address no shard had code for is taken for a plain account
+/// before the shards are asked again
+const NOT_A_CONTRACT_TTL: Duration = Duration::from_secs(2);
+
+/// Addresses whose contract shard is remembered
+const MAX_KNOWN_ADDRESSES: usize = 100_000;
+
+/// Where a call is answered
+#[derive(Debug, PartialEq)]
+enum Route {
+    /// By the gateway itself
+    Local(Value),
+    /// By the sequencer of one shard
+    Shard(u8),
+    /// By the shard holding the address's contract code, or the given
+    /// shard if no shard does
+    Contract(Address, u8),
+    /// By every shard; the first non-null result wins
+    AllShards,
+}
+
+/// Shard each contract lives on. Contracts stay on the shard that deployed
+/// them, which their address says nothing about, so the shards are asked.
+#[derive(Default)]
+struct ContractShards {
+    known: Mutex<HashMap<Address, (Option<u8>, Instant)>>,
+}
+
+impl ContractShards {
+    /// `Some(shard)` for a known contract, `Some(None)` for an address
+    /// recently found to hold no code, `None` if the shards must be asked
+    fn get(&self, address: &Address) -> Option<Option<u8>> {
+        match self.known.lock().get(address) {
+            Some((Some(shard), _)) => Some(Some(*shard)),
+            Some((None, checked)) if checked.elapsed() < NOT_A_CONTRACT_TTL => Some(None),
+            _ => None,
+        }
+    }
+
+    fn record(&self, address: Address, shard: Option<u8>) {
+        let mut known = self.known.lock();
+        if known.len() >= MAX_KNOWN_ADDRESSES {
+            known.retain(|_, (shard, _)| shard.is_some());
+            if known.len() >= MAX_KNOWN_ADDRESSES {
+                known.clear();
+            }
+        }
+        known.insert(address, (shard, Instant::now()));
+    }
+}
+
+/// Forwards JSON-RPC calls to the shard they concern
+struct Gateway {
+    endpoints: Vec<String>,
+    base_chain_id: u64,
+    client: reqwest::Client,
+    limiter: RpcRateLimiter,
+    /// Proxies in front of the gateway whose X-Forwarded-For names the client
+    trusted_proxies: Vec<IpAddr>,
+    contracts: ContractShards,
+}
+
+pub async fn start_gateway(config: GatewayConfig) -> Result<()> {
+    let gateway = Arc::new(Gateway::new(&config)?);
+    tracing::info!(
+        "RPC gateway starting on {} for {} shards",
+        config.bind,
+        gateway.endpoints.len()
+    );
+    let cleanup = gateway.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            cleanup.limiter.cleanup_old_limiters();
+        }
+    });
+    warp::serve(routes(gateway, config.max_request_bytes)).run(config.bind).await;
+    Ok(())
+}
+
+/// POST `/` routes each call by the account or transaction it concerns;
+/// POST `/shard/<id>` passes the body to one shard unchanged
+fn routes(
+    gateway: Arc<Gateway>,
+    max_request_bytes: u64,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let with_gateway = warp::any().map(move || gateway.clone());
+    let body = warp::body::content_length_limit(max_request_bytes).and(warp::body::json());
+
+    // Behind a trusted proxy, the client it names
+    let client = warp::addr::remote()
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .and(with_gateway.clone())
+        .map(|remote: Option<SocketAddr>, forwarded_for: Option<String>, gateway: Arc<Gateway>| {
+            let peer = remote.map(|addr| addr.ip());
+            network_security::client_ip(peer, forwarded_for.as_deref(), &gateway.trusted_proxies)
+        });
+
+    let routed = warp::post()
+        .and(warp::path::end())
+        .and(body)
+        .and(client.clone())
+        .and(with_gateway.clone())
+        .then(|body: Value, client: Option<IpAddr>, gateway: Arc<Gateway>| async move {
+            warp::reply::json(&gateway.handle_body(body, client).await)
+        });
+
+    let pinned = warp::post()
+        .and(warp::path!("shard" / u8))
+        .and(body)
+        .and(client)
+        .and(with_gateway)
+        .then(|shard: u8, body: Value, client: Option<IpAddr>, gateway: Arc<Gateway>| async move {
+            warp::reply::json(&gateway.handle_pinned(shard, body, client).await)
+        });
+
+    routed.or(pinned)
+}
+
+impl Gateway {
+    fn new(config: &GatewayConfig) -> Result<Self> {
+        if config.endpoints.is_empty() || config.endpoints.len() > u8::MAX as usize {
+            anyhow::bail!("Gateway needs between 1 and {} shard endpoints", u8::MAX);
+        }
+        let client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()
+            .context("Failed to create HTTP client")?;
+        Ok(Self {
+            endpoints: config.endpoints.clone(),
+            base_chain_id: config.base_chain_id,
+            client,
+            limiter: RpcRateLimiter::from_config(&config.rate_limit),
+            trusted_proxies: config.rate_limit.trusted_proxies.clone(),
+            contracts: ContractShards::default(),
+        })
+    }
+
+    fn shard_count(&self) -> u8 {
+        self.endpoints.len() as u8
+    }
+
+    fn endpoint(&self, shard: u8) -> Result<&str, String> {
+        self.endpoints
+            .get(shard as usize)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Unknown shard {}", shard))
+    }
+
+    /// Handle a single request object or a batch; batched calls may go to
+    /// different shards
+    async fn handle_body(&self, body: Value, client: Option<IpAddr>) -> Value {
+        match body {
+            Value::Array(requests) => {
+                if let Err(e) = check_batch_size(&requests) {
+                    return error_response(Value::Null, -32600, &e);
+                }
+                let routes: Vec<_> = requests.iter().map(|request| self.route_request(request)).collect();
+                let fan_out: usize = routes.iter().map(|route| self.shard_calls(route)).sum();
+                if fan_out > MAX_FAN_OUT {
+                    return error_response(
+                        Value::Null,
+                        -32600,
+                        &format!("Invalid request: batch would make {} shard calls, at most {}", fan_out, MAX_FAN_OUT),
+                    );
+                }
+                let responses = futures::future::join_all(
+                    requests.iter().zip(routes).map(|(request, route)| self.handle_request(request, route, client)),
+                )
+                .await;
+                Value::Array(responses)
+            }
+            request => {
+                let route = self.route_request(&request);
+                self.handle_request(&request, route, client).await
+            }
+        }
+    }
+
+    /// Pass a request (or batch) to one shard unchanged, refusing admin calls
+    async fn handle_pinned(&self, shard: u8, body: Value, client: Option<IpAddr>) -> Value {
+        let id = body.get("id").cloned().unwrap_or(Value::Null);
+        if let Err(e) = self.endpoint(shard) {
+            return error_response(id, -32602, &e);
+        }
+        let requests = match &body {
+            Value::Array(requests) => match check_batch_size(requests) {
+                Ok(()) => requests.as_slice(),
+                Err(e) => return error_response(Value::Null, -32600, &e),
+            },
+            request => std::slice::from_ref(request),
+        };
+        if requests.iter().any(|request| request.get("method").and_then(Value::as_str).is_some_and(is_admin)) {
+            return error_response(id, -32602, ADMIN_REFUSED);
+        }
+        if let Err(e) = self.charge(client, requests.len()) {
+            return error_response(id, LIMIT_EXCEEDED, &e);
+        }
+        self.forward(shard, &body, client).await
+    }
+
+    /// ID and route of one call
+    fn route_request(&self, request: &Value) -> (Value, Result<Route, String>) {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return (id, Err("Invalid request: missing method".to_string()));
+        };
+        let params = match request.get("params") {
+            Some(Value::Array(params)) => params.as_slice(),
+            _ => &[],
+        };
+        (id, route(method, params, self.shard_count(), self.base_chain_id))
+    }
+
+    /// Sequencer calls a routed request makes
+    fn shard_calls(&self, (_, route): &(Value, Result<Route, String>)) -> usize {
+        match route {
+            Ok(Route::Shard(_)) => 1,
+            Ok(Route::Contract(address, _)) => match self.contracts.get(address) {
+                Some(_) => 1,
+                None => 1 + self.shard_count() as usize,
+            },
+            Ok(Route::AllShards) => self.shard_count() as usize,
+            Ok(Route::Local(_)) | Err(_) => 0,
+        }
+    }
+
+    /// Charge `calls` requests (at least one) to the client's quota
+    fn charge(&self, client: Option<IpAddr>, calls: usize) -> Result<(), String> {
+        let ip = client.map(|ip| ip.to_string());
+        for _ in 0..calls.max(1) {
+            self.limiter.check_rate_limit(ip.as_deref())?;
+        }
+        Ok(())
+    }
+
+    async fn handle_request(
+        &self,
+        request: &Value,
+        routed: (Value, Result<Route, String>),
+        client: Option<IpAddr>,
+    ) -> Value {
+        if let Err(e) = self.charge(client, self.shard_calls(&routed)) {
+            return error_response(routed.0, LIMIT_EXCEEDED, &e);
+        }
+        let (id, route) = routed;
+        match route {
+            Ok(Route::Local(result)) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+            Ok(Route::Shard(shard)) => self.forward(shard, request, client).await,
+            Ok(Route::Contract(address, otherwise)) => {
+                let shard = self.contract_shard(address, client).await.unwrap_or(otherwise);
+                self.forward(shard, request, client).await
+            }
+            Ok(Route::AllShards) => {
+                let shards = 0..self.shard_count();
+                let responses =
+                    futures::future::join_all(shards.map(|shard| self.forward(shard, request, client))).await;
+                merge_responses(id, responses)
+            }
+            Err(e) => error_response(id, -32602, &e),
+        }
+    }
+
+    /// Shard holding code at `address`, if any. Asks every shard unless
+    /// the answer is known; an address no shard had code for is only
+    /// remembered briefly, as it may yet be deployed to.
+    async fn contract_shard(&self, address: Address, client: Option<IpAddr>) -> Option<u8> {
+        if let Some(known) = self.contracts.get(&address) {
+            return known;
+        }
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "eth_getCode",
+            "params": [address.to_string(), "latest"],
+            "id": 0,
+        });
+        let shards = 0..self.shard_count();
+        let responses = futures::future::join_all(shards.map(|shard| self.forward(shard, &request, client))).await;
+        let code = |response: &Value| response.get("result").and_then(Value::as_str).map(|code| code != "0x");
+        let found = responses.iter().position(|response| code(response) == Some(true)).map(|shard| shard as u8);
+        // A shard that failed to answer may hold the contract
+        if found.is_some() || responses.iter().all(|response| code(response).is_some()) {
+            self.contracts.record(address, found);
+        }
+        found
+    }
+
+    /// Send a request (or batch) to one shard and return its response. The
+    /// client is named in X-Forwarded-For, so sequencers that list the
+    /// gateway under `trusted_proxies` limit and ban each client rather than
+    /// the gateway.
+    async fn forward(&self, shard: u8, request: &Value, client: Option<IpAddr>) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let url = match self.endpoint(shard) {
+            Ok(url) => url,
+            Err(e) => return error_response(id, -32602, &e),
+        };
+        let mut post = self.client.post(url).json(request);
+        if let Some(ip) = client {
+            post = post.header("x-forwarded-for", ip.to_string());
+        }
+        let response = match post.send().await {
+            Ok(response) => response.json::<Value>().await,
+            Err(e) => Err(e),
+        };
+        response.unwrap_or_else(|e| {
+            tracing::warn!("Shard {} at {} failed: {}", shard, url, e);
+            error_response(id, -32603, &format!("Shard {} unavailable", shard))
+        })
+    }
+}
+
+/// Decide which shard answers a call. Account state lives on the account's
+/// home shard, contracts on the shard that deployed them, and transactions on
+/// the shard they were signed for; lookups by hash ask every shard; admin
+/// methods are refused, as they manage one sequencer and are for its
+/// operators only; anything else goes to shard 0.
+fn route(method: &str, params: &[Value], shard_count: u8, base_chain_id: u64) -> Result<Route, String> {
+    match method {
+        method if is_admin(method) => Err(ADMIN_REFUSED.to_string()),
+        "ionova_shardFor" => {
+            let shard = transaction::shard_for(&address_param(params.first())?, shard_count);
+            Ok(Route::Local(json!({
+                "shard": shard,
+                "chainId": format!("0x{:x}", base_chain_id + shard as u64),
+            })))
+        }
+        "eth_getBalance" | "eth_getTransactionCount" | "eth_getCode" | "eth_getStorageAt" | "eth_getProof" => {
+            let address = address_param(params.first())?;
+            Ok(Route::Contract(address, transaction::shard_for(&address, shard_count)))
+        }
+        "eth_call" | "eth_estimateGas" | "debug_traceCall" => {
+            // Calls run where the contract called is, else at the caller's
+            let call = params.first().ok_or("Missing call object")?;
+            let from = call.get("from").map(|from| address_param(Some(from))).transpose()?;
+            let to = call.get("to").filter(|to| !to.is_null()).map(|to| address_param(Some(to))).transpose()?;
+            let home = |address: &Address| transaction::shard_for(address, shard_count);
+            match (to, from.or(to)) {
+                (Some(to), Some(caller)) => Ok(Route::Contract(to, home(&caller))),
+                (None, Some(caller)) => Ok(Route::Shard(home(&caller))),
+                _ => Ok(Route::Shard(0)),
+            }
+        }
+        "eth_sendRawTransaction" => raw_transaction_shard(params.first(), shard_count, base_chain_id).map(Route::Shard),
+        "eth_getTransactionByHash" | "eth_getTransactionReceipt" | "eth_getBlockByHash" | "debug_traceTransaction" => {
+            Ok(Route::AllShards)
+        }
+        "eth_subscribe" | "eth_unsubscribe" => {
+            Err("Subscriptions are served by each shard's sequencer, not the gateway".to_string())
+        }
+        "eth_getLogs" | "eth_newFilter" | "eth_newBlockFilter" | "eth_getFilterChanges" | "eth_getFilterLogs"
+        | "eth_uninstallFilter" => {
+            // Block numbers and filter IDs are per shard
+            Err("Logs and filters are served per shard: use /shard/<id>".to_string())
+        }
+        _ => Ok(Route::Shard(0)),
+    }
+}
+
+const ADMIN_REFUSED: &str = "Admin methods are not served by the gateway";
+
+fn is_admin(method: &str) -> bool {
+    method.starts_with("admin_")
+}
+
+fn check_batch_size(requests: &[Value]) -> Result<(), String> {
+    if requests.is_empty() || requests.len() > MAX_BATCH_SIZE {
+        return Err(format!("Invalid request: batch must hold 1 to {} requests", MAX_BATCH_SIZE));
+    }
+    Ok(())
+}
+
+fn address_param(param: Option<&Value>) -> Result<Address, String> {
+    let address = param.and_then(Value::as_str).ok_or("Missing address")?;
+    address.parse().map_err(|e| format!("Invalid address: {}", e))
+}
+
+/// Shard a raw transaction was signed for, which must be its sender's home
+/// shard
+fn raw_transaction_shard(param: Option<&Value>, shard_count: u8, base_chain_id: u64) -> Result<u8, String> {
+    let (shard, sender) = match param {
+        Some(Value::String(raw)) => {
+            let bytes = hex::decode(raw.strip_prefix("0x").unwrap_or(raw))
+                .map_err(|e| format!("Invalid hex: {}", e))?;
+            if bytes.first() == Some(&TX_ENCODING_VERSION) {
+                let tx = Transaction::decode(&bytes).map_err(|e| format!("Failed to decode transaction: {}", e))?;
+                (tx.shard_id, tx.from)
+            } else {
+                let tx = eth_tx::decode(&bytes).map_err(|e| format!("Failed to decode Ethereum transaction: {}", e))?;
+                let shard = transaction::shard_for_chain_id(base_chain_id, tx.chain_id).map_err(|e| e.to_string())?;
+                (shard, tx.from)
+            }
+        }
+        Some(obj @ Value::Object(_)) => {
+            let tx = serde_json::from_value::<Transaction>(obj.clone())
+                .map_err(|e| format!("Failed to parse transaction: {}", e))?;
+            (tx.shard_id, tx.from)
+        }
+        _ => return Err("Invalid transaction format".to_string()),
+    };
+
+    let home = transaction::shard_for(&sender, shard_count);
+    if shard != home {
+        return Err(format!(
+            "Sender {} belongs to shard {}: sign for chain ID {}",
+            sender,
+            home,
+            base_chain_id + home as u64
+        ));
+    }
+    Ok(shard)
+}
+
+/// Answer of a call asked of every shard: the first non-null result, else a
+/// null result if any shard had one, else the first error
+fn merge_responses(id: Value, responses: Vec<Value>) -> Value {
+    let result = |response: &Value| response.get("error").is_none().then(|| response.get("result").cloned());
+    if let Some(found) = responses.iter().find(|r| matches!(result(r), Some(Some(ref v)) if !v.is_null())) {
+        return json!({ "jsonrpc": "2.0", "result": found["result"], "id": id });
+    }
+    if responses.iter().any(|r| result(r).is_some()) {
+        return json!({ "jsonrpc": "2.0", "result": Value::Null, "id": id });
+    }
+    responses
+        .into_iter()
+        .next()
+        .map(|mut error| {
+            error["id"] = id.clone();
+            error
+        })
+        .unwrap_or_else(|| error_response(id, -32603, "No shards configured"))
+}
+
+fn error_response(id: Value, code: i32, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": { "code": code, "message": message },
+        "id": id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{KeyPair, SignatureAlgorithm};
+    use crate::transaction::{TransactionBuilder, BASE_CHAIN_ID};
+
+    fn signed_for(keypair: &KeyPair, shard: u8) -> Value {
+        let tx = TransactionBuilder::new()
+            .shard(shard)
+            .chain_id(BASE_CHAIN_ID + shard as u64)
+            .to(Address::EVM([1; 20]))
+            .sign(keypair)
+            .unwrap();
+        json!(format!("0x{}", hex::encode(tx.encode())))
+    }
+
+    #[test]
+    fn test_routes_by_account_and_transaction() {
+        let address = Address::EVM([0x42; 20]);
+        let home = transaction::shard_for(&address, 8);
+        let param = json!(address.to_string());
+
+        assert_eq!(
+            route("eth_getBalance", &[param.clone(), json!("latest")], 8, BASE_CHAIN_ID),
+            Ok(Route::Contract(address, home))
+        );
+        assert_eq!(route("eth_call", &[json!({ "from": param, "data": "0x" })], 8, BASE_CHAIN_ID), Ok(Route::Shard(home)));
+        let contract = Address::EVM([0x43; 20]);
+        assert_eq!(
+            route("eth_call", &[json!({ "from": param, "to": contract.to_string() })], 8, BASE_CHAIN_ID),
+            Ok(Route::Contract(contract, home))
+        );
+        assert_eq!(route("eth_getTransactionReceipt", &[json!("0x00")], 8, BASE_CHAIN_ID), Ok(Route::AllShards));
+        assert_eq!(route("debug_traceTransaction", &[json!("0x00")], 8, BASE_CHAIN_ID), Ok(Route::AllShards));
+        assert_eq!(route("eth_blockNumber", &[], 8, BASE_CHAIN_ID), Ok(Route::Shard(0)));
+        assert!(route("eth_getBalance", &[json!("nonsense")], 8, BASE_CHAIN_ID).is_err());
+
+        let Ok(Route::Local(answer)) = route("ionova_shardFor", &[param], 8, BASE_CHAIN_ID) else {
+            panic!("ionova_shardFor is answered locally");
+        };
+        assert_eq!(answer["shard"], json!(home));
+
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let home = transaction::shard_for(&keypair.address(), 8);
+        assert_eq!(
+            route("eth_sendRawTransaction", &[signed_for(&keypair, home)], 8, BASE_CHAIN_ID),
+            Ok(Route::Shard(home))
+        );
+        let error =
+            route("eth_sendRawTransaction", &[signed_for(&keypair, (home + 1) % 8)], 8, BASE_CHAIN_ID).unwrap_err();
+        assert!(error.contains(&format!("belongs to shard {}", home)));
+    }
+
+    #[test]
+    fn test_merge_prefers_found_results() {
+        let found = json!({ "jsonrpc": "2.0", "result": { "status": "0x1" }, "id": 1 });
+        let missing = json!({ "jsonrpc": "2.0", "result": null, "id": 1 });
+        let failed = error_response(json!(1), -32603, "Shard 2 unavailable");
+
+        let merged = merge_responses(json!(7), vec![missing.clone(), failed.clone(), found]);
+        assert_eq!(merged["result"]["status"], "0x1");
+        assert_eq!(merged["id"], 7);
+        assert!(merge_responses(json!(7), vec![failed.clone(), missing])["result"].is_null());
+        assert_eq!(merge_responses(json!(7), vec![failed])["error"]["code"], -32603);
+    }
+
+    #[tokio::test]
+    async fn test_forwards_to_shard_sequencers() {
+        let mut on_shard_1 = (0..=255u8)
+            .map(|i| Address::EVM([i; 20]))
+            .filter(|address| transaction::shard_for(address, 2) == 1);
+        let (address, contract) = (on_shard_1.next().unwrap(), on_shard_1.next().unwrap());
+
+        // Stand-in sequencers answering with their shard number; shard 0
+        // deployed `contract`
+        let mut endpoints = Vec::new();
+        for shard in 0..2u8 {
+            let filter = warp::post()
+                .and(warp::body::json())
+                .and(warp::header::optional::<String>("x-forwarded-for"))
+                .map(move |request: Value, forwarded_for: Option<String>| {
+                    let result = match request["method"].as_str() {
+                        Some("web3_clientVersion") => json!(forwarded_for),
+                        Some("eth_getTransactionReceipt") if shard == 1 => json!({ "shard": shard }),
+                        Some("eth_getTransactionReceipt") => Value::Null,
+                        Some("eth_getCode") if shard == 0 && request["params"][0] == json!(contract.to_string()) => {
+                            json!("0x00")
+                        }
+                        Some("eth_getCode") => json!("0x"),
+                        _ => json!(shard),
+                    };
+                    warp::reply::json(&json!({ "jsonrpc": "2.0", "result": result, "id": request["id"] }))
+                });
+            let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+            tokio::spawn(server);
+            endpoints.push(format!("http://{}", addr));
+        }
+        let gateway = Gateway::new(&GatewayConfig {
+            bind: ([127, 0, 0, 1], 0).into(),
+            endpoints,
+            request_timeout: Duration::from_secs(5),
+            max_request_bytes: 1024 * 1024,
+            rate_limit: RateLimitConfig::default(),
+            base_chain_id: BASE_CHAIN_ID,
+        })
+        .unwrap();
+
+        let responses = gateway
+            .handle_body(json!([
+                { "jsonrpc": "2.0", "method": "eth_getBalance", "params": [address.to_string(), "latest"], "id": 1 },
+                { "jsonrpc": "2.0", "method": "eth_getTransactionReceipt", "params": ["0x00"], "id": 2 },
+                { "jsonrpc": "2.0", "method": "eth_chainId", "id": 3 },
+                { "jsonrpc": "2.0", "method": "eth_getBalance", "params": [contract.to_string(), "latest"], "id": 4 },
+                {
+                    "jsonrpc": "2.0",
+                    "method": "eth_call",
+                    "params": [{ "from": address.to_string(), "to": contract.to_string() }],
+                    "id": 5,
+                },
+            ]),
+                None,
+            )
+            .await;
+        assert_eq!(responses[0]["result"], 1);
+        assert_eq!(responses[1]["result"]["shard"], 1);
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[2]["result"], 0);
+        // The contract is served by the shard that deployed it
+        assert_eq!(responses[3]["result"], 0);
+        assert_eq!(responses[4]["result"], 0);
+        assert_eq!(gateway.contracts.get(&contract), Some(Some(0)));
+        assert_eq!(gateway.contracts.get(&address), Some(None));
+
+        // Sequencers learn which client a call is for
+        let request = json!({ "jsonrpc": "2.0", "method": "web3_clientVersion", "id": 6 });
+        let client = IpAddr::from([203, 0, 113, 5]);
+        assert_eq!(gateway.handle_body(request.clone(), Some(client)).await["result"], "203.0.113.5");
+        assert_eq!(gateway.handle_pinned(1, request, Some(client)).await["result"], "203.0.113.5");
+
+        // Unreachable shards answer with an error
+        let down = Gateway::new(&GatewayConfig {
+            bind: ([127, 0, 0, 1], 0).into(),
+            endpoints: vec!["http://127.0.0.1:1".to_string()],
+            request_timeout: Duration::from_secs(5),
+            max_request_bytes: 1024,
+            rate_limit: RateLimitConfig::default(),
+            base_chain_id: BASE_CHAIN_ID,
+        })
+        .unwrap();
+        let response = down.handle_body(json!({ "jsonrpc": "2.0", "method": "eth_blockNumber", "id": 4 }), None).await;
+        assert_eq!(response["error"]["message"], "Shard 0 unavailable");
+    }
+
+    fn offline_gateway(shards: usize, rate_limit: RateLimitConfig) -> Gateway {
+        Gateway::new(&GatewayConfig {
+            bind: ([127, 0, 0, 1], 0).into(),
+            endpoints: vec!["http://127.0.0.1:1".to_string(); shards],
+            request_timeout: Duration::from_secs(5),
+            max_request_bytes: 1024,
+            rate_limit,
+            base_chain_id: BASE_CHAIN_ID,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_admin_methods_refused() {
+        assert_eq!(route("admin_clearBans", &[], 8, BASE_CHAIN_ID), Err(ADMIN_REFUSED.to_string()));
+
+        // Neither routed nor passed through, so the unreachable shard is never asked
+        let gateway = offline_gateway(1, RateLimitConfig::default());
+        let request = json!({ "jsonrpc": "2.0", "method": "admin_listBans", "id": 1 });
+        let response = gateway.handle_body(request.clone(), None).await;
+        assert_eq!(response["error"]["message"], ADMIN_REFUSED);
+        let response = gateway.handle_pinned(0, request.clone(), None).await;
+        assert_eq!(response["error"]["message"], ADMIN_REFUSED);
+        let batch = json!([{ "jsonrpc": "2.0", "method": "eth_chainId", "id": 0 }, request]);
+        let response = gateway.handle_pinned(0, batch, None).await;
+        assert_eq!(response["error"]["message"], ADMIN_REFUSED);
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_capped() {
+        let gateway = offline_gateway(4, RateLimitConfig::default());
+        let lookup = json!({ "jsonrpc": "2.0", "method": "eth_getTransactionReceipt", "params": ["0x00"], "id": 1 });
+        let batch = Value::Array(vec![lookup; MAX_FAN_OUT / 4 + 1]);
+
+        let response = gateway.handle_body(batch, None).await;
+        assert_eq!(response["error"]["code"], -32600);
+        assert!(response["error"]["message"].as_str().unwrap().contains("shard calls"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_per_client() {
+        let gateway = offline_gateway(
+            1,
+            RateLimitConfig {
+                global_requests_per_second: 1000,
+                per_ip_requests_per_second: 3,
+                ..RateLimitConfig::default()
+            },
+        );
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "ionova_shardFor",
+            "params": [Address::EVM([1; 20]).to_string()],
+            "id": 1,
+        });
+        let client = Some(IpAddr::from([10, 0, 0, 1]));
+
+        for _ in 0..3 {
+            assert!(gateway.handle_body(request.clone(), client).await.get("result").is_some());
+        }
+        let response = gateway.handle_body(request.clone(), client).await;
+        assert_eq!(response["error"]["code"], LIMIT_EXCEEDED);
+        let other = Some(IpAddr::from([10, 0, 0, 2]));
+        assert!(gateway.handle_body(request, other).await.get("result").is_some());
+    }
+}
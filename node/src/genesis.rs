@@ -1,11 +1,14 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 
+use crate::crypto::Address;
+
 /// IONX decimals (18, same as ETH)
 pub const IONX_DECIMALS: u128 = 1_000_000_000_000_000_000; // 10^18
 
 /// Account structure holding native IONX
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Account {
     pub balance: u128,  // Native IONX balance in wei (10^-18)
     pub nonce: u64,
@@ -73,6 +76,18 @@ impl GenesisState {
     }
 }
 
+/// Resolve a genesis account key to a typed address.
+/// Keys that are not hex addresses (e.g. "ionova1validator0...") map to a
+/// native address derived from the label.
+pub fn account_address(key: &str) -> Address {
+    key.parse().unwrap_or_else(|_| {
+        let hash = Sha256::digest(key.as_bytes());
+        let mut addr = [0u8; 32];
+        addr.copy_from_slice(&hash);
+        Address::Native(addr)
+    })
+}
+
 /// Mint native IONX to an account (used for block rewards)
 pub fn mint_to(accounts: &mut HashMap<String, Account>, address: &str, amount: u128) {
     if let Some(account) = accounts.get_mut(address) {
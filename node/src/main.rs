@@ -13,21 +13,49 @@ mod crypto;  // Post-quantum signature support
 mod transaction;  // Quantum-safe transactions
 mod rate_limit;  // SECURITY FIX M-2
 mod config;  // SECURITY FIX L-5
+mod state_db;  // Persistent state
+mod state_trie;  // Merkle-Patricia state root
+mod eth_tx;  // Ethereum transaction envelopes
+mod eth_api;  // Ethereum JSON-RPC read API
+mod tracer;  // EVM tracers for debug_traceTransaction / debug_traceCall
+mod pq_precompiles;  // EVM precompiles verifying PQ signatures
+mod ai_compute;  // AI inference precompiles
+mod finality;  // PQ-BFT finality gadget
+mod p2p_network;  // libp2p gossip
+mod epoch;  // Epoch validator sets selected from stake
+mod validator;  // Validator node driving the finality gadget
+mod compact_qc;  // Bitmap-encoded quorum certificates for light clients
+mod cross_shard;  // Cross-shard transfer receipts
+mod gateway;  // Shard-aware JSON-RPC gateway
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use rust_decimal_macros::dec;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::sync::mpsc;
-use tracing::info;
+use tracing::{error, info};
 
-use crate::fee_model::FeeConfig;
-use crate::mempool::MempoolConfig;
+use crate::config::Config;
+use crate::crypto::SignatureAlgorithm;
+use crate::epoch::EpochManager;
+use crate::finality::FinalityConfig;
 use crate::metrics::Metrics;
-use crate::sequencer::{Sequencer, SequencerConfig, Transaction};
+use crate::p2p_network::{P2PMessage, P2PNetwork};
+use crate::sequencer::{BaseLayerMessage, BatchCommitment, Sequencer, SequencerConfig, TxSubmission};
+use crate::state_db::{FileStateStore, StateStore};
+use crate::validator::{ConsensusMessage, Validator, ValidatorSetEntry};
+
+/// Buffer of the channels between consensus and the P2P network
+const CONSENSUS_CHANNEL_SIZE: usize = 1024;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Ionova Node", long_about = None)]
 struct Args {
+    /// Node configuration file (TOML); built-in defaults when omitted
+    #[arg(long, global = true, env = "IONOVA_CONFIG")]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -37,22 +65,141 @@ enum Commands {
     /// Run as a validator node
     Validator {
         /// Validator ID
-        #[arg(short, long, default_value_t = 0)]
+        #[arg(short, long, default_value_t = 0, env = "IONOVA_VALIDATOR_ID")]
         id: u8,
+
+        /// State directory (default: data/validator-<id>)
+        #[arg(long, env = "IONOVA_DATA_DIR")]
+        data_dir: Option<PathBuf>,
+
+        /// Validator key file, created if missing (default: <data-dir>/validator_key.json)
+        #[arg(long, env = "IONOVA_VALIDATOR_KEY")]
+        key: Option<PathBuf>,
+
+        /// Validator set file (overrides consensus.validator_set)
+        #[arg(long, env = "IONOVA_VALIDATOR_SET")]
+        validator_set: Option<PathBuf>,
+
+        /// P2P listen port (overrides consensus.p2p_port)
+        #[arg(long, env = "IONOVA_P2P_PORT")]
+        p2p_port: Option<u16>,
+
+        /// Peer multiaddr to dial, repeatable (added to consensus.bootstrap_peers)
+        #[arg(long = "peer", env = "IONOVA_PEERS", value_delimiter = ',')]
+        peers: Vec<String>,
     },
     /// Run as a sequencer node
     Sequencer {
         /// Shard ID to sequence
-        #[arg(short, long, default_value_t = 0)]
+        #[arg(short, long, default_value_t = 0, env = "IONOVA_SHARD_ID")]
         shard_id: u8,
         
-        /// Metrics port
-        #[arg(short, long, default_value_t = 9100)]
-        metrics_port: u16,
+        /// Metrics port (overrides network.metrics_port)
+        #[arg(short, long, env = "IONOVA_METRICS_PORT")]
+        metrics_port: Option<u16>,
+
+        /// RPC port (default: network.rpc_port + 100 * shard_id)
+        #[arg(short, long, env = "IONOVA_RPC_PORT")]
+        rpc_port: Option<u16>,
+
+        /// RPC bind address (overrides network.rpc_host)
+        #[arg(long, env = "IONOVA_RPC_HOST")]
+        rpc_host: Option<IpAddr>,
+
+        /// Micro-block interval in milliseconds (overrides sequencer.micro_block_interval_ms)
+        #[arg(long, env = "IONOVA_MICRO_BLOCK_INTERVAL_MS")]
+        micro_block_interval_ms: Option<u64>,
+
+        /// State directory (default: data/sequencer-<shard_id>)
+        #[arg(long, env = "IONOVA_DATA_DIR")]
+        data_dir: Option<PathBuf>,
 
-        /// RPC port
-        #[arg(short, long, default_value_t = 27000)]
-        rpc_port: u16,
+        /// P2P listen port for reaching validators (default: any free port)
+        #[arg(long, default_value_t = 0, env = "IONOVA_P2P_PORT")]
+        p2p_port: u16,
+
+        /// Validator multiaddr to dial, repeatable (added to consensus.bootstrap_peers)
+        #[arg(long = "peer", env = "IONOVA_PEERS", value_delimiter = ',')]
+        peers: Vec<String>,
+
+        /// Key batch commitments are signed with, created if missing (default: <data-dir>/sequencer_key.json)
+        #[arg(long, env = "IONOVA_SEQUENCER_KEY")]
+        key: Option<PathBuf>,
+    },
+    /// Run a JSON-RPC gateway that forwards calls to the shard they concern
+    Gateway {
+        /// RPC port (overrides gateway.rpc_port)
+        #[arg(short, long, env = "IONOVA_GATEWAY_PORT")]
+        rpc_port: Option<u16>,
+
+        /// RPC bind address (overrides network.rpc_host)
+        #[arg(long, env = "IONOVA_RPC_HOST")]
+        rpc_host: Option<IpAddr>,
+
+        /// Sequencer RPC URL per shard, in shard order (overrides gateway.shard_endpoints)
+        #[arg(long = "shard-endpoint", env = "IONOVA_SHARD_ENDPOINTS", value_delimiter = ',')]
+        shard_endpoints: Vec<String>,
+    },
+    /// Generate a validator key and print its validator set entry
+    Keygen {
+        /// Key file to write
+        #[arg(long)]
+        out: PathBuf,
+
+        #[arg(long, value_enum, default_value_t = KeyAlgorithm::Dilithium)]
+        algorithm: KeyAlgorithm,
+
+        /// Stake recorded in the printed entry
+        #[arg(long, default_value_t = 100)]
+        stake: u64,
+
+        /// Name recorded in the printed entry
+        #[arg(long, default_value = "")]
+        name: String,
+    },
+    /// Print encoded quorum certificate sizes for several set sizes
+    QcSizes {
+        /// Validator set sizes to measure
+        #[arg(long, value_delimiter = ',', default_value = "4,16,64,100")]
+        validators: Vec<usize>,
+
+        #[arg(long, value_enum, default_value_t = KeyAlgorithm::Dilithium)]
+        algorithm: KeyAlgorithm,
+    },
+    /// Manage the node configuration file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+/// Post-quantum algorithms validators sign with
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum KeyAlgorithm {
+    Dilithium,
+    Sphincs,
+}
+
+impl From<KeyAlgorithm> for SignatureAlgorithm {
+    fn from(algorithm: KeyAlgorithm) -> Self {
+        match algorithm {
+            KeyAlgorithm::Dilithium => SignatureAlgorithm::Dilithium,
+            KeyAlgorithm::Sphincs => SignatureAlgorithm::SPHINCSPlus,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Write the default configuration
+    Init {
+        /// Output file
+        #[arg(default_value = "ionova.toml")]
+        path: PathBuf,
+
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
     },
 }
 
@@ -63,33 +210,231 @@ async fn main() -> Result<()> {
     let args = Args::parse();
 
     match args.command {
-        Commands::Validator { id } => {
-            run_validator(id).await?;
+        Commands::Validator { id, data_dir, key, validator_set, p2p_port, mut peers } => {
+            let mut config = load_config(args.config.as_ref())?;
+            let data_dir = data_dir.unwrap_or_else(|| PathBuf::from(format!("data/validator-{}", id)));
+            let key = key.unwrap_or_else(|| data_dir.join("validator_key.json"));
+            if validator_set.is_some() {
+                config.consensus.validator_set = validator_set;
+            }
+            if let Some(port) = p2p_port {
+                config.consensus.p2p_port = port;
+            }
+            peers.extend(config.consensus.bootstrap_peers.iter().cloned());
+            config.consensus.bootstrap_peers = peers;
+            run_validator(id, data_dir, key, config).await?;
+        }
+        Commands::Sequencer {
+            shard_id,
+            metrics_port,
+            rpc_port,
+            rpc_host,
+            micro_block_interval_ms,
+            data_dir,
+            p2p_port,
+            mut peers,
+            key,
+        } => {
+            // Precedence: CLI flags, then environment, then config file, then defaults
+            let mut config = load_config(args.config.as_ref())?;
+            if let Some(interval) = micro_block_interval_ms {
+                config.sequencer.micro_block_interval_ms = interval;
+            }
+            config.validate()?;
+            if shard_id >= config.network.shard_count {
+                anyhow::bail!("Shard {} out of range (shard_count = {})", shard_id, config.network.shard_count);
+            }
+
+            let data_dir = data_dir.unwrap_or_else(|| PathBuf::from(format!("data/sequencer-{}", shard_id)));
+            let key = key.unwrap_or_else(|| data_dir.join("sequencer_key.json"));
+            let rpc = rpc::RpcConfig {
+                bind: SocketAddr::new(
+                    rpc_host.unwrap_or(config.network.rpc_host),
+                    match rpc_port {
+                        Some(port) => port,
+                        None => config.rpc_port_for(shard_id)?,
+                    },
+                ),
+                rate_limit: config.rate_limit.clone(),
+                gas: config.gas.clone(),
+                chain_id: config.chain_id_for(shard_id),
+            };
+            let metrics_port = metrics_port.unwrap_or(config.network.metrics_port);
+            peers.extend(config.consensus.bootstrap_peers.iter().cloned());
+            run_sequencer(config.sequencer_config(shard_id), metrics_port, rpc, data_dir, key, p2p_port, peers).await?;
         }
-        Commands::Sequencer { shard_id, metrics_port, rpc_port } => {
-            run_sequencer(shard_id, metrics_port, rpc_port).await?;
+        Commands::Gateway { rpc_port, rpc_host, shard_endpoints } => {
+            let mut config = load_config(args.config.as_ref())?;
+            if !shard_endpoints.is_empty() {
+                config.gateway.shard_endpoints = shard_endpoints;
+            }
+            config.validate()?;
+            let gateway = gateway::GatewayConfig {
+                bind: SocketAddr::new(
+                    rpc_host.unwrap_or(config.network.rpc_host),
+                    rpc_port.unwrap_or(config.gateway.rpc_port),
+                ),
+                endpoints: config.gateway_endpoints()?,
+                request_timeout: std::time::Duration::from_millis(config.gateway.request_timeout_ms),
+                max_request_bytes: config.rate_limit.max_request_bytes,
+                rate_limit: config.rate_limit.clone(),
+                base_chain_id: config.network.chain_id,
+            };
+            gateway::start_gateway(gateway).await?;
+        }
+        Commands::Keygen { out, algorithm, stake, name } => {
+            if out.exists() {
+                anyhow::bail!("{} already exists", out.display());
+            }
+            let keypair = validator::load_or_generate_key(&out, algorithm.into())?;
+            let entry = ValidatorSetEntry { name, public_key: keypair.public_key, stake };
+            println!("{}", serde_json::to_string(&entry)?);
+        }
+        Commands::QcSizes { validators, algorithm } => {
+            println!("validators  signers  full QC (JSON)  compact");
+            for count in validators {
+                let sizes = compact_qc::measure_sizes(count, algorithm.into())?;
+                println!(
+                    "{:>10}  {:>7}  {:>14}  {:>7}",
+                    sizes.validators, sizes.signers, sizes.full_json, sizes.compact
+                );
+            }
+        }
+        Commands::Config { command: ConfigCommand::Init { path, force } } => {
+            if path.exists() && !force {
+                anyhow::bail!("{} already exists (use --force to overwrite)", path.display());
+            }
+            Config::default().save_to_file(&path)?;
+            println!("Wrote default configuration to {}", path.display());
         }
     }
 
     Ok(())
 }
 
-async fn run_validator(id: u8) -> Result<()> {
+/// Configuration file if one was given, otherwise the defaults
+fn load_config(path: Option<&PathBuf>) -> Result<Config> {
+    let config = match path {
+        Some(path) => {
+            let config = Config::load_from_file(path)
+                .with_context(|| format!("Failed to load config {}", path.display()))?;
+            info!("Loaded configuration from {}", path.display());
+            config
+        }
+        None => Config::default(),
+    };
+    config.validate()?;
+    Ok(config)
+}
+
+/// Join the gossip network; returns the consensus message sender and receiver
+async fn start_consensus_network(
+    port: u16,
+    peers: &[String],
+) -> Result<(mpsc::Sender<ConsensusMessage>, mpsc::Receiver<ConsensusMessage>)> {
+    let mut network = P2PNetwork::new(port).await?;
+    for peer in peers {
+        network.dial(peer.parse().with_context(|| format!("Invalid peer address {}", peer))?)?;
+    }
+
+    let (p2p_out, p2p_out_rx) = mpsc::channel(CONSENSUS_CHANNEL_SIZE);
+    let (p2p_in, mut p2p_in_rx) = mpsc::channel(CONSENSUS_CHANNEL_SIZE);
+    tokio::spawn(network.run(p2p_out_rx, p2p_in));
+
+    let (outbound, mut outbound_rx) = mpsc::channel(CONSENSUS_CHANNEL_SIZE);
+    tokio::spawn(async move {
+        while let Some(message) = outbound_rx.recv().await {
+            if p2p_out.send(P2PMessage::Consensus(Box::new(message))).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let (inbound_tx, inbound) = mpsc::channel(CONSENSUS_CHANNEL_SIZE);
+    tokio::spawn(async move {
+        while let Some(message) = p2p_in_rx.recv().await {
+            if let P2PMessage::Consensus(message) = message {
+                if inbound_tx.send(*message).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok((outbound, inbound))
+}
+
+async fn run_validator(id: u8, data_dir: PathBuf, key: PathBuf, config: Config) -> Result<()> {
     info!("Starting Ionova Validator node {}", id);
-    info!("Initializing PQ-BFT consensus engine...");
+
+    // Finalized base blocks and their QCs
+    let state: Arc<dyn StateStore> = Arc::new(FileStateStore::open(&data_dir)?);
+
+    let keypair = validator::load_or_generate_key(&key, SignatureAlgorithm::Dilithium)?;
+    let set_path = config
+        .consensus
+        .validator_set
+        .as_ref()
+        .context("No validator set configured (--validator-set or consensus.validator_set)")?;
+    let validators = validator::load_validator_set(set_path)?;
+    info!(
+        "Validator set: {} validators, {} total stake",
+        validators.validators.len(),
+        validators.total_stake
+    );
+    let epochs = EpochManager::new(config.consensus.epoch.clone(), &validators)?;
+    let sequencer_set = config
+        .consensus
+        .sequencer_set
+        .as_ref()
+        .context("No sequencer set configured (consensus.sequencer_set)")?;
+    let sequencers = validator::load_sequencer_set(sequencer_set)?;
+
+    let finality_config = FinalityConfig {
+        view_timeout_ms: config.consensus.view_timeout_ms,
+        max_view_timeout_ms: config.consensus.max_view_timeout_ms,
+        ..FinalityConfig::default()
+    };
+
     info!("Connecting to peer network...");
-    info!("Validator node {} started successfully", id);
+    let (outbound, inbound) =
+        start_consensus_network(config.consensus.p2p_port, &config.consensus.bootstrap_peers).await?;
+
+    info!("Initializing PQ-BFT consensus engine...");
+    let validator = Validator::new(keypair, epochs, sequencers, finality_config, state, outbound).await?;
+    info!("Validator node {} started successfully as {}", id, validator.id());
 
-    // Keep running
-    tokio::signal::ctrl_c().await?;
-    info!("Shutting down validator {}", id);
+    tokio::select! {
+        result = validator.run(inbound) => {
+            if let Err(e) = result {
+                error!("Validator {} stopped: {:#}", id, e);
+                std::process::exit(1);
+            }
+        }
+        result = tokio::signal::ctrl_c() => {
+            result?;
+            info!("Shutting down validator {}", id);
+        }
+    }
 
     Ok(())
 }
 
-async fn run_sequencer(shard_id: u8, metrics_port: u16, rpc_port: u16) -> Result<()> {
+async fn run_sequencer(
+    config: SequencerConfig,
+    metrics_port: u16,
+    rpc: rpc::RpcConfig,
+    data_dir: PathBuf,
+    key: PathBuf,
+    p2p_port: u16,
+    peers: Vec<String>,
+) -> Result<()> {
+    let shard_id = config.shard_id;
     info!("Starting Ionova Sequencer for shard {}", shard_id);
 
+    // Open persistent state (accounts, micro-blocks, batch commitments)
+    let state: Arc<dyn StateStore> = Arc::new(FileStateStore::open(&data_dir)?);
+
     // Initialize metrics
     let metrics = Metrics::new(shard_id);
     let metrics_clone = metrics.clone();
@@ -98,35 +443,53 @@ async fn run_sequencer(shard_id: u8, metrics_port: u16, rpc_port: u16) -> Result
     });
 
     // Create transaction queue
-    let (tx_sender, tx_receiver) = mpsc::channel::<Transaction>(10000);
+    let (tx_sender, tx_receiver) = mpsc::channel::<TxSubmission>(10000);
+
+    // Start sequencer
+    let mut sequencer = Sequencer::new(config, tx_receiver, state.clone())?;
+
+    // Gossip signed batch commitments to the validators
+    let keypair = validator::load_or_generate_key(&key, SignatureAlgorithm::Dilithium)?;
+    info!("Sequencer key {} ({})", keypair.address(), key.display());
+    let (outbound, mut inbound) = start_consensus_network(p2p_port, &peers).await?;
+    let (batch_sink, mut batches) = mpsc::channel::<BatchCommitment>(CONSENSUS_CHANNEL_SIZE);
+    sequencer.set_batch_sink(batch_sink, keypair);
+    tokio::spawn(async move {
+        while let Some(batch) = batches.recv().await {
+            if outbound.send(ConsensusMessage::Batch(batch)).await.is_err() {
+                break;
+            }
+        }
+    });
+    // Finalized base blocks report which batches are final, and other
+    // shards' batches carry cross-shard receipts; proposals and votes are
+    // only for validators
+    let base_layer = sequencer.base_layer_sender();
+    tokio::spawn(async move {
+        while let Some(message) = inbound.recv().await {
+            let message = match message {
+                ConsensusMessage::Finalized(block) => BaseLayerMessage::Finalized(block),
+                ConsensusMessage::Batch(batch) => BaseLayerMessage::Batch(batch),
+                _ => continue,
+            };
+            if base_layer.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
 
     // Start RPC server
     let rpc_sender = tx_sender.clone();
+    let rpc_state = state.clone();
+    let rpc_events = sequencer.events();
+    let rpc_bind = rpc.bind;
     tokio::spawn(async move {
-        rpc::start_rpc_server(rpc_port, shard_id, rpc_sender).await;
+        rpc::start_rpc_server(rpc, shard_id, rpc_sender, rpc_state, rpc_events).await;
     });
-
-    // Configure sequencer
-    let config = SequencerConfig {
-        shard_id,
-        micro_block_interval_ms: 200,
-        batch_interval_ms: 1000,
-        max_batch_size: 1000,
-        fee_config: FeeConfig {
-            base_tx_fee: dec!(0.0001),
-            base_fee_per_gas: dec!(0.000001),
-            target_utilization: dec!(0.8),
-            adjustment_factor: dec!(0.125),
-        },
-        mempool_config: MempoolConfig::default(),
-    };
-
-    // Start sequencer
-    let mut sequencer = Sequencer::new(config, tx_receiver);
     
     info!("Sequencer for shard {} started successfully", shard_id);
     info!("Metrics available at http://localhost:{}/metrics", metrics_port);
-    info!("RPC server available at http://localhost:{}", rpc_port);
+    info!("RPC server available at http://{}", rpc_bind);
     
     // Run the sequencer loop
     sequencer.run().await?;
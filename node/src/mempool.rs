@@ -3,11 +3,10 @@ use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
 use anyhow::Result;
-
 use crate::transaction::Transaction;
 
 /// Mempool configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MempoolConfig {
     pub max_size: usize,
     pub max_tx_age_secs: u64,
@@ -46,7 +45,8 @@ pub struct PooledTransaction {
     pub tx: Transaction,
     pub tx_hash: String,
     pub received_at: SystemTime,
-    pub gas_price: u64,
+    /// Tip paid to the sequencer, in wei
+    pub tip: u128,
 }
 
 impl Mempool {
@@ -64,28 +64,16 @@ impl Mempool {
         // Validate transaction
         self.validate_transaction(&tx).await?;
         
-        let tx_hash = format!("{:?}", tx.hash());
-        let from_address = format!("{:?}", tx.from);
+        let tx_hash = tx_hash_hex(&tx);
+        let from_address = tx.from.to_string();
         
         let pooled_tx = PooledTransaction {
             tx: tx.clone(),
             tx_hash: tx_hash.clone(),
             received_at: SystemTime::now(),
-            gas_price: tx.gas_price.to_string().parse().unwrap_or(0),
+            tip: tx.tip_wei()?,
         };
 
-        // Add to main pool
-        {
-            let mut txs = self.transactions.write().await;
-            
-            // Check mempool size limit
-            if txs.len() >= self.config.max_size {
-                self.evict_lowest_priority().await?;
-            }
-            
-            txs.insert(tx_hash.clone(), pooled_tx);
-        }
-
         // Add to account queue
         {
             let mut by_account = self.by_account.write().await;
@@ -99,6 +87,14 @@ impl Mempool {
             account_txs.push_back(tx_hash.clone());
         }
 
+        // Check mempool size limit (eviction takes the pool locks itself)
+        if self.size().await >= self.config.max_size {
+            self.evict_lowest_priority().await?;
+        }
+
+        // Add to main pool
+        self.transactions.write().await.insert(tx_hash.clone(), pooled_tx);
+
         // Update priority queue
         self.update_priority_queue().await;
 
@@ -112,14 +108,15 @@ impl Mempool {
             return Err(anyhow::anyhow!("Invalid signature"));
         }
 
-        // 2. Check gas price meets minimum
-        let gas_price: u64 = tx.gas_price.to_string().parse().unwrap_or(0);
-        if gas_price < self.config.min_gas_price {
+        // 2. Check gas price meets minimum, and that amounts fit in wei
+        tx.value_wei()?;
+        tx.tip_wei()?;
+        if tx.gas_price_wei()? < self.config.min_gas_price {
             return Err(anyhow::anyhow!("Gas price too low"));
         }
 
         // 3. Check if transaction already exists
-        let tx_hash = format!("{:?}", tx.hash());
+        let tx_hash = tx_hash_hex(tx);
         let txs = self.transactions.read().await;
         if txs.contains_key(&tx_hash) {
             return Err(anyhow::anyhow!("Transaction already in mempool"));
@@ -157,7 +154,7 @@ impl Mempool {
         let mut txs = self.transactions.write().await;
         
         if let Some(pooled) = txs.remove(tx_hash) {
-            let from = format!("{:?}", pooled.tx.from);
+            let from = pooled.tx.from.to_string();
             
             // Remove from account queue
             let mut by_account = self.by_account.write().await;
@@ -167,11 +164,18 @@ impl Mempool {
                     by_account.remove(&from);
                 }
             }
+
+            self.priority_queue.write().await.retain(|h| h != tx_hash);
         }
 
         Ok(())
     }
 
+    /// Number of pending transactions
+    pub async fn size(&self) -> usize {
+        self.transactions.read().await.len()
+    }
+
     /// Clean up expired transactions
     pub async fn cleanup_expired(&self) {
         let now = SystemTime::now();
@@ -193,7 +197,8 @@ impl Mempool {
         }
     }
 
-    /// Update priority queue based on gas prices
+    /// Update priority queue by the tip each transaction pays. The gas
+    /// price only caps the base fee, so it buys no priority.
     async fn update_priority_queue(&self) {
         let txs = self.transactions.read().await;
         let mut priority = self.priority_queue.write().await;
@@ -201,19 +206,19 @@ impl Mempool {
         priority.clear();
         priority.extend(txs.keys().cloned());
         
-        // Sort by gas price (highest first)
+        // Sort by tip (highest first)
         priority.sort_by(|a, b| {
-            let gas_a = txs.get(a).map(|t| t.gas_price).unwrap_or(0);
-            let gas_b = txs.get(b).map(|t| t.gas_price).unwrap_or(0);
-            gas_b.cmp(&gas_a)
+            let tip_a = txs.get(a).map(|t| t.tip).unwrap_or(0);
+            let tip_b = txs.get(b).map(|t| t.tip).unwrap_or(0);
+            tip_b.cmp(&tip_a)
         });
     }
 
     /// Evict lowest priority transaction
     async fn evict_lowest_priority(&self) -> Result<()> {
-        let priority = self.priority_queue.read().await;
-        if let Some(lowest) = priority.last() {
-            self.remove_transaction(lowest).await?;
+        let lowest = self.priority_queue.read().await.last().cloned();
+        if let Some(lowest) = lowest {
+            self.remove_transaction(&lowest).await?;
         }
         Ok(())
     }
@@ -231,6 +236,11 @@ impl Mempool {
     }
 }
 
+/// Transaction hash as returned to clients (0x-prefixed hex)
+pub fn tx_hash_hex(tx: &Transaction) -> String {
+    format!("0x{}", hex::encode(tx.hash()))
+}
+
 #[derive(Debug, Serialize)]
 pub struct MempoolStats {
     pub total_transactions: usize,
@@ -106,6 +106,23 @@ impl NetworkSecurity {
 
     /// Check if connection is allowed
     pub fn allow_connection(&mut self, ip: IpAddr) -> Result<(), NetworkSecurityError> {
+        self.check_access(ip)?;
+
+        let peer = self.peers.entry(ip).or_insert_with(|| PeerInfo::new(ip));
+
+        // Check connection limit
+        if peer.connection_count >= self.config.max_connections_per_ip {
+            return Err(NetworkSecurityError::TooManyConnections);
+        }
+
+        // Increment connection count
+        peer.connection_count += 1;
+
+        Ok(())
+    }
+
+    /// Check allow/deny lists, bans and reputation without opening a connection
+    pub fn check_access(&self, ip: IpAddr) -> Result<(), NetworkSecurityError> {
         // Check whitelist
         if self.config.enable_whitelist && !self.whitelist.contains(&ip) {
             return Err(NetworkSecurityError::NotWhitelisted);
@@ -116,27 +133,18 @@ impl NetworkSecurity {
             return Err(NetworkSecurityError::Blacklisted);
         }
 
-        // Get or create peer info
-        let peer = self.peers.entry(ip).or_insert_with(|| PeerInfo::new(ip));
-
-        // Check if banned
-        if peer.is_banned() {
-            return Err(NetworkSecurityError::Banned);
-        }
-
-        // Check connection limit
-        if peer.connection_count >= self.config.max_connections_per_ip {
-            return Err(NetworkSecurityError::TooManyConnections);
-        }
+        if let Some(peer) = self.peers.get(&ip) {
+            // Check if banned
+            if peer.is_banned() {
+                return Err(NetworkSecurityError::Banned);
+            }
 
-        // Check reputation
-        if peer.reputation < self.config.min_peer_reputation {
-            return Err(NetworkSecurityError::LowReputation);
+            // Check reputation
+            if peer.reputation < self.config.min_peer_reputation {
+                return Err(NetworkSecurityError::LowReputation);
+            }
         }
 
-        // Increment connection count
-        peer.connection_count += 1;
-
         Ok(())
     }
 
@@ -208,7 +216,7 @@ impl NetworkSecurity {
         let peer = self.peers.entry(ip).or_insert_with(|| PeerInfo::new(ip));
         peer.banned_until = Some(now + self.config.ban_duration);
 
-        println!("Banned peer {} for: {}", ip, reason);
+        tracing::warn!("Banned peer {} for: {}", ip, reason);
     }
 
     /// Unban peer
@@ -219,6 +227,25 @@ impl NetworkSecurity {
         }
     }
 
+    /// Currently banned peers
+    pub fn banned_peers(&self) -> Vec<PeerInfo> {
+        self.peers.values().filter(|p| p.is_banned()).cloned().collect()
+    }
+
+    /// Lift all bans and reset reputations; returns how many bans were lifted
+    pub fn clear_bans(&mut self) -> usize {
+        let mut cleared = 0;
+        for peer in self.peers.values_mut() {
+            if peer.is_banned() {
+                cleared += 1;
+            }
+            peer.banned_until = None;
+            peer.failed_attempts = 0;
+            peer.reputation = peer.reputation.max(0);
+        }
+        cleared
+    }
+
     /// Add to whitelist
     pub fn add_to_whitelist(&mut self, ip: IpAddr) {
         if !self.whitelist.contains(&ip) {
@@ -315,6 +342,33 @@ impl NetworkSecurity {
     }
 }
 
+/// Client a request came from. Behind a trusted reverse proxy the peer is
+/// the proxy, so the client is the last X-Forwarded-For hop not added by a
+/// trusted proxy; anyone else's header is ignored, as it is easily forged.
+pub fn client_ip(peer: Option<IpAddr>, forwarded_for: Option<&str>, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let peer = peer?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+    let Some(forwarded_for) = forwarded_for else {
+        return Some(peer);
+    };
+    let mut client = peer;
+    for hop in forwarded_for.rsplit(',') {
+        match hop.trim().parse::<IpAddr>() {
+            Ok(ip) => {
+                client = ip;
+                if !trusted_proxies.contains(&ip) {
+                    break;
+                }
+            }
+            // Whoever wrote a malformed hop is not trusted either
+            Err(_) => break,
+        }
+    }
+    Some(client)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkStats {
     pub total_peers: usize,
@@ -441,4 +495,37 @@ mod tests {
         // Should be banned after 3 attempts
         assert!(security.allow_connection(ip).is_err());
     }
+
+    #[test]
+    fn test_list_and_clear_bans() {
+        let mut security = NetworkSecurity::new(NetworkSecurityConfig::default());
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7));
+
+        security.ban_peer(ip, "Test ban");
+        assert_eq!(security.banned_peers().len(), 1);
+        assert!(security.check_access(ip).is_err());
+
+        assert_eq!(security.clear_bans(), 1);
+        assert!(security.banned_peers().is_empty());
+        assert!(security.check_access(ip).is_ok());
+    }
+
+    #[test]
+    fn test_client_ip_behind_trusted_proxies() {
+        let proxy: IpAddr = "127.0.0.1".parse().unwrap();
+        let gateway: IpAddr = "10.0.0.2".parse().unwrap();
+        let trusted = [proxy, gateway];
+        let client: IpAddr = "203.0.113.5".parse().unwrap();
+
+        // Untrusted peers cannot claim another address
+        assert_eq!(client_ip(Some(client), Some("198.51.100.1"), &trusted), Some(client));
+        assert_eq!(client_ip(Some(proxy), None, &trusted), Some(proxy));
+        assert_eq!(client_ip(None, Some("198.51.100.1"), &trusted), None);
+
+        // The hop before the trusted proxies, not what the client prepended
+        assert_eq!(client_ip(Some(proxy), Some("203.0.113.5"), &trusted), Some(client));
+        assert_eq!(client_ip(Some(proxy), Some("198.51.100.1, 203.0.113.5, 10.0.0.2"), &trusted), Some(client));
+        assert_eq!(client_ip(Some(proxy), Some("203.0.113.5, bogus"), &trusted), Some(proxy));
+        assert_eq!(client_ip(Some(proxy), Some("bogus, 203.0.113.5"), &[proxy]), Some(client));
+    }
 }
@@ -1,27 +1,32 @@
-use libp2p::{
-    gossipsub, identity, mdns, noise, swarm::NetworkBehaviour, swarm::SwarmEvent, tcp, yamux, PeerId,
-    Swarm, Transport,
-};
-use libp2p::gossipsub::{Gossipsub, GossipsubEvent, IdentTopic};
+use libp2p::{gossipsub, mdns, noise, swarm::NetworkBehaviour, swarm::SwarmEvent, tcp, yamux, Multiaddr, PeerId, Swarm};
+use libp2p::gossipsub::IdentTopic;
+use futures::StreamExt;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::time::Duration;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
 use serde::{Serialize, Deserialize};
 
+use crate::validator::ConsensusMessage;
+
+/// Largest gossiped message (batch commitments carry their micro-blocks)
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
 /// P2P Network Manager for Ionova
 pub struct P2PNetwork {
     swarm: Swarm<IonovaBehaviour>,
     peer_id: PeerId,
     block_topic: IdentTopic,
     tx_topic: IdentTopic,
+    consensus_topic: IdentTopic,
 }
 
 /// Network behavior combining gossipsub and mDNS
 #[derive(NetworkBehaviour)]
 struct IonovaBehaviour {
-    gossipsub: Gossipsub,
+    gossipsub: gossipsub::Behaviour,
     mdns: mdns::tokio::Behaviour,
 }
 
@@ -44,71 +49,69 @@ pub enum P2PMessage {
         block_hash: String,
         block_data: Vec<u8>,
     },
+    /// Batch commitments, proposals and votes of the finality gadget
+    Consensus(Box<ConsensusMessage>),
 }
 
 impl P2PNetwork {
+    /// Create the local peer and listen on `port` (0 picks a free port)
     pub async fn new(port: u16) -> Result<Self> {
         // Generate keypair for peer identity
-        let local_key = identity::Keypair::generate_ed25519();
+        let local_key = libp2p::identity::Keypair::generate_ed25519();
         let peer_id = PeerId::from(local_key.public());
-        
-        println!("🌐 Local peer id: {:?}", peer_id);
+
+        info!("Local peer id: {}", peer_id);
 
         // Configure Gossipsub
         let gossipsub_config = gossipsub::ConfigBuilder::default()
             .heartbeat_interval(Duration::from_secs(1))
             .validation_mode(gossipsub::ValidationMode::Strict)
+            .max_transmit_size(MAX_MESSAGE_SIZE)
             .message_id_fn(|message: &gossipsub::Message| {
                 let mut hasher = DefaultHasher::new();
                 message.data.hash(&mut hasher);
                 gossipsub::MessageId::from(hasher.finish().to_string())
             })
             .build()
-            .expect("Valid gossipsub config");
-
-        // Create gossipsub behavior
-        let mut gossipsub = Gossipsub::new(
-            gossipsub::MessageAuthenticity::Signed(local_key.clone()),
-            gossipsub_config,
-        )
-        .expect("Gossipsub creation failed");
+            .map_err(|e| anyhow!("Invalid gossipsub config: {}", e))?;
 
         // Create topics
         let block_topic = IdentTopic::new("ionova-blocks");
         let tx_topic = IdentTopic::new("ionova-transactions");
+        let consensus_topic = IdentTopic::new("ionova-consensus");
+
+        let mut swarm = libp2p::SwarmBuilder::with_existing_identity(local_key)
+            .with_tokio()
+            .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)?
+            .with_behaviour(|key| {
+                // Create gossipsub behavior
+                let gossipsub = gossipsub::Behaviour::new(
+                    gossipsub::MessageAuthenticity::Signed(key.clone()),
+                    gossipsub_config,
+                )?;
+
+                // Create mDNS for peer discovery
+                let mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), key.public().to_peer_id())?;
+
+                Ok(IonovaBehaviour { gossipsub, mdns })
+            })?
+            .with_swarm_config(|config| config.with_idle_connection_timeout(Duration::from_secs(60)))
+            .build();
 
         // Subscribe to topics
-        gossipsub.subscribe(&block_topic)?;
-        gossipsub.subscribe(&tx_topic)?;
-
-        // Create mDNS for peer discovery
-        let mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), peer_id)?;
-
-        // Build behavior
-        let behaviour = IonovaBehaviour { gossipsub, mdns };
-
-        // Create transport
-        let transport = tcp::tokio::Transport::default()
-            .upgrade(libp2p::core::upgrade::Version::V1)
-            .authenticate(noise::Config::new(&local_key)?)
-            .multiplex(yamux::Config::default())
-            .boxed();
-
-        // Create swarm
-        let swarm = Swarm::new(
-            transport,
-            behaviour,
-            peer_id,
-            libp2p::swarm::Config::with_tokio_executor()
-                .with_idle_connection_timeout(Duration::from_secs(60)),
-        );
+        for topic in [&block_topic, &tx_topic, &consensus_topic] {
+            swarm.behaviour_mut().gossipsub.subscribe(topic)?;
+        }
 
-        Ok(Self {
+        let mut network = Self {
             swarm,
             peer_id,
             block_topic,
             tx_topic,
-        })
+            consensus_topic,
+        };
+        network.listen(port).await?;
+        Ok(network)
     }
 
     /// Start listening on specified port
@@ -118,44 +121,55 @@ impl P2PNetwork {
         Ok(())
     }
 
-    /// Broadcast a new block to network
-    pub async fn broadcast_block(&mut self, block_hash: String, block_data: Vec<u8>, signature: Vec<u8>) -> Result<()> {
-        let message = P2PMessage::NewBlock {
-            block_hash,
-            block_data,
-            validator_signature: signature,
-        };
-        
-        let data = serde_json::to_vec(&message)?;
-        self.swarm.behaviour_mut().gossipsub.publish(self.block_topic.clone(), data)?;
+    /// Connect to a known peer (bootstrap nodes, or peers mDNS cannot reach)
+    pub fn dial(&mut self, addr: Multiaddr) -> Result<()> {
+        self.swarm.dial(addr)?;
         Ok(())
     }
 
-    /// Broadcast a new transaction to network
-    pub async fn broadcast_transaction(&mut self, tx_hash: String, tx_data: Vec<u8>) -> Result<()> {
-        let message = P2PMessage::NewTransaction { tx_hash, tx_data };
-        let data = serde_json::to_vec(&message)?;
-        self.swarm.behaviour_mut().gossipsub.publish(self.tx_topic.clone(), data)?;
+    /// Gossip a message on the topic for its kind
+    pub fn publish(&mut self, message: &P2PMessage) -> Result<()> {
+        let topic = match message {
+            P2PMessage::NewBlock { .. } | P2PMessage::BlockRequest { .. } | P2PMessage::BlockResponse { .. } => {
+                self.block_topic.clone()
+            }
+            P2PMessage::NewTransaction { .. } => self.tx_topic.clone(),
+            P2PMessage::Consensus(_) => self.consensus_topic.clone(),
+        };
+        let data = serde_json::to_vec(message)?;
+        self.swarm.behaviour_mut().gossipsub.publish(topic, data)?;
         Ok(())
     }
 
-    /// Process network events
-    pub async fn handle_events(&mut self, block_tx: mpsc::Sender<P2PMessage>, tx_tx: mpsc::Sender<P2PMessage>) {
+    /// Drive the swarm: publish `outbound` messages and deliver gossip
+    /// received from peers to `inbound`. Returns when `outbound` closes.
+    pub async fn run(mut self, mut outbound: mpsc::Receiver<P2PMessage>, inbound: mpsc::Sender<P2PMessage>) {
         loop {
-            match self.swarm.select_next_some().await {
-                SwarmEvent::Behaviour(IonovaBehaviourEvent::Mdns(event)) => {
-                    self.handle_mdns_event(event);
-                }
-                SwarmEvent::Behaviour(IonovaBehaviourEvent::Gossipsub(event)) => {
-                    self.handle_gossipsub_event(event, &block_tx, &tx_tx).await;
-                }
-                SwarmEvent::NewListenAddr { address, .. } => {
-                    println!("📡 Listening on {:?}", address);
-                }
-                SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                    println!("🤝 Connected to peer: {:?}", peer_id);
+            tokio::select! {
+                message = outbound.recv() => {
+                    let Some(message) = message else { return };
+                    // Peers that miss a message catch up through later rounds
+                    if let Err(e) = self.publish(&message) {
+                        warn!("Failed to publish P2P message: {}", e);
+                    }
                 }
-                _ => {}
+                event = self.swarm.select_next_some() => match event {
+                    SwarmEvent::Behaviour(IonovaBehaviourEvent::Mdns(event)) => {
+                        self.handle_mdns_event(event);
+                    }
+                    SwarmEvent::Behaviour(IonovaBehaviourEvent::Gossipsub(event)) => {
+                        self.handle_gossipsub_event(event, &inbound).await;
+                    }
+                    SwarmEvent::NewListenAddr { address, .. } => {
+                        info!("Listening on {}/p2p/{}", address, self.peer_id);
+                    }
+                    SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                        // Dialed peers join gossip like mDNS-discovered ones
+                        self.swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                        info!("Connected to peer: {} ({} gossip peers)", peer_id, self.peer_count());
+                    }
+                    _ => {}
+                },
             }
         }
     }
@@ -164,36 +178,26 @@ impl P2PNetwork {
         match event {
             mdns::Event::Discovered(peers) => {
                 for (peer_id, _addr) in peers {
-                    println!("🔍 Discovered peer: {:?}", peer_id);
+                    debug!("Discovered peer: {}", peer_id);
                     self.swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
                 }
             }
             mdns::Event::Expired(peers) => {
                 for (peer_id, _addr) in peers {
-                    println!("👋 Peer expired: {:?}", peer_id);
+                    debug!("Peer expired: {}", peer_id);
                     self.swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
                 }
             }
         }
     }
 
-    async fn handle_gossipsub_event(
-        &mut self,
-        event: GossipsubEvent,
-        block_tx: &mpsc::Sender<P2PMessage>,
-        tx_tx: &mpsc::Sender<P2PMessage>,
-    ) {
-        if let GossipsubEvent::Message { propagation_source, message, .. } = event {
-            if let Ok(p2p_message) = serde_json::from_slice::<P2PMessage>(&message.data) {
-                match p2p_message {
-                    P2PMessage::NewBlock { .. } => {
-                        let _ = block_tx.send(p2p_message).await;
-                    }
-                    P2PMessage::NewTransaction { .. } => {
-                        let _ = tx_tx.send(p2p_message).await;
-                    }
-                    _ => {}
+    async fn handle_gossipsub_event(&mut self, event: gossipsub::Event, inbound: &mpsc::Sender<P2PMessage>) {
+        if let gossipsub::Event::Message { propagation_source, message, .. } = event {
+            match serde_json::from_slice::<P2PMessage>(&message.data) {
+                Ok(p2p_message) => {
+                    let _ = inbound.send(p2p_message).await;
                 }
+                Err(e) => warn!("Malformed message from {}: {}", propagation_source, e),
             }
         }
     }
@@ -0,0 +1,213 @@
+// EVM precompiles verifying post-quantum signatures, so contracts (smart
+// wallets, bridges) can check the same Dilithium5, SPHINCS+ and hybrid
+// signatures the node accepts on transactions.
+// Solidity interface: contracts/interfaces/IPQVerifier.sol
+
+use alloy_primitives::{Address, Bytes};
+use alloy_sol_types::{sol, SolCall};
+use revm::precompile::PrecompileWithAddress;
+use revm::primitives::{Env, Precompile, PrecompileError, PrecompileOutput, PrecompileResult, StatefulPrecompile};
+
+use crate::config::GasConfig;
+use crate::crypto::{PublicKeyData, Signature, SignatureAlgorithm, DILITHIUM5_PK_SIZE};
+use crate::evm_executor::precompile_address;
+
+/// Dilithium5 verifier
+pub const DILITHIUM_VERIFY: Address = precompile_address(0x0200);
+/// SPHINCS+-SHA2-256f verifier
+pub const SPHINCS_VERIFY: Address = precompile_address(0x0201);
+/// Hybrid (ECDSA plus Dilithium5 or SPHINCS+) verifier
+pub const HYBRID_VERIFY: Address = precompile_address(0x0202);
+
+/// ECDSA part of a hybrid signature (r | s | v) and public key (compressed)
+const ECDSA_SIGNATURE_LEN: usize = 65;
+const ECDSA_PUBLIC_KEY_LEN: usize = 33;
+
+/// Gas per 32-byte word of the message, which every scheme hashes (as the
+/// SHA-256 precompile charges)
+const MESSAGE_WORD_GAS: u64 = 12;
+
+sol! {
+    /// Input of every verifier, ABI-encoded with its selector
+    function verify(bytes message, bytes signature, bytes publicKey) external view returns (bool);
+}
+
+/// The verifiers at their addresses, priced by `gas`
+pub fn precompiles(gas: &GasConfig) -> Vec<PrecompileWithAddress> {
+    [
+        (DILITHIUM_VERIFY, SignatureAlgorithm::Dilithium),
+        (SPHINCS_VERIFY, SignatureAlgorithm::SPHINCSPlus),
+        (HYBRID_VERIFY, SignatureAlgorithm::Hybrid),
+    ]
+    .into_iter()
+    .map(|(address, algorithm)| {
+        PrecompileWithAddress(address, Precompile::new_stateful(PqVerifier { algorithm, gas: gas.clone() }))
+    })
+    .collect()
+}
+
+/// Verifier of one signature scheme. Returns `true` or `false` for any
+/// well-formed call; signatures or keys of the wrong size are `false`.
+struct PqVerifier {
+    algorithm: SignatureAlgorithm,
+    gas: GasConfig,
+}
+
+impl PqVerifier {
+    /// Gas of one verification: the scheme's cost plus message hashing.
+    /// A hybrid costs its ECDSA check plus its PQ scheme's.
+    fn gas(&self, call: &verifyCall) -> u64 {
+        let scheme = match self.algorithm {
+            SignatureAlgorithm::Dilithium => self.gas.dilithium_signature,
+            SignatureAlgorithm::SPHINCSPlus => self.gas.sphincs_signature,
+            _ => match pq_algorithm(call.publicKey.get(ECDSA_PUBLIC_KEY_LEN..).unwrap_or_default()) {
+                SignatureAlgorithm::Dilithium => self.gas.ecdsa_signature + self.gas.dilithium_signature,
+                _ => self.gas.ecdsa_signature + self.gas.sphincs_signature,
+            },
+        };
+        scheme + MESSAGE_WORD_GAS * (call.message.len() as u64).div_ceil(32)
+    }
+
+    fn verify(&self, call: &verifyCall) -> bool {
+        let parsed = match self.algorithm {
+            SignatureAlgorithm::Hybrid => hybrid(&call.signature, &call.publicKey),
+            algorithm => Some(pq(algorithm, &call.signature, &call.publicKey)),
+        };
+        parsed.is_some_and(|(signature, public_key)| {
+            signature.verify(&call.message, &public_key).unwrap_or(false)
+        })
+    }
+}
+
+impl StatefulPrecompile for PqVerifier {
+    fn call(&self, input: &Bytes, gas_limit: u64, _env: &Env) -> PrecompileResult {
+        let call = verifyCall::abi_decode(input, true)
+            .map_err(|e| PrecompileError::other(format!("Invalid verify call: {}", e)))?;
+        let gas_used = self.gas(&call);
+        if gas_used > gas_limit {
+            return Err(PrecompileError::OutOfGas.into());
+        }
+        let valid = self.verify(&call);
+        Ok(PrecompileOutput::new(gas_used, verifyCall::abi_encode_returns(&(valid,)).into()))
+    }
+}
+
+/// PQ scheme of a public key, told apart by size
+fn pq_algorithm(public_key: &[u8]) -> SignatureAlgorithm {
+    match public_key.len() {
+        DILITHIUM5_PK_SIZE => SignatureAlgorithm::Dilithium,
+        _ => SignatureAlgorithm::SPHINCSPlus,
+    }
+}
+
+fn pq(algorithm: SignatureAlgorithm, signature: &[u8], public_key: &[u8]) -> (Signature, PublicKeyData) {
+    match algorithm {
+        SignatureAlgorithm::Dilithium => (
+            Signature::Dilithium { data: signature.to_vec() },
+            PublicKeyData::Dilithium { bytes: public_key.to_vec() },
+        ),
+        _ => (
+            Signature::SPHINCSPlus { data: signature.to_vec() },
+            PublicKeyData::SPHINCSPlus { bytes: public_key.to_vec() },
+        ),
+    }
+}
+
+/// Hybrid signature `r | s | v | pq signature` under the key
+/// `compressed ECDSA key | pq key`
+fn hybrid(signature: &[u8], public_key: &[u8]) -> Option<(Signature, PublicKeyData)> {
+    if signature.len() < ECDSA_SIGNATURE_LEN || public_key.len() < ECDSA_PUBLIC_KEY_LEN {
+        return None;
+    }
+    let (ecdsa, pq_signature) = signature.split_at(ECDSA_SIGNATURE_LEN);
+    let (ecdsa_key, pq_key) = public_key.split_at(ECDSA_PUBLIC_KEY_LEN);
+    let (pq_signature, pq_key) = pq(pq_algorithm(pq_key), pq_signature, pq_key);
+    Some((
+        Signature::Hybrid {
+            ecdsa: Box::new(Signature::ECDSA {
+                r: ecdsa[..32].try_into().ok()?,
+                s: ecdsa[32..64].try_into().ok()?,
+                v: ecdsa[64],
+            }),
+            pq: Box::new(pq_signature),
+        },
+        PublicKeyData::Hybrid {
+            ecdsa: Box::new(PublicKeyData::ECDSA { bytes: ecdsa_key.try_into().ok()? }),
+            pq: Box::new(pq_key),
+        },
+    ))
+}
+
+/// Encode a key pair's signature and public key in the verifiers' layout
+#[cfg(test)]
+pub fn encode(signature: &Signature, public_key: &PublicKeyData) -> (Vec<u8>, Vec<u8>) {
+    match (signature, public_key) {
+        (Signature::ECDSA { r, s, v }, PublicKeyData::ECDSA { bytes }) => ([&r[..], &s[..], &[*v]].concat(), bytes.to_vec()),
+        (Signature::Dilithium { data }, PublicKeyData::Dilithium { bytes })
+        | (Signature::SPHINCSPlus { data }, PublicKeyData::SPHINCSPlus { bytes }) => (data.clone(), bytes.clone()),
+        (Signature::Hybrid { ecdsa, pq }, PublicKeyData::Hybrid { ecdsa: ecdsa_key, pq: pq_key }) => {
+            let (ecdsa, ecdsa_key) = encode(ecdsa, ecdsa_key);
+            let (pq, pq_key) = encode(pq, pq_key);
+            ([ecdsa, pq].concat(), [ecdsa_key, pq_key].concat())
+        }
+        _ => panic!("Signature and public key schemes differ"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    fn verify_input(keypair: &KeyPair, message: &[u8], signed: &[u8]) -> Bytes {
+        let (signature, public_key) = encode(&keypair.sign(signed).unwrap(), &keypair.public_key);
+        verifyCall { message: message.to_vec().into(), signature: signature.into(), publicKey: public_key.into() }
+            .abi_encode()
+            .into()
+    }
+
+    fn run(address: Address, input: &Bytes, gas_limit: u64) -> PrecompileResult {
+        let gas = GasConfig::default();
+        let verifier = precompiles(&gas).into_iter().find(|p| p.0 == address).unwrap();
+        verifier.1.call_ref(input, gas_limit, &Env::default())
+    }
+
+    fn returned(result: PrecompileResult) -> bool {
+        verifyCall::abi_decode_returns(&result.unwrap().bytes, true).unwrap()._0
+    }
+
+    #[test]
+    fn test_verifiers() {
+        let message = b"withdraw 100 IONX to 0x42";
+        let gas = GasConfig::default();
+        for (address, algorithm, cost) in [
+            (DILITHIUM_VERIFY, SignatureAlgorithm::Dilithium, gas.dilithium_signature),
+            (SPHINCS_VERIFY, SignatureAlgorithm::SPHINCSPlus, gas.sphincs_signature),
+            (HYBRID_VERIFY, SignatureAlgorithm::Hybrid, gas.ecdsa_signature + gas.dilithium_signature),
+        ] {
+            let keypair = KeyPair::generate(algorithm);
+            let valid = verify_input(&keypair, message, message);
+            let output = run(address, &valid, 1_000_000).unwrap();
+            assert_eq!(output.gas_used, cost + MESSAGE_WORD_GAS);
+            assert!(returned(Ok(output)), "{:?}", algorithm);
+
+            // Signed something else
+            assert!(!returned(run(address, &verify_input(&keypair, message, b"other"), 1_000_000)));
+            assert!(run(address, &valid, cost).unwrap_err().to_string().contains("out of gas"));
+        }
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        // A key of another scheme or a truncated signature is just invalid
+        let wrong_scheme = verify_input(&keypair, b"m", b"m");
+        assert!(!returned(run(SPHINCS_VERIFY, &wrong_scheme, 1_000_000)));
+        assert!(!returned(run(HYBRID_VERIFY, &wrong_scheme, 1_000_000)));
+        let truncated = verifyCall { message: Bytes::from_static(b"m"), signature: Bytes::new(), publicKey: Bytes::new() };
+        assert!(!returned(run(HYBRID_VERIFY, &truncated.abi_encode().into(), 1_000_000)));
+
+        // Input that is not a verify call is an error
+        assert!(run(DILITHIUM_VERIFY, &Bytes::from_static(&[1, 2, 3]), 1_000_000).is_err());
+    }
+}
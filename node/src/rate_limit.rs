@@ -5,49 +5,71 @@ use std::num::NonZeroU32;
 use std::sync::Arc;
 use parking_lot::Mutex;
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::RateLimitConfig;
 
 /// Rate limiter for RPC endpoints
 pub struct RpcRateLimiter {
-    /// Global rate limiter (100 requests/second)
+    /// Global rate limiter (100 requests/second by default)
     global: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
     
-    /// Per-IP rate limiters (10 requests/second per IP)
+    /// Per-IP rate limiters (10 requests/second per IP by default)
     per_ip: Arc<Mutex<HashMap<String, RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>>,
+
+    /// Quota of each per-IP limiter
+    per_ip_quota: Quota,
+
+    /// Tracked IPs before the per-IP table is reset
+    max_tracked_ips: usize,
 }
 
 impl RpcRateLimiter {
     pub fn new() -> Self {
-        let global_quota = Quota::per_second(NonZeroU32::new(100).unwrap());
-        
+        Self::from_config(&RateLimitConfig::default())
+    }
+
+    /// Limiter with the configured global and per-IP quotas
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        let per_second = |n: u32| Quota::per_second(NonZeroU32::new(n).unwrap_or(NonZeroU32::MIN));
+
         Self {
-            global: Arc::new(RateLimiter::direct(global_quota)),
+            global: Arc::new(RateLimiter::direct(per_second(config.global_requests_per_second))),
             per_ip: Arc::new(Mutex::new(HashMap::new())),
+            per_ip_quota: per_second(config.per_ip_requests_per_second),
+            max_tracked_ips: config.max_tracked_ips,
         }
     }
     
     /// Check if request is allowed
     pub fn check_rate_limit(&self, ip: Option<&str>) -> Result<(), String> {
-        // Check global limit
+        // Per-IP first, so a client over its own quota never drains the
+        // global one
+        if let Some(ip_addr) = ip {
+            self.check_ip_rate_limit(ip_addr)?;
+        }
+        self.check_global_rate_limit()
+    }
+
+    /// Charge one request to an IP's own quota
+    pub fn check_ip_rate_limit(&self, ip: &str) -> Result<(), String> {
+        let mut limiters = self.per_ip.lock();
+
+        // Get or create limiter for this IP
+        let limiter = limiters
+            .entry(ip.to_string())
+            .or_insert_with(|| RateLimiter::direct(self.per_ip_quota));
+
+        if limiter.check().is_err() {
+            return Err(format!("Rate limit exceeded for IP: {}", ip));
+        }
+        Ok(())
+    }
+
+    /// Charge one request to the quota shared by every client
+    pub fn check_global_rate_limit(&self) -> Result<(), String> {
         if self.global.check().is_err() {
             return Err("Global rate limit exceeded".to_string());
         }
-        
-        // Check per-IP limit if IP provided
-        if let Some(ip_addr) = ip {
-            let mut limiters = self.per_ip.lock();
-            
-            // Get or create limiter for this IP
-            let limiter = limiters.entry(ip_addr.to_string()).or_insert_with(|| {
-                let quota = Quota::per_second(NonZeroU32::new(10).unwrap());
-                RateLimiter::direct(quota)
-            });
-            
-            if limiter.check().is_err() {
-                return Err(format!("Rate limit exceeded for IP: {}", ip_addr));
-            }
-        }
-        
         Ok(())
     }
     
@@ -55,15 +77,9 @@ impl RpcRateLimiter {
     pub fn cleanup_old_limiters(&self) {
         let mut limiters = self.per_ip.lock();
         
-        // Remove limiters that haven't been used in 5 minutes
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        
         // Note: In production, track last access time
         // For now, just limit total number of tracked IPs
-        if limiters.len() > 10000 {
+        if limiters.len() > self.max_tracked_ips {
             limiters.clear();
         }
     }
@@ -115,4 +131,36 @@ mod tests {
         // But IP2 should still work
         assert!(limiter.check_rate_limit(Some("192.168.1.2")).is_ok());
     }
+
+    #[test]
+    fn test_limits_from_config() {
+        let config = RateLimitConfig {
+            global_requests_per_second: 1000,
+            per_ip_requests_per_second: 3,
+            ..RateLimitConfig::default()
+        };
+        let limiter = RpcRateLimiter::from_config(&config);
+
+        for _ in 0..3 {
+            assert!(limiter.check_rate_limit(Some("10.0.0.1")).is_ok());
+        }
+        assert!(limiter.check_rate_limit(Some("10.0.0.1")).is_err());
+    }
+
+    #[test]
+    fn test_refused_requests_spare_global_quota() {
+        let config = RateLimitConfig {
+            global_requests_per_second: 20,
+            per_ip_requests_per_second: 10,
+            ..RateLimitConfig::default()
+        };
+        let limiter = RpcRateLimiter::from_config(&config);
+
+        // Only the 10 admitted requests count against the global quota
+        let admitted = (0..50).filter(|_| limiter.check_rate_limit(Some("10.0.0.1")).is_ok()).count();
+        assert_eq!(admitted, 10);
+        for _ in 0..10 {
+            assert!(limiter.check_rate_limit(Some("10.0.0.2")).is_ok());
+        }
+    }
 }
@@ -1,19 +1,60 @@
 use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::Duration;
+use parking_lot::Mutex;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{mpsc, oneshot};
+use warp::ws::{Message, WebSocket, Ws};
 use warp::{Filter, Rejection, Reply};
 
-// Import new transaction module with PQ signature support
-use crate::transaction::Transaction as PQTransaction;
-use crate::sequencer::Transaction as SequencerTransaction;
+use crate::config::{GasConfig, RateLimitConfig};
+use crate::eth_api::{self, ApiResult, EthApi, LogFilter};
+use crate::eth_tx;
+use crate::network_security::{self, NetworkSecurity};
+use crate::rate_limit::RpcRateLimiter;
+use crate::transaction::{Transaction, TX_ENCODING_VERSION};
+use crate::sequencer::{ChainEvent, TxSubmission};
+use crate::state_db::StateStore;
+
+/// Largest accepted JSON-RPC batch
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// Most gas the EVM-backed requests of one batch may reserve together
+pub const MAX_BATCH_GAS: u64 = 10 * eth_api::BLOCK_GAS_LIMIT;
+
+/// Most eth_subscribe subscriptions one WebSocket connection may hold
+const MAX_SUBSCRIPTIONS: usize = 100;
+
+/// JSON-RPC error code for rate limits and refused clients (EIP-1474)
+const LIMIT_EXCEEDED: i32 = -32005;
+
+/// How often idle limiter and peer entries are dropped
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// RPC server settings
+#[derive(Debug, Clone)]
+pub struct RpcConfig {
+    /// Address to listen on
+    pub bind: SocketAddr,
+    pub rate_limit: RateLimitConfig,
+    /// Precompile prices for eth_call and tracing
+    pub gas: GasConfig,
+    /// Chain ID of the shard served, checked on submitted transactions
+    pub chain_id: u64,
+}
 
 #[derive(Debug, Deserialize)]
 struct RpcRequest {
     jsonrpc: String,
     method: String,
+    #[serde(default)]
     params: Vec<Value>,
     id: Value,
 }
@@ -30,76 +71,258 @@ struct RpcResponse {
 struct RpcError {
     code: i32,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+/// State shared by the HTTP and WebSocket endpoints
+struct RpcContext {
+    shard_id: u8,
+    tx_sender: mpsc::Sender<TxSubmission>,
+    eth: EthApi,
+    events: broadcast::Sender<ChainEvent>,
+    limiter: RpcRateLimiter,
+    security: Mutex<NetworkSecurity>,
+    max_request_bytes: u64,
+    /// Clients allowed the admin_* methods
+    admin_allow_list: Vec<IpAddr>,
+    /// Proxies whose X-Forwarded-For names the client
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl RpcContext {
+    fn new(
+        config: &RateLimitConfig,
+        gas: GasConfig,
+        shard_id: u8,
+        chain_id: u64,
+        tx_sender: mpsc::Sender<TxSubmission>,
+        state: Arc<dyn StateStore>,
+        events: broadcast::Sender<ChainEvent>,
+    ) -> Self {
+        let mut security = NetworkSecurity::new(config.network_security());
+        for ip in &config.allow_list {
+            security.add_to_whitelist(*ip);
+        }
+        for ip in &config.deny_list {
+            security.add_to_blacklist(*ip);
+        }
+
+        Self {
+            shard_id,
+            tx_sender,
+            eth: EthApi::new(state, chain_id).with_gas_config(gas),
+            events,
+            limiter: RpcRateLimiter::from_config(config),
+            security: Mutex::new(security),
+            max_request_bytes: config.max_request_bytes,
+            admin_allow_list: config.admin_allow_list.clone(),
+            trusted_proxies: config.trusted_proxies.clone(),
+        }
+    }
+
+    /// Charge one request to the client, refusing banned, denied or
+    /// over-quota IPs. Exceeding the per-IP quota costs reputation, so
+    /// persistent offenders end up refused outright; a full global quota
+    /// is nobody's fault in particular and costs none.
+    fn admit(&self, client: Option<IpAddr>) -> Result<(), String> {
+        if let Some(ip) = client {
+            let mut security = self.security.lock();
+            security.check_access(ip).map_err(|e| e.to_string())?;
+            security.allow_request(ip).map_err(|e| e.to_string())?;
+            if let Err(e) = self.limiter.check_ip_rate_limit(&ip.to_string()) {
+                security.update_reputation(ip, -1);
+                return Err(e);
+            }
+        }
+        self.limiter.check_global_rate_limit()
+    }
+}
+
+/// Active eth_subscribe subscription of a WebSocket connection
+enum Subscription {
+    NewHeads,
+    Logs(LogFilter),
+    PendingTransactions,
+    /// Non-standard: this shard's batches as the base layer finalizes them
+    FinalizedBatches,
 }
 
 pub async fn start_rpc_server(
-    port: u16,
+    config: RpcConfig,
     shard_id: u8,
-    tx_sender: mpsc::Sender<Transaction>,
+    tx_sender: mpsc::Sender<TxSubmission>,
+    state: Arc<dyn StateStore>,
+    events: broadcast::Sender<ChainEvent>,
 ) {
-    let tx_sender = Arc::new(tx_sender);
-    let shard_id = Arc::new(shard_id);
+    let ctx = Arc::new(RpcContext::new(&config.rate_limit, config.gas, shard_id, config.chain_id, tx_sender, state, events));
+
+    let cleanup_ctx = ctx.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            cleanup_ctx.limiter.cleanup_old_limiters();
+            cleanup_ctx.security.lock().cleanup();
+        }
+    });
+
+    tracing::info!("RPC server starting on {} (HTTP and WebSocket)", config.bind);
+    warp::serve(routes(ctx)).run(config.bind).await;
+}
 
-    let rpc_route = warp::post()
+/// HTTP POST for requests and batches; WebSocket upgrade on the same path.
+/// Bodies and messages larger than the configured cap are refused.
+fn routes(ctx: Arc<RpcContext>) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let http_route = warp::post()
         .and(warp::path::end())
+        .and(client_ip(ctx.clone()))
+        .and(warp::body::content_length_limit(ctx.max_request_bytes))
         .and(warp::body::json())
-        .and(with_state(tx_sender))
-        .and(with_state(shard_id))
-        .and_then(handle_request);
+        .and(with_state(ctx.clone()))
+        .and_then(|client: Option<IpAddr>, body: Value, ctx: Arc<RpcContext>| async move {
+            let response = handle_body(body, &ctx, client).await;
+            Ok::<_, Rejection>(warp::reply::json(&response))
+        });
+
+    let ws_route = warp::path::end()
+        .and(client_ip(ctx.clone()))
+        .and(warp::ws())
+        .and(with_state(ctx))
+        .map(|client: Option<IpAddr>, ws: Ws, ctx: Arc<RpcContext>| {
+            let max_message_size = usize::try_from(ctx.max_request_bytes).unwrap_or(usize::MAX);
+            ws.max_message_size(max_message_size).on_upgrade(move |socket| handle_socket(socket, ctx, client))
+        });
 
-    tracing::info!("RPC server starting on port {}", port);
-    warp::serve(rpc_route).run(([0, 0, 0, 0], port)).await;
+    http_route.or(ws_route)
 }
 
-fn with_state<T: Clone + Send + Sync>(
+/// Client IP of a request, as named by a trusted proxy if it came through one
+fn client_ip(ctx: Arc<RpcContext>) -> impl Filter<Extract = (Option<IpAddr>,), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .map(move |remote: Option<SocketAddr>, forwarded_for: Option<String>| {
+            network_security::client_ip(remote.map(|addr| addr.ip()), forwarded_for.as_deref(), &ctx.trusted_proxies)
+        })
+}
+
+fn with_state<T: Send + Sync>(
     state: Arc<T>,
 ) -> impl Filter<Extract = (Arc<T>,), Error = Infallible> + Clone {
     warp::any().map(move || state.clone())
 }
 
-async fn handle_request(
-    req: RpcRequest,
-    tx_sender: Arc<mpsc::Sender<Transaction>>,
-    shard_id: Arc<u8>,
-) -> Result<impl Reply, Rejection> {
-    let response = match req.method.as_str() {
+/// Handle a single request object or a JSON-RPC 2.0 batch array.
+/// Each request of a batch counts against the client's rate limit.
+async fn handle_body(body: Value, ctx: &RpcContext, client: Option<IpAddr>) -> Value {
+    match body {
+        Value::Array(requests) => match batch_error(&requests) {
+            Some(error) => json!(error),
+            None => {
+                let responses = futures::future::join_all(
+                    requests.into_iter().map(|request| handle_value(request, ctx, client)),
+                )
+                .await;
+                json!(responses)
+            }
+        },
+        request => json!(handle_value(request, ctx, client).await),
+    }
+}
+
+fn batch_error(requests: &[Value]) -> Option<RpcResponse> {
+    if requests.is_empty() {
+        return Some(error_response(Value::Null, -32600, "Invalid request: empty batch"));
+    }
+    if requests.len() > MAX_BATCH_SIZE {
+        return Some(error_response(
+            Value::Null,
+            -32600,
+            &format!("Batch too large (max {} requests)", MAX_BATCH_SIZE),
+        ));
+    }
+    let gas = requests.iter().fold(0u64, |total, request| {
+        let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+        let params = request.get("params").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+        total.saturating_add(eth_api::reserved_gas(method, params))
+    });
+    if gas > MAX_BATCH_GAS {
+        return Some(error_response(
+            Value::Null,
+            -32600,
+            &format!("Batch needs too much gas (max {})", MAX_BATCH_GAS),
+        ));
+    }
+    None
+}
+
+/// Parse and dispatch one request of a (possibly batched) body
+async fn handle_value(value: Value, ctx: &RpcContext, client: Option<IpAddr>) -> RpcResponse {
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    if let Err(e) = ctx.admit(client) {
+        return error_response(id, LIMIT_EXCEEDED, &e);
+    }
+    match serde_json::from_value::<RpcRequest>(value) {
+        Ok(req) => handle_request(req, ctx, client).await,
+        Err(e) => error_response(id, -32600, &format!("Invalid request: {}", e)),
+    }
+}
+
+async fn handle_request(req: RpcRequest, ctx: &RpcContext, client: Option<IpAddr>) -> RpcResponse {
+    let eth = &ctx.eth;
+    if req.method.starts_with("admin_") {
+        return handle_admin(req, ctx, client);
+    }
+    match req.method.as_str() {
         "web3_clientVersion" => success_response(req.id, "Ionova/v0.1.0"),
-        "eth_chainId" => success_response(req.id, format!("0x{:x}", 31337 + *shard_id as u64)),
+        "net_version" => success_response(req.id, eth.chain_id().to_string()),
+        "eth_chainId" => success_response(req.id, format!("0x{:x}", eth.chain_id())),
+        "eth_blockNumber" => api_response(req.id, eth.block_number()),
+        "eth_getBalance" => api_response(req.id, eth.get_balance(&req.params)),
+        "eth_getTransactionCount" => api_response(req.id, eth.get_transaction_count(&req.params)),
+        "eth_getCode" => api_response(req.id, eth.get_code(&req.params)),
+        "eth_getStorageAt" => api_response(req.id, eth.get_storage_at(&req.params)),
+        "eth_getProof" => api_response(req.id, eth.get_proof(&req.params)),
+        "eth_getBlockByNumber" => api_response(req.id, eth.get_block_by_number(&req.params)),
+        "eth_getBlockByHash" => api_response(req.id, eth.get_block_by_hash(&req.params)),
+        "eth_getTransactionByHash" => api_response(req.id, eth.get_transaction_by_hash(&req.params)),
+        "eth_getTransactionReceipt" => api_response(req.id, eth.get_transaction_receipt(&req.params)),
+        "eth_call" => blocking_response(req.id, eth, req.params, EthApi::call).await,
+        "eth_estimateGas" => blocking_response(req.id, eth, req.params, EthApi::estimate_gas).await,
+        "eth_getLogs" => api_response(req.id, eth.get_logs(&req.params)),
+        "eth_newFilter" => api_response(req.id, eth.new_filter(&req.params)),
+        "eth_newBlockFilter" => api_response(req.id, eth.new_block_filter()),
+        "eth_getFilterChanges" => api_response(req.id, eth.get_filter_changes(&req.params)),
+        "eth_getFilterLogs" => api_response(req.id, eth.get_filter_logs(&req.params)),
+        "eth_uninstallFilter" => api_response(req.id, eth.uninstall_filter(&req.params)),
+        "debug_traceTransaction" => blocking_response(req.id, eth, req.params, EthApi::trace_transaction).await,
+        "debug_traceCall" => blocking_response(req.id, eth, req.params, EthApi::trace_call).await,
+        "eth_subscribe" | "eth_unsubscribe" => {
+            error_response(req.id, -32601, "Subscriptions are only available over WebSocket")
+        }
         "eth_sendRawTransaction" => {
             // Accept PQ signature transactions!
             // Supports: ECDSA, Dilithium, SPHINCS+, Hybrid (4 types)
-            
-            let tx_result = if let Some(tx_obj) = req.params.get(0).and_then(|v| v.as_object()) {
-                serde_json::from_value::<PQTransaction>(serde_json::Value::Object(tx_obj.clone()))
-                    .map_err(|e| format!("Failed to parse transaction: {}", e))
-            } else if let Some(tx_str) = req.params.get(0).and_then(|v| v.as_str()) {
-                serde_json::from_str::<PQTransaction>(tx_str)
-                    .map_err(|e| format!("Failed to parse transaction JSON: {}", e))
-            } else {
-                Err("Invalid transaction format".to_string())
-            };
+            // plus signed Ethereum envelopes (legacy EIP-155, EIP-2930, EIP-1559)
+            let tx_result = parse_raw_transaction(req.params.first(), ctx.shard_id, ctx.eth.chain_id());
 
             match tx_result {
-                Ok(pq_tx) => {
+                Ok(tx) => {
                     // Verify PQ signature before accepting
-                    match pq_tx.verify_signature() {
+                    match tx.verify_signature() {
                         Ok(true) => {
-                            // Convert to sequencer transaction format
-                            // In production, we'd do proper conversion here
                             tracing::info!(
-                                "Accepted {} signature transaction from {:?}",
-                                match pq_tx.signature.algorithm() {
+                                "Accepted {} signature transaction from {}",
+                                match tx.signature.algorithm() {
                                     crate::crypto::SignatureAlgorithm::ECDSA => "ECDSA",
                                     crate::crypto::SignatureAlgorithm::Dilithium => "Dilithium (PQ)",
                                     crate::crypto::SignatureAlgorithm::SPHINCSPlus => "SPHINCS+ (PQ)",
                                     crate::crypto::SignatureAlgorithm::Hybrid => "Hybrid (ECDSA+PQ)",
                                 },
-                                pq_tx.from
+                                tx.from
                             );
-                            
-                            // For devnet, just return success
-                            // In production, convert and send to sequencer
-                            success_response(req.id, "0x1") // Success
+
+                            submit_transaction(req.id, tx, &ctx.tx_sender).await
                         }
                         Ok(false) => {
                             error_response(req.id, -32000, "Invalid signature")
@@ -113,9 +336,281 @@ async fn handle_request(
             }
         }
         _ => error_response(req.id, -32601, "Method not found"),
+    }
+}
+
+/// Ban management, only served to clients on the admin allow-list
+fn handle_admin(req: RpcRequest, ctx: &RpcContext, client: Option<IpAddr>) -> RpcResponse {
+    // A request without a remote address is refused
+    if !client.is_some_and(|ip| ctx.admin_allow_list.contains(&ip)) {
+        return error_response(req.id, -32601, "Method not found");
+    }
+
+    let mut security = ctx.security.lock();
+    match req.method.as_str() {
+        "admin_listBans" => {
+            let bans: Vec<Value> = security
+                .banned_peers()
+                .into_iter()
+                .map(|peer| {
+                    json!({
+                        "ip": peer.ip.to_string(),
+                        "bannedUntil": peer.banned_until,
+                        "reputation": peer.reputation,
+                    })
+                })
+                .collect();
+            success_response(req.id, bans)
+        }
+        "admin_clearBans" => match req.params.first().and_then(Value::as_str) {
+            // A single IP, or every ban when no parameter is given
+            Some(ip) => match ip.parse::<IpAddr>() {
+                Ok(ip) => {
+                    let banned = security.banned_peers().iter().any(|peer| peer.ip == ip);
+                    security.unban_peer(ip);
+                    success_response(req.id, usize::from(banned))
+                }
+                Err(e) => error_response(req.id, -32602, &format!("Invalid IP address: {}", e)),
+            },
+            None => success_response(req.id, security.clear_bans()),
+        },
+        _ => error_response(req.id, -32601, "Method not found"),
+    }
+}
+
+/// Serve one WebSocket connection: regular requests plus eth_subscribe
+/// notifications fed from sequencer events
+async fn handle_socket(socket: WebSocket, ctx: Arc<RpcContext>, client: Option<IpAddr>) {
+    if let Some(ip) = client {
+        if let Err(e) = ctx.security.lock().allow_connection(ip) {
+            tracing::warn!("Refused WebSocket connection from {}: {}", ip, e);
+            return;
+        }
+    }
+
+    serve_socket(socket, &ctx, client).await;
+
+    if let Some(ip) = client {
+        ctx.security.lock().disconnect_peer(ip);
+    }
+}
+
+async fn serve_socket(socket: WebSocket, ctx: &RpcContext, client: Option<IpAddr>) {
+    let (mut sink, mut stream) = socket.split();
+    let mut events = ctx.events.subscribe();
+    let mut subscriptions: HashMap<String, Subscription> = HashMap::new();
+
+    loop {
+        let outgoing: Vec<Value> = tokio::select! {
+            message = stream.next() => {
+                let message = match message {
+                    Some(Ok(message)) if !message.is_close() => message,
+                    _ => break,
+                };
+                // Pings are answered by the protocol layer; binary frames are ignored
+                let Ok(text) = message.to_str() else { continue };
+                let response = match serde_json::from_str::<Value>(text) {
+                    Ok(body) => handle_socket_body(body, ctx, client, &mut subscriptions).await,
+                    Err(_) => json!(error_response(Value::Null, -32700, "Parse error")),
+                };
+                vec![response]
+            }
+            event = events.recv() => match event {
+                Ok(event) => notifications(&event, &subscriptions),
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!("WebSocket subscriber lagging, {} events dropped", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        for message in outgoing {
+            if sink.send(Message::text(message.to_string())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn handle_socket_body(
+    body: Value,
+    ctx: &RpcContext,
+    client: Option<IpAddr>,
+    subscriptions: &mut HashMap<String, Subscription>,
+) -> Value {
+    match body {
+        Value::Array(requests) => match batch_error(&requests) {
+            Some(error) => json!(error),
+            None => {
+                let mut responses = Vec::with_capacity(requests.len());
+                for request in requests {
+                    responses.push(handle_socket_value(request, ctx, client, subscriptions).await);
+                }
+                json!(responses)
+            }
+        },
+        request => json!(handle_socket_value(request, ctx, client, subscriptions).await),
+    }
+}
+
+async fn handle_socket_value(
+    value: Value,
+    ctx: &RpcContext,
+    client: Option<IpAddr>,
+    subscriptions: &mut HashMap<String, Subscription>,
+) -> RpcResponse {
+    match value.get("method").and_then(Value::as_str) {
+        Some("eth_subscribe") | Some("eth_unsubscribe") => {
+            let id = value.get("id").cloned().unwrap_or(Value::Null);
+            if let Err(e) = ctx.admit(client) {
+                return error_response(id, LIMIT_EXCEEDED, &e);
+            }
+            match serde_json::from_value::<RpcRequest>(value) {
+                Ok(req) if req.method == "eth_subscribe" => subscribe(req, subscriptions),
+                Ok(req) => {
+                    let removed = req
+                        .params
+                        .first()
+                        .and_then(Value::as_str)
+                        .is_some_and(|sub_id| subscriptions.remove(sub_id).is_some());
+                    success_response(req.id, removed)
+                }
+                Err(e) => error_response(id, -32600, &format!("Invalid request: {}", e)),
+            }
+        }
+        _ => handle_value(value, ctx, client).await,
+    }
+}
+
+fn subscribe(req: RpcRequest, subscriptions: &mut HashMap<String, Subscription>) -> RpcResponse {
+    if subscriptions.len() >= MAX_SUBSCRIPTIONS {
+        return error_response(req.id, LIMIT_EXCEEDED, "Too many subscriptions on this connection");
+    }
+    let subscription = match req.params.first().and_then(Value::as_str) {
+        Some("newHeads") => Subscription::NewHeads,
+        Some("newPendingTransactions") => Subscription::PendingTransactions,
+        Some("finalizedBatches") => Subscription::FinalizedBatches,
+        Some("logs") => match LogFilter::parse(req.params.get(1)) {
+            Ok(filter) => Subscription::Logs(filter),
+            Err(e) => return error_response(req.id, e.code, &e.message),
+        },
+        Some(kind) => return error_response(req.id, -32602, &format!("Unsupported subscription: {}", kind)),
+        None => return error_response(req.id, -32602, "Missing subscription type"),
     };
 
-    Ok(warp::reply::json(&response))
+    let sub_id = format!("0x{}", hex::encode(rand::thread_rng().gen::<[u8; 16]>()));
+    subscriptions.insert(sub_id.clone(), subscription);
+    success_response(req.id, sub_id)
+}
+
+/// eth_subscription notifications an event produces for a connection
+fn notifications(event: &ChainEvent, subscriptions: &HashMap<String, Subscription>) -> Vec<Value> {
+    let mut out = Vec::new();
+    for (sub_id, subscription) in subscriptions {
+        let results = match (event, subscription) {
+            (ChainEvent::MicroBlock(block), Subscription::NewHeads) => vec![eth_api::header_json(block)],
+            (ChainEvent::MicroBlock(block), Subscription::Logs(filter)) => {
+                eth_api::block_logs(block, filter)
+            }
+            (ChainEvent::PendingTransaction(hash), Subscription::PendingTransactions) => {
+                vec![json!(eth_api::data(hash))]
+            }
+            (ChainEvent::BatchFinalized { batch_sequence, base_height }, Subscription::FinalizedBatches) => {
+                vec![json!({
+                    "batchSequence": eth_api::quantity(*batch_sequence),
+                    "baseHeight": eth_api::quantity(*base_height),
+                })]
+            }
+            _ => Vec::new(),
+        };
+        out.extend(results.into_iter().map(|result| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": { "subscription": sub_id, "result": result },
+            })
+        }));
+    }
+    out
+}
+
+/// Convert RPC input into the canonical transaction. Raw transactions are
+/// 0x-prefixed hex of either the canonical encoding or a signed Ethereum
+/// envelope; a JSON object is also accepted. The chain ID must match this shard.
+fn parse_raw_transaction(param: Option<&Value>, shard_id: u8, chain_id: u64) -> Result<Transaction, String> {
+    let tx = match param {
+        Some(Value::String(raw)) => {
+            let bytes = hex::decode(raw.strip_prefix("0x").unwrap_or(raw))
+                .map_err(|e| format!("Invalid hex: {}", e))?;
+            if bytes.first() == Some(&TX_ENCODING_VERSION) {
+                Transaction::decode(&bytes)
+                    .map_err(|e| format!("Failed to decode transaction: {}", e))?
+            } else {
+                return eth_tx::decode_transaction(&bytes, chain_id, shard_id)
+                    .map_err(|e| format!("Failed to decode Ethereum transaction: {}", e));
+            }
+        }
+        Some(obj @ Value::Object(_)) => serde_json::from_value::<Transaction>(obj.clone())
+            .map_err(|e| format!("Failed to parse transaction: {}", e))?,
+        _ => return Err("Invalid transaction format".to_string()),
+    };
+
+    if tx.chain_id != chain_id || tx.shard_id != shard_id {
+        return Err(format!(
+            "Wrong chain ID: expected {}, got {}",
+            chain_id, tx.chain_id
+        ));
+    }
+    Ok(tx)
+}
+
+/// Hand a verified transaction to the sequencer and wait for mempool admission
+async fn submit_transaction(
+    id: Value,
+    tx: Transaction,
+    tx_sender: &mpsc::Sender<TxSubmission>,
+) -> RpcResponse {
+    let (responder, response) = oneshot::channel();
+    if tx_sender.send(TxSubmission { tx, responder }).await.is_err() {
+        return error_response(id, -32603, "Sequencer unavailable");
+    }
+
+    match response.await {
+        Ok(Ok(tx_hash)) => success_response(id, tx_hash),
+        Ok(Err(e)) => error_response(id, -32000, &format!("Transaction rejected: {}", e)),
+        Err(_) => error_response(id, -32603, "Sequencer dropped the transaction"),
+    }
+}
+
+/// Run an EVM-backed method on the blocking pool, off the async runtime
+async fn blocking_response(
+    id: Value,
+    eth: &EthApi,
+    params: Vec<Value>,
+    method: fn(&EthApi, &[Value]) -> ApiResult,
+) -> RpcResponse {
+    let eth = eth.clone();
+    match tokio::task::spawn_blocking(move || method(&eth, &params)).await {
+        Ok(result) => api_response(id, result),
+        Err(e) => error_response(id, -32603, &format!("Internal error: {}", e)),
+    }
+}
+
+fn api_response(id: Value, result: ApiResult) -> RpcResponse {
+    match result {
+        Ok(value) => success_response(id, value),
+        Err(e) => RpcResponse {
+            jsonrpc: "2.0".to_string(),
+            result: None,
+            error: Some(RpcError {
+                code: e.code,
+                message: e.message,
+                data: e.data,
+            }),
+            id,
+        },
+    }
 }
 
 fn success_response<T: Serialize>(id: Value, result: T) -> RpcResponse {
@@ -134,7 +629,285 @@ fn error_response(id: Value, code: i32, message: &str) -> RpcResponse {
         error: Some(RpcError {
             code,
             message: message.to_string(),
+            data: None,
         }),
         id,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_rlp::Decodable;
+    use crate::crypto::Address;
+    use crate::genesis::Account;
+    use crate::sequencer::MicroBlock;
+    use crate::state_db::{ChainHead, InMemoryStateStore, StateChanges};
+    use crate::state_trie;
+    use crate::transaction::BASE_CHAIN_ID;
+
+    fn test_context() -> Arc<RpcContext> {
+        context_with_limits(&RateLimitConfig::default())
+    }
+
+    fn context_with_limits(config: &RateLimitConfig) -> Arc<RpcContext> {
+        let state: Arc<dyn StateStore> = Arc::new(InMemoryStateStore::new());
+        let mut changes = StateChanges::new();
+        changes.set_head(ChainHead::default());
+        state.commit(changes).unwrap();
+
+        let (tx_sender, _) = mpsc::channel(1);
+        Arc::new(RpcContext::new(config, GasConfig::default(), 0, BASE_CHAIN_ID, tx_sender, state, broadcast::channel(16).0))
+    }
+
+    #[tokio::test]
+    async fn test_batch_requests() {
+        let ctx = test_context();
+        let batch = json!([
+            { "jsonrpc": "2.0", "method": "web3_clientVersion", "params": [], "id": 1 },
+            { "jsonrpc": "2.0", "id": 2 },
+            { "jsonrpc": "2.0", "method": "eth_chainId", "id": "three" },
+        ]);
+
+        let responses = handle_body(batch, &ctx, None).await;
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["result"], json!("Ionova/v0.1.0"));
+        assert_eq!(responses[1]["error"]["code"], json!(-32600));
+        assert_eq!(responses[1]["id"], json!(2));
+        assert_eq!(responses[2]["result"], json!("0x7a69"));
+        assert_eq!(responses[2]["id"], json!("three"));
+
+        let empty = handle_body(json!([]), &ctx, None).await;
+        assert_eq!(empty["error"]["code"], json!(-32600));
+
+        // EVM-backed requests run on the blocking pool, within the batch gas budget
+        let call = |gas: &str| {
+            let params = json!([{ "to": "0x00000000000000000000000000000000000000aa", "gas": gas }]);
+            json!({ "jsonrpc": "2.0", "method": "eth_call", "params": params, "id": 1 })
+        };
+        let calls = handle_body(json!(vec![call("0x5208"); 11]), &ctx, None).await;
+        assert_eq!(calls[10]["result"], json!("0x"));
+        let heavy = handle_body(json!(vec![call("0x1c9c380"); 11]), &ctx, None).await;
+        assert_eq!(heavy["error"]["code"], json!(-32600));
+        let mut trace = call("0x5208");
+        trace["method"] = json!("debug_traceCall");
+        assert_eq!(handle_body(trace, &ctx, None).await["result"]["failed"], json!(false));
+
+        let single = handle_body(json!({ "jsonrpc": "2.0", "method": "net_version", "id": 7 }), &ctx, None).await;
+        assert_eq!(single["result"], json!("31337"));
+    }
+
+    #[tokio::test]
+    async fn test_get_proof() {
+        let contract = Address::EVM([0xcc; 20]);
+        let state: Arc<dyn StateStore> = Arc::new(InMemoryStateStore::new());
+        let mut changes = StateChanges::new();
+        changes.set_account(contract, Account::new(5));
+        changes.set_storage(contract, [0u8; 32], [0x2a; 32]);
+        let root = state_trie::apply_changes(state.as_ref(), state_trie::EMPTY_ROOT, &mut changes).unwrap();
+        changes.set_head(ChainHead { state_root: hex::encode(root), ..ChainHead::default() });
+        state.commit(changes).unwrap();
+        let (tx_sender, _) = mpsc::channel(1);
+        let ctx = RpcContext::new(
+            &RateLimitConfig::default(), GasConfig::default(), 0, BASE_CHAIN_ID, tx_sender, state.clone(), broadcast::channel(16).0,
+        );
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "eth_getProof",
+            "params": [contract.to_string(), ["0x0", "0x1"], "latest"],
+            "id": 1,
+        });
+        let result = handle_body(request, &ctx, None).await["result"].clone();
+        assert_eq!(result["balance"], json!(eth_api::quantity(Account::new(5).balance)));
+        let nodes = |proof: &Value| -> Vec<Vec<u8>> {
+            proof.as_array().unwrap().iter().map(|node| hex::decode(&node.as_str().unwrap()[2..]).unwrap()).collect()
+        };
+
+        // The account proof checks against the state root
+        let key = state_trie::account_key(&contract);
+        let leaf = state_trie::verify_proof(&root, &key, &nodes(&result["accountProof"])).unwrap().unwrap();
+        let leaf = state_trie::AccountLeaf::decode(&mut leaf.as_slice()).unwrap();
+        assert_eq!(leaf.storage_root, state_trie::parse_root(result["storageHash"].as_str().unwrap()).unwrap());
+        assert_eq!(Some(leaf), state_trie::get_account(state.as_ref(), root, &contract).unwrap());
+
+        // Slot 0 is proven present and slot 1 absent under the storage hash
+        let storage_root = state_trie::parse_root(result["storageHash"].as_str().unwrap()).unwrap();
+        let slots = result["storageProof"].as_array().unwrap();
+        assert_eq!(slots[0]["key"], json!("0x0"));
+        assert_eq!(slots[0]["value"], json!(format!("0x{}", "2a".repeat(32))));
+        let key = state_trie::storage_key(&[0u8; 32]);
+        let proven = state_trie::verify_proof(&storage_root, &key, &nodes(&slots[0]["proof"])).unwrap();
+        assert_eq!(proven, Some(vec![0x2a; 32]));
+        assert_eq!(slots[1]["value"], json!("0x0"));
+        let mut slot = [0u8; 32];
+        slot[31] = 1;
+        let key = state_trie::storage_key(&slot);
+        assert_eq!(state_trie::verify_proof(&storage_root, &key, &nodes(&slots[1]["proof"])).unwrap(), None);
+    }
+
+    async fn recv_json(client: &mut warp::test::WsClient) -> Value {
+        let message = client.recv().await.unwrap();
+        serde_json::from_str(message.to_str().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_websocket_subscriptions() {
+        let ctx = context_with_limits(&RateLimitConfig {
+            global_requests_per_second: 1_000,
+            per_ip_requests_per_second: 1_000,
+            ..RateLimitConfig::default()
+        });
+        let events = ctx.events.clone();
+        let mut client = warp::test::ws().handshake(routes(ctx)).await.unwrap();
+
+        client.send_text(json!({ "jsonrpc": "2.0", "method": "eth_subscribe", "params": ["newHeads"], "id": 1 }).to_string()).await;
+        let heads = recv_json(&mut client).await["result"].clone();
+
+        client.send_text(json!({ "jsonrpc": "2.0", "method": "eth_subscribe", "params": ["newPendingTransactions"], "id": 2 }).to_string()).await;
+        let pending = recv_json(&mut client).await["result"].clone();
+        assert!(heads.is_string() && pending.is_string() && heads != pending);
+
+        // Regular requests work over the same socket
+        client.send_text(json!({ "jsonrpc": "2.0", "method": "eth_blockNumber", "id": 3 }).to_string()).await;
+        assert_eq!(recv_json(&mut client).await["result"], json!("0x0"));
+
+        let block = MicroBlock {
+            shard_id: 0,
+            sequence: 0,
+            parent_hash: [0u8; 32],
+            timestamp: 1_700_000_000,
+            transactions: vec![],
+            receipts: vec![],
+            state_root: hex::encode(crate::state_trie::EMPTY_ROOT),
+            sent_receipts: vec![],
+            claimed_receipts: vec![],
+        };
+        events.send(ChainEvent::MicroBlock(Arc::new(block))).unwrap();
+        let note = recv_json(&mut client).await;
+        assert_eq!(note["method"], json!("eth_subscription"));
+        assert_eq!(note["params"]["subscription"], heads);
+        assert_eq!(note["params"]["result"]["number"], json!("0x0"));
+
+        events.send(ChainEvent::PendingTransaction([9u8; 32])).unwrap();
+        let note = recv_json(&mut client).await;
+        assert_eq!(note["params"]["subscription"], pending);
+        assert_eq!(note["params"]["result"], json!(eth_api::data(&[9u8; 32])));
+
+        client.send_text(json!({ "jsonrpc": "2.0", "method": "eth_subscribe", "params": ["finalizedBatches"], "id": 6 }).to_string()).await;
+        let batches = recv_json(&mut client).await["result"].clone();
+        events.send(ChainEvent::BatchFinalized { batch_sequence: 2, base_height: 7 }).unwrap();
+        let note = recv_json(&mut client).await;
+        assert_eq!(note["params"]["subscription"], batches);
+        assert_eq!(note["params"]["result"], json!({ "batchSequence": "0x2", "baseHeight": "0x7" }));
+
+        client.send_text(json!({ "jsonrpc": "2.0", "method": "eth_unsubscribe", "params": [heads], "id": 4 }).to_string()).await;
+        assert_eq!(recv_json(&mut client).await["result"], json!(true));
+
+        // Each connection holds a bounded number of subscriptions
+        for id in 2..MAX_SUBSCRIPTIONS {
+            client.send_text(json!({ "jsonrpc": "2.0", "method": "eth_subscribe", "params": ["newHeads"], "id": id }).to_string()).await;
+            assert!(recv_json(&mut client).await["result"].is_string());
+        }
+        client.send_text(json!({ "jsonrpc": "2.0", "method": "eth_subscribe", "params": ["newHeads"], "id": 5 }).to_string()).await;
+        assert_eq!(recv_json(&mut client).await["error"]["code"], json!(LIMIT_EXCEEDED));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_and_bans() {
+        let ctx = context_with_limits(&RateLimitConfig {
+            per_ip_requests_per_second: 2,
+            admin_allow_list: vec![IpAddr::from([10, 0, 0, 1])],
+            ..RateLimitConfig::default()
+        });
+        let client = Some("203.0.113.5".parse().unwrap());
+        let request = || json!({ "jsonrpc": "2.0", "method": "eth_chainId", "id": 1 });
+
+        assert!(handle_body(request(), &ctx, client).await.get("result").is_some());
+        assert!(handle_body(request(), &ctx, client).await.get("result").is_some());
+        let limited = handle_body(request(), &ctx, client).await;
+        assert_eq!(limited["error"]["code"], json!(LIMIT_EXCEEDED));
+
+        // Every request of a batch is charged
+        let batch = handle_body(json!([request(), request()]), &ctx, client).await;
+        assert_eq!(batch[0]["error"]["code"], json!(LIMIT_EXCEEDED));
+
+        // Other clients keep their own quota
+        let other = Some("203.0.113.6".parse().unwrap());
+        assert!(handle_body(request(), &ctx, other).await.get("result").is_some());
+
+        // Admin methods are for allow-listed clients only, not even loopback
+        let list = json!({ "jsonrpc": "2.0", "method": "admin_listBans", "id": 2 });
+        assert_eq!(handle_body(list.clone(), &ctx, other).await["error"]["code"], json!(-32601));
+        assert_eq!(handle_body(list.clone(), &ctx, None).await["error"]["code"], json!(-32601));
+        let loopback = Some(IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(handle_body(list.clone(), &ctx, loopback).await["error"]["code"], json!(-32601));
+
+        let banned: IpAddr = "198.51.100.1".parse().unwrap();
+        ctx.security.lock().ban_peer(banned, "test");
+        let refused = handle_body(request(), &ctx, Some(banned)).await;
+        assert_eq!(refused["error"]["code"], json!(LIMIT_EXCEEDED));
+
+        let admin = Some(IpAddr::from([10, 0, 0, 1]));
+        let bans = handle_body(list, &ctx, admin).await;
+        assert_eq!(bans["result"][0]["ip"], json!("198.51.100.1"));
+
+        let clear = json!({ "jsonrpc": "2.0", "method": "admin_clearBans", "id": 3 });
+        assert_eq!(handle_body(clear, &ctx, admin).await["result"], json!(1));
+        assert!(handle_body(request(), &ctx, Some(banned)).await.get("result").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_limits_clients_behind_trusted_proxy() {
+        let proxy = SocketAddr::from(([127, 0, 0, 1], 40000));
+        let ctx = context_with_limits(&RateLimitConfig {
+            per_ip_requests_per_second: 1,
+            admin_allow_list: vec![IpAddr::from([10, 0, 0, 1])],
+            trusted_proxies: vec![proxy.ip()],
+            ..RateLimitConfig::default()
+        });
+        let routes = routes(ctx);
+        let call = |method: &str, forwarded_for: &str| {
+            warp::test::request()
+                .method("POST")
+                .remote_addr(proxy)
+                .header("x-forwarded-for", forwarded_for)
+                .json(&json!({ "jsonrpc": "2.0", "method": method, "id": 1 }))
+        };
+        let body = |response: warp::http::Response<warp::hyper::body::Bytes>| {
+            serde_json::from_slice::<Value>(response.body()).unwrap()
+        };
+
+        // Each client behind the proxy has its own quota
+        let first = body(call("eth_chainId", "203.0.113.5").reply(&routes).await);
+        assert!(first.get("result").is_some());
+        let limited = body(call("eth_chainId", "203.0.113.5").reply(&routes).await);
+        assert_eq!(limited["error"]["code"], json!(LIMIT_EXCEEDED));
+        let other = body(call("eth_chainId", "203.0.113.6").reply(&routes).await);
+        assert!(other.get("result").is_some());
+
+        // Proxied clients are not taken for the proxy host
+        let admin = body(call("admin_listBans", "198.51.100.1").reply(&routes).await);
+        assert_eq!(admin["error"]["code"], json!(-32601));
+        let admin = body(call("admin_listBans", "10.0.0.1").reply(&routes).await);
+        assert!(admin["result"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_global_limit_costs_no_reputation() {
+        let ctx = context_with_limits(&RateLimitConfig {
+            global_requests_per_second: 2,
+            ..RateLimitConfig::default()
+        });
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        let request = json!({ "jsonrpc": "2.0", "method": "eth_chainId", "id": 1 });
+        let reputation = ctx.security.lock().get_reputation(&client);
+
+        handle_body(request.clone(), &ctx, Some("203.0.113.8".parse().unwrap())).await;
+        handle_body(request.clone(), &ctx, Some("203.0.113.9".parse().unwrap())).await;
+        let limited = handle_body(request, &ctx, Some(client)).await;
+        assert_eq!(limited["error"]["code"], json!(LIMIT_EXCEEDED));
+        assert_eq!(ctx.security.lock().get_reputation(&client), reputation);
+    }
+}
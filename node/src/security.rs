@@ -3,6 +3,9 @@ use sha2::{Sha256, Digest};
 use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::crypto::Address;
+use crate::transaction::Transaction;
+
 /// Security module for Ionova blockchain
 /// Provides transaction validation, signature verification, and attack prevention
 
@@ -51,8 +54,8 @@ impl Default for SecurityConfig {
 #[derive(Debug, Clone)]
 pub struct SecurityValidator {
     config: SecurityConfig,
-    /// Track nonces for replay protection
-    nonce_tracker: HashMap<String, u64>,
+    /// Track next expected nonce per sender for replay protection
+    nonce_tracker: HashMap<Address, u64>,
     /// Track transaction hashes to prevent duplicates
     tx_hash_tracker: HashMap<String, u64>,
     /// Rate limiting: address -> (count, timestamp)
@@ -87,34 +90,21 @@ impl SecurityValidator {
         
         // 5. Rate limiting
         if self.config.enable_ddos_protection {
-            self.check_rate_limit(&tx.from)?;
+            self.check_rate_limit(&tx.from.to_string())?;
         }
         
-        // 6. Validate transaction hash
-        self.validate_tx_hash(tx)?;
-        
         Ok(())
     }
 
     /// Validate cryptographic signature
     fn validate_signature(&self, tx: &Transaction) -> Result<(), SecurityError> {
-        // Verify ECDSA signature
-        let message = self.create_signing_message(tx);
-        let message_hash = self.hash_message(&message);
-        
-        // In production, use proper ECDSA verification
-        // For now, basic validation
-        if tx.signature.is_empty() {
-            return Err(SecurityError::InvalidSignature("Empty signature".to_string()));
-        }
-        
-        if tx.signature.len() != 65 {
-            return Err(SecurityError::InvalidSignature(
-                "Invalid signature length".to_string()
-            ));
+        match tx.verify_signature() {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(SecurityError::InvalidSignature(
+                "Signature does not match sender".to_string()
+            )),
+            Err(e) => Err(SecurityError::InvalidSignature(e.to_string())),
         }
-        
-        Ok(())
     }
 
     /// Check for replay attacks
@@ -149,7 +139,7 @@ impl SecurityValidator {
     fn validate_nonce(&mut self, tx: &Transaction) -> Result<(), SecurityError> {
         let current_nonce = self.nonce_tracker.get(&tx.from).copied().unwrap_or(0);
         
-        // Nonce must be exactly current_nonce + 1 or within acceptable gap
+        // Nonce must be at least the next expected nonce and within acceptable gap
         if tx.nonce < current_nonce {
             return Err(SecurityError::InvalidNonce(format!(
                 "Nonce too low: {} < {}",
@@ -164,19 +154,22 @@ impl SecurityValidator {
             )));
         }
         
-        // Update nonce
-        self.nonce_tracker.insert(tx.from.clone(), tx.nonce);
+        // Next expected nonce
+        self.nonce_tracker.insert(tx.from, tx.nonce + 1);
         
         Ok(())
     }
 
     /// Validate gas parameters
     fn validate_gas(&self, tx: &Transaction) -> Result<(), SecurityError> {
-        // Check minimum gas price
-        if tx.gas_price < self.config.min_gas_price {
+        // Check minimum gas price (in wei)
+        let gas_price = tx
+            .gas_price_wei()
+            .map_err(|e| SecurityError::InsufficientGasPrice(e.to_string()))?;
+        if gas_price < self.config.min_gas_price {
             return Err(SecurityError::InsufficientGasPrice(format!(
                 "Gas price {} < minimum {}",
-                tx.gas_price, self.config.min_gas_price
+                gas_price, self.config.min_gas_price
             )));
         }
         
@@ -223,44 +216,9 @@ impl SecurityValidator {
         Ok(())
     }
 
-    /// Validate transaction hash
-    fn validate_tx_hash(&self, tx: &Transaction) -> Result<(), SecurityError> {
-        let calculated_hash = self.calculate_tx_hash(tx);
-        
-        if tx.hash != calculated_hash {
-            return Err(SecurityError::InvalidHash(
-                "Transaction hash mismatch".to_string()
-            ));
-        }
-        
-        Ok(())
-    }
-
-    /// Calculate transaction hash
+    /// Calculate transaction hash (hash of the canonical encoding)
     fn calculate_tx_hash(&self, tx: &Transaction) -> String {
-        let data = format!(
-            "{}{}{}{}{}{}",
-            tx.from, tx.to, tx.value, tx.nonce, tx.gas_price, tx.gas_limit
-        );
-        
-        let mut hasher = Sha256::new();
-        hasher.update(data.as_bytes());
-        format!("0x{:x}", hasher.finalize())
-    }
-
-    /// Create signing message
-    fn create_signing_message(&self, tx: &Transaction) -> String {
-        format!(
-            "Ionova Transaction\nFrom: {}\nTo: {}\nValue: {}\nNonce: {}\nGas: {}",
-            tx.from, tx.to, tx.value, tx.nonce, tx.gas_limit
-        )
-    }
-
-    /// Hash message for signing
-    fn hash_message(&self, message: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(message.as_bytes());
-        format!("0x{:x}", hasher.finalize())
+        format!("0x{}", hex::encode(tx.hash()))
     }
 
     /// Validate block security
@@ -306,7 +264,7 @@ impl SecurityValidator {
     fn calculate_block_hash(&self, block: &Block) -> String {
         let tx_hashes: Vec<String> = block.transactions
             .iter()
-            .map(|tx| tx.hash.clone())
+            .map(|tx| self.calculate_tx_hash(tx))
             .collect();
         
         let data = format!(
@@ -322,10 +280,9 @@ impl SecurityValidator {
         format!("0x{:x}", hasher.finalize())
     }
 
-    /// Estimate block size
+    /// Estimate block size from the canonical transaction encodings
     fn estimate_block_size(&self, block: &Block) -> usize {
-        // Rough estimate: 200 bytes per transaction + overhead
-        block.transactions.len() * 200 + 1000
+        block.transactions.iter().map(|tx| tx.encode().len()).sum::<usize>() + 1000
     }
 
     /// Clean up old tracking data
@@ -347,19 +304,6 @@ impl SecurityValidator {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Transaction {
-    pub hash: String,
-    pub from: String,
-    pub to: String,
-    pub value: u64,
-    pub nonce: u64,
-    pub gas_price: u64,
-    pub gas_limit: u64,
-    pub signature: Vec<u8>,
-    pub data: Vec<u8>,
-}
-
 #[derive(Debug, Clone)]
 pub struct Block {
     pub height: u64,
@@ -405,58 +349,52 @@ impl std::error::Error for SecurityError {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::{KeyPair, SignatureAlgorithm};
+    use crate::transaction::TransactionBuilder;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn signed_tx(keypair: &KeyPair, nonce: u64, gas_price: Decimal) -> Transaction {
+        TransactionBuilder::new()
+            .nonce(nonce)
+            .to(Address::EVM([0xde; 20]))
+            .value(dec!(1000)).unwrap()
+            .gas_price(gas_price)
+            .sign(keypair)
+            .unwrap()
+    }
 
     #[test]
     fn test_validate_transaction() {
         let config = SecurityConfig::default();
         let mut validator = SecurityValidator::new(config);
-
-        let tx = Transaction {
-            hash: "0x123".to_string(),
-            from: "0xabc".to_string(),
-            to: "0xdef".to_string(),
-            value: 1000,
-            nonce: 1,
-            gas_price: 1_000_000,
-            gas_limit: 21000,
-            signature: vec![0u8; 65],
-            data: vec![],
-        };
-
-        // First transaction should pass (after fixing hash)
-        let calculated_hash = validator.calculate_tx_hash(&tx);
-        let mut tx_with_hash = tx.clone();
-        tx_with_hash.hash = calculated_hash;
-
-        assert!(validator.validate_transaction(&tx_with_hash).is_ok());
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+
+        let tx = signed_tx(&keypair, 1, dec!(0.000001));
+        assert!(validator.validate_transaction(&tx).is_ok());
+
+        // Forged signature is rejected
+        let mut forged = signed_tx(&keypair, 2, dec!(0.000001));
+        forged.value = dec!(5000);
+        assert!(matches!(
+            validator.validate_transaction(&forged),
+            Err(SecurityError::InvalidSignature(_))
+        ));
     }
 
     #[test]
     fn test_replay_attack_prevention() {
         let config = SecurityConfig::default();
         let mut validator = SecurityValidator::new(config);
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
 
-        let tx = Transaction {
-            hash: "0x123".to_string(),
-            from: "0xabc".to_string(),
-            to: "0xdef".to_string(),
-            value: 1000,
-            nonce: 1,
-            gas_price: 1_000_000,
-            gas_limit: 21000,
-            signature: vec![0u8; 65],
-            data: vec![],
-        };
-
-        let calculated_hash = validator.calculate_tx_hash(&tx);
-        let mut tx_with_hash = tx.clone();
-        tx_with_hash.hash = calculated_hash;
+        let tx = signed_tx(&keypair, 1, dec!(0.000001));
 
         // First time should succeed
-        assert!(validator.validate_transaction(&tx_with_hash).is_ok());
+        assert!(validator.validate_transaction(&tx).is_ok());
 
         // Second time should fail (replay attack)
-        let result = validator.validate_transaction(&tx_with_hash);
+        let result = validator.validate_transaction(&tx);
         assert!(result.is_err());
     }
 
@@ -479,18 +417,9 @@ mod tests {
     fn test_nonce_validation() {
         let config = SecurityConfig::default();
         let mut validator = SecurityValidator::new(config);
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
 
-        let mut tx = Transaction {
-            hash: "0x123".to_string(),
-            from: "0xabc".to_string(),
-            to: "0xdef".to_string(),
-            value: 1000,
-            nonce: 1,
-            gas_price: 1_000_000,
-            gas_limit: 21000,
-            signature: vec![0u8; 65],
-            data: vec![],
-        };
+        let mut tx = signed_tx(&keypair, 1, dec!(0.000001));
 
         // Nonce 1 should work
         assert!(validator.validate_nonce(&tx).is_ok());
@@ -508,28 +437,19 @@ mod tests {
     fn test_gas_validation() {
         let config = SecurityConfig::default();
         let validator = SecurityValidator::new(config);
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
 
-        let mut tx = Transaction {
-            hash: "0x123".to_string(),
-            from: "0xabc".to_string(),
-            to: "0xdef".to_string(),
-            value: 1000,
-            nonce: 1,
-            gas_price: 1_000_000,
-            gas_limit: 21000,
-            signature: vec![0u8; 65],
-            data: vec![],
-        };
+        let mut tx = signed_tx(&keypair, 1, dec!(0.000001));
 
         // Valid gas should pass
         assert!(validator.validate_gas(&tx).is_ok());
 
-        // Too low gas price should fail
-        tx.gas_price = 100;
+        // Too low gas price should fail (100 wei)
+        tx.gas_price = dec!(0.0000000000000001);
         assert!(validator.validate_gas(&tx).is_err());
 
         // Too high gas limit should fail
-        tx.gas_price = 1_000_000;
+        tx.gas_price = dec!(0.000001);
         tx.gas_limit = 100_000_000;
         assert!(validator.validate_gas(&tx).is_err());
     }
@@ -1,35 +1,37 @@
-use anyhow::Result;
-use rust_decimal::Decimal;
+use alloy_primitives::{Bloom, BloomInput, Bytes, B256, U256};
+use anyhow::{anyhow, bail, Result};
+use revm::primitives::{AccountInfo, Bytecode, EvmState, KECCAK_EMPTY};
+use revm::DatabaseRef;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::mpsc;
-use tracing::{info, warn};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
 
-use crate::fee_model::{FeeConfig, TransactionFee};
-use crate::mempool::{Mempool, MempoolConfig};
+use crate::config::GasConfig;
+use crate::cross_shard::{
+    self, CrossShardLedger, CrossShardReceipt, ReceiptProof, ReceiptStatus, Settlement, CROSS_SHARD_ROUTER,
+};
+use crate::crypto::{keccak256, Address, KeyPair, PublicKeyData, Signature};
+use crate::eth_tx;
+use crate::evm_executor::{BlockContext, EvmExecutor, EvmTransaction};
+use crate::fee_model::{self, FeeConfig, TransactionFee, TREASURY, VALIDATOR_FEE_POOL};
+use crate::finality::FinalizedBlock;
+use crate::genesis::{Account, GenesisState};
+use crate::mempool::{self, Mempool, MempoolConfig};
+use crate::state_db::{ChainHead, StateChanges, StateStore};
+use crate::staking::{StakingAction, STAKING_ACCOUNT};
+use crate::state_trie::{self, AccountLeaf, EMPTY_CODE_HASH};
+use crate::transaction::{self, Transaction};
 
-/// Transaction data structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Transaction {
-    pub from: String,
-    pub to: String,
-    pub amount: Decimal,
-    pub gas_limit: u64,
-    pub gas_used: u64,
-    pub tip: Decimal,
-    pub nonce: u64,
-    pub shard_id: u8,
-    pub timestamp: u64,
-    pub signature: String,
-}
-
-impl Transaction {
-    pub fn hash(&self) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(serde_json::to_string(self).unwrap().as_bytes());
-        hex::encode(hasher.finalize())
-    }
+/// Signed transaction submitted to the sequencer, answered with its hash
+/// once admitted to the mempool
+#[derive(Debug)]
+pub struct TxSubmission {
+    pub tx: Transaction,
+    pub responder: oneshot::Sender<Result<String>>,
 }
 
 /// Micro-block produced by sequencer
@@ -37,12 +39,136 @@ impl Transaction {
 pub struct MicroBlock {
     pub shard_id: u8,
     pub sequence: u64,
+    /// Hash of the previous micro-block (zero for the first one)
+    #[serde(default)]
+    pub parent_hash: [u8; 32],
     pub timestamp: u64,
     pub transactions: Vec<Transaction>,
+    /// One receipt per transaction, in the same order
+    #[serde(default)]
+    pub receipts: Vec<TxReceipt>,
     pub state_root: String,
+    /// Cross-shard receipts emitted by this block's transfers
+    #[serde(default)]
+    pub sent_receipts: Vec<CrossShardReceipt>,
+    /// IDs of incoming cross-shard receipts claimed by this block
+    #[serde(default)]
+    pub claimed_receipts: Vec<[u8; 32]>,
+}
+
+impl MicroBlock {
+    /// Block hash: keccak256 over the header fields and transaction hashes
+    pub fn hash(&self) -> [u8; 32] {
+        let mut buf = Vec::with_capacity(128 + 32 * self.transactions.len());
+        buf.push(self.shard_id);
+        buf.extend_from_slice(&self.sequence.to_be_bytes());
+        buf.extend_from_slice(&self.parent_hash);
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+        buf.extend_from_slice(self.state_root.as_bytes());
+        for tx in &self.transactions {
+            buf.extend_from_slice(&tx.hash());
+        }
+        for receipt in &self.sent_receipts {
+            buf.push(b's');
+            buf.extend_from_slice(&receipt.id());
+        }
+        for id in &self.claimed_receipts {
+            buf.push(b'c');
+            buf.extend_from_slice(id);
+        }
+        keccak256(&buf)
+    }
+
+    /// Total gas used by the micro-block's transactions
+    pub fn gas_used(&self) -> u64 {
+        self.receipts.last().map_or(0, |r| r.cumulative_gas_used)
+    }
+
+    /// Union of the receipts' logs blooms
+    pub fn logs_bloom(&self) -> Bloom {
+        let mut bloom = Bloom::ZERO;
+        for receipt in &self.receipts {
+            bloom.accrue_bloom(&receipt.logs_bloom());
+        }
+        bloom
+    }
+}
+
+/// Outcome of a transaction included in a micro-block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxReceipt {
+    pub tx_hash: [u8; 32],
+    /// Whether execution succeeded
+    pub success: bool,
+    pub gas_used: u64,
+    /// Gas used by this and all earlier transactions of the micro-block
+    pub cumulative_gas_used: u64,
+    /// Contract deployed by this transaction
+    pub contract_address: Option<Address>,
+    /// Events emitted during execution
+    #[serde(default)]
+    pub logs: Vec<Log>,
+    /// Fee charged in wei (none for transactions that were not valid)
+    #[serde(default)]
+    pub fee: u128,
+    /// Why the transaction failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TxReceipt {
+    /// Ethereum logs bloom over the emitting addresses and topics
+    pub fn logs_bloom(&self) -> Bloom {
+        let mut bloom = Bloom::ZERO;
+        for log in &self.logs {
+            bloom.accrue(BloomInput::Raw(&log.address.evm_bytes()));
+            for topic in &log.topics {
+                bloom.accrue(BloomInput::Raw(topic));
+            }
+        }
+        bloom
+    }
 }
 
-/// Batch commitment posted to base layer
+/// EVM event emitted by a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Log {
+    pub address: Address,
+    pub topics: Vec<[u8; 32]>,
+    pub data: Vec<u8>,
+}
+
+/// Chain activity published to RPC subscribers
+#[derive(Debug, Clone)]
+pub enum ChainEvent {
+    /// A micro-block was applied and persisted
+    MicroBlock(Arc<MicroBlock>),
+    /// A transaction was admitted to the mempool
+    PendingTransaction([u8; 32]),
+    /// A batch commitment was finalized in a base block
+    BatchFinalized { batch_sequence: u64, base_height: u64 },
+}
+
+/// Base-layer traffic a sequencer follows
+#[derive(Debug, Clone)]
+pub enum BaseLayerMessage {
+    /// Base block announced by the validators that finalized it
+    Finalized(FinalizedBlock),
+    /// Batch commitment of another shard, which may carry receipts for this one
+    Batch(BatchCommitment),
+}
+
+/// Events buffered per subscriber before it starts missing them
+const EVENT_BUFFER: usize = 1024;
+
+/// Resubmit unfinalized batches after this many batch intervals without
+/// finality progress (validators may have restarted and lost them)
+const RESUBMIT_AFTER_INTERVALS: u32 = 10;
+
+/// Micro-blocks between two passes pruning old history
+const PRUNE_EVERY: u64 = 1_000;
+
+/// Batch commitment posted to base layer, signed by the shard's sequencer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchCommitment {
     pub shard_id: u8,
@@ -51,43 +177,289 @@ pub struct BatchCommitment {
     pub state_root: String,
     pub transactions_count: usize,
     pub timestamp: u64,
+    /// State root the batch starts from: that of the previous batch
+    #[serde(default)]
+    pub parent_state_root: String,
+    /// Root of the cross-shard receipts its micro-blocks sent
+    #[serde(default)]
+    pub receipts_root: [u8; 32],
+    /// Post-quantum key of the sequencer that signed the batch
+    #[serde(default)]
+    pub sequencer: Option<PublicKeyData>,
+    /// Raw signature over `signing_payload()`
+    #[serde(default)]
+    pub signature: Vec<u8>,
+}
+
+impl BatchCommitment {
+    /// Batch hash: keccak256 over the header fields and micro-block hashes
+    pub fn hash(&self) -> [u8; 32] {
+        let mut buf = Vec::with_capacity(192 + 32 * self.micro_blocks.len());
+        buf.push(self.shard_id);
+        buf.extend_from_slice(&self.batch_sequence.to_be_bytes());
+        buf.extend_from_slice(self.state_root.as_bytes());
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+        buf.extend_from_slice(self.parent_state_root.as_bytes());
+        buf.extend_from_slice(&self.receipts_root);
+        for block in &self.micro_blocks {
+            buf.extend_from_slice(&block.hash());
+        }
+        keccak256(&buf)
+    }
+
+    pub fn signing_payload(&self) -> Vec<u8> {
+        format!("ionova-batch:{}", hex::encode(self.hash())).into_bytes()
+    }
+
+    /// Cross-shard receipts sent by the batch's micro-blocks, in order
+    pub fn sent_receipts(&self) -> Vec<CrossShardReceipt> {
+        self.micro_blocks.iter().flat_map(|mb| mb.sent_receipts.iter().cloned()).collect()
+    }
+
+    /// Incoming cross-shard receipts claimed by the batch's micro-blocks
+    pub fn claims(&self) -> Vec<[u8; 32]> {
+        self.micro_blocks.iter().flat_map(|mb| mb.claimed_receipts.iter().copied()).collect()
+    }
+
+    /// Successful staking transactions of the batch's micro-blocks, in order
+    pub fn staking_actions(&self) -> Vec<StakingAction> {
+        self.micro_blocks
+            .iter()
+            .flat_map(|mb| mb.transactions.iter().zip(&mb.receipts))
+            .filter(|(tx, receipt)| receipt.success && tx.to == Some(STAKING_ACCOUNT))
+            .filter_map(|(tx, _)| StakingAction::from_transaction(tx).ok())
+            .collect()
+    }
+
+    /// Sign the batch with a sequencer's post-quantum key
+    pub fn sign(&mut self, keypair: &KeyPair) -> Result<()> {
+        self.signature = match keypair.sign(&self.signing_payload())? {
+            Signature::Dilithium { data } | Signature::SPHINCSPlus { data } => data,
+            other => bail!("Sequencer keys must be post-quantum, got {:?}", other.algorithm()),
+        };
+        self.sequencer = Some(keypair.public_key.clone());
+        Ok(())
+    }
+
+    /// Check the sequencer signature; returns the signing key
+    pub fn verify_signature(&self) -> Result<&PublicKeyData> {
+        let key = self
+            .sequencer
+            .as_ref()
+            .ok_or_else(|| anyhow!("Batch {} of shard {} is unsigned", self.batch_sequence, self.shard_id))?;
+        let signature = match key {
+            PublicKeyData::Dilithium { .. } => Signature::Dilithium { data: self.signature.clone() },
+            PublicKeyData::SPHINCSPlus { .. } => Signature::SPHINCSPlus { data: self.signature.clone() },
+            _ => bail!("Batch {} of shard {} is not signed with a post-quantum key", self.batch_sequence, self.shard_id),
+        };
+        if !signature.verify(&self.signing_payload(), key)? {
+            bail!("Invalid sequencer signature on batch {} of shard {}", self.batch_sequence, self.shard_id);
+        }
+        Ok(key)
+    }
+
+    /// Check the micro-blocks against the commitment: a linked chain of the
+    /// shard's blocks with one receipt per signed transaction, ending at the
+    /// committed state root
+    pub fn verify_contents(&self) -> Result<()> {
+        let Some(last) = self.micro_blocks.last() else {
+            bail!("Batch {} of shard {} has no micro-blocks", self.batch_sequence, self.shard_id);
+        };
+        let mut previous: Option<&MicroBlock> = None;
+        for block in &self.micro_blocks {
+            if block.shard_id != self.shard_id {
+                bail!("Micro-block {} belongs to shard {}, not {}", block.sequence, block.shard_id, self.shard_id);
+            }
+            if let Some(previous) = previous {
+                if block.sequence != previous.sequence + 1 || block.parent_hash != previous.hash() {
+                    bail!("Micro-block {} does not follow micro-block {}", block.sequence, previous.sequence);
+                }
+            }
+            if block.receipts.len() != block.transactions.len() {
+                bail!("Micro-block {} has {} receipts for {} transactions", block.sequence, block.receipts.len(), block.transactions.len());
+            }
+            for (tx, receipt) in block.transactions.iter().zip(&block.receipts) {
+                if receipt.tx_hash != tx.hash() {
+                    bail!("Micro-block {} has a receipt for another transaction", block.sequence);
+                }
+                if !tx.verify_signature()? {
+                    bail!("Micro-block {} includes a transaction with an invalid signature", block.sequence);
+                }
+            }
+            previous = Some(block);
+        }
+        if last.state_root != self.state_root {
+            bail!("Batch {} of shard {} does not commit to its last state root", self.batch_sequence, self.shard_id);
+        }
+        let transactions: usize = self.micro_blocks.iter().map(|mb| mb.transactions.len()).sum();
+        if transactions != self.transactions_count {
+            bail!("Batch {} of shard {} claims {} transactions, has {}", self.batch_sequence, self.shard_id, self.transactions_count, transactions);
+        }
+        let sent = self.sent_receipts();
+        if sent.iter().any(|r| r.source_shard != self.shard_id || r.destination_shard == self.shard_id) {
+            bail!("Batch {} of shard {} sends a receipt from another shard", self.batch_sequence, self.shard_id);
+        }
+        if cross_shard::receipts_root(&sent)? != self.receipts_root {
+            bail!("Batch {} of shard {} does not commit to its receipts", self.batch_sequence, self.shard_id);
+        }
+        Ok(())
+    }
 }
 
 /// Sequencer configuration
 #[derive(Debug, Clone)]
 pub struct SequencerConfig {
     pub shard_id: u8,
+    /// Shards accounts are routed over (see `transaction::shard_for`)
+    pub shard_count: u8,
+    /// Chain ID of shard 0; shard N's is this + N
+    pub base_chain_id: u64,
     pub micro_block_interval_ms: u64,
     pub batch_interval_ms: u64,
     pub max_batch_size: usize,
     pub fee_config: FeeConfig,
     pub mempool_config: MempoolConfig,
+    /// Base blocks a cross-shard receipt stays claimable for
+    pub cross_shard_timeout_blocks: u64,
+    /// Micro-blocks of history kept in the state store; 0 keeps everything
+    pub history_blocks: u64,
+    /// Prices of the signature-verification precompiles
+    pub gas: GasConfig,
 }
 
 /// Sequencer processes transactions and produces batches
 pub struct Sequencer {
     config: SequencerConfig,
-    tx_queue: mpsc::Receiver<Transaction>,
+    tx_queue: mpsc::Receiver<TxSubmission>,
     mempool: Mempool,
+    state: Arc<dyn StateStore>,
     micro_blocks: Vec<MicroBlock>,
     sequence_counter: u64,
     batch_counter: u64,
+    state_root: String,
+    genesis_root: String,
+    last_block_hash: [u8; 32],
+    events: broadcast::Sender<ChainEvent>,
+    /// Where batch commitments go for base-layer finality (validator network)
+    batch_sink: Option<mpsc::Sender<BatchCommitment>>,
+    /// Key batch commitments are signed with
+    signing_key: Option<KeyPair>,
+    /// Batches below this sequence are final on the base layer
+    finalized_batches: u64,
+    /// Finalized base blocks and other shards' batches from the validator network
+    base_layer_tx: mpsc::Sender<BaseLayerMessage>,
+    base_layer_rx: mpsc::Receiver<BaseLayerMessage>,
+    /// Last time a batch was finalized (or the sequencer started)
+    last_finality: Instant,
+    /// Open cross-shard transfers, persisted with the chain head
+    cross_shard: CrossShardLedger,
+    /// Receipts of other shards' batches sending to this shard, by batch
+    /// hash, until the batch is finalized: (shard, batch sequence, receipts)
+    foreign_receipts: HashMap<[u8; 32], (u8, u64, Vec<CrossShardReceipt>)>,
 }
 
 impl Sequencer {
-    pub fn new(config: SequencerConfig, tx_queue: mpsc::Receiver<Transaction>) -> Self {
-        let mempool = Mempool::new(
-            config.mempool_config.clone(),
-            config.fee_config.clone(),
-        );
+    /// Create a sequencer on top of a state store.
+    /// An empty store is seeded from genesis; otherwise the sequencer resumes
+    /// from the persisted chain head, including micro-blocks not yet batched.
+    pub fn new(
+        config: SequencerConfig,
+        tx_queue: mpsc::Receiver<TxSubmission>,
+        state: Arc<dyn StateStore>,
+    ) -> Result<Self> {
+        let mempool = Mempool::new(config.mempool_config.clone());
+
+        let head = match state.head()? {
+            Some(head) => head,
+            None => {
+                info!("Empty state store, initializing shard {} from genesis", config.shard_id);
+                let mut changes = StateChanges::from_genesis(&GenesisState::create());
+                let root = state_trie::apply_changes(state.as_ref(), state_trie::EMPTY_ROOT, &mut changes)?;
+                let head = ChainHead {
+                    state_root: hex::encode(root),
+                    genesis_root: hex::encode(root),
+                    ..ChainHead::default()
+                };
+                changes.set_head(head.clone());
+                state.commit(changes)?;
+                head
+            }
+        };
+
+        // Reload micro-blocks produced since the last batch commitment
+        let mut micro_blocks = Vec::new();
+        for sequence in head.batched_through..head.next_sequence {
+            match state.micro_block(sequence)? {
+                Some(block) => micro_blocks.push(block),
+                None => warn!("Missing micro-block {} in state store", sequence),
+            }
+        }
+
+        let last_block_hash = match head.next_sequence.checked_sub(1) {
+            Some(sequence) => state.micro_block(sequence)?.map_or([0u8; 32], |b| b.hash()),
+            None => [0u8; 32],
+        };
 
-        Self {
+        if head.next_sequence > 0 {
+            info!(
+                "Resuming shard {} at micro-block {} (batch {}, {} pending micro-blocks)",
+                config.shard_id,
+                head.next_sequence,
+                head.next_batch,
+                micro_blocks.len()
+            );
+        }
+
+        let (base_layer_tx, base_layer_rx) = mpsc::channel(EVENT_BUFFER);
+        Ok(Self {
             config,
             tx_queue,
             mempool,
-            micro_blocks: Vec::new(),
-            sequence_counter: 0,
-            batch_counter: 0,
+            state,
+            micro_blocks,
+            sequence_counter: head.next_sequence,
+            batch_counter: head.next_batch,
+            state_root: head.state_root,
+            genesis_root: head.genesis_root,
+            cross_shard: head.cross_shard,
+            last_block_hash,
+            events: broadcast::channel(EVENT_BUFFER).0,
+            batch_sink: None,
+            signing_key: None,
+            finalized_batches: head.finalized_batches,
+            base_layer_tx,
+            base_layer_rx,
+            last_finality: Instant::now(),
+            foreign_receipts: HashMap::new(),
+        })
+    }
+
+    /// Forward produced batch commitments, signed with `keypair`, to the validators
+    pub fn set_batch_sink(&mut self, sink: mpsc::Sender<BatchCommitment>, keypair: KeyPair) {
+        self.batch_sink = Some(sink);
+        self.signing_key = Some(keypair);
+    }
+
+    /// Where finalized base blocks and other shards' batches are delivered
+    pub fn base_layer_sender(&self) -> mpsc::Sender<BaseLayerMessage> {
+        self.base_layer_tx.clone()
+    }
+
+    /// Publisher of micro-block and mempool events (for RPC subscriptions)
+    pub fn events(&self) -> broadcast::Sender<ChainEvent> {
+        self.events.clone()
+    }
+
+    /// Current chain head as it would be persisted
+    fn chain_head(&self) -> ChainHead {
+        ChainHead {
+            next_sequence: self.sequence_counter,
+            next_batch: self.batch_counter,
+            batched_through: self.sequence_counter - self.micro_blocks.len() as u64,
+            state_root: self.state_root.clone(),
+            genesis_root: self.genesis_root.clone(),
+            finalized_batches: self.finalized_batches,
+            cross_shard: self.cross_shard.clone(),
         }
     }
 
@@ -111,64 +483,172 @@ impl Sequencer {
         loop {
             tokio::select! {
                 // Collect transactions and add to mempool
-                Some(tx) = self.tx_queue.recv() => {
-                    match self.mempool.add_tx(tx) {
-                        Ok(_) => {},
-                        Err(e) => {
-                            warn!("Transaction rejected: {}", e);
-                        }
+                Some(submission) = self.tx_queue.recv() => {
+                    let tx_hash = submission.tx.hash();
+                    let result = self.admit_transaction(submission.tx).await;
+                    match &result {
+                        // No subscribers is not an error
+                        Ok(_) => { let _ = self.events.send(ChainEvent::PendingTransaction(tx_hash)); }
+                        Err(e) => warn!("Transaction rejected: {}", e),
                     }
+                    // The submitter may have gone away; the tx stays pooled either way
+                    let _ = submission.responder.send(result);
                 }
 
                 // Produce micro-block
                 _ = micro_block_interval.tick() => {
-                    if self.mempool.size() > 0 {
-                        let pending_txs = self.mempool.get_batch(self.config.max_batch_size);
-                        if !pending_txs.is_empty() {
-                            let micro_block = self.produce_micro_block(pending_txs);
-                            self.micro_blocks.push(micro_block);
-                        }
+                    let pending_txs = if self.mempool.size().await > 0 {
+                        self.mempool.get_pending_transactions(self.config.max_batch_size).await
+                    } else {
+                        Vec::new()
+                    };
+                    // Cross-shard claims and settlements need a block too
+                    if !pending_txs.is_empty() || self.cross_shard.has_work() {
+                        self.produce_micro_block(pending_txs).await?;
+                    }
+                }
+
+                // Base-layer finality and cross-shard receipts
+                Some(message) = self.base_layer_rx.recv() => {
+                    match message {
+                        BaseLayerMessage::Finalized(finalized) => self.on_finalized(&finalized).await?,
+                        BaseLayerMessage::Batch(batch) => self.on_foreign_batch(batch),
                     }
                 }
 
                 // Produce batch commitment
                 _ = batch_interval.tick() => {
+                    self.resubmit_unfinalized().await?;
                     if !self.micro_blocks.is_empty() {
-                        let batch = self.produce_batch();
-                        self.submit_batch(batch).await?;
-                        self.micro_blocks.clear();
+                        self.commit_batch().await?;
                     }
                 }
             }
         }
     }
 
-    fn produce_micro_block(&mut self, transactions: Vec<Transaction>) -> MicroBlock {
+    /// Pool a transaction sent by an account of this shard
+    async fn admit_transaction(&mut self, tx: Transaction) -> Result<String> {
+        let home = transaction::shard_for(&tx.from, self.config.shard_count);
+        if home != self.config.shard_id {
+            bail!(
+                "Sender {} belongs to shard {} (chain ID {}), not shard {}",
+                tx.from,
+                home,
+                self.config.base_chain_id + home as u64,
+                self.config.shard_id
+            );
+        }
+        self.mempool.add_transaction(tx).await
+    }
+
+    /// Apply a micro-block, compute the resulting state root and persist both
+    async fn produce_micro_block(&mut self, transactions: Vec<Transaction>) -> Result<MicroBlock> {
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
-        // Compute state root (simplified - in production use Merkle tree)
-        let mut hasher = Sha256::new();
         for tx in &transactions {
-            hasher.update(tx.hash().as_bytes());
+            self.mempool.remove_transaction(&mempool::tx_hash_hex(tx)).await?;
         }
-        let state_root = hex::encode(hasher.finalize());
+
+        // State writes of this micro-block
+        let mut changes = StateChanges::new();
+
+        let parent_root = state_trie::parse_root(&self.state_root)?;
+        let store = self.state.clone();
+        let mut pending = PendingState::new(store.as_ref(), parent_root);
+        let block = BlockContext {
+            number: self.sequence_counter,
+            timestamp,
+            coinbase: self
+                .signing_key
+                .as_ref()
+                .map(|keypair| keypair.address().evm_bytes().into())
+                .unwrap_or_default(),
+            basefee: U256::from(fee_model::to_wei(self.config.fee_config.base_fee_per_gas)),
+        };
+
+        let mut included = Vec::with_capacity(transactions.len());
+        let mut receipts = Vec::with_capacity(transactions.len());
+        let mut sent_receipts = Vec::new();
+        let mut cumulative_gas_used = 0u64;
+        for tx in order_by_nonce(transactions) {
+            let sender_nonce = pending.account(&tx.from)?.nonce;
+            if tx.nonce > sender_nonce {
+                // Waits in the mempool for the transactions before it
+                if let Err(e) = self.mempool.add_transaction(tx).await {
+                    debug!("Dropping transaction with a future nonce: {}", e);
+                }
+                continue;
+            }
+            let outcome = self.apply_transaction(&tx, &mut pending, &block)?;
+            cumulative_gas_used += outcome.gas_used;
+            sent_receipts.extend(outcome.sent_receipt);
+            receipts.push(TxReceipt {
+                tx_hash: tx.hash(),
+                success: outcome.error.is_none(),
+                gas_used: outcome.gas_used,
+                cumulative_gas_used,
+                contract_address: outcome.contract_address,
+                logs: outcome.logs,
+                fee: outcome.fee,
+                error: outcome.error,
+            });
+            included.push(tx);
+        }
+        let transactions = included;
+        let claimed_receipts = self.claim_receipts(&mut pending)?;
+        self.settle_receipts(&mut pending)?;
+        pending.write_to(&mut changes);
+
+        let root = state_trie::apply_changes(self.state.as_ref(), parent_root, &mut changes)?;
+        let state_root = hex::encode(root);
 
         let micro_block = MicroBlock {
             shard_id: self.config.shard_id,
             sequence: self.sequence_counter,
+            parent_hash: self.last_block_hash,
             timestamp,
             transactions,
-            state_root,
+            receipts,
+            state_root: state_root.clone(),
+            sent_receipts,
+            claimed_receipts,
         };
 
         self.sequence_counter += 1;
-        micro_block
+        self.last_block_hash = micro_block.hash();
+        self.state_root = state_root;
+        self.micro_blocks.push(micro_block.clone());
+
+        changes.put_micro_block(micro_block.clone());
+        changes.set_head(self.chain_head());
+        self.persist(changes).await?;
+        self.prune_history().await;
+
+        let _ = self.events.send(ChainEvent::MicroBlock(Arc::new(micro_block.clone())));
+
+        Ok(micro_block)
     }
 
-    fn produce_batch(&mut self) -> BatchCommitment {
+    /// Seal the pending micro-blocks into a signed batch, persist it and
+    /// send it to the validators
+    async fn commit_batch(&mut self) -> Result<BatchCommitment> {
+        let batch = self.produce_batch()?;
+        self.micro_blocks.clear();
+
+        let mut changes = StateChanges::new();
+        changes.put_batch(batch.clone());
+        changes.set_head(self.chain_head());
+        self.persist(changes).await?;
+
+        self.submit_batch(batch.clone()).await?;
+        Ok(batch)
+    }
+
+    fn produce_batch(&mut self) -> Result<BatchCommitment> {
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -180,24 +660,398 @@ impl Sequencer {
             .map(|mb| mb.transactions.len())
             .sum();
 
-        // Compute batch state root
-        let mut hasher = Sha256::new();
-        for mb in &self.micro_blocks {
-            hasher.update(mb.state_root.as_bytes());
-        }
-        let state_root = hex::encode(hasher.finalize());
+        // The previous batch ended where this one starts
+        let parent_state_root = match self.batch_counter.checked_sub(1) {
+            Some(previous) => self.state.batch(previous)?.map(|b| b.state_root).unwrap_or_default(),
+            None => String::new(),
+        };
+
+        // Batch commits to the state after its last micro-block
+        let state_root = self
+            .micro_blocks
+            .last()
+            .map(|mb| mb.state_root.clone())
+            .unwrap_or_else(|| self.state_root.clone());
 
-        let batch = BatchCommitment {
+        let sent: Vec<CrossShardReceipt> =
+            self.micro_blocks.iter().flat_map(|mb| mb.sent_receipts.iter().cloned()).collect();
+
+        let mut batch = BatchCommitment {
             shard_id: self.config.shard_id,
             batch_sequence: self.batch_counter,
             micro_blocks: self.micro_blocks.clone(),
             state_root,
             transactions_count,
             timestamp,
+            parent_state_root,
+            receipts_root: cross_shard::receipts_root(&sent)?,
+            sequencer: None,
+            signature: Vec::new(),
         };
+        if let Some(keypair) = &self.signing_key {
+            batch.sign(keypair)?;
+        }
 
         self.batch_counter += 1;
-        batch
+        Ok(batch)
+    }
+
+    /// Record this shard's batches in a finalized base block. Base blocks
+    /// order a shard's batches by sequence, so earlier ones are final too.
+    /// Receipts for this shard in other shards' batches become claimable,
+    /// and cross-shard claims and expiries the block decides are settled.
+    async fn on_finalized(&mut self, finalized: &FinalizedBlock) -> Result<()> {
+        let base_height = finalized.block.height;
+        let shard_id = self.config.shard_id;
+        let mut progressed = false;
+
+        for header in finalized.block.batches.iter().filter(|h| h.shard_id != shard_id) {
+            if let Some((_, _, receipts)) = self.foreign_receipts.remove(&header.hash) {
+                for (index, receipt) in receipts.iter().enumerate() {
+                    if receipt.destination_shard != shard_id {
+                        continue;
+                    }
+                    let proof = ReceiptProof::build(&receipts, index, header.batch_sequence)?;
+                    match proof.verify(header) {
+                        Ok(()) => self.cross_shard.receive(shard_id, proof),
+                        Err(e) => warn!("Dropping receipt from shard {}: {}", header.shard_id, e),
+                    }
+                }
+            }
+            // Conflicting batches the finalized one replaced
+            self.foreign_receipts
+                .retain(|_, (shard, sequence, _)| *shard != header.shard_id || *sequence > header.batch_sequence);
+        }
+        let ledger = self.cross_shard.clone();
+        self.cross_shard.on_finalized(shard_id, base_height, &finalized.block.batches);
+        progressed |= self.cross_shard != ledger;
+
+        for header in finalized.block.batches.iter().filter(|h| h.shard_id == self.config.shard_id) {
+            if header.batch_sequence < self.finalized_batches {
+                continue;
+            }
+            let local = self.state.batch(header.batch_sequence)?.map(|b| b.hash());
+            if local != Some(header.hash) {
+                warn!(
+                    "Base block {} finalized batch {} of shard {}, which does not match the local batch",
+                    base_height, header.batch_sequence, header.shard_id
+                );
+                continue;
+            }
+            info!("Batch {} final in base block {}", header.batch_sequence, base_height);
+            self.finalized_batches = header.batch_sequence + 1;
+            self.last_finality = Instant::now();
+            progressed = true;
+            let _ = self.events.send(ChainEvent::BatchFinalized {
+                batch_sequence: header.batch_sequence,
+                base_height,
+            });
+        }
+        if progressed {
+            let mut changes = StateChanges::new();
+            changes.set_head(self.chain_head());
+            self.persist(changes).await?;
+        }
+        Ok(())
+    }
+
+    /// Commit a change set on a blocking thread, off the async runtime
+    async fn persist(&self, changes: StateChanges) -> Result<()> {
+        let state = self.state.clone();
+        tokio::task::spawn_blocking(move || state.commit(changes)).await?
+    }
+
+    /// Every `PRUNE_EVERY` micro-blocks, drop history older than
+    /// `history_blocks`. A failed prune is retried next time.
+    async fn prune_history(&self) {
+        let history = self.config.history_blocks;
+        if history == 0 || !self.sequence_counter.is_multiple_of(PRUNE_EVERY) || self.sequence_counter <= history {
+            return;
+        }
+        let state = self.state.clone();
+        let before = self.sequence_counter - history;
+        let result = tokio::task::spawn_blocking(move || state.prune(before)).await;
+        if let Err(e) = result.map_err(anyhow::Error::from).and_then(|pruned| pruned) {
+            warn!("Pruning below micro-block {} failed: {}", before, e);
+        }
+    }
+
+    /// Keep the receipts another shard's batch sends to this shard until the
+    /// batch is finalized
+    fn on_foreign_batch(&mut self, batch: BatchCommitment) {
+        if batch.shard_id == self.config.shard_id {
+            return;
+        }
+        let receipts = batch.sent_receipts();
+        if !receipts.iter().any(|r| r.destination_shard == self.config.shard_id) {
+            return;
+        }
+        if let Err(e) = batch.verify_contents() {
+            warn!("Ignoring batch {} of shard {}: {}", batch.batch_sequence, batch.shard_id, e);
+            return;
+        }
+        self.foreign_receipts
+            .insert(batch.hash(), (batch.shard_id, batch.batch_sequence, receipts));
+    }
+
+    /// State transition of a transaction. Transactions with a stale nonce,
+    /// too little gas or a balance short of value plus fee change nothing.
+    /// Otherwise the fee is charged and shared out and the nonce is bumped,
+    /// even if execution then fails.
+    fn apply_transaction(
+        &mut self,
+        tx: &Transaction,
+        pending: &mut PendingState,
+        block: &BlockContext,
+    ) -> Result<TxOutcome> {
+        if let Err(e) = tx.validate_nonce(pending.account(&tx.from)?.nonce) {
+            return Ok(TxOutcome::invalid(e.to_string()));
+        }
+        let max_fee_per_gas = match tx.value_wei().and(tx.tip_wei()).and_then(|_| tx.gas_price_wei()) {
+            Ok(price) => price,
+            Err(e) => return Ok(TxOutcome::invalid(e.to_string())),
+        };
+        let base_fee_per_gas = fee_model::to_wei(self.config.fee_config.base_fee_per_gas);
+        if u128::from(max_fee_per_gas) < base_fee_per_gas {
+            return Ok(TxOutcome::invalid(format!(
+                "Gas price {} below the base fee per gas {}",
+                max_fee_per_gas, base_fee_per_gas
+            )));
+        }
+        if runs_on_evm(tx, pending)? {
+            return self.apply_evm_transaction(tx, pending, block);
+        }
+        // Crediting another shard's account here would strand the funds
+        if let Some(to) = tx.to.filter(|to| !is_system_account(to)) {
+            let home = transaction::shard_for(&to, self.config.shard_count);
+            if home != self.config.shard_id {
+                return Ok(TxOutcome::invalid(format!(
+                    "Recipient {} belongs to shard {}: send through the cross-shard router {}",
+                    to, home, CROSS_SHARD_ROUTER
+                )));
+            }
+        }
+
+        let value = tx.value_wei()?;
+        let gas_used = self.config.gas.intrinsic_gas(tx)?;
+        let fee = self.fee(tx, gas_used)?;
+        let mut sender = pending.account(&tx.from)?;
+        if gas_used > tx.gas_limit {
+            return Ok(TxOutcome::invalid(format!("Gas limit {} below intrinsic gas {}", tx.gas_limit, gas_used)));
+        }
+        if sender.balance < value.saturating_add(fee.total_wei()) {
+            return Ok(TxOutcome::invalid(format!(
+                "Insufficient balance: {} < {} value + {} fee",
+                sender.balance,
+                value,
+                fee.total_wei()
+            )));
+        }
+
+        sender.nonce += 1;
+        pending.set_account(tx.from, sender);
+        let fee = self.charge_fee(tx, gas_used, pending)?;
+
+        let mut outcome = TxOutcome { gas_used, fee, ..TxOutcome::default() };
+        let result = match tx.to {
+            Some(CROSS_SHARD_ROUTER) => self.send_cross_shard(tx, pending).map(|receipt| {
+                outcome.sent_receipt = Some(receipt);
+            }),
+            // The value stays locked while the validators hold the stake
+            Some(STAKING_ACCOUNT) => StakingAction::from_transaction(tx)
+                .and_then(|_| pending.transfer(&tx.from, &STAKING_ACCOUNT, value)),
+            Some(to) => pending.transfer(&tx.from, &to, value),
+            None => unreachable!("contract creations run on the EVM"),
+        };
+        if let Err(e) = result {
+            debug!("Transaction 0x{} failed: {}", hex::encode(tx.hash()), e);
+            outcome.error = Some(e.to_string());
+        }
+        Ok(outcome)
+    }
+
+    /// Run a contract creation or call on the EVM against the pending state.
+    /// The EVM meters gas and bumps the nonce; the fee is charged here on
+    /// the gas it used plus signature verification.
+    fn apply_evm_transaction(
+        &self,
+        tx: &Transaction,
+        pending: &mut PendingState,
+        block: &BlockContext,
+    ) -> Result<TxOutcome> {
+        let signature_gas = self.config.gas.signature_gas(tx);
+        if tx.gas_limit <= signature_gas {
+            return Ok(TxOutcome::invalid(format!(
+                "Gas limit {} does not cover signature verification ({})",
+                tx.gas_limit, signature_gas
+            )));
+        }
+        let max_fee = self.fee(tx, tx.gas_limit)?.total_wei();
+        let balance = pending.account(&tx.from)?.balance;
+        let value = tx.value_wei()?;
+        if balance < value.saturating_add(max_fee) {
+            return Ok(TxOutcome::invalid(format!(
+                "Insufficient balance: {} < {} value + {} max fee",
+                balance,
+                value,
+                max_fee
+            )));
+        }
+
+        let evm_tx = EvmTransaction::from_transaction(tx, &self.config.gas)?;
+        let executor = EvmExecutor::new(self.config.base_chain_id + self.config.shard_id as u64)
+            .with_gas_config(self.config.gas.clone());
+        let view = PendingView { pending, sender: tx.from };
+        let (result, state) = match executor.transact(view, block, &evm_tx) {
+            Ok(executed) => executed,
+            Err(e) => return Ok(TxOutcome::invalid(e.to_string())),
+        };
+        pending.apply_evm_state(tx.from, state)?;
+
+        let gas_used = result.gas_used + signature_gas;
+        let fee = self.charge_fee(tx, gas_used, pending)?;
+        let error = match (result.success, result.halt_reason, result.revert_reason) {
+            (true, _, _) => None,
+            (false, Some(reason), _) => Some(format!("Execution halted: {}", reason)),
+            (false, None, Some(reason)) => Some(format!("Execution reverted: {}", reason)),
+            (false, None, None) => Some("Execution reverted".to_string()),
+        };
+        if let Some(e) = &error {
+            debug!("Transaction 0x{} failed: {}", hex::encode(tx.hash()), e);
+        }
+        Ok(TxOutcome {
+            gas_used,
+            fee,
+            contract_address: result.contract_address.map(|address| Address::EVM(address.into_array())),
+            logs: result.logs,
+            error,
+            ..TxOutcome::default()
+        })
+    }
+
+    /// Fee for `gas_used`. Native tips are flat; an Ethereum transaction's
+    /// tip is its priority fee per gas, capped by what maxFeePerGas leaves
+    /// above the base fee, on the gas used, so unused gas is never charged.
+    fn fee(&self, tx: &Transaction, gas_used: u64) -> Result<TransactionFee> {
+        let tip = match tx.eth_envelope {
+            Some(_) => {
+                let base_fee_per_gas = fee_model::to_wei(self.config.fee_config.base_fee_per_gas);
+                let priority = tx.tip_wei()?.checked_div(u128::from(tx.gas_limit)).unwrap_or(0);
+                let headroom = u128::from(tx.gas_price_wei()?).saturating_sub(base_fee_per_gas);
+                eth_tx::wei_to_ionx(priority.min(headroom) * u128::from(gas_used))?
+            }
+            None => tx.tip,
+        };
+        Ok(TransactionFee::calculate(&self.config.fee_config, gas_used, tip))
+    }
+
+    /// Debit the fee for `gas_used` from the sender and share it out
+    fn charge_fee(&self, tx: &Transaction, gas_used: u64, pending: &mut PendingState) -> Result<u128> {
+        let fee = self.fee(tx, gas_used)?;
+        pending.debit(&tx.from, fee.total_wei())?;
+        let shares = fee.shares(&self.config.fee_config.split);
+        pending.credit(&VALIDATOR_FEE_POOL, shares.validators)?;
+        pending.credit(&TREASURY, shares.treasury)?;
+        match &self.signing_key {
+            Some(keypair) => pending.credit(&keypair.address(), shares.sequencer)?,
+            None => pending.credit(&VALIDATOR_FEE_POOL, shares.sequencer)?,
+        }
+        Ok(fee.total_wei())
+    }
+
+    /// Debit a transfer to the cross-shard router and emit its receipt
+    fn send_cross_shard(&mut self, tx: &Transaction, pending: &mut PendingState) -> Result<CrossShardReceipt> {
+        let (destination_shard, recipient) = cross_shard::parse_transfer(&tx.data)?;
+        if destination_shard == self.config.shard_id {
+            bail!("Destination is this shard");
+        }
+        let home = transaction::shard_for(&recipient, self.config.shard_count);
+        if destination_shard != home {
+            bail!("Recipient {} belongs to shard {}, not {}", recipient, home, destination_shard);
+        }
+        let amount = tx.value_wei()?;
+        if amount == 0 {
+            bail!("Nothing to transfer");
+        }
+        pending.debit(&tx.from, amount)?;
+
+        let receipt = CrossShardReceipt {
+            source_shard: self.config.shard_id,
+            destination_shard,
+            nonce: self.cross_shard.next_nonce,
+            sender: tx.from,
+            recipient,
+            amount,
+            source_tx: tx.hash(),
+            expires_at: self.cross_shard.expiry(self.config.cross_shard_timeout_blocks),
+        };
+        self.cross_shard.next_nonce += 1;
+        pending.set_status(&receipt.id(), ReceiptStatus::Sent);
+        self.cross_shard.outbound.push(receipt.clone());
+        Ok(receipt)
+    }
+
+    /// Claim proven incoming receipts; the claim is credited once final
+    fn claim_receipts(&mut self, pending: &mut PendingState) -> Result<Vec<[u8; 32]>> {
+        let mut claims = Vec::new();
+        for proof in std::mem::take(&mut self.cross_shard.inbox) {
+            let id = proof.receipt.id();
+            if pending.status(&id)?.is_some() {
+                warn!("Receipt 0x{} was already claimed", hex::encode(id));
+                continue;
+            }
+            if self.cross_shard.base_height.is_some_and(|h| proof.receipt.expires_at <= h) {
+                continue;
+            }
+            pending.set_status(&id, ReceiptStatus::Claimed);
+            self.cross_shard.claimed.push(proof.receipt);
+            claims.push(id);
+        }
+        Ok(claims)
+    }
+
+    /// Apply credits, refunds and status changes finalized base blocks made due
+    fn settle_receipts(&mut self, pending: &mut PendingState) -> Result<()> {
+        for settlement in std::mem::take(&mut self.cross_shard.settlements) {
+            match settlement {
+                Settlement::Credit(receipt) => {
+                    let id = receipt.id();
+                    if pending.status(&id)? == Some(ReceiptStatus::Claimed) {
+                        pending.credit(&receipt.recipient, receipt.amount)?;
+                        pending.set_status(&id, ReceiptStatus::Credited);
+                    }
+                }
+                Settlement::Refund(receipt) => {
+                    let id = receipt.id();
+                    if pending.status(&id)? == Some(ReceiptStatus::Sent) {
+                        pending.credit(&receipt.sender, receipt.amount)?;
+                        pending.set_status(&id, ReceiptStatus::Refunded);
+                    }
+                }
+                Settlement::Deliver(id) => pending.set_status(&id, ReceiptStatus::Delivered),
+                Settlement::Expire(id) => pending.set_status(&id, ReceiptStatus::Expired),
+            }
+        }
+        Ok(())
+    }
+
+    /// Send batches that are still not final again once finality stalls
+    async fn resubmit_unfinalized(&mut self) -> Result<()> {
+        let stalled = std::time::Duration::from_millis(self.config.batch_interval_ms) * RESUBMIT_AFTER_INTERVALS;
+        if self.finalized_batches >= self.batch_counter || self.last_finality.elapsed() < stalled {
+            return Ok(());
+        }
+        self.last_finality = Instant::now();
+        for sequence in self.finalized_batches..self.batch_counter {
+            match self.state.batch(sequence)? {
+                Some(batch) => {
+                    debug!("Resubmitting unfinalized batch {}", sequence);
+                    self.submit_batch(batch).await?;
+                }
+                None => warn!("Missing batch {} in state store", sequence),
+            }
+        }
+        Ok(())
     }
 
     async fn submit_batch(&self, batch: BatchCommitment) -> Result<()> {
@@ -206,8 +1060,854 @@ impl Sequencer {
             batch.shard_id, batch.batch_sequence, batch.transactions_count
         );
 
-        // In production, this would submit to base layer consensus
-        // For now, just log
+        match &self.batch_sink {
+            Some(sink) => {
+                if sink.send(batch).await.is_err() {
+                    warn!("Validator network unavailable, batch not submitted");
+                }
+            }
+            None => warn!("No validator network configured, batch stays local"),
+        }
         Ok(())
     }
 }
+
+/// Result of applying one transaction
+#[derive(Default)]
+struct TxOutcome {
+    gas_used: u64,
+    fee: u128,
+    sent_receipt: Option<CrossShardReceipt>,
+    contract_address: Option<Address>,
+    logs: Vec<Log>,
+    /// Why the transaction was invalid or failed
+    error: Option<String>,
+}
+
+impl TxOutcome {
+    /// A transaction that was not valid: no gas, no fee, no state change
+    fn invalid(error: String) -> Self {
+        Self { error: Some(error), ..Self::default() }
+    }
+}
+
+/// Contract creations, calls carrying data and transfers to contracts run
+/// on the EVM; everything else is a native transfer
+fn runs_on_evm(tx: &Transaction, pending: &PendingState) -> Result<bool> {
+    Ok(match tx.to {
+        None => true,
+        Some(CROSS_SHARD_ROUTER | STAKING_ACCOUNT) => false,
+        Some(to) => !tx.data.is_empty() || !pending.code(&to)?.is_empty(),
+    })
+}
+
+/// Protocol accounts present on every shard
+fn is_system_account(address: &Address) -> bool {
+    [CROSS_SHARD_ROUTER, STAKING_ACCOUNT, VALIDATOR_FEE_POOL, TREASURY].contains(address)
+}
+
+/// Put each sender's transactions in nonce order, keeping the slots the
+/// mempool's priority order gave that sender
+fn order_by_nonce(transactions: Vec<Transaction>) -> Vec<Transaction> {
+    let mut by_sender: HashMap<Address, Vec<Transaction>> = HashMap::new();
+    let senders: Vec<Address> = transactions.iter().map(|tx| tx.from).collect();
+    for tx in transactions {
+        by_sender.entry(tx.from).or_default().push(tx);
+    }
+    for txs in by_sender.values_mut() {
+        // Popped from the back below
+        txs.sort_by_key(|tx| std::cmp::Reverse(tx.nonce));
+    }
+    senders
+        .iter()
+        .filter_map(|sender| by_sender.get_mut(sender).and_then(Vec::pop))
+        .collect()
+}
+
+/// Account, code and storage writes of a micro-block on top of its parent
+/// state
+struct PendingState<'a> {
+    store: &'a dyn StateStore,
+    root: [u8; 32],
+    accounts: HashMap<Address, Account>,
+    code: HashMap<Address, Vec<u8>>,
+    storage: HashMap<(Address, [u8; 32]), [u8; 32]>,
+}
+
+impl<'a> PendingState<'a> {
+    fn new(store: &'a dyn StateStore, root: [u8; 32]) -> Self {
+        Self { store, root, accounts: HashMap::new(), code: HashMap::new(), storage: HashMap::new() }
+    }
+
+    fn account(&self, address: &Address) -> Result<Account> {
+        if let Some(account) = self.accounts.get(address) {
+            return Ok(account.clone());
+        }
+        Ok(state_trie::get_account(self.store, self.root, address)?
+            .unwrap_or_else(AccountLeaf::empty)
+            .account())
+    }
+
+    fn set_account(&mut self, address: Address, account: Account) {
+        self.accounts.insert(address, account);
+    }
+
+    fn credit(&mut self, address: &Address, amount: u128) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        let mut account = self.account(address)?;
+        account.balance = account.balance.checked_add(amount).ok_or_else(|| anyhow!("Balance overflow"))?;
+        self.set_account(*address, account);
+        Ok(())
+    }
+
+    fn debit(&mut self, address: &Address, amount: u128) -> Result<()> {
+        let mut account = self.account(address)?;
+        account.balance = account
+            .balance
+            .checked_sub(amount)
+            .ok_or_else(|| anyhow!("Insufficient balance: {} < {}", account.balance, amount))?;
+        self.set_account(*address, account);
+        Ok(())
+    }
+
+    /// Move value between accounts (checked before anything is written)
+    fn transfer(&mut self, from: &Address, to: &Address, amount: u128) -> Result<()> {
+        if self.account(from)?.balance < amount {
+            bail!("Insufficient balance for value {}", amount);
+        }
+        self.debit(from, amount)?;
+        self.credit(to, amount)
+    }
+
+    /// Contract code of an account (empty for plain accounts)
+    fn code(&self, address: &Address) -> Result<Vec<u8>> {
+        if let Some(code) = self.code.get(address) {
+            return Ok(code.clone());
+        }
+        match state_trie::get_account(self.store, self.root, address)? {
+            Some(leaf) if leaf.code_hash != EMPTY_CODE_HASH => self
+                .store
+                .trie_node(&leaf.code_hash)?
+                .ok_or_else(|| anyhow!("Missing contract code of {}", address)),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn storage(&self, address: &Address, slot: &[u8; 32]) -> Result<[u8; 32]> {
+        if let Some(value) = self.storage.get(&(*address, *slot)) {
+            return Ok(*value);
+        }
+        match state_trie::get_account(self.store, self.root, address)? {
+            Some(leaf) => state_trie::get_storage(self.store, leaf.storage_root, slot),
+            None => Ok([0u8; 32]),
+        }
+    }
+
+    /// Cross-shard receipt status in router storage
+    fn status(&self, id: &[u8; 32]) -> Result<Option<ReceiptStatus>> {
+        Ok(ReceiptStatus::from_word(&self.storage(&CROSS_SHARD_ROUTER, id)?))
+    }
+
+    fn set_status(&mut self, id: &[u8; 32], status: ReceiptStatus) {
+        self.storage.insert((CROSS_SHARD_ROUTER, *id), status.to_word());
+    }
+
+    /// Apply the accounts an EVM transaction touched. `sender` is the
+    /// transaction's own account, which the EVM knows by its 20 bytes.
+    fn apply_evm_state(&mut self, sender: Address, state: EvmState) -> Result<()> {
+        for (address, account) in state {
+            if !account.is_touched() {
+                continue;
+            }
+            let address = evm_account(sender, address);
+            if account.is_selfdestructed() {
+                self.set_account(address, Account { balance: 0, nonce: 0 });
+                self.code.insert(address, Vec::new());
+                continue;
+            }
+            let changed: Vec<_> = account.changed_storage_slots().map(|(slot, value)| (*slot, value.present_value)).collect();
+            let existing = self.account(&address)?;
+            if account.is_empty() && changed.is_empty() && existing.balance == 0 && existing.nonce == 0 {
+                // Touched but never existed, like a zero-value call
+                continue;
+            }
+            let balance = u128::try_from(account.info.balance).map_err(|_| anyhow!("Balance of {} overflows", address))?;
+            self.set_account(address, Account { balance, nonce: account.info.nonce });
+            if account.is_created() {
+                let code = account.info.code.map(|code| code.original_bytes().to_vec()).unwrap_or_default();
+                self.code.insert(address, code);
+            }
+            for (slot, value) in changed {
+                self.storage.insert((address, slot.to_be_bytes()), value.to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    fn write_to(self, changes: &mut StateChanges) {
+        for (address, account) in self.accounts {
+            changes.set_account(address, account);
+        }
+        for (address, code) in self.code {
+            changes.set_code(address, code);
+        }
+        for ((address, slot), value) in self.storage {
+            changes.set_storage(address, slot, value);
+        }
+    }
+}
+
+/// Node account behind an EVM address: the sender's own account (which may
+/// be native), otherwise the EVM account of those bytes
+fn evm_account(sender: Address, address: alloy_primitives::Address) -> Address {
+    if address.0 == sender.evm_bytes() {
+        sender
+    } else {
+        Address::EVM(address.into_array())
+    }
+}
+
+/// EVM view of a micro-block's pending state for one transaction
+struct PendingView<'p, 'a> {
+    pending: &'p PendingState<'a>,
+    sender: Address,
+}
+
+impl DatabaseRef for PendingView<'_, '_> {
+    type Error = anyhow::Error;
+
+    fn basic_ref(&self, address: alloy_primitives::Address) -> Result<Option<AccountInfo>> {
+        let address = evm_account(self.sender, address);
+        let account = self.pending.account(&address)?;
+        let code = self.pending.code(&address)?;
+        let code_hash = if code.is_empty() { KECCAK_EMPTY } else { B256::from(keccak256(&code)) };
+        Ok(Some(AccountInfo::new(
+            U256::from(account.balance),
+            account.nonce,
+            code_hash,
+            Bytecode::new_raw(Bytes::from(code)),
+        )))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode> {
+        // basic_ref always hands out the code, so this is only a fallback
+        if code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::default());
+        }
+        let code = self
+            .pending
+            .store
+            .trie_node(&code_hash.0)?
+            .ok_or_else(|| anyhow!("Missing contract code {}", code_hash))?;
+        Ok(Bytecode::new_raw(Bytes::from(code)))
+    }
+
+    fn storage_ref(&self, address: alloy_primitives::Address, index: U256) -> Result<U256> {
+        let address = evm_account(self.sender, address);
+        let value = self.pending.storage(&address, &index.to_be_bytes::<32>())?;
+        Ok(U256::from_be_bytes(value))
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256> {
+        Ok(self
+            .pending
+            .store
+            .micro_block(number)?
+            .map(|block| B256::from(block.hash()))
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_sol_types::{Revert, SolCall, SolError};
+    use crate::config::ACCESS_LIST_ADDRESS_GAS;
+    use crate::pq_precompiles;
+    use crate::crypto::SignatureAlgorithm;
+    use crate::finality::{BaseBlock, BatchHeader, QuorumCertificate, VotePhase};
+    use crate::staking::StakingOp;
+    use crate::state_db::{FileStateStore, InMemoryStateStore};
+    use crate::transaction::{TransactionBuilder, BASE_CHAIN_ID};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn test_sequencer() -> (Sequencer, mpsc::Receiver<BatchCommitment>, Arc<InMemoryStateStore>) {
+        test_sequencer_on(3, Arc::new(InMemoryStateStore::new()))
+    }
+
+    fn test_sequencer_on(
+        shard_id: u8,
+        store: Arc<InMemoryStateStore>,
+    ) -> (Sequencer, mpsc::Receiver<BatchCommitment>, Arc<InMemoryStateStore>) {
+        let config = SequencerConfig {
+            shard_id,
+            shard_count: 8,
+            base_chain_id: BASE_CHAIN_ID,
+            micro_block_interval_ms: 100,
+            batch_interval_ms: 1000,
+            max_batch_size: 100,
+            fee_config: FeeConfig::default(),
+            mempool_config: MempoolConfig::default(),
+            cross_shard_timeout_blocks: 10,
+            history_blocks: 0,
+            gas: GasConfig::default(),
+        };
+        let (_tx, tx_queue) = mpsc::channel(1);
+        let mut sequencer = Sequencer::new(config, tx_queue, store.clone()).unwrap();
+        let (sink, batches) = mpsc::channel(16);
+        sequencer.set_batch_sink(sink, KeyPair::generate(SignatureAlgorithm::Dilithium));
+        (sequencer, batches, store)
+    }
+
+    fn finalized(height: u64, batches: &[&BatchCommitment]) -> FinalizedBlock {
+        FinalizedBlock {
+            block: BaseBlock {
+                height,
+                parent_hash: [0u8; 32],
+                proposer: "v0".to_string(),
+                timestamp: 0,
+                batches: batches.iter().map(|b| BatchHeader::from(*b)).collect(),
+                evidence: vec![],
+            },
+            qc: QuorumCertificate {
+                view: height,
+                block_hash: String::new(),
+                phase: VotePhase::Commit,
+                signatures: vec![],
+                aggregated_stake: 0,
+            },
+        }
+    }
+
+    /// Genesis state plus a funded account
+    fn funded_store(address: Address, balance: u128) -> Arc<InMemoryStateStore> {
+        let store = Arc::new(InMemoryStateStore::new());
+        fund(store.as_ref(), address, balance);
+        store
+    }
+
+    fn fund(store: &dyn StateStore, address: Address, balance: u128) {
+        let mut changes = StateChanges::from_genesis(&GenesisState::create());
+        changes.set_account(address, Account { balance, nonce: 0 });
+        let root = state_trie::apply_changes(store, state_trie::EMPTY_ROOT, &mut changes).unwrap();
+        changes.set_head(ChainHead { state_root: hex::encode(root), ..ChainHead::default() });
+        store.commit(changes).unwrap();
+    }
+
+    fn balance(sequencer: &Sequencer, address: &Address) -> u128 {
+        let root = state_trie::parse_root(&sequencer.state_root).unwrap();
+        state_trie::get_account(sequencer.state.as_ref(), root, address)
+            .unwrap()
+            .map_or(0, |leaf| leaf.account().balance)
+    }
+
+    fn status(sequencer: &Sequencer, id: &[u8; 32]) -> Option<ReceiptStatus> {
+        let root = state_trie::parse_root(&sequencer.state_root).unwrap();
+        PendingState::new(sequencer.state.as_ref(), root).status(id).unwrap()
+    }
+
+    fn transfer(keypair: &KeyPair, nonce: u64, destination_shard: u8, recipient: &Address) -> Transaction {
+        TransactionBuilder::new()
+            .nonce(nonce)
+            .to(CROSS_SHARD_ROUTER)
+            .value(dec!(2))
+            .unwrap()
+            .data(cross_shard::transfer_data(destination_shard, recipient))
+            .unwrap()
+            .gas_limit(100_000)
+            .unwrap()
+            .sign(keypair)
+            .unwrap()
+    }
+
+    /// An account whose home shard is shard 0
+    fn local_account() -> Address {
+        (1..=255u8).map(|i| Address::EVM([i; 20])).find(|address| transaction::shard_for(address, 8) == 0).unwrap()
+    }
+
+    /// An account whose home shard is not shard 0, and that shard
+    fn remote_account() -> (Address, u8) {
+        (1..=255u8)
+            .map(|i| Address::EVM([i; 20]))
+            .map(|address| (address, transaction::shard_for(&address, 8)))
+            .find(|(_, shard)| *shard != 0)
+            .unwrap()
+    }
+
+    fn payment(keypair: &KeyPair, nonce: u64, to: Address, value: Decimal) -> Transaction {
+        TransactionBuilder::new()
+            .nonce(nonce)
+            .to(to)
+            .value(value)
+            .unwrap()
+            .gas_limit(100_000)
+            .unwrap()
+            .tip(dec!(0.001))
+            .sign(keypair)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_native_transfers_charge_fees() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let funded = Account::new(10).balance;
+        let (mut sequencer, _batches, _store) = test_sequencer_on(0, funded_store(keypair.address(), funded));
+        let sequencer_address = sequencer.signing_key.as_ref().unwrap().address();
+        let to = local_account();
+
+        // Out of nonce order and with a gap: nonce 1 follows 0, nonce 5 waits
+        let block = sequencer
+            .produce_micro_block(vec![
+                payment(&keypair, 1, to, dec!(2)),
+                payment(&keypair, 5, to, dec!(1)),
+                payment(&keypair, 0, to, dec!(1)),
+            ])
+            .await
+            .unwrap();
+        let nonces: Vec<u64> = block.transactions.iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![0, 1]);
+        assert!(block.receipts.iter().all(|r| r.success));
+        assert_eq!(sequencer.mempool.size().await, 1);
+
+        let fee = TransactionFee::calculate(&FeeConfig::default(), block.receipts[0].gas_used, dec!(0.001));
+        let shares = fee.shares(&FeeConfig::default().split);
+        assert_eq!(block.receipts[0].fee, fee.total_wei());
+        let fees = block.receipts[0].fee + block.receipts[1].fee;
+        assert_eq!(balance(&sequencer, &keypair.address()), funded - Account::new(3).balance - fees);
+        assert_eq!(balance(&sequencer, &to), Account::new(3).balance);
+        assert_eq!(balance(&sequencer, &VALIDATOR_FEE_POOL), 2 * shares.validators);
+        assert_eq!(balance(&sequencer, &TREASURY), 2 * shares.treasury);
+        assert_eq!(balance(&sequencer, &sequencer_address), 2 * shares.sequencer);
+
+        // Replays and overdrafts change nothing but are recorded as failed
+        let before = balance(&sequencer, &keypair.address());
+        let block = sequencer
+            .produce_micro_block(vec![payment(&keypair, 0, to, dec!(1)), payment(&keypair, 2, to, dec!(100))])
+            .await
+            .unwrap();
+        assert!(block.receipts[0].error.as_ref().unwrap().contains("Nonce too low"));
+        assert!(block.receipts[1].error.as_ref().unwrap().contains("Insufficient balance"));
+        assert!(block.receipts.iter().all(|r| !r.success && r.fee == 0 && r.gas_used == 0));
+        assert_eq!(balance(&sequencer, &keypair.address()), before);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_transfers_to_other_shards() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let funded = Account::new(10).balance;
+        let (mut sequencer, _batches, _store) = test_sequencer_on(0, funded_store(keypair.address(), funded));
+        let (recipient, shard) = remote_account();
+
+        let block = sequencer.produce_micro_block(vec![payment(&keypair, 0, recipient, dec!(1))]).await.unwrap();
+        let error = block.receipts[0].error.as_ref().unwrap();
+        assert!(error.contains(&format!("belongs to shard {}", shard)), "{}", error);
+        assert_eq!(balance(&sequencer, &keypair.address()), funded);
+        assert_eq!(balance(&sequencer, &recipient), 0);
+    }
+
+    /// Init code deploying a contract that stores its first calldata word
+    /// in slot 0 and logs 42 under topic 7
+    const STORE_AND_LOG: &str = "6013600c60003960136000f3600035600055602a600052600760206000a100";
+
+    fn contract_tx(keypair: &KeyPair, nonce: u64, to: Option<Address>, data: Vec<u8>) -> Transaction {
+        let builder = TransactionBuilder::new().nonce(nonce);
+        let builder = match to {
+            Some(to) => builder.to(to),
+            None => builder,
+        };
+        builder.data(data).unwrap().gas_limit(200_000).unwrap().sign(keypair).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_evm_deploy_and_call() {
+        // Dilithium sender: a native account the EVM knows by its 20 bytes
+        let keypair = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let funded = Account::new(10).balance;
+        let (mut sequencer, _batches, store) = test_sequencer_on(0, funded_store(keypair.address(), funded));
+
+        let deploy = contract_tx(&keypair, 0, None, hex::decode(STORE_AND_LOG).unwrap());
+        let block = sequencer.produce_micro_block(vec![deploy]).await.unwrap();
+        let receipt = &block.receipts[0];
+        assert!(receipt.success, "{:?}", receipt.error);
+        let expected = alloy_primitives::Address::from(keypair.address().evm_bytes()).create(0);
+        let contract = Address::EVM(expected.into_array());
+        assert_eq!(receipt.contract_address, Some(contract));
+        assert!(receipt.gas_used > 21_000 + GasConfig::default().signature_gas(&block.transactions[0]));
+
+        let mut word = [0u8; 32];
+        word[31] = 5;
+        let call = contract_tx(&keypair, 1, Some(contract), word.to_vec());
+        let block = sequencer.produce_micro_block(vec![call]).await.unwrap();
+        let receipt = &block.receipts[0];
+        assert!(receipt.success, "{:?}", receipt.error);
+        let mut topic = [0u8; 32];
+        topic[31] = 7;
+        assert_eq!(receipt.logs.len(), 1);
+        assert_eq!(receipt.logs[0].address, contract);
+        assert_eq!(receipt.logs[0].topics, vec![topic]);
+        assert_eq!(receipt.logs[0].data[31], 42);
+
+        let root = state_trie::parse_root(&sequencer.state_root).unwrap();
+        let pending = PendingState::new(store.as_ref(), root);
+        assert_eq!(pending.storage(&contract, &[0u8; 32]).unwrap(), word);
+        assert_eq!(pending.code(&contract).unwrap().len(), 0x13);
+        let sender = pending.account(&keypair.address()).unwrap();
+        assert_eq!(sender.nonce, 2);
+        let fee = TransactionFee::calculate(&FeeConfig::default(), receipt.gas_used, Decimal::ZERO);
+        assert_eq!(receipt.fee, fee.total_wei());
+    }
+
+    /// Verifier contract: forwards its calldata (a `verify` call) to
+    /// `precompile`, returning true or reverting. Hand-assembled equivalent
+    /// of contracts/contracts/PQSignatureVerifier.sol without the message.
+    fn verifier_contract(precompile: alloy_primitives::Address) -> Vec<u8> {
+        let runtime = format!(
+            "3660006000376020600036600061{}5afa156022576000511560225760206000f35b60006000fd",
+            hex::encode(&precompile[18..])
+        );
+        hex::decode(format!("6028600c60003960286000f3{}", runtime)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pq_verifier_contract() {
+        // An ECDSA relayer submits what a Dilithium owner signed, as for a
+        // quantum-safe smart wallet
+        let relayer = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let funded = Account::new(1_000).balance;
+        let (mut sequencer, _batches, _store) = test_sequencer_on(0, funded_store(relayer.address(), funded));
+
+        let deploy = contract_tx(&relayer, 0, None, verifier_contract(pq_precompiles::DILITHIUM_VERIFY));
+        let block = sequencer.produce_micro_block(vec![deploy]).await.unwrap();
+        let contract = block.receipts[0].contract_address.unwrap();
+
+        let owner = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let message = b"transfer 5 IONX to 0x42, nonce 0";
+        let (signature, public_key) = pq_precompiles::encode(&owner.sign(message).unwrap(), &owner.public_key);
+        let call = |nonce: u64, message: &[u8]| {
+            let data = pq_precompiles::verifyCall {
+                message: message.to_vec().into(),
+                signature: signature.clone().into(),
+                publicKey: public_key.clone().into(),
+            }
+            .abi_encode();
+            TransactionBuilder::new()
+                .nonce(nonce)
+                .to(contract)
+                .data(data)
+                .unwrap()
+                .gas_limit(1_000_000)
+                .unwrap()
+                .sign(&relayer)
+                .unwrap()
+        };
+        let tampered = b"transfer 500 IONX to 0x42, nonce 0";
+        let block = sequencer.produce_micro_block(vec![call(1, message), call(2, tampered)]).await.unwrap();
+        assert!(block.receipts[0].success, "{:?}", block.receipts[0].error);
+        assert!(block.receipts[0].gas_used > GasConfig::default().dilithium_signature);
+        assert_eq!(block.receipts[1].error.as_deref(), Some("Execution reverted"));
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_amounts_rejected() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let (mut sequencer, _batches, _store) = test_sequencer_on(0, funded_store(keypair.address(), Account::new(10).balance));
+
+        // Self-signed amounts past the builder's checks overflow in wei
+        let mut huge_value = TransactionBuilder::new().to(Address::EVM([0x42; 20])).sign(&keypair).unwrap();
+        huge_value.value = Decimal::MAX;
+        huge_value.sign(&keypair).unwrap();
+        let mut huge_price = TransactionBuilder::new().to(Address::EVM([0x42; 20])).sign(&keypair).unwrap();
+        huge_price.gas_price = Decimal::MAX;
+        huge_price.sign(&keypair).unwrap();
+        let e = sequencer.mempool.add_transaction(huge_value.clone()).await.unwrap_err().to_string();
+        assert!(e.contains("out of range"), "{}", e);
+
+        let block = sequencer.produce_micro_block(vec![huge_value, huge_price]).await.unwrap();
+        for receipt in &block.receipts {
+            assert!(receipt.error.as_deref().unwrap().contains("out of range"));
+            assert_eq!(receipt.fee, 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tip_orders_mempool_and_gas_price_caps_base_fee() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let (mut sequencer, _batches, _store) = test_sequencer_on(0, funded_store(keypair.address(), Account::new(10).balance));
+
+        // A high gas price buys no priority; the tip does
+        let other = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let pricey = TransactionBuilder::new().to(Address::EVM([0x42; 20])).gas_price(dec!(0.01)).sign(&other).unwrap();
+        let tipped = TransactionBuilder::new().to(Address::EVM([0x42; 20])).tip(dec!(0.001)).sign(&keypair).unwrap();
+        sequencer.mempool.add_transaction(pricey).await.unwrap();
+        sequencer.mempool.add_transaction(tipped.clone()).await.unwrap();
+        assert_eq!(sequencer.mempool.get_pending_transactions(2).await[0].hash(), tipped.hash());
+
+        let cheap = TransactionBuilder::new().to(Address::EVM([0x42; 20])).gas_price(dec!(0.0000001)).sign(&keypair).unwrap();
+        let block = sequencer.produce_micro_block(vec![cheap]).await.unwrap();
+        assert!(block.receipts[0].error.as_deref().unwrap().contains("below the base fee"));
+        assert_eq!(block.receipts[0].fee, 0);
+    }
+
+    #[tokio::test]
+    async fn test_eip1559_charges_effective_tip_on_gas_used() {
+        // 1000 gwei priority, but a 1500 gwei max fee leaves only 500 gwei
+        // above the 1000 gwei base fee
+        let key = [0x46; 32];
+        let unsigned = |chain_id: u64, to: [u8; 20]| {
+            let warmed = eth_tx::encode_list(&[&alloy_rlp::encode([0x36u8; 20]), &[0xc0]]);
+            vec![
+                alloy_rlp::encode(chain_id),
+                alloy_rlp::encode(0u64),
+                alloy_rlp::encode(1_000_000_000_000u64),
+                alloy_rlp::encode(1_500_000_000_000u64),
+                alloy_rlp::encode(50_000u64),
+                alloy_rlp::encode(to),
+                alloy_rlp::encode(0u64),
+                alloy_rlp::encode(&[][..] as &[u8]),
+                eth_tx::encode_list(&[&warmed]),
+            ]
+        };
+        let sender = eth_tx::decode(&eth_tx::sign_1559(&key, unsigned(BASE_CHAIN_ID, [0; 20]))).unwrap().from;
+        let shard = transaction::shard_for(&sender, 8);
+        let chain_id = BASE_CHAIN_ID + shard as u64;
+        let raw = eth_tx::sign_1559(&key, unsigned(chain_id, sender.evm_bytes()));
+        let tx = eth_tx::decode_transaction(&raw, chain_id, shard).unwrap();
+        let (mut sequencer, _batches, _store) = test_sequencer_on(shard, funded_store(sender, Account::new(10).balance));
+
+        let block = sequencer.produce_micro_block(vec![tx]).await.unwrap();
+        let receipt = &block.receipts[0];
+        assert_eq!(receipt.error, None);
+        // The access list's account is charged as intrinsic gas
+        assert_eq!(receipt.gas_used, 21_000 + ACCESS_LIST_ADDRESS_GAS);
+        let tip = Decimal::new(500, 9) * Decimal::from(receipt.gas_used);
+        let expected = TransactionFee::calculate(&FeeConfig::default(), receipt.gas_used, tip).total_wei();
+        assert_eq!(receipt.fee, expected);
+        assert_eq!(balance(&sequencer, &sender), Account::new(10).balance - expected);
+    }
+
+    #[tokio::test]
+    async fn test_evm_revert_charges_fee() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let funded = Account::new(10).balance;
+        let (mut sequencer, _batches, _store) = test_sequencer_on(0, funded_store(keypair.address(), funded));
+
+        // PUSH1 0 PUSH1 0 REVERT
+        let tx = contract_tx(&keypair, 0, None, hex::decode("60006000fd").unwrap());
+        let block = sequencer.produce_micro_block(vec![tx]).await.unwrap();
+        let receipt = &block.receipts[0];
+        assert_eq!(receipt.error.as_deref(), Some("Execution reverted"));
+        assert_eq!(receipt.contract_address, None);
+        assert!(receipt.fee > 0);
+        assert_eq!(balance(&sequencer, &keypair.address()), funded - receipt.fee);
+
+        // Error(string) payloads are decoded into the receipt:
+        // CODECOPY the payload after the code, REVERT with it
+        let mut init = hex::decode("6064600c60003960646000fd").unwrap();
+        init.extend(Revert::from("not allowed").abi_encode());
+        let block = sequencer.produce_micro_block(vec![contract_tx(&keypair, 1, None, init)]).await.unwrap();
+        assert_eq!(block.receipts[0].error.as_deref(), Some("Execution reverted: not allowed"));
+    }
+
+    #[tokio::test]
+    async fn test_transactions_routed_by_sender() {
+        let (mut sequencer, _batches, _store) = test_sequencer();
+        let keypair = |home: bool| loop {
+            let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+            if (transaction::shard_for(&keypair.address(), 8) == 3) == home {
+                break keypair;
+            }
+        };
+        let tx = |keypair: &KeyPair| TransactionBuilder::new().shard(3).to(Address::EVM([0x42; 20])).sign(keypair).unwrap();
+
+        let error = sequencer.admit_transaction(tx(&keypair(false))).await.unwrap_err();
+        assert!(error.to_string().contains("belongs to shard"));
+        sequencer.admit_transaction(tx(&keypair(true))).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cross_shard_transfer_delivered_once() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let (recipient, shard) = remote_account();
+        let funded = Account::new(10).balance;
+        let (mut source, mut source_batches, _) = test_sequencer_on(0, funded_store(keypair.address(), funded));
+        let (mut destination, mut destination_batches, _) =
+            test_sequencer_on(shard, Arc::new(InMemoryStateStore::new()));
+
+        // Only the recipient's home shard can receive; the failed transfer
+        // still pays its fee
+        let wrong_shard = (shard + 1) % 8;
+        let block = source.produce_micro_block(vec![transfer(&keypair, 0, wrong_shard, &recipient)]).await.unwrap();
+        assert!(block.receipts[0].error.as_ref().unwrap().contains("belongs to shard"));
+        let mut fees = block.receipts[0].fee;
+
+        let block = source.produce_micro_block(vec![transfer(&keypair, 1, shard, &recipient)]).await.unwrap();
+        assert!(block.receipts[0].success);
+        fees += block.receipts[0].fee;
+        let receipt = block.sent_receipts[0].clone();
+        assert_eq!(balance(&source, &keypair.address()), funded - fees - receipt.amount);
+        source.commit_batch().await.unwrap();
+        let sent = source_batches.recv().await.unwrap();
+
+        // The receipt becomes claimable once the source batch is final
+        destination.on_foreign_batch(sent.clone());
+        let block = finalized(0, &[&sent]);
+        source.on_finalized(&block).await.unwrap();
+        destination.on_finalized(&block).await.unwrap();
+        let proof = destination.cross_shard.inbox[0].clone();
+        let claim = destination.produce_micro_block(vec![]).await.unwrap();
+        assert_eq!(claim.claimed_receipts, vec![receipt.id()]);
+        assert_eq!(balance(&destination, &recipient), 0);
+        destination.commit_batch().await.unwrap();
+        let claimed = destination_batches.recv().await.unwrap();
+
+        // Both shards settle the finalized claim
+        let block = finalized(1, &[&claimed]);
+        source.on_finalized(&block).await.unwrap();
+        destination.on_finalized(&block).await.unwrap();
+        source.produce_micro_block(vec![]).await.unwrap();
+        destination.produce_micro_block(vec![]).await.unwrap();
+        assert_eq!(balance(&destination, &recipient), receipt.amount);
+        assert_eq!(status(&destination, &receipt.id()), Some(ReceiptStatus::Credited));
+        assert_eq!(status(&source, &receipt.id()), Some(ReceiptStatus::Delivered));
+
+        // Replaying the proof claims nothing
+        destination.cross_shard.receive(shard, proof);
+        let replay = destination.produce_micro_block(vec![]).await.unwrap();
+        assert!(replay.claimed_receipts.is_empty());
+        assert_eq!(balance(&destination, &recipient), receipt.amount);
+    }
+
+    #[tokio::test]
+    async fn test_staking_transactions_lock_value_and_reach_the_batch() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let funded = Account::new(10).balance;
+        let (mut sequencer, mut batches, _) = test_sequencer_on(0, funded_store(keypair.address(), funded));
+        let staking = |nonce: u64, op: &StakingOp, value: Decimal| {
+            let builder = TransactionBuilder::new().nonce(nonce).to(STAKING_ACCOUNT).data(op.encode()).unwrap();
+            builder.value(value).unwrap().gas_limit(200_000).unwrap().sign(&keypair).unwrap()
+        };
+
+        let register = StakingOp::Register { public_key: keypair.public_key.clone(), commission_rate: dec!(0.05) };
+        let block = sequencer
+            .produce_micro_block(vec![staking(0, &register, dec!(5)), staking(1, &StakingOp::Exit, dec!(1))])
+            .await
+            .unwrap();
+        assert_eq!(block.receipts[0].error, None);
+        assert!(block.receipts[1].error.as_deref().unwrap().contains("carries no value"));
+        let fees = block.receipts[0].fee + block.receipts[1].fee;
+        assert_eq!(balance(&sequencer, &STAKING_ACCOUNT), Account::new(5).balance);
+        assert_eq!(balance(&sequencer, &keypair.address()), funded - Account::new(5).balance - fees);
+
+        // Validators learn of the stake from the batch header
+        sequencer.commit_batch().await.unwrap();
+        let header = BatchHeader::from(&batches.recv().await.unwrap());
+        assert_eq!(header.staking, vec![StakingAction { staker: keypair.address(), op: register, amount: dec!(5) }]);
+    }
+
+    #[tokio::test]
+    async fn test_unclaimed_transfer_refunded() {
+        let keypair = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let funded = Account::new(10).balance;
+        let (mut source, mut batches, _) = test_sequencer_on(0, funded_store(keypair.address(), funded));
+
+        let (recipient, shard) = remote_account();
+        let block = source.produce_micro_block(vec![transfer(&keypair, 0, shard, &recipient)]).await.unwrap();
+        let fee = block.receipts[0].fee;
+        let receipt = block.sent_receipts[0].clone();
+        source.commit_batch().await.unwrap();
+        let sent = batches.recv().await.unwrap();
+        source.on_finalized(&finalized(0, &[&sent])).await.unwrap();
+
+        // Not refunded before the timeout
+        source.on_finalized(&finalized(receipt.expires_at - 1, &[])).await.unwrap();
+        source.produce_micro_block(vec![]).await.unwrap();
+        assert_eq!(status(&source, &receipt.id()), Some(ReceiptStatus::Sent));
+
+        source.on_finalized(&finalized(receipt.expires_at, &[])).await.unwrap();
+        source.produce_micro_block(vec![]).await.unwrap();
+        assert_eq!(status(&source, &receipt.id()), Some(ReceiptStatus::Refunded));
+        assert_eq!(balance(&source, &keypair.address()), funded - fee);
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_signed_and_chained() {
+        let (mut sequencer, mut batches, _store) = test_sequencer();
+
+        sequencer.produce_micro_block(vec![]).await.unwrap();
+        sequencer.produce_micro_block(vec![]).await.unwrap();
+        sequencer.commit_batch().await.unwrap();
+        let first = batches.recv().await.unwrap();
+        first.verify_signature().unwrap();
+        first.verify_contents().unwrap();
+        assert_eq!(first.micro_blocks.len(), 2);
+
+        sequencer.produce_micro_block(vec![]).await.unwrap();
+        sequencer.commit_batch().await.unwrap();
+        let second = batches.recv().await.unwrap();
+        assert_eq!(second.batch_sequence, 1);
+        assert_eq!(second.parent_state_root, first.state_root);
+        assert_eq!(second.micro_blocks[0].parent_hash, first.micro_blocks[1].hash());
+    }
+
+    #[tokio::test]
+    async fn test_finalized_batches_recorded() {
+        let (mut sequencer, mut batches, store) = test_sequencer();
+        let mut events = sequencer.events().subscribe();
+        for _ in 0..2 {
+            sequencer.produce_micro_block(vec![]).await.unwrap();
+            sequencer.commit_batch().await.unwrap();
+        }
+        let first = batches.recv().await.unwrap();
+        let second = batches.recv().await.unwrap();
+
+        // Batches of other shards and unknown batches are not ours
+        let mut foreign = second.clone();
+        foreign.shard_id = 4;
+        let mut forged = second.clone();
+        forged.timestamp += 1;
+        sequencer.on_finalized(&finalized(0, &[&foreign, &forged])).await.unwrap();
+        assert_eq!(store.head().unwrap().unwrap().finalized_batches, 0);
+
+        sequencer.on_finalized(&finalized(7, &[&first, &second])).await.unwrap();
+        assert_eq!(store.head().unwrap().unwrap().finalized_batches, 2);
+
+        let mut finalized_events = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let ChainEvent::BatchFinalized { batch_sequence, base_height } = event {
+                finalized_events.push((batch_sequence, base_height));
+            }
+        }
+        assert_eq!(finalized_events, vec![(0, 7), (1, 7)]);
+    }
+
+    #[tokio::test]
+    async fn test_resumes_after_restart() {
+        let dir = std::env::temp_dir().join(format!("ionova-sequencer-restart-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let keypair = KeyPair::generate(SignatureAlgorithm::ECDSA);
+        let to = local_account();
+
+        let state_root = {
+            let store = Arc::new(FileStateStore::open(&dir).unwrap());
+            fund(store.as_ref(), keypair.address(), Account::new(10).balance);
+            let (mut sequencer, _batches, _store) = test_sequencer_on(0, store);
+            sequencer.produce_micro_block(vec![payment(&keypair, 0, to, dec!(1))]).await.unwrap();
+            sequencer.commit_batch().await.unwrap();
+            sequencer.produce_micro_block(vec![payment(&keypair, 1, to, dec!(1))]).await.unwrap();
+            sequencer.state_root.clone()
+        };
+
+        // Reopened from disk, with the micro-block after the batch still pending
+        let (sequencer, _batches, _store) = test_sequencer_on(0, Arc::new(FileStateStore::open(&dir).unwrap()));
+        assert_eq!(sequencer.sequence_counter, 2);
+        assert_eq!(sequencer.batch_counter, 1);
+        assert_eq!(sequencer.state_root, state_root);
+        assert_eq!(sequencer.micro_blocks.len(), 1);
+        assert_eq!(balance(&sequencer, &to), Account::new(2).balance);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
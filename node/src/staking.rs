@@ -1,7 +1,158 @@
+use anyhow::{anyhow, bail};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+use crate::crypto::{Address, PublicKeyData};
+use crate::emission::EmissionSchedule;
+use crate::eth_tx;
+use crate::finality::{EquivocationEvidence, VotePhase};
+use crate::transaction::Transaction;
+
+/// System address staking transactions are sent to. Registrations and
+/// delegations lock the transaction's value here and `data` holds the
+/// operation (`StakingOp::encode`). Validators apply the operations of
+/// finalized batches to their stake ledger; released stake, like rewards,
+/// stays in the ledger, as nothing pays it back to balances yet.
+pub const STAKING_ACCOUNT: Address = Address::EVM([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0x03,
+]);
+
+/// Staking operation of a transaction to `STAKING_ACCOUNT`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StakingOp {
+    /// Become a validator candidate, self-staking the value. The key must be
+    /// the sender's.
+    Register { public_key: PublicKeyData, commission_rate: Decimal },
+    /// Delegate the value to a validator
+    Delegate { validator: Address },
+    /// Withdraw the sender's delegation, or an operator's self-stake
+    Unbond { validator: Address, amount: Decimal },
+    /// Leave the validator set at a coming boundary
+    Exit,
+    /// Make a jailed validator selectable again
+    Unjail,
+}
+
+impl StakingOp {
+    /// Transaction data of the operation
+    #[cfg(test)]
+    pub fn encode(&self) -> Vec<u8> {
+        use rust_decimal::prelude::ToPrimitive;
+
+        let mut data = Vec::new();
+        match self {
+            Self::Register { public_key, commission_rate } => {
+                data.push(1);
+                let bps = (commission_rate * Decimal::from(10_000)).trunc().to_u16().unwrap_or(u16::MAX);
+                data.extend_from_slice(&bps.to_be_bytes());
+                let (tag, bytes): (u8, &[u8]) = match public_key {
+                    PublicKeyData::Dilithium { bytes } => (1, &bytes[..]),
+                    PublicKeyData::SPHINCSPlus { bytes } => (2, &bytes[..]),
+                    // Not a validator key; rejected by `decode`
+                    _ => (0, &[]),
+                };
+                data.push(tag);
+                data.extend_from_slice(bytes);
+            }
+            Self::Delegate { validator } => {
+                data.push(2);
+                encode_address(&mut data, validator);
+            }
+            Self::Unbond { validator, amount } => {
+                data.push(3);
+                encode_address(&mut data, validator);
+                data.extend_from_slice(&crate::fee_model::to_wei(*amount).to_be_bytes());
+            }
+            Self::Exit => data.push(4),
+            Self::Unjail => data.push(5),
+        }
+        data
+    }
+
+    /// Parse transaction data: a tag, then commission in basis points and
+    /// the key, the validator, or the validator and amount in wei
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        let (&tag, rest) = data.split_first().ok_or_else(|| anyhow!("Missing staking operation"))?;
+        Ok(match (tag, rest) {
+            (1, [high, low, key_tag, key @ ..]) => {
+                let bps = u16::from_be_bytes([*high, *low]);
+                if bps > 10_000 {
+                    bail!("Commission {} bps exceeds 100%", bps);
+                }
+                let public_key = match key_tag {
+                    1 => PublicKeyData::Dilithium { bytes: key.to_vec() },
+                    2 => PublicKeyData::SPHINCSPlus { bytes: key.to_vec() },
+                    _ => bail!("Validators must register a Dilithium or SPHINCS+ key"),
+                };
+                Self::Register { public_key, commission_rate: Decimal::new(bps.into(), 4) }
+            }
+            (2, address) => Self::Delegate { validator: decode_address(address)? },
+            (3, rest) if rest.len() >= 16 => {
+                let (address, amount) = rest.split_at(rest.len() - 16);
+                let amount = u128::from_be_bytes(amount.try_into()?);
+                Self::Unbond { validator: decode_address(address)?, amount: eth_tx::wei_to_ionx(amount)? }
+            }
+            (4, []) => Self::Exit,
+            (5, []) => Self::Unjail,
+            _ => bail!("Invalid staking operation"),
+        })
+    }
+}
+
+/// Staking transaction included in a batch, as validators apply it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StakingAction {
+    pub staker: Address,
+    pub op: StakingOp,
+    /// Value the transaction locked in `STAKING_ACCOUNT`
+    pub amount: Decimal,
+}
+
+impl StakingAction {
+    /// Check a transaction to `STAKING_ACCOUNT`. Registrations and
+    /// delegations must lock a value; the other operations carry none.
+    pub fn from_transaction(tx: &Transaction) -> anyhow::Result<Self> {
+        let op = StakingOp::decode(&tx.data)?;
+        match &op {
+            StakingOp::Register { public_key, .. } if public_key.to_address() != tx.from => {
+                bail!("Validator key does not belong to sender {}", tx.from)
+            }
+            StakingOp::Register { .. } | StakingOp::Delegate { .. } if tx.value <= Decimal::ZERO => {
+                bail!("Staking needs a positive value")
+            }
+            StakingOp::Unbond { amount, .. } if *amount <= Decimal::ZERO => bail!("Unbond amount must be positive"),
+            StakingOp::Unbond { .. } | StakingOp::Exit | StakingOp::Unjail if !tx.value.is_zero() => {
+                bail!("Staking operation carries no value")
+            }
+            _ => {}
+        }
+        Ok(Self { staker: tx.from, op, amount: tx.value })
+    }
+}
+
+#[cfg(test)]
+fn encode_address(buf: &mut Vec<u8>, address: &Address) {
+    match address {
+        Address::EVM(bytes) => {
+            buf.push(0);
+            buf.extend_from_slice(bytes);
+        }
+        Address::Native(bytes) => {
+            buf.push(1);
+            buf.extend_from_slice(bytes);
+        }
+    }
+}
+
+fn decode_address(data: &[u8]) -> anyhow::Result<Address> {
+    Ok(match data {
+        [0, bytes @ ..] if bytes.len() == 20 => Address::EVM(bytes.try_into()?),
+        [1, bytes @ ..] if bytes.len() == 32 => Address::Native(bytes.try_into()?),
+        _ => bail!("Invalid validator address"),
+    })
+}
 
 /// Block reward configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +181,25 @@ impl Default for BlockRewardConfig {
     }
 }
 
+/// Penalties for provable validator misbehaviour
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlashingConfig {
+    /// Share of self-stake and delegations burned for a double sign
+    pub double_sign_fraction: Decimal, // 0.05 (5%)
+
+    /// Whether a double sign also jails the validator
+    pub jail_on_double_sign: bool,
+}
+
+impl Default for SlashingConfig {
+    fn default() -> Self {
+        Self {
+            double_sign_fraction: dec!(0.05),
+            jail_on_double_sign: true,
+        }
+    }
+}
+
 /// Validator stake information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidatorStake {
@@ -38,6 +208,9 @@ pub struct ValidatorStake {
     pub delegated_stake: Decimal,
     pub commission_rate: Decimal, // 0.05 = 5%
     pub delegators: HashMap<String, Decimal>,
+    /// Jailed validators earn no rewards and leave the active set
+    #[serde(default)]
+    pub jailed: bool,
 }
 
 impl ValidatorStake {
@@ -46,24 +219,53 @@ impl ValidatorStake {
     }
 }
 
+/// Stake withdrawn from a validator, slashable until released
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnbondingEntry {
+    pub validator: String,
+    /// None for the operator's self-stake
+    pub delegator: Option<String>,
+    pub amount: Decimal,
+    /// Epoch at which the amount is released
+    pub release_epoch: u64,
+}
+
 /// Staking rewards distributor
 pub struct StakingRewards {
     config: BlockRewardConfig,
+    slashing: SlashingConfig,
     validators: HashMap<String, ValidatorStake>,
+    /// Validators of the current epoch; None pays every unjailed validator
+    active: Option<HashSet<String>>,
+    unbonding: Vec<UnbondingEntry>,
     treasury_balance: Decimal,
+    /// Rewards earned and not yet withdrawn, by operator, delegator or sequencer
+    accrued: HashMap<String, Decimal>,
     genesis_height: u64,
+    /// Offences already punished: (validator, view, phase)
+    slashed_offences: HashSet<(String, u64, VotePhase)>,
 }
 
 impl StakingRewards {
     pub fn new(config: BlockRewardConfig, genesis_height: u64) -> Self {
         Self {
             config,
+            slashing: SlashingConfig::default(),
             validators: HashMap::new(),
+            active: None,
+            unbonding: Vec::new(),
             treasury_balance: Decimal::ZERO,
+            accrued: HashMap::new(),
             genesis_height,
+            slashed_offences: HashSet::new(),
         }
     }
 
+    pub fn with_slashing(mut self, slashing: SlashingConfig) -> Self {
+        self.slashing = slashing;
+        self
+    }
+
     /// Calculate current block reward based on height (with halving)
     pub fn calculate_block_reward(&self, block_height: u64) -> Decimal {
         let blocks_since_genesis = block_height.saturating_sub(self.genesis_height);
@@ -91,7 +293,7 @@ impl StakingRewards {
         let total_stake = self.total_network_stake();
         let mut validator_rewards = HashMap::new();
 
-        for (address, validator) in &self.validators {
+        for (address, validator) in self.validators.iter().filter(|(_, v)| self.earns(v)) {
             let stake_fraction = validator.total_stake() / total_stake;
             let validator_reward = validator_pool * stake_fraction;
             
@@ -123,6 +325,16 @@ impl StakingRewards {
         // Add to treasury
         self.treasury_balance += treasury_amount;
 
+        for (address, reward) in &validator_rewards {
+            *self.accrued.entry(address.clone()).or_default() += reward.operator_reward;
+            for (delegator, amount) in &reward.delegator_rewards {
+                *self.accrued.entry(delegator.clone()).or_default() += *amount;
+            }
+        }
+        for (sequencer, amount) in &sequencer_rewards {
+            *self.accrued.entry(sequencer.clone()).or_default() += *amount;
+        }
+
         BlockRewardDistribution {
             total_reward,
             validator_rewards,
@@ -170,14 +382,25 @@ impl StakingRewards {
         rewards
     }
 
-    /// Get total staked across all validators
+    /// Get total staked across the validators that earn rewards
     fn total_network_stake(&self) -> Decimal {
         self.validators
             .values()
+            .filter(|v| self.earns(v))
             .map(|v| v.total_stake())
             .sum()
     }
 
+    /// Unjailed and, once an epoch set is known, in it
+    fn earns(&self, validator: &ValidatorStake) -> bool {
+        !validator.jailed && self.active.as_ref().is_none_or(|active| active.contains(&validator.operator))
+    }
+
+    /// Restrict rewards to the validators of the current epoch
+    pub fn set_active_validators(&mut self, active: HashSet<String>) {
+        self.active = Some(active);
+    }
+
     /// Add a validator
     pub fn add_validator(&mut self, validator: ValidatorStake) {
         self.validators.insert(validator.operator.clone(), validator);
@@ -199,6 +422,175 @@ impl StakingRewards {
 
         Ok(())
     }
+
+    /// Rewards an operator, delegator or sequencer has earned so far
+    #[cfg(test)]
+    pub fn accrued_rewards(&self, account: &str) -> Decimal {
+        self.accrued.get(account).copied().unwrap_or_default()
+    }
+
+    pub fn validator(&self, address: &str) -> Option<&ValidatorStake> {
+        self.validators.get(address)
+    }
+
+    pub fn validators(&self) -> impl Iterator<Item = &ValidatorStake> {
+        self.validators.values()
+    }
+
+    /// Withdraw self-stake (no delegator) or a delegation. The amount stops
+    /// counting at once but stays slashable until `release_epoch`.
+    pub fn unbond(
+        &mut self,
+        validator_address: &str,
+        delegator: Option<&str>,
+        amount: Decimal,
+        release_epoch: u64,
+    ) -> Result<(), String> {
+        if amount <= Decimal::ZERO {
+            return Err("Unbond amount must be positive".to_string());
+        }
+        let validator = self.validators
+            .get_mut(validator_address)
+            .ok_or("Validator not found")?;
+
+        match delegator {
+            Some(delegator) => {
+                let stake = validator.delegators.get_mut(delegator).ok_or("Delegation not found")?;
+                if *stake < amount {
+                    return Err("Insufficient delegated stake".to_string());
+                }
+                *stake -= amount;
+                if stake.is_zero() {
+                    validator.delegators.remove(delegator);
+                }
+                validator.delegated_stake -= amount;
+            }
+            None => {
+                if validator.self_stake < amount {
+                    return Err("Insufficient self-stake".to_string());
+                }
+                validator.self_stake -= amount;
+            }
+        }
+
+        self.unbonding.push(UnbondingEntry {
+            validator: validator_address.to_string(),
+            delegator: delegator.map(str::to_string),
+            amount,
+            release_epoch,
+        });
+        Ok(())
+    }
+
+    /// Stake still unbonding
+    #[cfg(test)]
+    pub fn unbonding(&self) -> &[UnbondingEntry] {
+        &self.unbonding
+    }
+
+    /// Remove and return the entries released by `epoch`
+    pub fn release_unbonded(&mut self, epoch: u64) -> Vec<UnbondingEntry> {
+        let (released, pending) = self.unbonding.drain(..).partition(|entry| entry.release_epoch <= epoch);
+        self.unbonding = pending;
+        released
+    }
+
+    /// Slash a validator for verified double-sign evidence. Each offence
+    /// (validator, view, phase) is punished once.
+    pub fn slash_double_sign(
+        &mut self,
+        evidence: &EquivocationEvidence,
+        emission: &mut EmissionSchedule,
+    ) -> Result<SlashOutcome, String> {
+        let offence = evidence.offence();
+        if self.slashed_offences.contains(&offence) {
+            return Err("Evidence already processed".to_string());
+        }
+        let outcome = self.slash(
+            evidence.offender(),
+            self.slashing.double_sign_fraction,
+            self.slashing.jail_on_double_sign,
+            emission,
+        )?;
+        self.slashed_offences.insert(offence);
+        Ok(outcome)
+    }
+
+    /// Cut `fraction` of a validator's self-stake, of every delegation and of
+    /// stake still unbonding from it, burn the total, and optionally jail
+    /// the validator
+    pub fn slash(
+        &mut self,
+        validator_address: &str,
+        fraction: Decimal,
+        jail: bool,
+        emission: &mut EmissionSchedule,
+    ) -> Result<SlashOutcome, String> {
+        if fraction < Decimal::ZERO || fraction > Decimal::ONE {
+            return Err("Slash fraction must be between 0 and 1".to_string());
+        }
+        let validator = self.validators
+            .get_mut(validator_address)
+            .ok_or("Validator not found")?;
+
+        let self_stake_slashed = validator.self_stake * fraction;
+        validator.self_stake -= self_stake_slashed;
+
+        let mut delegations_slashed = HashMap::new();
+        for (delegator, stake) in validator.delegators.iter_mut() {
+            let cut = *stake * fraction;
+            *stake -= cut;
+            delegations_slashed.insert(delegator.clone(), cut);
+        }
+        let delegated_slashed: Decimal = delegations_slashed.values().sum();
+        validator.delegated_stake -= delegated_slashed;
+
+        if jail {
+            validator.jailed = true;
+        }
+        let jailed = validator.jailed;
+
+        let mut unbonding_slashed = Decimal::ZERO;
+        for entry in self.unbonding.iter_mut().filter(|entry| entry.validator == validator_address) {
+            let cut = entry.amount * fraction;
+            entry.amount -= cut;
+            unbonding_slashed += cut;
+        }
+
+        let total_slashed = self_stake_slashed + delegated_slashed + unbonding_slashed;
+        emission.burn(total_slashed);
+
+        Ok(SlashOutcome {
+            validator: validator_address.to_string(),
+            self_stake_slashed,
+            delegations_slashed,
+            unbonding_slashed,
+            total_slashed,
+            jailed,
+        })
+    }
+
+    /// Let a jailed validator earn rewards again
+    pub fn unjail(&mut self, validator_address: &str) -> Result<(), String> {
+        let validator = self.validators
+            .get_mut(validator_address)
+            .ok_or("Validator not found")?;
+        validator.jailed = false;
+        Ok(())
+    }
+}
+
+/// Result of slashing one validator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlashOutcome {
+    pub validator: String,
+    pub self_stake_slashed: Decimal,
+    pub delegations_slashed: HashMap<String, Decimal>,
+    #[serde(default)]
+    pub unbonding_slashed: Decimal,
+    /// Burned through the emission schedule
+    pub total_slashed: Decimal,
+    pub jailed: bool,
 }
 
 /// Result of block reward distribution
@@ -219,6 +611,8 @@ pub struct ValidatorReward {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::{KeyPair, SignatureAlgorithm};
+    use crate::transaction::TransactionBuilder;
 
     #[test]
     fn test_block_reward_halving() {
@@ -249,6 +643,7 @@ mod tests {
             delegated_stake: dec!(900000), // 900k delegated
             commission_rate: dec!(0.10),   // 10%
             delegators: HashMap::new(),
+            jailed: false,
         };
 
         let staking = StakingRewards::new(BlockRewardConfig::default(), 0);
@@ -282,4 +677,140 @@ mod tests {
         // APR = 2.5B / 100M = 25x = 2500%
         // With proper delegation across all validators, APR normalizes
     }
+
+    fn staked_validator() -> StakingRewards {
+        let mut staking = StakingRewards::new(BlockRewardConfig::default(), 0);
+        staking.add_validator(ValidatorStake {
+            operator: "val1".to_string(),
+            self_stake: dec!(1000),
+            delegated_stake: Decimal::ZERO,
+            commission_rate: dec!(0.05),
+            delegators: HashMap::new(),
+            jailed: false,
+        });
+        staking.delegate("val1", "alice".to_string(), dec!(600)).unwrap();
+        staking.delegate("val1", "bob".to_string(), dec!(400)).unwrap();
+        staking
+    }
+
+    fn double_sign(validator_id: &str, view: u64) -> EquivocationEvidence {
+        let vote = |block_hash: &str| crate::finality::Vote::Prepare {
+            view,
+            block_hash: block_hash.to_string(),
+            validator_id: validator_id.to_string(),
+            signature: vec![],
+        };
+        EquivocationEvidence { first: vote("aa"), second: vote("bb") }
+    }
+
+    #[test]
+    fn test_slash_cuts_stake_proportionally_and_burns() {
+        let mut staking = staked_validator();
+        let mut emission = EmissionSchedule::default();
+        let supply = emission.circulating_supply;
+
+        let outcome = staking.slash_double_sign(&double_sign("val1", 7), &mut emission).unwrap();
+
+        // 5% of 1000 self-stake and of each delegation
+        assert_eq!(outcome.self_stake_slashed, dec!(50));
+        assert_eq!(outcome.delegations_slashed["alice"], dec!(30));
+        assert_eq!(outcome.delegations_slashed["bob"], dec!(20));
+        assert_eq!(outcome.total_slashed, dec!(100));
+        assert!(outcome.jailed);
+
+        let validator = staking.validator("val1").unwrap();
+        assert_eq!(validator.self_stake, dec!(950));
+        assert_eq!(validator.delegated_stake, dec!(950));
+        assert_eq!(validator.delegators["alice"], dec!(570));
+        assert_eq!(emission.total_burned, dec!(100));
+        assert_eq!(emission.circulating_supply, supply - dec!(100));
+
+        // The same offence is punished once; another view is a new offence
+        assert!(staking.slash_double_sign(&double_sign("val1", 7), &mut emission).is_err());
+        staking.slash_double_sign(&double_sign("val1", 8), &mut emission).unwrap();
+        assert!(staking.slash_double_sign(&double_sign("val2", 8), &mut emission).is_err());
+    }
+
+    #[test]
+    fn test_unbonding_stays_slashable_until_released() {
+        let mut staking = staked_validator();
+        let mut emission = EmissionSchedule::default();
+        staking.unbond("val1", Some("alice"), dec!(600), 3).unwrap();
+        staking.unbond("val1", None, dec!(200), 4).unwrap();
+        assert!(staking.unbond("val1", Some("bob"), dec!(500), 3).is_err());
+
+        // Unbonded stake stops counting at once
+        let validator = staking.validator("val1").unwrap();
+        assert_eq!(validator.total_stake(), dec!(1200));
+        assert!(!validator.delegators.contains_key("alice"));
+
+        // ...but is still cut by a slash
+        let outcome = staking.slash("val1", dec!(0.10), false, &mut emission).unwrap();
+        assert_eq!(outcome.unbonding_slashed, dec!(80));
+        assert_eq!(outcome.total_slashed, dec!(200));
+
+        assert!(staking.release_unbonded(2).is_empty());
+        let released = staking.release_unbonded(3);
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].amount, dec!(540));
+        assert_eq!(staking.unbonding().len(), 1);
+    }
+
+    #[test]
+    fn test_jailed_validator_earns_nothing() {
+        let mut staking = staked_validator().with_slashing(SlashingConfig {
+            double_sign_fraction: dec!(0.10),
+            jail_on_double_sign: true,
+        });
+        staking.add_validator(ValidatorStake {
+            operator: "val2".to_string(),
+            self_stake: dec!(1000),
+            delegated_stake: Decimal::ZERO,
+            commission_rate: dec!(0.05),
+            delegators: HashMap::new(),
+            jailed: false,
+        });
+        let mut emission = EmissionSchedule::default();
+        staking.slash_double_sign(&double_sign("val1", 1), &mut emission).unwrap();
+        assert_eq!(emission.total_burned, dec!(200));
+
+        let distribution = staking.distribute_block_reward(0, &[]);
+        assert!(!distribution.validator_rewards.contains_key("val1"));
+        assert_eq!(
+            distribution.validator_rewards["val2"].operator_reward,
+            distribution.total_reward * dec!(0.70)
+        );
+
+        staking.unjail("val1").unwrap();
+        let distribution = staking.distribute_block_reward(0, &[]);
+        assert!(distribution.validator_rewards.contains_key("val1"));
+    }
+
+    #[test]
+    fn test_staking_transactions() {
+        let key = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let staking_tx = |op: &StakingOp, value: Decimal| {
+            let builder = TransactionBuilder::new().to(STAKING_ACCOUNT).data(op.encode()).unwrap();
+            builder.value(value).unwrap().gas_limit(100_000).unwrap().sign(&key).unwrap()
+        };
+
+        let register = StakingOp::Register { public_key: key.public_key.clone(), commission_rate: dec!(0.05) };
+        let unbond = StakingOp::Unbond { validator: Address::Native([7; 32]), amount: dec!(1.5) };
+        for op in [register.clone(), StakingOp::Delegate { validator: Address::EVM([7; 20]) }, unbond.clone()] {
+            assert_eq!(StakingOp::decode(&op.encode()).unwrap(), op);
+        }
+        let action = StakingAction::from_transaction(&staking_tx(&register, dec!(500))).unwrap();
+        assert_eq!(action, StakingAction { staker: key.address(), op: register.clone(), amount: dec!(500) });
+
+        // Stake must be paid for, withdrawals carry no value, and keys are the sender's own
+        assert!(StakingAction::from_transaction(&staking_tx(&register, Decimal::ZERO)).is_err());
+        assert!(StakingAction::from_transaction(&staking_tx(&unbond, dec!(1))).is_err());
+        let other = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let foreign = StakingOp::Register { public_key: other.public_key, commission_rate: Decimal::ZERO };
+        assert!(StakingAction::from_transaction(&staking_tx(&foreign, dec!(500))).is_err());
+        let ecdsa = KeyPair::generate(SignatureAlgorithm::ECDSA).public_key;
+        let ecdsa = StakingOp::Register { public_key: ecdsa, commission_rate: Decimal::ZERO };
+        assert!(StakingOp::decode(&ecdsa.encode()).is_err());
+        assert!(StakingOp::decode(&[4, 0]).is_err());
+    }
 }
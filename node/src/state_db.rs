@@ -0,0 +1,565 @@
+use alloy_primitives::Bloom;
+use anyhow::{anyhow, Result};
+use redb::backends::InMemoryBackend;
+use redb::{AccessGuard, Database, Key, ReadOnlyTable, ReadableTable, TableDefinition, Value};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+use crate::cross_shard::CrossShardLedger;
+use crate::crypto::Address;
+use crate::finality::FinalizedBlock;
+use crate::genesis::{self, Account, GenesisState};
+use crate::sequencer::{BatchCommitment, MicroBlock};
+use crate::state_trie;
+
+/// Chain position persisted alongside state so a node resumes where it stopped
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainHead {
+    /// Sequence number of the next micro-block to produce
+    pub next_sequence: u64,
+
+    /// Sequence number of the next batch commitment
+    pub next_batch: u64,
+
+    /// Micro-blocks below this sequence are already part of a batch
+    pub batched_through: u64,
+
+    /// State root after the last applied micro-block
+    pub state_root: String,
+
+    /// State root of the genesis state the first micro-block applied to
+    #[serde(default)]
+    pub genesis_root: String,
+
+    /// Batches below this sequence are final on the base layer
+    #[serde(default)]
+    pub finalized_batches: u64,
+
+    /// Open cross-shard transfers
+    #[serde(default)]
+    pub cross_shard: CrossShardLedger,
+}
+
+/// Atomic set of writes applied to a state store in a single commit
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateChanges {
+    /// Account, code and storage writes. `state_trie::apply_changes` folds
+    /// them into trie nodes, which are what the store keeps.
+    pub accounts: Vec<(Address, Account)>,
+    pub code: Vec<(Address, Vec<u8>)>,
+    pub storage: Vec<(Address, [u8; 32], [u8; 32])>,
+    pub micro_blocks: Vec<MicroBlock>,
+    pub batches: Vec<BatchCommitment>,
+    pub head: Option<ChainHead>,
+    /// State trie nodes (and contract code) keyed by keccak256
+    #[serde(default)]
+    pub trie_nodes: Vec<([u8; 32], Vec<u8>)>,
+    /// Base blocks finalized by the validator set, with their QCs
+    #[serde(default)]
+    pub finalized_blocks: Vec<FinalizedBlock>,
+}
+
+impl StateChanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the initial write set from genesis allocations
+    pub fn from_genesis(genesis: &GenesisState) -> Self {
+        let mut changes = Self::new();
+        for (key, account) in &genesis.accounts {
+            changes.set_account(genesis::account_address(key), account.clone());
+        }
+        changes.set_head(ChainHead::default());
+        changes
+    }
+
+    pub fn set_account(&mut self, address: Address, account: Account) {
+        self.accounts.push((address, account));
+    }
+
+    pub fn set_code(&mut self, address: Address, code: Vec<u8>) {
+        self.code.push((address, code));
+    }
+
+    /// Set a storage slot (a zero value clears the slot)
+    pub fn set_storage(&mut self, address: Address, slot: [u8; 32], value: [u8; 32]) {
+        self.storage.push((address, slot, value));
+    }
+
+    pub fn put_micro_block(&mut self, block: MicroBlock) {
+        self.micro_blocks.push(block);
+    }
+
+    pub fn put_batch(&mut self, batch: BatchCommitment) {
+        self.batches.push(batch);
+    }
+
+    pub fn set_head(&mut self, head: ChainHead) {
+        self.head = Some(head);
+    }
+
+    pub fn put_trie_node(&mut self, hash: [u8; 32], node: Vec<u8>) {
+        self.trie_nodes.push((hash, node));
+    }
+
+    pub fn put_finalized_block(&mut self, block: FinalizedBlock) {
+        self.finalized_blocks.push(block);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+            && self.code.is_empty()
+            && self.storage.is_empty()
+            && self.micro_blocks.is_empty()
+            && self.batches.is_empty()
+            && self.head.is_none()
+            && self.trie_nodes.is_empty()
+            && self.finalized_blocks.is_empty()
+    }
+}
+
+/// Persistent node state: state trie nodes (and contract code), micro-blocks,
+/// batch commitments and finalized base blocks
+pub trait StateStore: Send + Sync {
+    /// Get a micro-block by sequence number
+    fn micro_block(&self, sequence: u64) -> Result<Option<MicroBlock>>;
+
+    /// Get a micro-block by its hash
+    fn micro_block_by_hash(&self, hash: &[u8; 32]) -> Result<Option<MicroBlock>>;
+
+    /// Locate an included transaction: (micro-block sequence, index in block)
+    fn transaction_location(&self, tx_hash: &[u8; 32]) -> Result<Option<(u64, usize)>>;
+
+    /// Get a batch commitment by batch sequence number
+    fn batch(&self, batch_sequence: u64) -> Result<Option<BatchCommitment>>;
+
+    /// Get the persisted chain head (None if the store was never initialized)
+    fn head(&self) -> Result<Option<ChainHead>>;
+
+    /// Get an encoded state trie node (or contract code) by hash
+    fn trie_node(&self, hash: &[u8; 32]) -> Result<Option<Vec<u8>>>;
+
+    /// Logs blooms of the micro-blocks in `from..=to` that emitted logs
+    fn log_blooms(&self, from: u64, to: u64) -> Result<Vec<(u64, Bloom)>>;
+
+    /// Get a finalized base block by height
+    fn finalized_block(&self, height: u64) -> Result<Option<FinalizedBlock>>;
+
+    /// Get the highest finalized base block
+    fn latest_finalized(&self) -> Result<Option<FinalizedBlock>>;
+
+    /// Apply a set of changes atomically
+    fn commit(&self, changes: StateChanges) -> Result<()>;
+
+    /// Drop micro-blocks below `before` (never past the last batched one),
+    /// finalized batches made only of them, and the trie nodes no remaining
+    /// state root reaches
+    fn prune(&self, before: u64) -> Result<()>;
+}
+
+const MICRO_BLOCKS: TableDefinition<u64, &[u8]> = TableDefinition::new("micro_blocks");
+const BATCHES: TableDefinition<u64, &[u8]> = TableDefinition::new("batches");
+const FINALIZED: TableDefinition<u64, &[u8]> = TableDefinition::new("finalized");
+const TRIE_NODES: TableDefinition<[u8; 32], &[u8]> = TableDefinition::new("trie_nodes");
+const META: TableDefinition<&str, &[u8]> = TableDefinition::new("meta");
+/// Derived indexes, written as micro-blocks are stored
+const BLOCK_HASHES: TableDefinition<[u8; 32], u64> = TableDefinition::new("block_hashes");
+const TX_LOCATIONS: TableDefinition<[u8; 32], (u64, u64)> = TableDefinition::new("tx_locations");
+const LOG_BLOOMS: TableDefinition<u64, &[u8]> = TableDefinition::new("log_blooms");
+const STATE_ROOTS: TableDefinition<u64, [u8; 32]> = TableDefinition::new("state_roots");
+
+const HEAD_KEY: &str = "head";
+
+/// State store on a redb key-value database
+///
+/// Every commit is one write transaction, durable once `commit` returns.
+/// Reads go to the database rather than to copies in memory, and `prune`
+/// bounds how much history it keeps.
+pub struct KvStateStore {
+    db: Database,
+}
+
+/// In-memory state store (tests and ephemeral devnets)
+pub type InMemoryStateStore = KvStateStore;
+
+/// File-backed state store
+pub type FileStateStore = KvStateStore;
+
+impl KvStateStore {
+    const DB_FILE: &'static str = "state.redb";
+
+    pub fn new() -> Self {
+        Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .map_err(anyhow::Error::from)
+            .and_then(Self::with_tables)
+            .expect("in-memory state database")
+    }
+
+    /// Open (or create) a state store in the given directory
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let store = Self::with_tables(Database::create(dir.join(Self::DB_FILE))?)?;
+        if let Some(head) = store.head()? {
+            info!("Opened state store at {:?}: height {}", dir, head.next_sequence);
+        }
+        Ok(store)
+    }
+
+    /// Create every table up front so reads never find one missing
+    fn with_tables(db: Database) -> Result<Self> {
+        let txn = db.begin_write()?;
+        txn.open_table(MICRO_BLOCKS)?;
+        txn.open_table(BATCHES)?;
+        txn.open_table(FINALIZED)?;
+        txn.open_table(TRIE_NODES)?;
+        txn.open_table(META)?;
+        txn.open_table(BLOCK_HASHES)?;
+        txn.open_table(TX_LOCATIONS)?;
+        txn.open_table(LOG_BLOOMS)?;
+        txn.open_table(STATE_ROOTS)?;
+        txn.commit()?;
+        Ok(Self { db })
+    }
+
+    fn read<K: Key + 'static, V: Value + 'static>(&self, table: TableDefinition<K, V>) -> Result<ReadOnlyTable<K, V>> {
+        Ok(self.db.begin_read()?.open_table(table)?)
+    }
+
+    fn micro_block_at(&self, sequence: Option<u64>) -> Result<Option<MicroBlock>> {
+        match sequence {
+            Some(sequence) => decode(self.read(MICRO_BLOCKS)?.get(sequence)?),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Default for KvStateStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decode a JSON-encoded table value
+fn decode<T: DeserializeOwned>(value: Option<AccessGuard<&[u8]>>) -> Result<Option<T>> {
+    value
+        .map(|value| serde_json::from_slice(value.value()).map_err(|e| anyhow!("Corrupt state entry: {}", e)))
+        .transpose()
+}
+
+impl StateStore for KvStateStore {
+    fn micro_block(&self, sequence: u64) -> Result<Option<MicroBlock>> {
+        self.micro_block_at(Some(sequence))
+    }
+
+    fn micro_block_by_hash(&self, hash: &[u8; 32]) -> Result<Option<MicroBlock>> {
+        let sequence = self.read(BLOCK_HASHES)?.get(hash)?.map(|sequence| sequence.value());
+        self.micro_block_at(sequence)
+    }
+
+    fn transaction_location(&self, tx_hash: &[u8; 32]) -> Result<Option<(u64, usize)>> {
+        Ok(self.read(TX_LOCATIONS)?.get(tx_hash)?.map(|location| {
+            let (sequence, index) = location.value();
+            (sequence, index as usize)
+        }))
+    }
+
+    fn batch(&self, batch_sequence: u64) -> Result<Option<BatchCommitment>> {
+        decode(self.read(BATCHES)?.get(batch_sequence)?)
+    }
+
+    fn head(&self) -> Result<Option<ChainHead>> {
+        decode(self.read(META)?.get(HEAD_KEY)?)
+    }
+
+    fn trie_node(&self, hash: &[u8; 32]) -> Result<Option<Vec<u8>>> {
+        Ok(self.read(TRIE_NODES)?.get(hash)?.map(|node| node.value().to_vec()))
+    }
+
+    fn log_blooms(&self, from: u64, to: u64) -> Result<Vec<(u64, Bloom)>> {
+        if from > to {
+            return Ok(Vec::new());
+        }
+        self.read(LOG_BLOOMS)?
+            .range(from..=to)?
+            .map(|entry| {
+                let (sequence, bloom) = entry?;
+                Ok((sequence.value(), Bloom::from_slice(bloom.value())))
+            })
+            .collect()
+    }
+
+    fn finalized_block(&self, height: u64) -> Result<Option<FinalizedBlock>> {
+        decode(self.read(FINALIZED)?.get(height)?)
+    }
+
+    fn latest_finalized(&self) -> Result<Option<FinalizedBlock>> {
+        decode(self.read(FINALIZED)?.last()?.map(|(_, block)| block))
+    }
+
+    fn commit(&self, changes: StateChanges) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut micro_blocks = txn.open_table(MICRO_BLOCKS)?;
+            let mut block_hashes = txn.open_table(BLOCK_HASHES)?;
+            let mut tx_locations = txn.open_table(TX_LOCATIONS)?;
+            let mut log_blooms = txn.open_table(LOG_BLOOMS)?;
+            let mut state_roots = txn.open_table(STATE_ROOTS)?;
+            for block in &changes.micro_blocks {
+                block_hashes.insert(block.hash(), block.sequence)?;
+                for (index, tx) in block.transactions.iter().enumerate() {
+                    tx_locations.insert(tx.hash(), (block.sequence, index as u64))?;
+                }
+                let bloom = block.logs_bloom();
+                if bloom != Bloom::ZERO {
+                    log_blooms.insert(block.sequence, bloom.as_slice())?;
+                }
+                state_roots.insert(block.sequence, state_trie::parse_root(&block.state_root)?)?;
+                micro_blocks.insert(block.sequence, serde_json::to_vec(block)?.as_slice())?;
+            }
+
+            let mut batches = txn.open_table(BATCHES)?;
+            for batch in &changes.batches {
+                batches.insert(batch.batch_sequence, serde_json::to_vec(batch)?.as_slice())?;
+            }
+
+            if let Some(head) = &changes.head {
+                txn.open_table(META)?.insert(HEAD_KEY, serde_json::to_vec(head)?.as_slice())?;
+            }
+
+            // Nodes are content-addressed: rewriting one is a no-op
+            let mut trie_nodes = txn.open_table(TRIE_NODES)?;
+            for (hash, node) in &changes.trie_nodes {
+                trie_nodes.insert(hash, node.as_slice())?;
+            }
+
+            let mut finalized = txn.open_table(FINALIZED)?;
+            for block in &changes.finalized_blocks {
+                finalized.insert(block.block.height, serde_json::to_vec(block)?.as_slice())?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn prune(&self, before: u64) -> Result<()> {
+        // The write transaction keeps commits out until the sweep is done
+        let txn = self.db.begin_write()?;
+        let head: ChainHead = decode(txn.open_table(META)?.get(HEAD_KEY)?)?
+            .ok_or_else(|| anyhow!("Cannot prune an uninitialized state store"))?;
+        let before = before.min(head.batched_through);
+
+        let (pruned_blocks, pruned_batches) = {
+            let mut micro_blocks = txn.open_table(MICRO_BLOCKS)?;
+            let mut block_hashes = txn.open_table(BLOCK_HASHES)?;
+            let mut tx_locations = txn.open_table(TX_LOCATIONS)?;
+            let mut log_blooms = txn.open_table(LOG_BLOOMS)?;
+            let mut state_roots = txn.open_table(STATE_ROOTS)?;
+            let removed: Vec<MicroBlock> = micro_blocks
+                .extract_from_if(..before, |_, _| true)?
+                .map(|entry| {
+                    let (_, block) = entry?;
+                    serde_json::from_slice(block.value()).map_err(|e| anyhow!("Corrupt micro-block: {}", e))
+                })
+                .collect::<Result<_>>()?;
+            for block in &removed {
+                block_hashes.remove(block.hash())?;
+                for tx in &block.transactions {
+                    tx_locations.remove(tx.hash())?;
+                }
+                log_blooms.remove(block.sequence)?;
+                state_roots.remove(block.sequence)?;
+            }
+
+            // Batches carry their micro-blocks; unfinalized ones may still be resubmitted
+            let mut batches = txn.open_table(BATCHES)?;
+            let mut pruned_batches = 0u64;
+            loop {
+                let Some(sequence) = batches.first()?.map(|(sequence, _)| sequence.value()) else {
+                    break;
+                };
+                let batch: Option<BatchCommitment> = decode(batches.get(sequence)?)?;
+                let covered = batch.is_some_and(|batch| batch.micro_blocks.iter().all(|mb| mb.sequence < before));
+                if sequence >= head.finalized_batches || !covered {
+                    break;
+                }
+                batches.remove(sequence)?;
+                pruned_batches += 1;
+            }
+            (removed.len() as u64, pruned_batches)
+        };
+
+        // Mark from every state root still served, then sweep the rest
+        let mut pruned_nodes = 0u64;
+        if pruned_blocks > 0 {
+            let mut roots = vec![state_trie::parse_root(&head.state_root)?];
+            for entry in txn.open_table(STATE_ROOTS)?.iter()? {
+                roots.push(entry?.1.value());
+            }
+            let mut trie_nodes = txn.open_table(TRIE_NODES)?;
+            let reachable = state_trie::reachable_nodes(&roots, |hash| {
+                Ok(trie_nodes.get(hash)?.map(|node| node.value().to_vec()))
+            })?;
+            trie_nodes.retain(|hash, _| {
+                let keep = reachable.contains(&hash);
+                pruned_nodes += u64::from(!keep);
+                keep
+            })?;
+        }
+        txn.commit()?;
+
+        if pruned_blocks > 0 {
+            info!(
+                "Pruned {} micro-blocks, {} batches and {} trie nodes below micro-block {}",
+                pruned_blocks, pruned_batches, pruned_nodes, before
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "ionova-state-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Fold `changes` into the trie under `root`, commit them and return the new root
+    fn commit_over(store: &dyn StateStore, root: [u8; 32], mut changes: StateChanges) -> [u8; 32] {
+        let root = state_trie::apply_changes(store, root, &mut changes).unwrap();
+        store.commit(changes).unwrap();
+        root
+    }
+
+    fn account(store: &dyn StateStore, root: [u8; 32], address: &Address) -> Option<Account> {
+        state_trie::get_account(store, root, address).unwrap().map(|leaf| leaf.account())
+    }
+
+    #[test]
+    fn test_in_memory_accounts_and_storage() {
+        let store = InMemoryStateStore::new();
+        let addr = Address::EVM([1u8; 20]);
+
+        let mut changes = StateChanges::new();
+        changes.set_account(addr, Account::new(100));
+        changes.set_storage(addr, [1u8; 32], [2u8; 32]);
+        let root = commit_over(&store, state_trie::EMPTY_ROOT, changes);
+
+        assert_eq!(account(&store, root, &addr), Some(Account::new(100)));
+        let storage_root = state_trie::get_account(&store, root, &addr).unwrap().unwrap().storage_root;
+        assert_eq!(state_trie::get_storage(&store, storage_root, &[1u8; 32]).unwrap(), [2u8; 32]);
+
+        // Zero value clears the slot
+        let mut changes = StateChanges::new();
+        changes.set_storage(addr, [1u8; 32], [0u8; 32]);
+        let root = commit_over(&store, root, changes);
+        let storage_root = state_trie::get_account(&store, root, &addr).unwrap().unwrap().storage_root;
+        assert_eq!(storage_root, state_trie::EMPTY_ROOT);
+    }
+
+    #[test]
+    fn test_genesis_seed() {
+        let genesis = GenesisState::create();
+        let store = InMemoryStateStore::new();
+        let root = commit_over(&store, state_trie::EMPTY_ROOT, StateChanges::from_genesis(&genesis));
+
+        let addr = genesis::account_address("ionova1airdropqqqqqqqqqqqqqqqqqqqqqqqqqqqqq");
+        assert_eq!(account(&store, root, &addr), Some(Account::new(10_000_000)));
+        assert_eq!(store.head().unwrap(), Some(ChainHead::default()));
+    }
+
+    #[test]
+    fn test_file_store_survives_restart() {
+        let dir = temp_dir("restart");
+        let addr = Address::Native([7u8; 32]);
+
+        let root = {
+            let store = FileStateStore::open(&dir).unwrap();
+            let mut changes = StateChanges::new();
+            changes.set_account(addr, Account::new(42));
+            changes.set_code(addr, vec![0x60, 0x00]);
+            changes.set_head(ChainHead {
+                next_sequence: 5,
+                next_batch: 1,
+                batched_through: 3,
+                state_root: "ab".repeat(32),
+                genesis_root: "cd".repeat(32),
+                finalized_batches: 0,
+                cross_shard: CrossShardLedger::default(),
+            });
+            commit_over(&store, state_trie::EMPTY_ROOT, changes)
+        };
+
+        let store = FileStateStore::open(&dir).unwrap();
+        assert_eq!(account(&store, root, &addr), Some(Account::new(42)));
+        let code_hash = state_trie::get_account(&store, root, &addr).unwrap().unwrap().code_hash;
+        assert_eq!(store.trie_node(&code_hash).unwrap(), Some(vec![0x60, 0x00]));
+        assert_eq!(store.head().unwrap().unwrap().next_sequence, 5);
+        assert_eq!(store.head().unwrap().unwrap().state_root, "ab".repeat(32));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prune_keeps_retained_history() {
+        let store = InMemoryStateStore::new();
+        let addr = Address::EVM([4u8; 20]);
+
+        // Micro-block n sets the balance to n + 1
+        let mut root = state_trie::EMPTY_ROOT;
+        let mut roots = Vec::new();
+        for sequence in 0..4u64 {
+            let mut changes = StateChanges::new();
+            changes.set_account(addr, Account { balance: sequence as u128 + 1, nonce: 0 });
+            root = state_trie::apply_changes(&store, root, &mut changes).unwrap();
+            roots.push(root);
+            changes.put_micro_block(MicroBlock {
+                shard_id: 0,
+                sequence,
+                parent_hash: [0u8; 32],
+                timestamp: sequence,
+                transactions: vec![],
+                receipts: vec![],
+                state_root: hex::encode(root),
+                sent_receipts: vec![],
+                claimed_receipts: vec![],
+            });
+            changes.set_head(ChainHead {
+                next_sequence: sequence + 1,
+                batched_through: 3,
+                state_root: hex::encode(root),
+                ..ChainHead::default()
+            });
+            store.commit(changes).unwrap();
+        }
+        let first_hash = store.micro_block(0).unwrap().unwrap().hash();
+
+        // Micro-block 3 is not batched yet, so it stays whatever is asked
+        store.prune(10).unwrap();
+        assert!(store.micro_block(2).unwrap().is_none());
+        assert!(store.micro_block_by_hash(&first_hash).unwrap().is_none());
+        assert!(store.micro_block(3).unwrap().is_some());
+
+        // Only the retained root's nodes survive
+        assert!(store.trie_node(&roots[0]).unwrap().is_none());
+        let balance = |root| state_trie::get_account(&store, root, &addr).unwrap().unwrap().balance;
+        assert_eq!(balance(roots[3]), 4);
+    }
+}
@@ -0,0 +1,909 @@
+// Authenticated state: Merkle-Patricia trie over accounts and EVM storage
+
+use alloy_rlp::{Decodable, Encodable, Header, RlpDecodable, RlpEncodable};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+pub use crate::crypto::keccak256;
+use crate::crypto::Address;
+use crate::genesis::Account;
+use crate::state_db::{InMemoryStateStore, StateChanges, StateStore};
+
+/// Root of an empty trie: keccak256(rlp(""))
+pub const EMPTY_ROOT: [u8; 32] = [
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+];
+
+/// Hash of empty contract code: keccak256("")
+pub const EMPTY_CODE_HASH: [u8; 32] = [
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+    0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+];
+
+/// Parse a hex state root ("0x" prefix optional, empty string = empty trie)
+pub fn parse_root(root: &str) -> Result<[u8; 32]> {
+    let hex_part = root.strip_prefix("0x").unwrap_or(root);
+    if hex_part.is_empty() {
+        return Ok(EMPTY_ROOT);
+    }
+    let bytes = hex::decode(hex_part).map_err(|e| anyhow!("Invalid state root: {}", e))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("State root must be 32 bytes"))
+}
+
+/// Decoded trie node. Children are always referenced by hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Leaf { path: Vec<u8>, value: Vec<u8> },
+    Extension { path: Vec<u8>, child: [u8; 32] },
+    Branch { children: Box<[Option<[u8; 32]>; 16]>, value: Option<Vec<u8>> },
+}
+
+impl Node {
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match self {
+            Node::Leaf { path, value } => {
+                encode_path(path, true).as_slice().encode(&mut payload);
+                value.as_slice().encode(&mut payload);
+            }
+            Node::Extension { path, child } => {
+                encode_path(path, false).as_slice().encode(&mut payload);
+                child.encode(&mut payload);
+            }
+            Node::Branch { children, value } => {
+                for child in children.iter() {
+                    match child {
+                        Some(hash) => hash.encode(&mut payload),
+                        None => (&[] as &[u8]).encode(&mut payload),
+                    }
+                }
+                value.as_deref().unwrap_or(&[]).encode(&mut payload);
+            }
+        }
+
+        let mut out = Vec::with_capacity(payload.len() + 3);
+        Header { list: true, payload_length: payload.len() }.encode(&mut out);
+        out.extend_from_slice(&payload);
+        out
+    }
+
+    fn decode(mut buf: &[u8]) -> Result<Self> {
+        let mut payload = Header::decode_bytes(&mut buf, true)
+            .map_err(|e| anyhow!("Invalid trie node: {}", e))?;
+
+        let mut items = Vec::with_capacity(17);
+        while !payload.is_empty() {
+            let item = Header::decode_bytes(&mut payload, false)
+                .map_err(|e| anyhow!("Invalid trie node item: {}", e))?;
+            items.push(item);
+        }
+
+        match items.len() {
+            2 => {
+                let (path, is_leaf) = decode_path(items[0])?;
+                if is_leaf {
+                    Ok(Node::Leaf { path, value: items[1].to_vec() })
+                } else {
+                    Ok(Node::Extension { path, child: to_hash(items[1])? })
+                }
+            }
+            17 => {
+                let mut children: Box<[Option<[u8; 32]>; 16]> = Box::new([None; 16]);
+                for (i, item) in items[..16].iter().enumerate() {
+                    if !item.is_empty() {
+                        children[i] = Some(to_hash(item)?);
+                    }
+                }
+                let value = if items[16].is_empty() { None } else { Some(items[16].to_vec()) };
+                Ok(Node::Branch { children, value })
+            }
+            n => Err(anyhow!("Invalid trie node with {} items", n)),
+        }
+    }
+}
+
+fn to_hash(bytes: &[u8]) -> Result<[u8; 32]> {
+    bytes.try_into().map_err(|_| anyhow!("Invalid child hash length"))
+}
+
+fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// Hex-prefix encoding of a nibble path (Ethereum Yellow Paper, appendix C)
+fn encode_path(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = if is_leaf { 2 } else { 0 };
+    let mut out = Vec::with_capacity(path.len() / 2 + 1);
+    let rest = if path.len() % 2 == 1 {
+        out.push(((flag + 1) << 4) | path[0]);
+        &path[1..]
+    } else {
+        out.push(flag << 4);
+        path
+    };
+    for pair in rest.chunks(2) {
+        out.push((pair[0] << 4) | pair[1]);
+    }
+    out
+}
+
+fn decode_path(encoded: &[u8]) -> Result<(Vec<u8>, bool)> {
+    let first = *encoded.first().ok_or_else(|| anyhow!("Empty trie path"))?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(anyhow!("Invalid hex-prefix flag"));
+    }
+    let mut path = Vec::with_capacity(encoded.len() * 2);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(to_nibbles(&encoded[1..]));
+    Ok((path, flag & 2 == 2))
+}
+
+/// Merkle-Patricia trie over a node source.
+///
+/// Updates never modify existing nodes: every change writes new nodes and
+/// yields a new root, so older roots stay readable for historical queries
+/// and proofs. New nodes are buffered until `take_nodes` is called.
+pub struct MerkleTrie<'a> {
+    nodes: &'a dyn StateStore,
+    root: [u8; 32],
+    pending: HashMap<[u8; 32], Vec<u8>>,
+}
+
+impl<'a> MerkleTrie<'a> {
+    pub fn new(nodes: &'a dyn StateStore, root: [u8; 32]) -> Self {
+        Self {
+            nodes,
+            root,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.root
+    }
+
+    /// Drain nodes written since the last call (hash, encoding)
+    pub fn take_nodes(&mut self) -> Vec<([u8; 32], Vec<u8>)> {
+        self.pending.drain().collect()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let path = to_nibbles(key);
+        let mut current = self.root_node();
+        let mut offset = 0;
+
+        while let Some(hash) = current {
+            match self.load(&hash)? {
+                Node::Leaf { path: leaf_path, value } => {
+                    return Ok((leaf_path[..] == path[offset..]).then_some(value));
+                }
+                Node::Extension { path: ext_path, child } => {
+                    if !path[offset..].starts_with(&ext_path) {
+                        return Ok(None);
+                    }
+                    offset += ext_path.len();
+                    current = Some(child);
+                }
+                Node::Branch { children, value } => {
+                    if offset == path.len() {
+                        return Ok(value);
+                    }
+                    current = children[path[offset] as usize];
+                    offset += 1;
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<()> {
+        if value.is_empty() {
+            return self.remove(key);
+        }
+        let path = to_nibbles(key);
+        let root = self.root_node();
+        self.root = self.insert_at(root, &path, value)?;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Result<()> {
+        let path = to_nibbles(key);
+        let root = self.root_node();
+        self.root = self.remove_at(root, &path)?.unwrap_or(EMPTY_ROOT);
+        Ok(())
+    }
+
+    /// Encoded nodes on the path to `key`. Proves inclusion when the key is
+    /// present and exclusion when it is not.
+    pub fn prove(&self, key: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let path = to_nibbles(key);
+        let mut proof = Vec::new();
+        let mut current = self.root_node();
+        let mut offset = 0;
+
+        while let Some(hash) = current {
+            let encoded = self.load_raw(&hash)?;
+            let node = Node::decode(&encoded)?;
+            proof.push(encoded);
+
+            current = match node {
+                Node::Leaf { .. } => None,
+                Node::Extension { path: ext_path, child } => {
+                    if path[offset..].starts_with(&ext_path) {
+                        offset += ext_path.len();
+                        Some(child)
+                    } else {
+                        None
+                    }
+                }
+                Node::Branch { children, .. } => {
+                    if offset == path.len() {
+                        None
+                    } else {
+                        offset += 1;
+                        children[path[offset - 1] as usize]
+                    }
+                }
+            };
+        }
+
+        Ok(proof)
+    }
+
+    fn root_node(&self) -> Option<[u8; 32]> {
+        (self.root != EMPTY_ROOT).then_some(self.root)
+    }
+
+    fn load_raw(&self, hash: &[u8; 32]) -> Result<Vec<u8>> {
+        if let Some(encoded) = self.pending.get(hash) {
+            return Ok(encoded.clone());
+        }
+        self.nodes
+            .trie_node(hash)?
+            .ok_or_else(|| anyhow!("Missing trie node 0x{}", hex::encode(hash)))
+    }
+
+    fn load(&self, hash: &[u8; 32]) -> Result<Node> {
+        Node::decode(&self.load_raw(hash)?)
+    }
+
+    fn store(&mut self, node: Node) -> [u8; 32] {
+        let encoded = node.encode();
+        let hash = keccak256(&encoded);
+        self.pending.insert(hash, encoded);
+        hash
+    }
+
+    fn insert_at(&mut self, node: Option<[u8; 32]>, path: &[u8], value: Vec<u8>) -> Result<[u8; 32]> {
+        let Some(hash) = node else {
+            return Ok(self.store(Node::Leaf { path: path.to_vec(), value }));
+        };
+
+        match self.load(&hash)? {
+            Node::Leaf { path: leaf_path, value: leaf_value } => {
+                let common = common_prefix(&leaf_path, path);
+                if common == leaf_path.len() && common == path.len() {
+                    return Ok(self.store(Node::Leaf { path: leaf_path, value }));
+                }
+
+                // Split into a branch holding both entries
+                let mut children: Box<[Option<[u8; 32]>; 16]> = Box::new([None; 16]);
+                let mut branch_value = None;
+                for (entry_path, entry_value) in [(leaf_path, leaf_value), (path.to_vec(), value)] {
+                    if entry_path.len() == common {
+                        branch_value = Some(entry_value);
+                    } else {
+                        let child = self.store(Node::Leaf {
+                            path: entry_path[common + 1..].to_vec(),
+                            value: entry_value,
+                        });
+                        children[entry_path[common] as usize] = Some(child);
+                    }
+                }
+                let branch = self.store(Node::Branch { children, value: branch_value });
+                Ok(self.wrap_extension(&path[..common], branch))
+            }
+            Node::Extension { path: ext_path, child } => {
+                let common = common_prefix(&ext_path, path);
+                if common == ext_path.len() {
+                    let new_child = self.insert_at(Some(child), &path[common..], value)?;
+                    return Ok(self.store(Node::Extension { path: ext_path, child: new_child }));
+                }
+
+                // Split the extension at the divergence point
+                let mut children: Box<[Option<[u8; 32]>; 16]> = Box::new([None; 16]);
+                let remainder = &ext_path[common + 1..];
+                children[ext_path[common] as usize] = Some(if remainder.is_empty() {
+                    child
+                } else {
+                    self.store(Node::Extension { path: remainder.to_vec(), child })
+                });
+
+                let mut branch_value = None;
+                if common == path.len() {
+                    branch_value = Some(value);
+                } else {
+                    let leaf = self.store(Node::Leaf { path: path[common + 1..].to_vec(), value });
+                    children[path[common] as usize] = Some(leaf);
+                }
+
+                let branch = self.store(Node::Branch { children, value: branch_value });
+                Ok(self.wrap_extension(&path[..common], branch))
+            }
+            Node::Branch { mut children, value: mut branch_value } => {
+                if path.is_empty() {
+                    branch_value = Some(value);
+                } else {
+                    let index = path[0] as usize;
+                    children[index] = Some(self.insert_at(children[index], &path[1..], value)?);
+                }
+                Ok(self.store(Node::Branch { children, value: branch_value }))
+            }
+        }
+    }
+
+    fn remove_at(&mut self, node: Option<[u8; 32]>, path: &[u8]) -> Result<Option<[u8; 32]>> {
+        let Some(hash) = node else {
+            return Ok(None);
+        };
+
+        match self.load(&hash)? {
+            Node::Leaf { path: leaf_path, .. } => {
+                Ok(if leaf_path == path { None } else { Some(hash) })
+            }
+            Node::Extension { path: ext_path, child } => {
+                if !path.starts_with(&ext_path) {
+                    return Ok(Some(hash));
+                }
+                match self.remove_at(Some(child), &path[ext_path.len()..])? {
+                    None => Ok(None),
+                    Some(new_child) if new_child == child => Ok(Some(hash)),
+                    Some(new_child) => Ok(Some(self.prefix_node(&ext_path, new_child)?)),
+                }
+            }
+            Node::Branch { mut children, value } => {
+                let mut value = value;
+                if path.is_empty() {
+                    if value.is_none() {
+                        return Ok(Some(hash));
+                    }
+                    value = None;
+                } else {
+                    let index = path[0] as usize;
+                    let new_child = self.remove_at(children[index], &path[1..])?;
+                    if new_child == children[index] {
+                        return Ok(Some(hash));
+                    }
+                    children[index] = new_child;
+                }
+
+                // Collapse branches left with a single entry
+                let occupied: Vec<usize> = (0..16).filter(|i| children[*i].is_some()).collect();
+                match (occupied.len(), value) {
+                    (0, None) => Ok(None),
+                    (0, Some(value)) => Ok(Some(self.store(Node::Leaf { path: vec![], value }))),
+                    (1, None) => {
+                        let index = occupied[0];
+                        let child = children[index].expect("occupied child");
+                        Ok(Some(self.prefix_node(&[index as u8], child)?))
+                    }
+                    (_, value) => Ok(Some(self.store(Node::Branch { children, value }))),
+                }
+            }
+        }
+    }
+
+    /// Attach `prefix` in front of `child`, merging paths where possible
+    fn prefix_node(&mut self, prefix: &[u8], child: [u8; 32]) -> Result<[u8; 32]> {
+        match self.load(&child)? {
+            Node::Leaf { path, value } => {
+                Ok(self.store(Node::Leaf { path: [prefix, &path[..]].concat(), value }))
+            }
+            Node::Extension { path, child } => {
+                Ok(self.store(Node::Extension { path: [prefix, &path[..]].concat(), child }))
+            }
+            Node::Branch { .. } => Ok(self.store(Node::Extension { path: prefix.to_vec(), child })),
+        }
+    }
+
+    fn wrap_extension(&mut self, prefix: &[u8], child: [u8; 32]) -> [u8; 32] {
+        if prefix.is_empty() {
+            child
+        } else {
+            self.store(Node::Extension { path: prefix.to_vec(), child })
+        }
+    }
+}
+
+/// Verify a trie proof against a root.
+/// Returns `Some(value)` for an inclusion proof, `None` for a valid
+/// exclusion proof, and an error if the proof does not match the root.
+pub fn verify_proof(root: &[u8; 32], key: &[u8], proof: &[Vec<u8>]) -> Result<Option<Vec<u8>>> {
+    if *root == EMPTY_ROOT {
+        return Ok(None);
+    }
+
+    let by_hash: HashMap<[u8; 32], &Vec<u8>> =
+        proof.iter().map(|node| (keccak256(node), node)).collect();
+
+    let path = to_nibbles(key);
+    let mut expected = *root;
+    let mut offset = 0;
+
+    loop {
+        let encoded = by_hash
+            .get(&expected)
+            .ok_or_else(|| anyhow!("Proof is missing node 0x{}", hex::encode(expected)))?;
+
+        match Node::decode(encoded)? {
+            Node::Leaf { path: leaf_path, value } => {
+                return Ok((leaf_path[..] == path[offset..]).then_some(value));
+            }
+            Node::Extension { path: ext_path, child } => {
+                if !path[offset..].starts_with(&ext_path) {
+                    return Ok(None);
+                }
+                offset += ext_path.len();
+                expected = child;
+            }
+            Node::Branch { children, value } => {
+                if offset == path.len() {
+                    return Ok(value);
+                }
+                match children[path[offset] as usize] {
+                    Some(child) => expected = child,
+                    None => return Ok(None),
+                }
+                offset += 1;
+            }
+        }
+    }
+}
+
+/// Account record committed in the state trie
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, RlpEncodable, RlpDecodable)]
+pub struct AccountLeaf {
+    pub nonce: u64,
+    pub balance: u128,
+    pub storage_root: [u8; 32],
+    pub code_hash: [u8; 32],
+}
+
+impl AccountLeaf {
+    pub fn empty() -> Self {
+        Self {
+            nonce: 0,
+            balance: 0,
+            storage_root: EMPTY_ROOT,
+            code_hash: EMPTY_CODE_HASH,
+        }
+    }
+
+    pub fn account(&self) -> Account {
+        Account {
+            balance: self.balance,
+            nonce: self.nonce,
+        }
+    }
+}
+
+/// Proof of an account's presence (or absence) under a state root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountProof {
+    pub address: Address,
+    pub state_root: [u8; 32],
+    /// None when the proof shows the account does not exist
+    pub account: Option<AccountLeaf>,
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// Trie key for an account
+pub fn account_key(address: &Address) -> [u8; 32] {
+    keccak256(address.as_bytes())
+}
+
+/// Trie key for a storage slot
+pub fn storage_key(slot: &[u8; 32]) -> [u8; 32] {
+    keccak256(slot)
+}
+
+/// Read an account leaf under a state root
+pub fn get_account(nodes: &dyn StateStore, root: [u8; 32], address: &Address) -> Result<Option<AccountLeaf>> {
+    let trie = MerkleTrie::new(nodes, root);
+    trie.get(&account_key(address))?
+        .map(|value| AccountLeaf::decode(&mut value.as_slice()))
+        .transpose()
+        .map_err(|e| anyhow!("Invalid account leaf: {}", e))
+}
+
+/// Read a storage slot under an account's storage root
+pub fn get_storage(nodes: &dyn StateStore, storage_root: [u8; 32], slot: &[u8; 32]) -> Result<[u8; 32]> {
+    let trie = MerkleTrie::new(nodes, storage_root);
+    let mut value = [0u8; 32];
+    if let Some(bytes) = trie.get(&storage_key(slot))? {
+        if bytes.len() > 32 {
+            return Err(anyhow!("Invalid storage value"));
+        }
+        value[32 - bytes.len()..].copy_from_slice(&bytes);
+    }
+    Ok(value)
+}
+
+/// Build an inclusion or exclusion proof for an account
+pub fn prove_account(nodes: &dyn StateStore, root: [u8; 32], address: &Address) -> Result<AccountProof> {
+    let trie = MerkleTrie::new(nodes, root);
+    Ok(AccountProof {
+        address: *address,
+        state_root: root,
+        account: get_account(nodes, root, address)?,
+        proof: trie.prove(&account_key(address))?,
+    })
+}
+
+/// Proof of a storage slot's value (or absence) under a storage root
+pub fn prove_storage(nodes: &dyn StateStore, storage_root: [u8; 32], slot: &[u8; 32]) -> Result<Vec<Vec<u8>>> {
+    MerkleTrie::new(nodes, storage_root).prove(&storage_key(slot))
+}
+
+/// Root of a trie keyed by `rlp(index)`, as used for cross-shard receipt
+/// commitments
+pub fn ordered_trie_root(items: &[Vec<u8>]) -> Result<[u8; 32]> {
+    let scratch = InMemoryStateStore::new();
+    let mut trie = MerkleTrie::new(&scratch, EMPTY_ROOT);
+    for (index, item) in items.iter().enumerate() {
+        trie.insert(&alloy_rlp::encode(index), item.clone())?;
+    }
+    Ok(trie.root())
+}
+
+/// Proof of `items[index]` under `ordered_trie_root(items)`
+pub fn ordered_trie_proof(items: &[Vec<u8>], index: usize) -> Result<Vec<Vec<u8>>> {
+    let scratch = InMemoryStateStore::new();
+    let mut trie = MerkleTrie::new(&scratch, EMPTY_ROOT);
+    for (i, item) in items.iter().enumerate() {
+        trie.insert(&alloy_rlp::encode(i), item.clone())?;
+    }
+    trie.prove(&alloy_rlp::encode(index))
+}
+
+/// Ethereum ordered trie root (keys `rlp(index)`), as in a block header's
+/// transactionsRoot and receiptsRoot. Unlike `MerkleTrie`, nodes shorter
+/// than 32 bytes are embedded in their parent instead of hashed.
+pub fn ethereum_ordered_root(items: &[Vec<u8>]) -> [u8; 32] {
+    let mut entries: Vec<(Vec<u8>, &[u8])> = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| (to_nibbles(&alloy_rlp::encode(index)), item.as_slice()))
+        .collect();
+    entries.sort();
+    ethereum_root(&entries)
+}
+
+/// Ethereum trie root of entries with distinct, sorted nibble paths
+fn ethereum_root(entries: &[(Vec<u8>, &[u8])]) -> [u8; 32] {
+    if entries.is_empty() {
+        return EMPTY_ROOT;
+    }
+    keccak256(&encode_subtrie(entries, 0))
+}
+
+/// Encoded node holding `entries`, whose paths share their first `depth`
+/// nibbles
+fn encode_subtrie(entries: &[(Vec<u8>, &[u8])], depth: usize) -> Vec<u8> {
+    let mut payload = Vec::new();
+    if let [(path, value)] = entries {
+        encode_path(&path[depth..], true).as_slice().encode(&mut payload);
+        (*value).encode(&mut payload);
+    } else {
+        let (first, last) = (&entries[0].0, &entries[entries.len() - 1].0);
+        let shared = common_prefix(&first[depth..], &last[depth..]);
+        if shared > 0 {
+            encode_path(&first[depth..depth + shared], false).as_slice().encode(&mut payload);
+            push_child(&mut payload, encode_subtrie(entries, depth + shared));
+        } else {
+            // Sorted paths: only the first can end at this branch
+            let (value, rest) = match entries.split_first() {
+                Some(((path, value), rest)) if path.len() == depth => (*value, rest),
+                _ => (&[] as &[u8], entries),
+            };
+            for nibble in 0..16 {
+                let start = rest.partition_point(|(path, _)| path[depth] < nibble);
+                let end = rest.partition_point(|(path, _)| path[depth] <= nibble);
+                if start == end {
+                    (&[] as &[u8]).encode(&mut payload);
+                } else {
+                    push_child(&mut payload, encode_subtrie(&rest[start..end], depth + 1));
+                }
+            }
+            value.encode(&mut payload);
+        }
+    }
+
+    let mut out = Vec::with_capacity(payload.len() + 3);
+    Header { list: true, payload_length: payload.len() }.encode(&mut out);
+    out.extend_from_slice(&payload);
+    out
+}
+
+/// Reference a child node: embedded when shorter than its hash
+fn push_child(payload: &mut Vec<u8>, node: Vec<u8>) {
+    if node.len() < 32 {
+        payload.extend_from_slice(&node);
+    } else {
+        keccak256(&node).encode(payload);
+    }
+}
+
+/// Fold a change set into the state trie.
+///
+/// Updates storage tries and account leaves for every account touched by
+/// `changes`, appends the new trie nodes (and contract code, stored by code
+/// hash) to `changes`, and returns the new state root.
+pub fn apply_changes(
+    store: &dyn StateStore,
+    root: [u8; 32],
+    changes: &mut StateChanges,
+) -> Result<[u8; 32]> {
+    // Group writes by account (last write wins, as in the flat store)
+    let mut accounts: BTreeMap<Vec<u8>, (Address, Option<Account>)> = BTreeMap::new();
+    let mut code: HashMap<Address, Vec<u8>> = HashMap::new();
+    let mut storage: HashMap<Address, BTreeMap<[u8; 32], [u8; 32]>> = HashMap::new();
+
+    for (address, account) in &changes.accounts {
+        accounts.insert(address.as_bytes().to_vec(), (*address, Some(account.clone())));
+    }
+    for (address, bytecode) in &changes.code {
+        accounts.entry(address.as_bytes().to_vec()).or_insert((*address, None));
+        code.insert(*address, bytecode.clone());
+    }
+    for (address, slot, value) in &changes.storage {
+        accounts.entry(address.as_bytes().to_vec()).or_insert((*address, None));
+        storage.entry(*address).or_default().insert(*slot, *value);
+    }
+
+    let mut state_trie = MerkleTrie::new(store, root);
+    let mut new_nodes = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (address, account) in accounts.values() {
+        let key = account_key(address);
+        let mut leaf = match state_trie.get(&key)? {
+            Some(value) => AccountLeaf::decode(&mut value.as_slice())
+                .map_err(|e| anyhow!("Invalid account leaf: {}", e))?,
+            None => AccountLeaf::empty(),
+        };
+
+        if let Some(account) = account {
+            leaf.balance = account.balance;
+            leaf.nonce = account.nonce;
+        }
+
+        if let Some(bytecode) = code.get(address) {
+            if bytecode.is_empty() {
+                leaf.code_hash = EMPTY_CODE_HASH;
+            } else {
+                leaf.code_hash = keccak256(bytecode);
+                if seen.insert(leaf.code_hash) {
+                    new_nodes.push((leaf.code_hash, bytecode.clone()));
+                }
+            }
+        }
+
+        if let Some(slots) = storage.get(address) {
+            let mut storage_trie = MerkleTrie::new(store, leaf.storage_root);
+            for (slot, value) in slots {
+                let first_nonzero = value.iter().position(|b| *b != 0).unwrap_or(32);
+                storage_trie.insert(&storage_key(slot), value[first_nonzero..].to_vec())?;
+            }
+            leaf.storage_root = storage_trie.root();
+            new_nodes.extend(storage_trie.take_nodes());
+        }
+
+        state_trie.insert(&key, alloy_rlp::encode(&leaf))?;
+    }
+
+    new_nodes.extend(state_trie.take_nodes());
+    for (hash, node) in new_nodes {
+        changes.put_trie_node(hash, node);
+    }
+
+    Ok(state_trie.root())
+}
+
+/// Hashes of the trie nodes and contract code reachable from `roots`,
+/// following account leaves into their storage tries. `load` fetches an
+/// encoded node by hash.
+pub fn reachable_nodes(
+    roots: &[[u8; 32]],
+    load: impl Fn(&[u8; 32]) -> Result<Option<Vec<u8>>>,
+) -> Result<HashSet<[u8; 32]>> {
+    let mut reachable = HashSet::new();
+    // (node, whether it belongs to the state trie rather than a storage trie)
+    let mut pending: Vec<([u8; 32], bool)> = roots.iter().map(|root| (*root, true)).collect();
+
+    while let Some((hash, in_state_trie)) = pending.pop() {
+        if hash == EMPTY_ROOT || !reachable.insert(hash) {
+            continue;
+        }
+        let encoded = load(&hash)?.ok_or_else(|| anyhow!("Missing trie node 0x{}", hex::encode(hash)))?;
+        let value = match Node::decode(&encoded)? {
+            Node::Leaf { value, .. } => Some(value),
+            Node::Extension { child, .. } => {
+                pending.push((child, in_state_trie));
+                None
+            }
+            Node::Branch { children, value } => {
+                pending.extend(children.iter().flatten().map(|child| (*child, in_state_trie)));
+                value
+            }
+        };
+        if let (Some(value), true) = (value, in_state_trie) {
+            let leaf = AccountLeaf::decode(&mut value.as_slice()).map_err(|e| anyhow!("Invalid account leaf: {}", e))?;
+            pending.push((leaf.storage_root, false));
+            if leaf.code_hash != EMPTY_CODE_HASH {
+                reachable.insert(leaf.code_hash);
+            }
+        }
+    }
+
+    Ok(reachable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_db::InMemoryStateStore;
+
+    #[test]
+    fn test_empty_constants() {
+        assert_eq!(keccak256(&[0x80]), EMPTY_ROOT);
+        assert_eq!(keccak256(&[]), EMPTY_CODE_HASH);
+    }
+
+    #[test]
+    fn test_ethereum_trie_vectors() {
+        // "puppy" and "dogs" from the Ethereum trieanyorder tests
+        let root = |pairs: &[(&str, &str)]| {
+            let mut entries: Vec<(Vec<u8>, &[u8])> = pairs
+                .iter()
+                .map(|(key, value)| (to_nibbles(key.as_bytes()), value.as_bytes()))
+                .collect();
+            entries.sort();
+            hex::encode(ethereum_root(&entries))
+        };
+        assert_eq!(
+            root(&[("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")]),
+            "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+        );
+        assert_eq!(
+            root(&[("doe", "reindeer"), ("dog", "puppy"), ("dogglesworth", "cat")]),
+            "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+        );
+
+        assert_eq!(ethereum_ordered_root(&[]), EMPTY_ROOT);
+        // Without nodes short enough to embed, both tries agree
+        let items: Vec<Vec<u8>> = (0..300u32).map(|i| [i.to_be_bytes().to_vec(), vec![0xab; 60]].concat()).collect();
+        assert_eq!(ethereum_ordered_root(&items), ordered_trie_root(&items).unwrap());
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let nodes = InMemoryStateStore::new();
+        let mut trie = MerkleTrie::new(&nodes, EMPTY_ROOT);
+
+        let keys: Vec<[u8; 32]> = (0u8..50).map(|i| keccak256(&[i])).collect();
+        for (i, key) in keys.iter().enumerate() {
+            trie.insert(key, vec![i as u8 + 1]).unwrap();
+        }
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(trie.get(key).unwrap(), Some(vec![i as u8 + 1]));
+        }
+        assert_eq!(trie.get(&keccak256(b"missing")).unwrap(), None);
+
+        // Removing every key returns the trie to the empty root
+        for key in &keys {
+            trie.remove(key).unwrap();
+        }
+        assert_eq!(trie.root(), EMPTY_ROOT);
+    }
+
+    #[test]
+    fn test_root_is_order_independent() {
+        let nodes = InMemoryStateStore::new();
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = vec![
+            (b"do".to_vec(), b"verb".to_vec()),
+            (b"dog".to_vec(), b"puppy".to_vec()),
+            (b"doge".to_vec(), b"coin".to_vec()),
+            (b"horse".to_vec(), b"stallion".to_vec()),
+        ];
+
+        let mut forward = MerkleTrie::new(&nodes, EMPTY_ROOT);
+        for (k, v) in &entries {
+            forward.insert(k, v.clone()).unwrap();
+        }
+
+        let mut backward = MerkleTrie::new(&nodes, EMPTY_ROOT);
+        for (k, v) in entries.iter().rev() {
+            backward.insert(k, v.clone()).unwrap();
+        }
+
+        assert_eq!(forward.root(), backward.root());
+        assert_eq!(forward.get(b"dog").unwrap(), Some(b"puppy".to_vec()));
+        assert_eq!(forward.get(b"do").unwrap(), Some(b"verb".to_vec()));
+    }
+
+    /// Check an account proof against its state root, as a light client would
+    fn verify(proof: &AccountProof) -> bool {
+        let proven = verify_proof(&proof.state_root, &account_key(&proof.address), &proof.proof).unwrap();
+        proven.map(|value| AccountLeaf::decode(&mut value.as_slice()).unwrap()) == proof.account
+    }
+
+    #[test]
+    fn test_inclusion_and_exclusion_proofs() {
+        let store = InMemoryStateStore::new();
+        let alice = Address::EVM([1u8; 20]);
+        let bob = Address::EVM([2u8; 20]);
+        let carol = Address::Native([3u8; 32]);
+
+        let mut changes = StateChanges::new();
+        changes.set_account(alice, Account::new(10));
+        changes.set_account(bob, Account::new(20));
+        let root = apply_changes(&store, EMPTY_ROOT, &mut changes).unwrap();
+        store.commit(changes).unwrap();
+
+        let proof = prove_account(&store, root, &alice).unwrap();
+        assert_eq!(proof.account.as_ref().unwrap().balance, Account::new(10).balance);
+        assert!(verify(&proof));
+
+        // Carol was never created: exclusion proof
+        let proof = prove_account(&store, root, &carol).unwrap();
+        assert!(proof.account.is_none());
+        assert!(verify(&proof));
+
+        // Tampered balance must not verify
+        let mut forged = prove_account(&store, root, &bob).unwrap();
+        forged.account.as_mut().unwrap().balance += 1;
+        assert!(!verify(&forged));
+    }
+
+    #[test]
+    fn test_storage_and_code_change_root() {
+        let store = InMemoryStateStore::new();
+        let contract = Address::EVM([9u8; 20]);
+
+        let mut changes = StateChanges::new();
+        changes.set_account(contract, Account::new(0));
+        let root1 = apply_changes(&store, EMPTY_ROOT, &mut changes).unwrap();
+        store.commit(changes).unwrap();
+
+        let mut changes = StateChanges::new();
+        changes.set_code(contract, vec![0x60, 0x2a]);
+        let mut slot_value = [0u8; 32];
+        slot_value[31] = 42;
+        changes.set_storage(contract, [0u8; 32], slot_value);
+        let root2 = apply_changes(&store, root1, &mut changes).unwrap();
+        store.commit(changes).unwrap();
+        assert_ne!(root1, root2);
+
+        let leaf = get_account(&store, root2, &contract).unwrap().unwrap();
+        assert_eq!(leaf.code_hash, keccak256(&[0x60, 0x2a]));
+        assert_eq!(store.trie_node(&leaf.code_hash).unwrap(), Some(vec![0x60, 0x2a]));
+        assert_eq!(get_storage(&store, leaf.storage_root, &[0u8; 32]).unwrap(), slot_value);
+
+        // The previous root is still readable
+        let old_leaf = get_account(&store, root1, &contract).unwrap().unwrap();
+        assert_eq!(old_leaf.storage_root, EMPTY_ROOT);
+    }
+}
@@ -0,0 +1,370 @@
+// EVM execution tracers behind debug_traceTransaction / debug_traceCall:
+// geth's default struct logger (one entry per opcode) and its callTracer
+// (the tree of calls and creations).
+
+use alloy_primitives::{Address, U256};
+use revm::interpreter::{
+    CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, Interpreter, OpCode,
+};
+use revm::primitives::{CreateScheme, Log as EvmLog};
+use revm::{Database, EvmContext, Inspector};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::eth_api::{data, quantity};
+use crate::evm_executor::{self, EvmExecutionResult};
+
+/// Struct logger entries recorded when no `limit` is given
+pub const MAX_STRUCT_LOGS: usize = 100_000;
+
+const SLOAD: u8 = 0x54;
+const SSTORE: u8 = 0x55;
+
+/// Options of the default tracer (geth names)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLoggerConfig {
+    #[serde(default)]
+    pub disable_stack: bool,
+    #[serde(default)]
+    pub enable_memory: bool,
+    #[serde(default)]
+    pub disable_storage: bool,
+    /// Most entries to record (0: MAX_STRUCT_LOGS)
+    #[serde(default)]
+    pub limit: usize,
+}
+
+/// One executed opcode
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLog {
+    pub pc: u64,
+    pub op: String,
+    /// Gas left before the opcode
+    pub gas: u64,
+    pub gas_cost: u64,
+    pub depth: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack: Option<Vec<String>>,
+    /// Memory in 32-byte words
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<Vec<String>>,
+    /// Slots of the executing contract seen so far (SLOAD and SSTORE only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Output of the struct logger
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLogTrace {
+    pub gas: u64,
+    pub failed: bool,
+    pub return_value: String,
+    pub struct_logs: Vec<StructLog>,
+}
+
+/// geth's default tracer: records every opcode with gas, stack, memory and
+/// the storage slots touched
+#[derive(Debug, Default)]
+pub struct StructLogger {
+    config: StructLoggerConfig,
+    logs: Vec<StructLog>,
+    /// Slots read or written, per contract
+    storage: HashMap<Address, BTreeMap<U256, U256>>,
+    /// Opcode between `step` and `step_end`: its entry, the gas before it
+    /// and the slot an SLOAD reads
+    current: Option<(StructLog, u64, Option<U256>)>,
+}
+
+impl StructLogger {
+    pub fn new(config: StructLoggerConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    pub fn finish(self, result: &EvmExecutionResult) -> StructLogTrace {
+        StructLogTrace {
+            gas: result.gas_used,
+            failed: !result.success,
+            return_value: data(&result.output),
+            struct_logs: self.logs,
+        }
+    }
+
+    fn limit(&self) -> usize {
+        match self.config.limit {
+            0 => MAX_STRUCT_LOGS,
+            limit => limit.min(MAX_STRUCT_LOGS),
+        }
+    }
+
+    /// Record a slot of `contract` and snapshot what was seen of it
+    fn touch(&mut self, contract: Address, slot: U256, value: U256) -> BTreeMap<String, String> {
+        let seen = self.storage.entry(contract).or_default();
+        seen.insert(slot, value);
+        seen.iter().map(|(slot, value)| (word(slot), word(value))).collect()
+    }
+}
+
+impl<DB: Database> Inspector<DB> for StructLogger {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        if self.logs.len() >= self.limit() {
+            return;
+        }
+        let opcode = interp.current_opcode();
+        let mut log = StructLog {
+            pc: interp.program_counter() as u64,
+            op: match OpCode::new(opcode) {
+                Some(op) => op.as_str().to_string(),
+                None => format!("opcode {:#04x} not defined", opcode),
+            },
+            gas: interp.gas.remaining(),
+            gas_cost: 0,
+            depth: context.journaled_state.depth(),
+            stack: (!self.config.disable_stack)
+                .then(|| interp.stack.data().iter().map(quantity_u256).collect()),
+            memory: self.config.enable_memory.then(|| {
+                interp.shared_memory.context_memory().chunks(32).map(hex::encode).collect()
+            }),
+            storage: None,
+            error: None,
+        };
+        let mut loaded = None;
+        if !self.config.disable_storage {
+            match opcode {
+                SLOAD => loaded = interp.stack.peek(0).ok(),
+                SSTORE => {
+                    if let (Ok(slot), Ok(value)) = (interp.stack.peek(0), interp.stack.peek(1)) {
+                        log.storage = Some(self.touch(interp.contract.target_address, slot, value));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.current = Some((log, interp.gas.remaining(), loaded));
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let Some((mut log, gas_before, loaded)) = self.current.take() else {
+            return;
+        };
+        log.gas_cost = gas_before.saturating_sub(interp.gas.remaining());
+        if interp.instruction_result.is_error() {
+            log.error = Some(format!("{:?}", interp.instruction_result));
+        } else if let (Some(slot), Ok(value)) = (loaded, interp.stack.peek(0)) {
+            // The loaded value replaced the slot on top of the stack
+            log.storage = Some(self.touch(interp.contract.target_address, slot, value));
+        }
+        self.logs.push(log);
+    }
+}
+
+/// Options of `callTracer`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallTracerConfig {
+    /// Trace only the transaction's own call
+    #[serde(default)]
+    pub only_top_call: bool,
+    /// Include the logs each call emitted
+    #[serde(default)]
+    pub with_log: bool,
+}
+
+/// Log emitted inside a call frame
+#[derive(Debug, Clone, Serialize)]
+pub struct CallLog {
+    pub address: String,
+    pub topics: Vec<String>,
+    pub data: String,
+}
+
+/// One call or creation, with the calls it made
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub from: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    pub gas: String,
+    pub gas_used: String,
+    pub input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallFrame>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub logs: Vec<CallLog>,
+}
+
+impl CallFrame {
+    fn new(kind: &'static str, from: Address, to: Option<Address>, value: Option<U256>, gas: u64, input: &[u8]) -> Self {
+        Self {
+            kind,
+            from: data(from.as_slice()),
+            to: to.map(|to| data(to.as_slice())),
+            value: value.map(|value| quantity_u256(&value)),
+            gas: quantity(gas),
+            gas_used: quantity(0u64),
+            input: data(input),
+            output: None,
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    /// Fill in how the frame ended
+    fn end(&mut self, gas_limit: u64, result: &revm::interpreter::InterpreterResult) {
+        self.gas_used = quantity(gas_limit.saturating_sub(result.gas.remaining()));
+        if !result.output.is_empty() {
+            self.output = Some(data(&result.output));
+        }
+        if result.is_revert() {
+            self.error = Some("execution reverted".to_string());
+            self.revert_reason = evm_executor::revert_reason(&result.output);
+        } else if !result.is_ok() {
+            self.error = Some(format!("{:?}", result.result));
+        }
+    }
+}
+
+/// geth's `callTracer`: the tree of calls and creations with gas, value,
+/// input, output and errors
+#[derive(Debug, Default)]
+pub struct CallTracer {
+    config: CallTracerConfig,
+    /// Open frames, innermost last
+    frames: Vec<CallFrame>,
+    /// Nested calls not traced under `onlyTopCall`
+    skipped: usize,
+    root: Option<CallFrame>,
+}
+
+impl CallTracer {
+    pub fn new(config: CallTracerConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    /// The top frame, reporting the transaction's gas limit and gas used
+    pub fn finish(self, result: &EvmExecutionResult, gas_limit: u64) -> Option<CallFrame> {
+        self.root.map(|mut root| {
+            root.gas = quantity(gas_limit);
+            root.gas_used = quantity(result.gas_used);
+            root
+        })
+    }
+
+    /// Whether a frame starting now is traced
+    fn enter(&mut self) -> bool {
+        if self.config.only_top_call && !self.frames.is_empty() {
+            self.skipped += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Close the innermost frame (None if it was not traced)
+    fn exit(&mut self) -> Option<CallFrame> {
+        if self.skipped > 0 {
+            self.skipped -= 1;
+            return None;
+        }
+        self.frames.pop()
+    }
+
+    fn attach(&mut self, frame: CallFrame) {
+        match self.frames.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CallTracer {
+    fn log(&mut self, _interp: &mut Interpreter, _context: &mut EvmContext<DB>, log: &EvmLog) {
+        if !self.config.with_log || self.skipped > 0 {
+            return;
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            frame.logs.push(CallLog {
+                address: data(log.address.as_slice()),
+                topics: log.data.topics().iter().map(|topic| data(topic.as_slice())).collect(),
+                data: data(&log.data.data),
+            });
+        }
+    }
+
+    fn call(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        if self.enter() {
+            let (kind, to, value) = match inputs.scheme {
+                CallScheme::CallCode => ("CALLCODE", inputs.bytecode_address, Some(inputs.call_value())),
+                CallScheme::DelegateCall | CallScheme::ExtDelegateCall => ("DELEGATECALL", inputs.bytecode_address, None),
+                CallScheme::StaticCall | CallScheme::ExtStaticCall => ("STATICCALL", inputs.target_address, None),
+                CallScheme::Call | CallScheme::ExtCall => ("CALL", inputs.target_address, Some(inputs.call_value())),
+            };
+            self.frames
+                .push(CallFrame::new(kind, inputs.caller, Some(to), value, inputs.gas_limit, &inputs.input));
+        }
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut EvmContext<DB>, inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        if let Some(mut frame) = self.exit() {
+            frame.end(inputs.gas_limit, &outcome.result);
+            self.attach(frame);
+        }
+        outcome
+    }
+
+    fn create(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        if self.enter() {
+            let kind = match inputs.scheme {
+                CreateScheme::Create => "CREATE",
+                CreateScheme::Create2 { .. } => "CREATE2",
+            };
+            self.frames
+                .push(CallFrame::new(kind, inputs.caller, None, Some(inputs.value), inputs.gas_limit, &inputs.init_code));
+        }
+        None
+    }
+
+    fn create_end(&mut self, _context: &mut EvmContext<DB>, inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        if let Some(mut frame) = self.exit() {
+            frame.to = outcome.address.map(|address| data(address.as_slice()));
+            frame.end(inputs.gas_limit, &outcome.result);
+            self.attach(frame);
+        }
+        outcome
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        if (self.config.only_top_call && self.frames.len() > 1) || self.skipped > 0 {
+            return;
+        }
+        let frame = CallFrame::new("SELFDESTRUCT", contract, Some(target), Some(value), 0, &[]);
+        self.attach(frame);
+    }
+}
+
+/// Hex quantity of a 256-bit value
+fn quantity_u256(value: &U256) -> String {
+    format!("{:#x}", value)
+}
+
+/// 32-byte word as unprefixed hex (geth's memory and storage format)
+fn word(value: &U256) -> String {
+    hex::encode(value.to_be_bytes::<32>())
+}
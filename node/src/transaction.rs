@@ -1,23 +1,60 @@
 // Transaction structure with post-quantum signature support
 
-use crate::crypto::{Address, PublicKeyData, Signature, SignatureAlgorithm};
-use anyhow::Result;
+use crate::crypto::{keccak256, Address, KeyPair, PublicKeyData, Signature};
+use crate::eth_tx;
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-/// Transaction with multi-signature support
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Wei per IONX (18 decimals)
+pub const IONX_WEI: u64 = 1_000_000_000_000_000_000;
+
+/// Version byte prefixed to the canonical encoding. It lies outside the
+/// EIP-2718 type range (0x00-0x7f) and RLP list prefixes (0xc0+), so raw
+/// bytes can always be told apart from Ethereum transaction envelopes.
+pub const TX_ENCODING_VERSION: u8 = 0x81;
+
+/// Default chain ID of shard 0 (`network.chain_id`); shard N reports the
+/// base chain ID + N via eth_chainId
+pub const BASE_CHAIN_ID: u64 = 31337;
+
+/// Shard addressed by a chain ID, given shard 0's chain ID
+pub fn shard_for_chain_id(base_chain_id: u64, chain_id: u64) -> Result<u8> {
+    chain_id
+        .checked_sub(base_chain_id)
+        .and_then(|shard| u8::try_from(shard).ok())
+        .ok_or_else(|| anyhow!("Unknown chain ID {}", chain_id))
+}
+
+/// Home shard of an account: keccak256 of its EVM account bytes, reduced
+/// modulo the shard count. Native and EVM forms of an account share a shard.
+/// Contracts live on the shard that deployed them instead.
+pub fn shard_for(address: &Address, shard_count: u8) -> u8 {
+    let hash = keccak256(&address.evm_bytes());
+    let word = u64::from_be_bytes(hash[..8].try_into().expect("8 bytes"));
+    (word % shard_count.max(1) as u64) as u8
+}
+
+/// Canonical transaction used by the RPC, mempool, sequencer and validators
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
+    /// Chain ID of the target shard (replay protection across networks)
+    pub chain_id: u64,
+
+    /// Target shard
+    pub shard_id: u8,
+
     /// Transaction nonce (prevents replay attacks)
     pub nonce: u64,
     
     /// Sender address
     pub from: Address,
     
-    /// Recipient address
-    pub to: Address,
+    /// Recipient address (None = contract creation)
+    pub to: Option<Address>,
     
     /// Amount to transfer (in IONX)
     pub value: Decimal,
@@ -25,8 +62,12 @@ pub struct Transaction {
     /// Gas limit
     pub gas_limit: u64,
     
-    /// Gas price (in IONX per gas unit)
+    /// Most the sender pays per gas unit (in IONX); the sequencer charges
+    /// the base fee per gas, which must not exceed it
     pub gas_price: Decimal,
+
+    /// Priority tip paid to the sequencer (in IONX)
+    pub tip: Decimal,
     
     /// Optional data payload
     pub data: Vec<u8>,
@@ -39,74 +80,157 @@ pub struct Transaction {
     
     /// SECURITY FIX M-4: Transaction expiry (Unix timestamp)
     pub expiry: Option<u64>,
+
+    /// Signed Ethereum envelope (EIP-2718 bytes) this transaction was decoded
+    /// from. When present it is the authority for the hash and signature.
+    #[serde(default)]
+    pub eth_envelope: Option<Vec<u8>>,
 }
 
 impl Transaction {
-    /// Create hash of transaction for signing
+    /// Hash of the unsigned fields; this is the message that gets signed
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut buf = Vec::with_capacity(128 + self.data.len());
+        self.encode_unsigned(&mut buf);
+        sha256(&buf)
+    }
+
+    /// Transaction hash (identifier): hash of the full canonical encoding,
+    /// or the Ethereum hash (keccak256 of the envelope) for Ethereum transactions
     pub fn hash(&self) -> [u8; 32] {
-        let mut hasher = Sha256::new();
-        
-        // Hash transaction fields (excluding signature)
-        hasher.update(self.nonce.to_le_bytes());
-        hasher.update(self.value.to_string().as_bytes());
-        hasher.update(self.gas_limit.to_le_bytes());
-        hasher.update(self.gas_price.to_string().as_bytes());
-        hasher.update(&self.data);
-        
-        let result = hasher.finalize();
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&result);
-        hash
+        match &self.eth_envelope {
+            Some(envelope) => keccak256(envelope),
+            None => sha256(&self.encode()),
+        }
     }
     
     /// Verify transaction signature
     pub fn verify_signature(&self) -> Result<bool> {
-        let message = self.hash();
+        // Ethereum transactions: the envelope must decode to exactly this
+        // transaction, which re-recovers the sender from its signature
+        if let Some(envelope) = &self.eth_envelope {
+            let decoded = eth_tx::decode(envelope)?.into_transaction(envelope.clone(), self.shard_id)?;
+            return Ok(decoded == *self);
+        }
+
+        // The key must belong to the claimed sender
+        if self.public_key.to_address() != self.from {
+            return Ok(false);
+        }
+        let message = self.signing_hash();
         self.signature.verify(&message, &self.public_key)
     }
-    
-    /// Calculate gas cost with signature type consideration
-    /// SECURITY FIX M-1: Added overflow protection
-    pub fn calculate_gas_cost(&self) -> Result<u64> {
-        let base_gas = 21_000u64; // Base transaction cost
-        
-        // Signature verification cost varies by algorithm
-        let sig_gas = match self.signature.algorithm() {
-            SignatureAlgorithm::ECDSA => 3_000,
-            SignatureAlgorithm::Dilithium => {
-                // Higher verification cost, but subsidized 50%
-                let actual_cost = 50_000;
-                actual_cost / 2 // Subsidy during migration period
+
+    /// Sign the transaction in place with the sender's key pair, as tests
+    /// do after editing a field
+    #[cfg(test)]
+    pub fn sign(&mut self, keypair: &KeyPair) -> Result<()> {
+        self.from = keypair.address();
+        self.public_key = keypair.public_key.clone();
+        self.signature = keypair.sign(&self.signing_hash())?;
+        Ok(())
+    }
+
+    /// Canonical binary encoding (see `decode` for the layout)
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(256 + self.data.len() + self.signature.size());
+        self.encode_unsigned(&mut buf);
+        encode_signature(&mut buf, &self.signature);
+        encode_public_key(&mut buf, &self.public_key);
+        match &self.eth_envelope {
+            Some(envelope) => {
+                buf.push(1);
+                encode_bytes(&mut buf, envelope);
             }
-            SignatureAlgorithm::SPHINCSPlus => {
-                // SPHINCS+ is slower
-                let actual_cost = 70_000;
-                actual_cost / 2 // Subsidy
+            None => buf.push(0),
+        }
+        buf
+    }
+
+    /// Decode the canonical encoding. Rejects trailing bytes and
+    /// non-normalized amounts so every transaction has exactly one encoding.
+    ///
+    /// Layout (integers big-endian, byte strings u32 length-prefixed):
+    /// version u8 | chain_id u64 | shard_id u8 | nonce u64 | from | to? |
+    /// value dec | gas_limit u64 | gas_price dec | tip dec | data | expiry? |
+    /// signature | public_key | eth_envelope?
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut r = Reader::new(bytes);
+
+        let version = r.u8()?;
+        if version != TX_ENCODING_VERSION {
+            return Err(anyhow!("Unsupported transaction encoding version {}", version));
+        }
+
+        let tx = Transaction {
+            chain_id: r.u64()?,
+            shard_id: r.u8()?,
+            nonce: r.u64()?,
+            from: r.address()?,
+            to: if r.flag()? { Some(r.address()?) } else { None },
+            value: r.decimal()?,
+            gas_limit: r.u64()?,
+            gas_price: r.decimal()?,
+            tip: r.decimal()?,
+            data: r.bytes()?.to_vec(),
+            expiry: if r.flag()? { Some(r.u64()?) } else { None },
+            signature: r.signature()?,
+            public_key: r.public_key()?,
+            eth_envelope: if r.flag()? { Some(r.bytes()?.to_vec()) } else { None },
+        };
+
+        if !r.is_empty() {
+            return Err(anyhow!("Trailing bytes after transaction"));
+        }
+        Ok(tx)
+    }
+
+    fn encode_unsigned(&self, buf: &mut Vec<u8>) {
+        buf.push(TX_ENCODING_VERSION);
+        buf.extend_from_slice(&self.chain_id.to_be_bytes());
+        buf.push(self.shard_id);
+        buf.extend_from_slice(&self.nonce.to_be_bytes());
+        encode_address(buf, &self.from);
+        match &self.to {
+            Some(to) => {
+                buf.push(1);
+                encode_address(buf, to);
             }
-            SignatureAlgorithm::Hybrid => {
-                // Both signatures verified
-                3_000 + 25_000 // ECDSA + subsidized PQ
+            None => buf.push(0),
+        }
+        encode_decimal(buf, self.value);
+        buf.extend_from_slice(&self.gas_limit.to_be_bytes());
+        encode_decimal(buf, self.gas_price);
+        encode_decimal(buf, self.tip);
+        encode_bytes(buf, &self.data);
+        match self.expiry {
+            Some(expiry) => {
+                buf.push(1);
+                buf.extend_from_slice(&expiry.to_be_bytes());
             }
-        };
-        
-        // SECURITY FIX M-1: Protected data gas calculation
-        let data_gas = (self.data.len() as u64)
-            .checked_mul(16)
-            .ok_or(anyhow::anyhow!("Data size causes gas overflow"))?;
-        
-        // SECURITY FIX M-1: Protected total calculation
-        let total = base_gas
-            .checked_add(sig_gas)
-            .and_then(|sum| sum.checked_add(data_gas))
-            .ok_or(anyhow::anyhow!("Total gas cost overflow"))?;
-        
-        Ok(total)
+            None => buf.push(0),
+        }
     }
-    
-    /// Calculate total fee (gas cost × gas price)
-    pub fn calculate_fee(&self) -> Result<Decimal> {
-        let gas_used = Decimal::from(self.calculate_gas_cost()?);
-        Ok(gas_used * self.gas_price)
+
+    /// Transferred value in wei
+    pub fn value_wei(&self) -> Result<u128> {
+        to_wei(self.value)
+            .and_then(|wei| wei.to_u128())
+            .ok_or_else(|| anyhow!("Value {} IONX out of range", self.value))
+    }
+
+    /// Priority tip in wei
+    pub fn tip_wei(&self) -> Result<u128> {
+        to_wei(self.tip)
+            .and_then(|wei| wei.to_u128())
+            .ok_or_else(|| anyhow!("Tip {} IONX out of range", self.tip))
+    }
+
+    /// Gas price in wei per gas unit
+    pub fn gas_price_wei(&self) -> Result<u64> {
+        to_wei(self.gas_price)
+            .and_then(|wei| wei.to_u64())
+            .ok_or_else(|| anyhow!("Gas price {} IONX out of range", self.gas_price))
     }
     
     /// SECURITY FIX M-4: Validate nonce against account state
@@ -132,27 +256,46 @@ impl Transaction {
 
 /// Transaction builder for easier construction
 pub struct TransactionBuilder {
+    chain_id: u64,
+    shard_id: u8,
     nonce: u64,
     from: Option<Address>,
     to: Option<Address>,
     value: Decimal,
     gas_limit: u64,
     gas_price: Decimal,
+    tip: Decimal,
     data: Vec<u8>,
+    expiry: Option<u64>,
 }
 
 impl TransactionBuilder {
     pub fn new() -> Self {
         Self {
+            chain_id: BASE_CHAIN_ID,
+            shard_id: 0,
             nonce: 0,
             from: None,
             to: None,
             value: dec!(0),
             gas_limit: 21_000,
             gas_price: dec!(0.000001), // Default gas price
+            tip: dec!(0),
             data: Vec::new(),
+            expiry: None,
         }
     }
+
+    pub fn shard(mut self, shard_id: u8) -> Self {
+        self.shard_id = shard_id;
+        self
+    }
+
+    /// Chain ID of the target shard, as reported by its eth_chainId
+    pub fn chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
     
     pub fn nonce(mut self, nonce: u64) -> Self {
         self.nonce = nonce;
@@ -197,6 +340,11 @@ impl TransactionBuilder {
         self.gas_price = gas_price;
         self
     }
+
+    pub fn tip(mut self, tip: Decimal) -> Self {
+        self.tip = tip;
+        self
+    }
     
     /// SECURITY FIX H-2: Validate data size
     pub fn data(mut self, data: Vec<u8>) -> Result<Self> {
@@ -219,18 +367,243 @@ impl TransactionBuilder {
         public_key: PublicKeyData,
     ) -> Result<Transaction> {
         Ok(Transaction {
+            chain_id: self.chain_id,
+            shard_id: self.shard_id,
             nonce: self.nonce,
             from: self.from.ok_or(anyhow::anyhow!("From address required"))?,
-            to: self.to.ok_or(anyhow::anyhow!("To address required"))?,
+            to: self.to,
             value: self.value,
             gas_limit: self.gas_limit,
             gas_price: self.gas_price,
+            tip: self.tip,
             data: self.data,
             signature,
             public_key,
-            expiry: None,  // Can be set via expiry() method
+            expiry: self.expiry,
+            eth_envelope: None,
         })
     }
+
+    /// Build and sign with the sender's key pair (sets `from`)
+    pub fn sign(mut self, keypair: &KeyPair) -> Result<Transaction> {
+        self.from = Some(keypair.address());
+        let mut tx = self.build(
+            Signature::ECDSA { r: [0u8; 32], s: [0u8; 32], v: 0 },
+            keypair.public_key.clone(),
+        )?;
+        tx.signature = keypair.sign(&tx.signing_hash())?;
+        Ok(tx)
+    }
+}
+
+/// IONX amount in wei, `None` on overflow
+fn to_wei(amount: Decimal) -> Option<Decimal> {
+    amount.checked_mul(Decimal::from(IONX_WEI)).map(|wei| wei.trunc())
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&Sha256::digest(data));
+    hash
+}
+
+fn encode_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+/// Decimals are normalized first so that 1.0 and 1 encode identically
+fn encode_decimal(buf: &mut Vec<u8>, value: Decimal) {
+    buf.extend_from_slice(&value.normalize().serialize());
+}
+
+fn encode_address(buf: &mut Vec<u8>, address: &Address) {
+    match address {
+        Address::EVM(bytes) => {
+            buf.push(0);
+            buf.extend_from_slice(bytes);
+        }
+        Address::Native(bytes) => {
+            buf.push(1);
+            buf.extend_from_slice(bytes);
+        }
+    }
+}
+
+fn encode_signature(buf: &mut Vec<u8>, signature: &Signature) {
+    match signature {
+        Signature::ECDSA { r, s, v } => {
+            buf.push(0);
+            buf.extend_from_slice(r);
+            buf.extend_from_slice(s);
+            buf.push(*v);
+        }
+        Signature::Dilithium { data } => {
+            buf.push(1);
+            encode_bytes(buf, data);
+        }
+        Signature::SPHINCSPlus { data } => {
+            buf.push(2);
+            encode_bytes(buf, data);
+        }
+        Signature::Hybrid { ecdsa, pq } => {
+            buf.push(3);
+            encode_signature(buf, ecdsa);
+            encode_signature(buf, pq);
+        }
+    }
+}
+
+fn encode_public_key(buf: &mut Vec<u8>, public_key: &PublicKeyData) {
+    match public_key {
+        PublicKeyData::ECDSA { bytes } => {
+            buf.push(0);
+            buf.extend_from_slice(bytes);
+        }
+        PublicKeyData::Dilithium { bytes } => {
+            buf.push(1);
+            encode_bytes(buf, bytes);
+        }
+        PublicKeyData::SPHINCSPlus { bytes } => {
+            buf.push(2);
+            encode_bytes(buf, bytes);
+        }
+        PublicKeyData::Hybrid { ecdsa, pq } => {
+            buf.push(3);
+            encode_public_key(buf, ecdsa);
+            encode_public_key(buf, pq);
+        }
+    }
+}
+
+/// Cursor over a canonical encoding
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(anyhow!("Transaction encoding truncated"));
+        }
+        let (head, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    fn flag(&mut self) -> Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(anyhow!("Invalid option flag {}", other)),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = u32::from_be_bytes(self.array()?) as usize;
+        self.take(len)
+    }
+
+    fn decimal(&mut self) -> Result<Decimal> {
+        let raw: [u8; 16] = self.array()?;
+        let value = Decimal::deserialize(raw);
+        if value.normalize().serialize() != raw {
+            return Err(anyhow!("Non-canonical decimal encoding"));
+        }
+        Ok(value)
+    }
+
+    fn address(&mut self) -> Result<Address> {
+        match self.u8()? {
+            0 => Ok(Address::EVM(self.array()?)),
+            1 => Ok(Address::Native(self.array()?)),
+            other => Err(anyhow!("Invalid address type {}", other)),
+        }
+    }
+
+    fn signature(&mut self) -> Result<Signature> {
+        match self.u8()? {
+            3 => {
+                // The parts of a hybrid are read without recursing again,
+                // so nested hybrid tags cannot exhaust the stack
+                let ecdsa = self.single_signature()?;
+                let pq = self.single_signature()?;
+                if !matches!(ecdsa, Signature::ECDSA { .. }) {
+                    return Err(anyhow!("Invalid hybrid signature"));
+                }
+                Ok(Signature::Hybrid { ecdsa: Box::new(ecdsa), pq: Box::new(pq) })
+            }
+            tag => self.signature_of(tag),
+        }
+    }
+
+    fn single_signature(&mut self) -> Result<Signature> {
+        let tag = self.u8()?;
+        self.signature_of(tag)
+    }
+
+    /// Non-hybrid signature with type `tag`
+    fn signature_of(&mut self, tag: u8) -> Result<Signature> {
+        match tag {
+            0 => Ok(Signature::ECDSA { r: self.array()?, s: self.array()?, v: self.u8()? }),
+            1 => Ok(Signature::Dilithium { data: self.bytes()?.to_vec() }),
+            2 => Ok(Signature::SPHINCSPlus { data: self.bytes()?.to_vec() }),
+            3 => Err(anyhow!("Invalid hybrid signature")),
+            other => Err(anyhow!("Invalid signature type {}", other)),
+        }
+    }
+
+    fn public_key(&mut self) -> Result<PublicKeyData> {
+        match self.u8()? {
+            3 => {
+                let ecdsa = self.single_public_key()?;
+                let pq = self.single_public_key()?;
+                if !matches!(ecdsa, PublicKeyData::ECDSA { .. }) {
+                    return Err(anyhow!("Invalid hybrid public key"));
+                }
+                Ok(PublicKeyData::Hybrid { ecdsa: Box::new(ecdsa), pq: Box::new(pq) })
+            }
+            tag => self.public_key_of(tag),
+        }
+    }
+
+    fn single_public_key(&mut self) -> Result<PublicKeyData> {
+        let tag = self.u8()?;
+        self.public_key_of(tag)
+    }
+
+    /// Non-hybrid public key with type `tag`
+    fn public_key_of(&mut self, tag: u8) -> Result<PublicKeyData> {
+        match tag {
+            0 => Ok(PublicKeyData::ECDSA { bytes: self.array()? }),
+            1 => Ok(PublicKeyData::Dilithium { bytes: self.bytes()?.to_vec() }),
+            2 => Ok(PublicKeyData::SPHINCSPlus { bytes: self.bytes()?.to_vec() }),
+            3 => Err(anyhow!("Invalid hybrid public key")),
+            other => Err(anyhow!("Invalid public key type {}", other)),
+        }
+    }
 }
 
 impl Default for TransactionBuilder {
@@ -242,47 +615,26 @@ impl Default for TransactionBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::crypto::Signature;
+    use crate::crypto::{Signature, SignatureAlgorithm};
 
     #[test]
-    fn test_gas_cost_ecdsa() {
-        let tx = Transaction {
-            nonce: 1,
-            from: Address::EVM([0u8; 20]),
-            to: Address::EVM([1u8; 20]),
-            value: dec!(100),
-            gas_limit: 21_000,
-            gas_price: dec!(0.000001),
-            data: vec![],
-            signature: Signature::ECDSA {
-                r: [0u8; 32],
-                s: [0u8; 32],
-                v: 0,
-            },
-            public_key: PublicKeyData::ECDSA { bytes: [0u8; 33] },
-        };
-        
-        assert_eq!(tx.calculate_gas_cost().unwrap(), 24_000); // 21k base + 3k sig
-    }
-    
-    #[test]
-    fn test_gas_cost_dilithium_subsidized() {
-        let tx = Transaction {
-            nonce: 1,
-            from: Address::EVM([0u8; 20]),
-            to: Address::EVM([1u8; 20]),
-            value: dec!(100),
-            gas_limit: 50_000,
-            gas_price: dec!(0.000001),
-            data: vec![],
-            signature: Signature::Dilithium { data: vec![0u8; 2420] },
-            public_key: PublicKeyData::Dilithium { bytes: vec![0u8; 2528] },
-        };
-        
-        // 21k base + 25k subsidized (50k / 2)
-        assert_eq!(tx.calculate_gas_cost().unwrap(), 46_000);
+    fn test_shard_for_is_deterministic() {
+        let evm = Address::EVM([0x42; 20]);
+        let mut native = [0u8; 32];
+        native[12..].copy_from_slice(&[0x42; 20]);
+        assert_eq!(shard_for(&evm, 8), shard_for(&Address::Native(native), 8));
+        assert_eq!(shard_for(&evm, 1), 0);
+
+        // Accounts spread over every shard
+        let mut seen = [false; 8];
+        for i in 0..=255u8 {
+            let shard = shard_for(&Address::EVM([i; 20]), 8);
+            assert!(shard < 8);
+            seen[shard as usize] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
     }
-    
+
     #[test]
     fn test_transaction_builder() {
         let from = Address::EVM([0u8; 20]);
@@ -293,7 +645,7 @@ mod tests {
             .from(from)
             .to(to)
             .value(dec!(50)).unwrap()
-            .gas_limit(25_000)
+            .gas_limit(25_000).unwrap()
             .build(
                 Signature::ECDSA {
                     r: [0u8; 32],
@@ -308,4 +660,83 @@ mod tests {
         assert_eq!(tx.value, dec!(50));
         assert_eq!(tx.gas_limit, 25_000);
     }
+
+    fn signed_tx(algorithm: SignatureAlgorithm) -> Transaction {
+        TransactionBuilder::new()
+            .shard(2)
+            .chain_id(31339)
+            .nonce(7)
+            .to(Address::EVM([1u8; 20]))
+            .value(dec!(1.50)).unwrap()
+            .tip(dec!(0.001))
+            .data(vec![1, 2, 3]).unwrap()
+            .expiry(u64::MAX)
+            .sign(&KeyPair::generate(algorithm))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_canonical_encoding_roundtrip() {
+        for algorithm in [SignatureAlgorithm::ECDSA, SignatureAlgorithm::Hybrid] {
+            let tx = signed_tx(algorithm);
+            let encoded = tx.encode();
+            let decoded = Transaction::decode(&encoded).unwrap();
+
+            assert_eq!(decoded, tx);
+            assert_eq!(decoded.hash(), tx.hash());
+            assert_eq!(decoded.chain_id, 31339);
+            assert!(decoded.verify_signature().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_decimal_scale_does_not_change_hash() {
+        let tx = signed_tx(SignatureAlgorithm::ECDSA);
+        let mut rescaled = tx.clone();
+        rescaled.value = dec!(1.5000);
+
+        assert_eq!(rescaled.encode(), tx.encode());
+        assert_eq!(rescaled.signing_hash(), tx.signing_hash());
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        let encoded = signed_tx(SignatureAlgorithm::ECDSA).encode();
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(Transaction::decode(&trailing).is_err());
+        assert!(Transaction::decode(&encoded[..encoded.len() - 1]).is_err());
+
+        // Nested hybrid tags are rejected, not recursed into
+        let nested = vec![3u8; 1_000_000];
+        assert!(Reader::new(&nested).signature().is_err());
+        assert!(Reader::new(&nested).public_key().is_err());
+    }
+
+    #[test]
+    fn test_wei_overflow_is_an_error() {
+        let mut tx = signed_tx(SignatureAlgorithm::ECDSA);
+        assert_eq!(tx.value_wei().unwrap(), 1_500_000_000_000_000_000);
+        tx.value = Decimal::MAX;
+        tx.gas_price = Decimal::MAX;
+        assert!(tx.value_wei().is_err());
+        assert!(tx.gas_price_wei().is_err());
+
+        // Fits a Decimal but not a u64
+        tx.gas_price = dec!(100);
+        assert!(tx.gas_price_wei().is_err());
+    }
+
+    #[test]
+    fn test_tampered_transaction_fails_verification() {
+        let mut tx = signed_tx(SignatureAlgorithm::ECDSA);
+        tx.value = dec!(1000);
+        assert!(!tx.verify_signature().unwrap());
+
+        // Signature from a different key than the claimed sender
+        let mut tx = signed_tx(SignatureAlgorithm::ECDSA);
+        tx.from = Address::EVM([9u8; 20]);
+        assert!(!tx.verify_signature().unwrap());
+    }
 }
@@ -0,0 +1,1373 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::crypto::{KeyPair, PublicKeyData, Signature, SignatureAlgorithm};
+use crate::emission::EmissionSchedule;
+use crate::epoch::EpochManager;
+use crate::finality::{
+    timeout_payload, vote_payload, BaseBlock, BatchHeader, ConsensusResult, EquivocationEvidence, FinalityConfig,
+    FinalityGadget, FinalizedBlock, Pacemaker, Proposal, QuorumCertificate, TimeoutCertificate, TimeoutVote,
+    ValidatorInfo, ValidatorSet, Vote, VotePhase,
+};
+use crate::sequencer::BatchCommitment;
+use crate::state_db::{StateChanges, StateStore};
+
+/// Most batch commitments ordered into one base block
+const MAX_BATCHES_PER_BLOCK: usize = 256;
+
+/// Messages validators (and sequencers) exchange over the consensus topic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ConsensusMessage {
+    /// Batch commitment from a sequencer, waiting to be ordered
+    Batch(BatchCommitment),
+    /// Leader's base block for a view; `signature` covers `proposal.signing_payload()`
+    Proposal {
+        proposal: Proposal,
+        block: BaseBlock,
+        signature: Vec<u8>,
+    },
+    Vote(Vote),
+    /// Timeout vote, with the block of its high QC so the next leader can
+    /// propose it again
+    Timeout {
+        vote: TimeoutVote,
+        block: Option<BaseBlock>,
+    },
+    /// Proof that a validator double-signed, waiting to be included in a block
+    Evidence(EquivocationEvidence),
+    /// Base block announced by the leader that finalized it, so sequencers
+    /// learn which of their batches are final
+    Finalized(FinalizedBlock),
+}
+
+/// Entry of the validator set file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidatorSetEntry {
+    #[serde(default)]
+    pub name: String,
+    pub public_key: PublicKeyData,
+    pub stake: u64,
+}
+
+impl ValidatorSetEntry {
+    /// Validator ID: the address of its consensus key
+    pub fn id(&self) -> String {
+        self.public_key.to_address().to_string()
+    }
+}
+
+/// Load the validator set file (a JSON array of entries). Validators sign
+/// consensus messages with post-quantum keys only.
+pub fn load_validator_set(path: &Path) -> Result<ValidatorSet> {
+    let content = std::fs::read(path)?;
+    let entries: Vec<ValidatorSetEntry> = serde_json::from_slice(&content)
+        .map_err(|e| anyhow!("Invalid validator set {}: {}", path.display(), e))?;
+
+    let mut set = ValidatorSet::new();
+    for entry in entries {
+        if !matches!(entry.public_key, PublicKeyData::Dilithium { .. } | PublicKeyData::SPHINCSPlus { .. }) {
+            bail!("Validator {} must use a Dilithium or SPHINCS+ key", entry.name);
+        }
+        let id = entry.id();
+        set.add_validator(id, ValidatorInfo { public_key: entry.public_key, stake: entry.stake, is_active: true });
+    }
+    if set.validators.is_empty() {
+        bail!("Validator set {} is empty", path.display());
+    }
+    Ok(set)
+}
+
+/// Entry of the sequencer set file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencerSetEntry {
+    pub shard_id: u8,
+    pub public_key: PublicKeyData,
+}
+
+/// Load the sequencer set file (a JSON array of entries): the key each
+/// shard's batch commitments must be signed with
+pub fn load_sequencer_set(path: &Path) -> Result<HashMap<u8, PublicKeyData>> {
+    let content = std::fs::read(path)?;
+    let entries: Vec<SequencerSetEntry> = serde_json::from_slice(&content)
+        .map_err(|e| anyhow!("Invalid sequencer set {}: {}", path.display(), e))?;
+
+    let mut sequencers = HashMap::new();
+    for entry in entries {
+        if !matches!(entry.public_key, PublicKeyData::Dilithium { .. } | PublicKeyData::SPHINCSPlus { .. }) {
+            bail!("Sequencer of shard {} must use a Dilithium or SPHINCS+ key", entry.shard_id);
+        }
+        if sequencers.insert(entry.shard_id, entry.public_key).is_some() {
+            bail!("Shard {} has more than one sequencer", entry.shard_id);
+        }
+    }
+    Ok(sequencers)
+}
+
+/// Last finalized batch of a shard, which its next batch must extend
+#[derive(Debug, Clone, Default)]
+struct ShardTip {
+    next_batch: u64,
+    /// None until the shard's first batch anchors it
+    state_root: Option<String>,
+}
+
+impl ShardTip {
+    fn extended_by(&self, header: &BatchHeader) -> bool {
+        header.batch_sequence == self.next_batch
+            && self.state_root.as_ref().is_none_or(|root| *root == header.parent_state_root)
+    }
+
+    fn advance(&mut self, header: &BatchHeader) {
+        self.next_batch = header.batch_sequence + 1;
+        self.state_root = Some(header.state_root.clone());
+    }
+}
+
+/// Load a validator key file, creating a fresh key if it does not exist yet
+pub fn load_or_generate_key(path: &Path, algorithm: SignatureAlgorithm) -> Result<KeyPair> {
+    if path.exists() {
+        let keypair: KeyPair = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| anyhow!("Invalid key file {}: {}", path.display(), e))?;
+        return Ok(keypair);
+    }
+
+    let keypair = KeyPair::generate(algorithm);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_vec(&keypair)?)?;
+    info!("Generated {:?} key at {}", algorithm, path.display());
+    Ok(keypair)
+}
+
+/// Validator node: collects batch commitments, runs the prepare, pre-commit
+/// and commit rounds of the finality gadget with its peers and persists each
+/// finalized base block with its QC.
+///
+/// Batch commitments are checked before they are ordered: the sequencer's
+/// signature, the micro-blocks they commit to, and that each shard's batches
+/// follow on from its last finalized one in sequence and state root. A
+/// cross-shard receipt may be claimed by one batch only. Votes
+/// only go to blocks whose batches all passed these checks here. The leader
+/// that finalizes a block announces it so sequencers learn their batches
+/// are final.
+///
+/// A pacemaker times out views that do not finalize. Timeout votes form a
+/// timeout certificate that moves everyone to the next view; it carries the
+/// highest prepare QC so a block some validator may have committed is
+/// proposed again rather than replaced.
+///
+/// Conflicting votes become equivocation evidence. It is gossiped, included
+/// in the next base block, and once that block is final every validator
+/// slashes and jails the signer in its stake ledger and validator set.
+///
+/// Every message this validator sends is also handled locally, since gossip
+/// does not deliver a node's own messages back to it.
+pub struct Validator {
+    id: String,
+    keypair: KeyPair,
+    gadget: FinalityGadget,
+    /// Validator set of the current epoch and the stake ledger behind it
+    epochs: EpochManager,
+    state: Arc<dyn StateStore>,
+    outbound: mpsc::Sender<ConsensusMessage>,
+    pacemaker: Pacemaker,
+    /// When the current view times out
+    deadline: Instant,
+
+    /// Batch commitments not yet finalized, by (shard, batch sequence)
+    pending: BTreeMap<(u8, u64), BatchCommitment>,
+    /// Hashes of batches already in a finalized base block
+    included: HashSet<[u8; 32]>,
+    /// Cross-shard receipts claimed by finalized batches
+    claimed: HashSet<[u8; 32]>,
+    /// Last finalized batch of each shard
+    shards: HashMap<u8, ShardTip>,
+    /// Sequencer key of each shard; batches of other shards or signed by
+    /// other keys are dropped
+    sequencers: HashMap<u8, PublicKeyData>,
+
+    /// Supply accounting slashed stake is burned from
+    emission: EmissionSchedule,
+    /// Verified evidence waiting for a base block, by offence
+    pending_evidence: HashMap<(String, u64, VotePhase), EquivocationEvidence>,
+
+    /// Current view and the base block it extends
+    view: u64,
+    height: u64,
+    parent_hash: [u8; 32],
+    /// Commit QC of the last finalized block
+    high_qc: Option<QuorumCertificate>,
+    /// TC of the previous view, when it timed out
+    last_tc: Option<TimeoutCertificate>,
+    /// Last view this validator proposed in, so a rejected proposal is not resent
+    proposed: Option<u64>,
+
+    /// Highest prepare QC seen at this height
+    prepared: Option<QuorumCertificate>,
+    /// View and hash of the block a pre-commit quorum locked at this height
+    locked: Option<(u64, String)>,
+    /// Blocks seen at this height, by hash
+    blocks: HashMap<String, BaseBlock>,
+
+    /// Accepted proposals by view
+    proposals: HashMap<u64, (Proposal, BaseBlock)>,
+    /// Proposals for views this validator has not reached yet
+    future_proposals: HashMap<u64, ConsensusMessage>,
+    /// Commit QCs that formed before the proposal arrived here
+    early_commits: HashMap<u64, QuorumCertificate>,
+    voted: HashSet<(u64, VotePhase)>,
+
+    /// Messages to handle locally, including this validator's own
+    queue: VecDeque<ConsensusMessage>,
+}
+
+impl Validator {
+    /// Create a validator from the genesis epoch, resuming after the last
+    /// finalized block in `state`
+    pub async fn new(
+        keypair: KeyPair,
+        epochs: EpochManager,
+        sequencers: HashMap<u8, PublicKeyData>,
+        config: FinalityConfig,
+        state: Arc<dyn StateStore>,
+        outbound: mpsc::Sender<ConsensusMessage>,
+    ) -> Result<Self> {
+        let id = keypair.address().to_string();
+        if epochs.staking().validator(&id).is_none() {
+            bail!("Validator key {} is not in the validator set", id);
+        }
+        if sequencers.is_empty() {
+            bail!("No sequencer keys: every batch would be rejected");
+        }
+        if epochs.validators().validators.len() < config.min_validators {
+            warn!(
+                "Only {} validators (< {}): the set cannot tolerate a faulty validator",
+                epochs.validators().validators.len(),
+                config.min_validators
+            );
+        }
+
+        let pacemaker = Pacemaker::new(&config);
+        let gadget = FinalityGadget::new(config);
+        gadget.init_validators(epochs.validators().clone()).await;
+
+        let mut validator = Self {
+            id,
+            keypair,
+            gadget,
+            epochs,
+            state,
+            outbound,
+            deadline: Instant::now() + pacemaker.view_timeout(),
+            pacemaker,
+            pending: BTreeMap::new(),
+            included: HashSet::new(),
+            claimed: HashSet::new(),
+            shards: HashMap::new(),
+            sequencers,
+            emission: EmissionSchedule::default(),
+            pending_evidence: HashMap::new(),
+            view: 0,
+            height: 0,
+            parent_hash: [0u8; 32],
+            high_qc: None,
+            last_tc: None,
+            proposed: None,
+            prepared: None,
+            locked: None,
+            blocks: HashMap::new(),
+            proposals: HashMap::new(),
+            future_proposals: HashMap::new(),
+            early_commits: HashMap::new(),
+            voted: HashSet::new(),
+            queue: VecDeque::new(),
+        };
+
+        if let Some(last) = validator.state.latest_finalized()? {
+            for height in 0..=last.block.height {
+                if let Some(finalized) = validator.state.finalized_block(height)? {
+                    validator.apply_block(&finalized.block).await;
+                }
+            }
+            validator.height = last.block.height + 1;
+            validator.parent_hash = last.block.hash();
+            validator.view = last.qc.view + 1;
+            validator.gadget.set_finalized_qc(last.qc.clone()).await;
+            validator.high_qc = Some(last.qc);
+            validator.gadget.advance_to(validator.view).await;
+            info!("Resuming at base height {} (view {})", validator.height, validator.view);
+        }
+
+        Ok(validator)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Handle inbound messages and view timeouts; fails once the consensus
+    /// network stops delivering messages
+    pub async fn run(mut self, mut inbound: mpsc::Receiver<ConsensusMessage>) -> Result<()> {
+        info!("Validator {} running at view {}", self.id, self.view);
+        loop {
+            tokio::select! {
+                message = inbound.recv() => {
+                    let Some(message) = message else { bail!("Consensus network closed at view {}", self.view) };
+                    if let Err(e) = self.handle(message).await {
+                        warn!("Consensus message rejected: {}", e);
+                    }
+                }
+                _ = tokio::time::sleep_until(self.deadline) => {
+                    if let Err(e) = self.on_timeout().await {
+                        warn!("View {} timeout failed: {}", self.view, e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handle one message and everything it triggers locally; the error is
+    /// that of `message` itself
+    pub async fn handle(&mut self, message: ConsensusMessage) -> Result<()> {
+        let result = self.dispatch(message).await;
+        self.process_queue().await?;
+        result
+    }
+
+    /// The current view ran out of time: vote to leave it
+    pub async fn on_timeout(&mut self) -> Result<()> {
+        self.local_timeout()?;
+        self.process_queue().await
+    }
+
+    async fn process_queue(&mut self) -> Result<()> {
+        loop {
+            while let Some(message) = self.queue.pop_front() {
+                if let Err(e) = self.dispatch(message).await {
+                    warn!("Queued consensus message rejected: {}", e);
+                }
+            }
+            self.maybe_propose()?;
+            if self.queue.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn dispatch(&mut self, message: ConsensusMessage) -> Result<()> {
+        match message {
+            ConsensusMessage::Batch(batch) => self.on_batch(batch),
+            ConsensusMessage::Proposal { proposal, block, signature } => {
+                self.on_proposal(proposal, block, signature).await
+            }
+            ConsensusMessage::Vote(vote) => self.on_vote(vote).await,
+            ConsensusMessage::Timeout { vote, block } => self.on_timeout_vote(vote, block).await,
+            ConsensusMessage::Evidence(evidence) => self.on_evidence(evidence),
+            // For sequencers; validators finalize blocks themselves
+            ConsensusMessage::Finalized(_) => Ok(()),
+        }
+    }
+
+    /// Verify a sequencer's batch commitment and keep it until it is ordered
+    fn on_batch(&mut self, batch: BatchCommitment) -> Result<()> {
+        let hash = batch.hash();
+        let key = (batch.shard_id, batch.batch_sequence);
+        if self.included.contains(&hash) {
+            return Ok(());
+        }
+        if let Some(pending) = self.pending.get(&key) {
+            if pending.hash() == hash {
+                return Ok(());
+            }
+            bail!("Conflicting batch {} for shard {}", batch.batch_sequence, batch.shard_id);
+        }
+        let next_batch = self.shards.get(&batch.shard_id).map_or(0, |tip| tip.next_batch);
+        if batch.batch_sequence < next_batch {
+            bail!(
+                "Batch {} of shard {} is behind its finalized batches (next {})",
+                batch.batch_sequence,
+                batch.shard_id,
+                next_batch
+            );
+        }
+
+        let signer = batch.verify_signature()?;
+        if self.sequencers.get(&batch.shard_id) != Some(signer) {
+            bail!("Batch {} is not signed by the sequencer of shard {}", batch.batch_sequence, batch.shard_id);
+        }
+        batch.verify_contents()?;
+        let claims = batch.claims();
+        let mut unique = HashSet::new();
+        if claims.iter().any(|id| self.claimed.contains(id) || !unique.insert(*id)) {
+            bail!("Batch {} of shard {} claims a receipt twice", batch.batch_sequence, batch.shard_id);
+        }
+
+        debug!("Batch {} of shard {} pending", batch.batch_sequence, batch.shard_id);
+        self.pending.insert(key, batch);
+        Ok(())
+    }
+
+    /// Pending batches that extend their shard's finalized batches, in
+    /// (shard, sequence) order
+    fn orderable(&self) -> Vec<BatchHeader> {
+        let mut tips: HashMap<u8, ShardTip> = HashMap::new();
+        let mut claims = HashSet::new();
+        let mut headers = Vec::new();
+        for ((shard_id, _), batch) in &self.pending {
+            let tip = tips
+                .entry(*shard_id)
+                .or_insert_with(|| self.shards.get(shard_id).cloned().unwrap_or_default());
+            let header = BatchHeader::from(batch);
+            // A receipt claimed by two shards goes to the first one ordered
+            if tip.extended_by(&header) && header.claims.iter().all(|id| !claims.contains(id) && !self.claimed.contains(id)) {
+                claims.extend(header.claims.iter().copied());
+                tip.advance(&header);
+                headers.push(header);
+                if headers.len() == MAX_BATCHES_PER_BLOCK {
+                    break;
+                }
+            }
+        }
+        headers
+    }
+
+    /// A proposed block may only order batches verified here, each extending
+    /// its shard's chain of batches
+    fn check_batches(&self, block: &BaseBlock) -> Result<()> {
+        let mut tips: HashMap<u8, ShardTip> = HashMap::new();
+        let mut claims = HashSet::new();
+        for header in &block.batches {
+            if header.claims.iter().any(|id| self.claimed.contains(id) || !claims.insert(*id)) {
+                bail!("Base block {} orders a second claim of a cross-shard receipt", block.height);
+            }
+            let verified = self
+                .pending
+                .get(&(header.shard_id, header.batch_sequence))
+                .is_some_and(|batch| BatchHeader::from(batch) == *header);
+            if !verified {
+                bail!(
+                    "Base block {} orders batch {} of shard {}, which was not verified here",
+                    block.height,
+                    header.batch_sequence,
+                    header.shard_id
+                );
+            }
+            let tip = tips
+                .entry(header.shard_id)
+                .or_insert_with(|| self.shards.get(&header.shard_id).cloned().unwrap_or_default());
+            if !tip.extended_by(header) {
+                bail!(
+                    "Base block {} orders batch {} of shard {} out of sequence",
+                    block.height,
+                    header.batch_sequence,
+                    header.shard_id
+                );
+            }
+            tip.advance(header);
+        }
+        Ok(())
+    }
+
+    /// Propose a base block when leading this view. After a timeout the
+    /// block of the TC's high QC is proposed again if it is at this height.
+    fn maybe_propose(&mut self) -> Result<()> {
+        if self.proposed == Some(self.view)
+            || self.proposals.contains_key(&self.view)
+            || self.validators().leader(self.view) != Some(&self.id)
+        {
+            return Ok(());
+        }
+        let tc = self.last_tc.clone().filter(|tc| tc.view + 1 == self.view);
+        let justified = self.view == 0
+            || tc.is_some()
+            || self.high_qc.as_ref().is_some_and(|qc| qc.view + 1 == self.view);
+        if !justified {
+            return Ok(());
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let block = match tc.as_ref().and_then(|tc| tc.high_qc.as_ref()) {
+            Some(qc) => match self.blocks.get(&qc.block_hash) {
+                Some(block) => block.clone(),
+                // Cannot propose without the block; the view will time out
+                None => return Ok(()),
+            },
+            None => {
+                let batches = self.orderable();
+                if batches.is_empty() && self.pending_evidence.is_empty() {
+                    return Ok(());
+                }
+                BaseBlock {
+                    height: self.height,
+                    parent_hash: self.parent_hash,
+                    proposer: self.id.clone(),
+                    timestamp,
+                    batches,
+                    evidence: self.pending_evidence.values().cloned().collect(),
+                }
+            }
+        };
+        let proposal = Proposal {
+            view: self.view,
+            block_hash: hex::encode(block.hash()),
+            parent_hash: hex::encode(self.parent_hash),
+            proposer: self.id.clone(),
+            timestamp,
+            qc: self.high_qc.clone(),
+            tc,
+        };
+        let signature = self.sign(&proposal.signing_payload())?;
+
+        info!("Proposing base block {} with {} batches (view {})", block.height, block.batches.len(), self.view);
+        self.proposed = Some(self.view);
+        self.broadcast(ConsensusMessage::Proposal { proposal, block, signature });
+        Ok(())
+    }
+
+    async fn on_proposal(&mut self, proposal: Proposal, block: BaseBlock, signature: Vec<u8>) -> Result<()> {
+        if proposal.view < self.view || self.proposals.contains_key(&proposal.view) {
+            return Ok(());
+        }
+        let block_hash = hex::encode(block.hash());
+        let reproposal = proposal
+            .tc
+            .as_ref()
+            .and_then(|tc| tc.high_qc.as_ref())
+            .is_some_and(|qc| qc.block_hash == block_hash);
+        if proposal.block_hash != block_hash || (proposal.proposer != block.proposer && !reproposal) {
+            bail!("Proposal for view {} does not match its block", proposal.view);
+        }
+
+        if proposal.view > self.view {
+            // Checked now so a forged proposal cannot displace the leader's
+            self.validators().check_proposal(&proposal, &signature, self.high_qc.as_ref())?;
+            self.catch_up(&proposal, &block).await?;
+            if proposal.view > self.view {
+                self.future_proposals
+                    .insert(proposal.view, ConsensusMessage::Proposal { proposal, block, signature });
+                return Ok(());
+            }
+        }
+        if block.height != self.height || block.parent_hash != self.parent_hash {
+            bail!("Proposal for view {} does not extend the finalized chain", proposal.view);
+        }
+        self.check_batches(&block)?;
+        for evidence in &block.evidence {
+            self.validators().check_evidence(evidence)?;
+        }
+        if let Some((locked_view, locked_hash)) = &self.locked {
+            let justify_view = proposal.tc.as_ref().and_then(|tc| tc.high_qc.as_ref()).map(|qc| qc.view);
+            if *locked_hash != block_hash && justify_view.is_none_or(|v| v <= *locked_view) {
+                bail!("Proposal for view {} conflicts with the block locked in view {}", proposal.view, locked_view);
+            }
+        }
+
+        let view = proposal.view;
+        self.gadget.propose_block(proposal.clone(), &signature).await?;
+        self.blocks.insert(block_hash.clone(), block.clone());
+        self.proposals.insert(view, (proposal, block));
+        self.vote(VotePhase::Prepare, view, block_hash)?;
+
+        if let Some(qc) = self.early_commits.remove(&view) {
+            self.finalize(view, qc).await?;
+        }
+        Ok(())
+    }
+
+    /// Use the certificates of a verified proposal from a later view: a
+    /// commit QC for this view's block finalizes it, and a TC of this view
+    /// or later moves to the view after it
+    async fn catch_up(&mut self, proposal: &Proposal, block: &BaseBlock) -> Result<()> {
+        if let Some(qc) = &proposal.qc {
+            if qc.view == self.view && self.accepted(qc.view, &qc.block_hash) {
+                self.finalize(qc.view, qc.clone()).await?;
+            }
+        }
+        if let Some(tc) = &proposal.tc {
+            if tc.view >= self.view && block.height == self.height {
+                self.enter_view(tc.view + 1, Some(tc.clone())).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_vote(&mut self, vote: Vote) -> Result<()> {
+        if vote.view() < self.view {
+            return Ok(());
+        }
+
+        match self.gadget.process_vote(vote).await? {
+            ConsensusResult::PrepareQuorum { view, block_hash, qc } => {
+                if self.accepted(view, &block_hash) {
+                    if self.prepared.as_ref().is_none_or(|prepared| prepared.view < qc.view) {
+                        self.prepared = Some(qc);
+                    }
+                    self.vote(VotePhase::PreCommit, view, block_hash)?;
+                }
+            }
+            ConsensusResult::PreCommitQuorum { view, block_hash } => {
+                if self.accepted(view, &block_hash) {
+                    self.locked = Some((view, block_hash.clone()));
+                    self.vote(VotePhase::Commit, view, block_hash)?;
+                }
+            }
+            ConsensusResult::Finalized { view, block_hash, qc } => {
+                if view < self.view {
+                    return Ok(());
+                }
+                if self.accepted(view, &block_hash) {
+                    self.finalize(view, qc).await?;
+                } else {
+                    self.early_commits.insert(view, qc);
+                }
+            }
+            ConsensusResult::Equivocation(evidence) => self.on_evidence(evidence)?,
+            ConsensusResult::Pending => {}
+        }
+        Ok(())
+    }
+
+    /// Verify double-sign evidence and gossip it until a block includes it
+    fn on_evidence(&mut self, evidence: EquivocationEvidence) -> Result<()> {
+        let offence = evidence.offence();
+        if self.pending_evidence.contains_key(&offence) {
+            return Ok(());
+        }
+        self.validators().check_evidence(&evidence)?;
+
+        warn!(
+            "Validator {} double-signed {} votes in view {}",
+            offence.0,
+            offence.2.as_str(),
+            offence.1
+        );
+        self.pending_evidence.insert(offence, evidence.clone());
+        self.broadcast(ConsensusMessage::Evidence(evidence));
+        Ok(())
+    }
+
+    /// Advance the shards past a finalized block's batches and apply their
+    /// staking transactions, slash the signers of its evidence, pay the block
+    /// reward, and open the next epoch after the epoch's last block
+    async fn apply_block(&mut self, block: &BaseBlock) {
+        for header in &block.batches {
+            self.included.insert(header.hash);
+            self.claimed.extend(header.claims.iter().copied());
+            self.shards.entry(header.shard_id).or_default().advance(header);
+            for action in &header.staking {
+                if let Err(e) = self.epochs.apply(action) {
+                    debug!("Staking transaction of {} not applied: {}", action.staker, e);
+                }
+            }
+        }
+        // Batches the finalized ones superseded can never be ordered
+        let shards = &self.shards;
+        self.pending.retain(|(shard_id, sequence), _| shards.get(shard_id).is_none_or(|tip| *sequence >= tip.next_batch));
+
+        for evidence in &block.evidence {
+            self.pending_evidence.remove(&evidence.offence());
+            let outcome = match self.epochs.slash_double_sign(evidence, &mut self.emission) {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    debug!("Evidence against {} not applied: {}", evidence.offender(), e);
+                    continue;
+                }
+            };
+            warn!(
+                "Slashed {} for double signing: {} burned{}",
+                outcome.validator,
+                outcome.total_slashed,
+                if outcome.jailed { ", jailed" } else { "" }
+            );
+        }
+
+        // Sequencers share the reward of the blocks their batches made it into
+        let mut sequencers: Vec<String> = block
+            .batches
+            .iter()
+            .filter_map(|header| self.sequencers.get(&header.shard_id))
+            .map(|key| key.to_address().to_string())
+            .collect();
+        sequencers.sort();
+        sequencers.dedup();
+        self.epochs.distribute_block_reward(block.height, &sequencers);
+
+        let new_epoch = self.epochs.on_finalized(block.height);
+        if block.evidence.is_empty() && !new_epoch {
+            return;
+        }
+
+        // Evidence against validators that left the set can no longer be verified
+        let validators = self.epochs.validators();
+        self.pending_evidence
+            .retain(|(id, _, _), _| validators.validators.get(id).is_some_and(|info| info.is_active));
+        self.gadget.init_validators(validators.clone()).await;
+    }
+
+    fn validators(&self) -> &ValidatorSet {
+        self.epochs.validators()
+    }
+
+    /// Stop voting in the current view and ask peers to move on
+    fn local_timeout(&mut self) -> Result<()> {
+        // Nothing to finalize: wait for work instead of churning views
+        if self.orderable().is_empty()
+            && self.pending_evidence.is_empty()
+            && !self.proposals.contains_key(&self.view)
+            && self.prepared.is_none()
+        {
+            self.deadline = Instant::now() + self.pacemaker.view_timeout();
+            return Ok(());
+        }
+
+        warn!("View {} timed out (leader {:?})", self.view, self.validators().leader(self.view));
+        self.pacemaker.on_timeout();
+        self.deadline = Instant::now() + self.pacemaker.view_timeout();
+        if !self.is_active() {
+            return Ok(());
+        }
+        for phase in [VotePhase::Prepare, VotePhase::PreCommit, VotePhase::Commit] {
+            self.voted.insert((self.view, phase));
+        }
+
+        let high_qc = self.prepared.clone();
+        let block = high_qc.as_ref().and_then(|qc| self.blocks.get(&qc.block_hash).cloned());
+        let vote = TimeoutVote {
+            view: self.view,
+            validator_id: self.id.clone(),
+            high_qc,
+            signature: self.sign(&timeout_payload(self.view))?,
+        };
+        self.broadcast(ConsensusMessage::Timeout { vote, block });
+        Ok(())
+    }
+
+    async fn on_timeout_vote(&mut self, vote: TimeoutVote, block: Option<BaseBlock>) -> Result<()> {
+        if vote.view < self.view {
+            return Ok(());
+        }
+        let qc_hash = vote.high_qc.as_ref().map(|qc| qc.block_hash.clone());
+
+        let tc = self.gadget.process_timeout(vote).await?;
+
+        // Keep the block so this validator can propose it again as leader
+        if let (Some(block), Some(qc_hash)) = (block, qc_hash) {
+            if block.height == self.height && hex::encode(block.hash()) == qc_hash {
+                self.blocks.insert(qc_hash, block);
+            }
+        }
+        if let Some(tc) = tc.filter(|tc| tc.view >= self.view) {
+            info!("Timeout certificate for view {} ({} stake)", tc.view, tc.aggregated_stake);
+            self.enter_view(tc.view + 1, Some(tc)).await;
+        }
+        Ok(())
+    }
+
+    /// Whether this validator accepted `block_hash` as the proposal of `view`
+    fn accepted(&self, view: u64, block_hash: &str) -> bool {
+        view == self.view
+            && self
+                .proposals
+                .get(&view)
+                .is_some_and(|(proposal, _)| proposal.block_hash == block_hash)
+    }
+
+    /// Jailed or unknown validators follow the chain without voting
+    fn is_active(&self) -> bool {
+        self.validators().validators.get(&self.id).is_some_and(|info| info.is_active)
+    }
+
+    /// Sign and send this validator's vote for a phase, at most once
+    fn vote(&mut self, phase: VotePhase, view: u64, block_hash: String) -> Result<()> {
+        if !self.is_active() || !self.voted.insert((view, phase)) {
+            return Ok(());
+        }
+        let validator_id = self.id.clone();
+        let signature = self.sign(&vote_payload(phase, view, &block_hash))?;
+        let vote = match phase {
+            VotePhase::Prepare => Vote::Prepare { view, block_hash, validator_id, signature },
+            VotePhase::PreCommit => Vote::PreCommit { view, block_hash, validator_id, signature },
+            VotePhase::Commit => Vote::Commit { view, block_hash, validator_id, signature },
+        };
+        self.broadcast(ConsensusMessage::Vote(vote));
+        Ok(())
+    }
+
+    /// Persist the finalized block and move on to the next view
+    async fn finalize(&mut self, view: u64, qc: QuorumCertificate) -> Result<()> {
+        let Some((proposal, block)) = self.proposals.get(&view).cloned() else {
+            return Ok(());
+        };
+
+        let finalized = FinalizedBlock { block: block.clone(), qc: qc.clone() };
+        let mut changes = StateChanges::new();
+        changes.put_finalized_block(finalized.clone());
+        let state = self.state.clone();
+        tokio::task::spawn_blocking(move || state.commit(changes)).await??;
+
+        self.apply_block(&block).await;
+        info!(
+            "Finalized base block {} ({} batches, view {}, {} stake)",
+            block.height,
+            block.batches.len(),
+            view,
+            qc.aggregated_stake
+        );
+
+        self.height = block.height + 1;
+        self.parent_hash = block.hash();
+        self.gadget.set_finalized_qc(qc.clone()).await;
+        self.high_qc = Some(qc);
+        self.prepared = None;
+        self.locked = None;
+        self.blocks.clear();
+        self.pacemaker.on_progress();
+        if proposal.proposer == self.id && !block.batches.is_empty() {
+            self.broadcast(ConsensusMessage::Finalized(finalized));
+        }
+        self.enter_view(view + 1, None).await;
+        Ok(())
+    }
+
+    /// Move to `view`, restarting its timer and replaying a proposal for it
+    async fn enter_view(&mut self, view: u64, tc: Option<TimeoutCertificate>) {
+        if view <= self.view {
+            return;
+        }
+        self.view = view;
+        if tc.is_some() {
+            self.last_tc = tc;
+        }
+        self.deadline = Instant::now() + self.pacemaker.view_timeout();
+        self.gadget.advance_to(view).await;
+
+        self.proposals.retain(|v, _| *v >= view);
+        self.early_commits.retain(|v, _| *v >= view);
+        self.voted.retain(|(v, _)| *v >= view);
+        if let Some(message) = self.future_proposals.remove(&view) {
+            self.queue.push_back(message);
+        }
+        self.future_proposals.retain(|v, _| *v > view);
+    }
+
+    /// Send to peers and handle locally
+    fn broadcast(&mut self, message: ConsensusMessage) {
+        if self.outbound.try_send(message.clone()).is_err() {
+            warn!("Consensus outbound queue full, message dropped");
+        }
+        self.queue.push_back(message);
+    }
+
+    /// Raw post-quantum signature bytes over `payload`
+    fn sign(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        match self.keypair.sign(payload)? {
+            Signature::Dilithium { data } | Signature::SPHINCSPlus { data } => Ok(data),
+            other => bail!("Validator keys must be post-quantum, got {:?}", other.algorithm()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoch::EpochConfig;
+    use rust_decimal::Decimal;
+    use crate::sequencer::{MicroBlock, TxReceipt};
+    use crate::staking::{StakingOp, STAKING_ACCOUNT};
+    use crate::state_db::InMemoryStateStore;
+    use crate::transaction::{Transaction, TransactionBuilder};
+
+    const TEST_SHARDS: u8 = 4;
+
+    /// Registered sequencer key of a test shard
+    fn sequencer_key(shard_id: u8) -> &'static KeyPair {
+        static KEYS: std::sync::OnceLock<Vec<KeyPair>> = std::sync::OnceLock::new();
+        &KEYS.get_or_init(|| {
+            (0..TEST_SHARDS).map(|_| KeyPair::generate(SignatureAlgorithm::Dilithium)).collect()
+        })[shard_id as usize]
+    }
+
+    fn sequencer_set() -> HashMap<u8, PublicKeyData> {
+        (0..TEST_SHARDS).map(|shard| (shard, sequencer_key(shard).public_key.clone())).collect()
+    }
+
+    fn test_batch(shard_id: u8, batch_sequence: u64) -> BatchCommitment {
+        signed_batch(shard_id, batch_sequence, sequencer_key(shard_id))
+    }
+
+    fn signed_batch(shard_id: u8, batch_sequence: u64, sequencer: &KeyPair) -> BatchCommitment {
+        let mut batch = BatchCommitment {
+            shard_id,
+            batch_sequence,
+            micro_blocks: vec![MicroBlock {
+                shard_id,
+                sequence: batch_sequence,
+                parent_hash: [0u8; 32],
+                timestamp: 1_700_000_000,
+                transactions: vec![],
+                receipts: vec![],
+                state_root: "00".repeat(32),
+                sent_receipts: vec![],
+                claimed_receipts: vec![],
+            }],
+            state_root: "00".repeat(32),
+            transactions_count: 0,
+            timestamp: 1_700_000_000,
+            parent_state_root: "00".repeat(32),
+            receipts_root: crate::state_trie::EMPTY_ROOT,
+            sequencer: None,
+            signature: vec![],
+        };
+        batch.sign(sequencer).unwrap();
+        batch
+    }
+
+    /// Batch whose micro-block includes `transactions`, all successful
+    fn batch_with(shard_id: u8, batch_sequence: u64, transactions: Vec<Transaction>) -> BatchCommitment {
+        let mut batch = test_batch(shard_id, batch_sequence);
+        let block = &mut batch.micro_blocks[0];
+        block.receipts = transactions
+            .iter()
+            .map(|tx| TxReceipt {
+                tx_hash: tx.hash(),
+                success: true,
+                gas_used: 21_000,
+                cumulative_gas_used: 21_000,
+                contract_address: None,
+                logs: vec![],
+                fee: 0,
+                error: None,
+            })
+            .collect();
+        batch.transactions_count = transactions.len();
+        block.transactions = transactions;
+        batch.sign(sequencer_key(shard_id)).unwrap();
+        batch
+    }
+
+    /// Validators wired through an in-memory network that delivers every
+    /// outbound message to all other validators. Crashed validators neither
+    /// send nor receive, and messages matching `drop` are lost.
+    struct TestNetwork {
+        validators: Vec<Validator>,
+        outbound: Vec<mpsc::Receiver<ConsensusMessage>>,
+        stores: Vec<Arc<InMemoryStateStore>>,
+        /// Validator set the network started with
+        set: ValidatorSet,
+        epoch_config: EpochConfig,
+        crashed: HashSet<usize>,
+        drop: Option<fn(&ConsensusMessage) -> bool>,
+    }
+
+    impl TestNetwork {
+        async fn new(count: usize) -> Self {
+            Self::with_epochs(count, EpochConfig::default()).await
+        }
+
+        async fn with_epochs(count: usize, epoch_config: EpochConfig) -> Self {
+            let keys: Vec<KeyPair> = (0..count).map(|_| KeyPair::generate(SignatureAlgorithm::Dilithium)).collect();
+            let mut set = ValidatorSet::new();
+            for key in &keys {
+                set.add_validator(
+                    key.address().to_string(),
+                    ValidatorInfo { public_key: key.public_key.clone(), stake: 100, is_active: true },
+                );
+            }
+
+            let mut network = Self {
+                validators: vec![],
+                outbound: vec![],
+                stores: vec![],
+                set,
+                epoch_config,
+                crashed: HashSet::new(),
+                drop: None,
+            };
+            for key in keys {
+                let store = Arc::new(InMemoryStateStore::new());
+                let (tx, rx) = mpsc::channel(1024);
+                let validator = Validator::new(key, network.genesis(), sequencer_set(), FinalityConfig::default(), store.clone(), tx)
+                    .await
+                    .unwrap();
+                network.validators.push(validator);
+                network.outbound.push(rx);
+                network.stores.push(store);
+            }
+            network
+        }
+
+        /// Deliver a message to every live validator, then relay until quiet
+        async fn deliver_all(&mut self, message: ConsensusMessage) {
+            for (i, validator) in self.validators.iter_mut().enumerate() {
+                if !self.crashed.contains(&i) {
+                    validator.handle(message.clone()).await.unwrap();
+                }
+            }
+            self.relay().await;
+        }
+
+        /// Let the current view of every live validator time out
+        async fn timeout_all(&mut self) {
+            for (i, validator) in self.validators.iter_mut().enumerate() {
+                if !self.crashed.contains(&i) {
+                    validator.on_timeout().await.unwrap();
+                }
+            }
+            self.relay().await;
+        }
+
+        fn genesis(&self) -> EpochManager {
+            EpochManager::new(self.epoch_config.clone(), &self.set).unwrap()
+        }
+
+        fn index_of(&self, id: &str) -> usize {
+            self.validators.iter().position(|v| v.id == id).unwrap()
+        }
+
+        async fn relay(&mut self) {
+            loop {
+                let mut in_flight = Vec::new();
+                for (from, rx) in self.outbound.iter_mut().enumerate() {
+                    while let Ok(message) = rx.try_recv() {
+                        in_flight.push((from, message));
+                    }
+                }
+                if in_flight.is_empty() {
+                    return;
+                }
+                for (from, message) in in_flight {
+                    if self.crashed.contains(&from) || self.drop.is_some_and(|drop| drop(&message)) {
+                        continue;
+                    }
+                    for (to, validator) in self.validators.iter_mut().enumerate() {
+                        if to != from && !self.crashed.contains(&to) {
+                            validator.handle(message.clone()).await.unwrap();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validators_finalize_batches() {
+        let mut network = TestNetwork::new(4).await;
+
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 0))).await;
+        network.deliver_all(ConsensusMessage::Batch(test_batch(1, 0))).await;
+
+        // The first batch was finalized on its own; the second in the next view
+        for store in &network.stores {
+            let first = store.finalized_block(0).unwrap().unwrap();
+            assert_eq!(first.block.batches.len(), 1);
+            assert_eq!(first.qc.block_hash, hex::encode(first.block.hash()));
+            assert!(first.qc.aggregated_stake > 400 * 2 / 3);
+
+            let second = store.finalized_block(1).unwrap().unwrap();
+            assert_eq!(second.block.parent_hash, first.block.hash());
+            assert_eq!(second.block.batches[0].shard_id, 1);
+        }
+        for validator in &network.validators {
+            assert_eq!(validator.view, 2);
+            assert!(validator.pending.is_empty());
+        }
+
+        // Each finalized block paid its reward to the validators and the
+        // sequencer whose batch it holds
+        let staking = network.validators[0].epochs.staking();
+        let sequencer = sequencer_key(0).address().to_string();
+        assert!(staking.accrued_rewards(&network.validators[1].id) > Decimal::ZERO);
+        assert!(staking.accrued_rewards(&sequencer) > Decimal::ZERO);
+        assert_eq!(
+            staking.accrued_rewards(&sequencer),
+            staking.accrued_rewards(&sequencer_key(1).address().to_string())
+        );
+        assert_eq!(
+            network.stores[0].latest_finalized().unwrap().unwrap().block,
+            network.stores[3].latest_finalized().unwrap().unwrap().block
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_batches_rejected() {
+        let mut network = TestNetwork::new(4).await;
+        let sequencer = sequencer_key(0);
+        let validator = &mut network.validators[0];
+
+        let mut unsigned = signed_batch(0, 0, sequencer);
+        unsigned.sequencer = None;
+        assert!(validator.handle(ConsensusMessage::Batch(unsigned)).await.is_err());
+
+        let mut tampered = signed_batch(0, 0, sequencer);
+        tampered.state_root = "11".repeat(32);
+        assert!(validator.handle(ConsensusMessage::Batch(tampered.clone())).await.is_err());
+
+        // Re-signed, the batch still does not commit to its last micro-block
+        tampered.sign(sequencer).unwrap();
+        assert!(validator.handle(ConsensusMessage::Batch(tampered)).await.is_err());
+
+        // Only the registered sequencer may submit for its shard
+        validator.handle(ConsensusMessage::Batch(signed_batch(0, 0, sequencer))).await.unwrap();
+        assert!(validator.handle(ConsensusMessage::Batch(signed_batch(1, 0, sequencer))).await.is_err());
+        assert_eq!(validator.pending.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_from_unknown_key_rejected() {
+        let mut network = TestNetwork::new(4).await;
+        let unknown = KeyPair::generate(SignatureAlgorithm::Dilithium);
+
+        // An unregistered key cannot claim a free (shard, sequence) slot,
+        // nor sign for a shard without a sequencer
+        let error = network.validators[0].handle(ConsensusMessage::Batch(signed_batch(0, 0, &unknown))).await.unwrap_err();
+        assert!(error.to_string().contains("not signed by the sequencer"));
+        assert!(network.validators[0].handle(ConsensusMessage::Batch(signed_batch(TEST_SHARDS, 0, &unknown))).await.is_err());
+        assert!(network.validators[0].pending.is_empty());
+
+        // The registered sequencer still gets the slot finalized
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 0))).await;
+        let first = network.stores[0].finalized_block(0).unwrap().unwrap();
+        assert_eq!(first.block.batches[0].hash, test_batch(0, 0).hash());
+
+        // Validators need a sequencer set
+        let keypair = network.validators[0].keypair.clone();
+        let (tx, _rx) = mpsc::channel(16);
+        let store = Arc::new(InMemoryStateStore::new());
+        assert!(Validator::new(keypair, network.genesis(), HashMap::new(), FinalityConfig::default(), store, tx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batches_ordered_in_sequence() {
+        let mut network = TestNetwork::new(4).await;
+
+        // A gap in a shard's sequence holds its later batches back
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 1))).await;
+        assert!(network.stores[0].latest_finalized().unwrap().is_none());
+
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 0))).await;
+        let first = network.stores[0].finalized_block(0).unwrap().unwrap();
+        let sequences: Vec<u64> = first.block.batches.iter().map(|b| b.batch_sequence).collect();
+        assert_eq!(sequences, vec![0, 1]);
+
+        // A batch that does not start from the shard's last state root is never ordered
+        let mut forked = test_batch(0, 2);
+        forked.parent_state_root = "22".repeat(32);
+        forked.sign(sequencer_key(0)).unwrap();
+        network.deliver_all(ConsensusMessage::Batch(forked)).await;
+        assert!(network.stores[0].finalized_block(1).unwrap().is_none());
+
+        // Finalized batches cannot be replaced
+        let mut replaced = test_batch(0, 1);
+        replaced.timestamp += 1;
+        replaced.sign(sequencer_key(0)).unwrap();
+        assert!(network.validators[0].handle(ConsensusMessage::Batch(replaced)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validator_resumes_from_store() {
+        let mut network = TestNetwork::new(4).await;
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 0))).await;
+
+        let keypair = network.validators[0].keypair.clone();
+        let (tx, _rx) = mpsc::channel(16);
+        let mut restarted = Validator::new(keypair, network.genesis(), sequencer_set(), FinalityConfig::default(), network.stores[0].clone(), tx)
+            .await
+            .unwrap();
+
+        assert_eq!(restarted.view, 1);
+        assert_eq!(restarted.height, 1);
+
+        // Already finalized batches are not proposed again
+        restarted.handle(ConsensusMessage::Batch(test_batch(0, 0))).await.unwrap();
+        assert!(restarted.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_crashed_leader_is_replaced() {
+        let mut network = TestNetwork::new(4).await;
+        let leader = network.validators[0].validators().leader(0).unwrap().clone();
+        let crashed = network.index_of(&leader);
+        network.crashed.insert(crashed);
+
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 0))).await;
+        let live = (0..4).find(|i| *i != crashed).unwrap();
+        assert!(network.stores[live].latest_finalized().unwrap().is_none());
+
+        // Views time out until one led by a live validator finalizes the batch
+        for _ in 0..20 {
+            if network.stores[live].latest_finalized().unwrap().is_some() {
+                break;
+            }
+            network.timeout_all().await;
+        }
+
+        let finalized = network.stores[live].latest_finalized().unwrap().expect("no block finalized");
+        assert_eq!(finalized.block.height, 0);
+        assert_ne!(finalized.block.proposer, leader);
+        assert!(finalized.qc.view > 0);
+        for i in (0..4).filter(|i| *i != crashed) {
+            let block = network.stores[i].latest_finalized().unwrap().unwrap().block;
+            assert_eq!(block, finalized.block);
+            assert_eq!(network.validators[i].view, finalized.qc.view + 1);
+            assert_eq!(network.validators[i].pacemaker.view_timeout(), Pacemaker::new(&FinalityConfig::default()).view_timeout());
+        }
+        assert!(network.stores[crashed].latest_finalized().unwrap().is_none());
+
+        // The chain keeps going without the crashed validator
+        network.deliver_all(ConsensusMessage::Batch(test_batch(1, 0))).await;
+        for _ in 0..20 {
+            if network.stores[live].finalized_block(1).unwrap().is_some() {
+                break;
+            }
+            network.timeout_all().await;
+        }
+        let second = network.stores[live].finalized_block(1).unwrap().expect("chain halted");
+        assert_eq!(second.block.parent_hash, finalized.block.hash());
+    }
+
+    #[tokio::test]
+    async fn test_locked_block_is_proposed_again() {
+        let mut network = TestNetwork::new(4).await;
+
+        // Commit votes are lost: everyone locks the block but none finalizes it
+        network.drop = Some(|message| matches!(message, ConsensusMessage::Vote(Vote::Commit { .. })));
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 0))).await;
+        let locked = network.validators[0].locked.clone().expect("not locked");
+        for (validator, store) in network.validators.iter().zip(&network.stores) {
+            assert_eq!(validator.locked.as_ref(), Some(&locked));
+            assert!(store.latest_finalized().unwrap().is_none());
+        }
+
+        // A later leader must propose the locked block, even with new batches pending
+        network.drop = None;
+        network.deliver_all(ConsensusMessage::Batch(test_batch(1, 0))).await;
+        network.timeout_all().await;
+
+        for store in &network.stores {
+            let finalized = store.finalized_block(0).unwrap().expect("locked block not finalized");
+            assert_eq!(hex::encode(finalized.block.hash()), locked.1);
+            assert_eq!(finalized.qc.view, 1);
+
+            // The new batch follows in the next view
+            let next = store.finalized_block(1).unwrap().unwrap();
+            assert_eq!(next.block.batches[0].shard_id, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forged_timeouts_rejected() {
+        let mut network = TestNetwork::new(4).await;
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 0))).await;
+
+        let outsider = KeyPair::generate(SignatureAlgorithm::Dilithium);
+        let forged = TimeoutVote {
+            view: 1,
+            validator_id: network.validators[1].id.clone(),
+            high_qc: None,
+            signature: match outsider.sign(&timeout_payload(1)).unwrap() {
+                Signature::Dilithium { data } => data,
+                _ => unreachable!(),
+            },
+        };
+        let result = network.validators[0].handle(ConsensusMessage::Timeout { vote: forged, block: None }).await;
+        assert!(result.is_err());
+        assert_eq!(network.validators[0].view, 1);
+    }
+
+    #[tokio::test]
+    async fn test_double_signer_is_slashed() {
+        let mut network = TestNetwork::new(4).await;
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 0))).await;
+
+        // Validator 2 signs two different blocks for view 1
+        let offender = network.validators[2].id.clone();
+        for block_hash in ["aa", "bb"] {
+            let payload = vote_payload(VotePhase::Prepare, 1, block_hash);
+            let signature = network.validators[2].sign(&payload).unwrap();
+            let vote = Vote::Prepare { view: 1, block_hash: block_hash.to_string(), validator_id: offender.clone(), signature };
+            network.deliver_all(ConsensusMessage::Vote(vote)).await;
+        }
+
+        // The leader of view 1 proposes the evidence at once; finalizing it slashes
+        for (validator, store) in network.validators.iter().zip(&network.stores) {
+            let block = store.finalized_block(1).unwrap().unwrap().block;
+            assert_eq!(block.evidence.len(), 1);
+            assert_eq!(block.evidence[0].offender(), offender);
+
+            let info = &validator.validators().validators[&offender];
+            assert!(!info.is_active);
+            assert_eq!(info.stake, 95);
+            assert_eq!(validator.validators().total_stake, 300);
+            assert!(validator.epochs.staking().validator(&offender).unwrap().jailed);
+            assert_eq!(validator.emission.total_burned, Decimal::from(5));
+            assert!(validator.pending_evidence.is_empty());
+        }
+
+        // The slashed validator no longer counts, yet the rest finalize
+        network.deliver_all(ConsensusMessage::Batch(test_batch(1, 0))).await;
+        for _ in 0..20 {
+            if network.stores[0].finalized_block(2).unwrap().is_some() {
+                break;
+            }
+            network.timeout_all().await;
+        }
+        let third = network.stores[0].finalized_block(2).unwrap().expect("chain halted");
+        assert_ne!(third.block.proposer, offender);
+
+        // A restarted validator replays the slash from its store
+        let keypair = network.validators[0].keypair.clone();
+        let (tx, _rx) = mpsc::channel(16);
+        let restarted = Validator::new(
+            keypair,
+            network.genesis(),
+            sequencer_set(),
+            FinalityConfig::default(),
+            network.stores[0].clone(),
+            tx,
+        )
+        .await
+        .unwrap();
+        assert!(!restarted.validators().validators[&offender].is_active);
+        assert_eq!(restarted.emission.total_burned, Decimal::from(5));
+    }
+
+    #[tokio::test]
+    async fn test_epoch_boundary_changes_voting_stake() {
+        let epoch_config = EpochConfig { epoch_length: 2, ..EpochConfig::default() };
+        let mut network = TestNetwork::with_epochs(4, epoch_config).await;
+        let delegated = network.validators[0].id.clone();
+        let delegate = StakingOp::Delegate { validator: delegated.parse().unwrap() };
+        let delegation = TransactionBuilder::new()
+            .to(STAKING_ACCOUNT)
+            .value(Decimal::from(200))
+            .and_then(|builder| builder.data(delegate.encode()))
+            .and_then(|builder| builder.gas_limit(100_000))
+            .and_then(|builder| builder.sign(&KeyPair::generate(SignatureAlgorithm::ECDSA)))
+            .unwrap();
+
+        // The delegation counts once its batch is final, from the next epoch
+        network.deliver_all(ConsensusMessage::Batch(batch_with(0, 0, vec![delegation]))).await;
+        assert_eq!(network.validators[1].validators().validators[&delegated].stake, 100);
+
+        // Finalizing the epoch's last block installs the new weights everywhere
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 1))).await;
+        for validator in &network.validators {
+            assert_eq!(validator.epochs.current().number, 1);
+            assert_eq!(validator.validators().validators[&delegated].stake, 300);
+            assert_eq!(validator.validators().total_stake, 600);
+        }
+
+        // The first block of the new epoch needs a quorum of the new stake
+        network.deliver_all(ConsensusMessage::Batch(test_batch(0, 2))).await;
+        let block = network.stores[0].finalized_block(2).unwrap().expect("chain halted");
+        assert!(block.qc.aggregated_stake >= 401);
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use ionova_sdk::{dec, IonovaWallet, RpcClient};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Connect to Ionova
+    let client = RpcClient::new("http://localhost:27000".to_string());
+
+    // Create quantum-safe wallet
+    let wallet = IonovaWallet::create_dilithium()?;
+    println!("Wallet address: {}", wallet.address());
+
+    // Send transaction
+    let tx_hash = wallet
+        .send_transaction(
+            &client,
+            "0x742d35Cc6634C0532925a3b844Bc9e7595f0bEb0".parse()?,
+            dec!(100), // 100 IONX
+        )
+        .await?;
+
+    println!("Transaction sent: {}", tx_hash);
+    Ok(())
+}
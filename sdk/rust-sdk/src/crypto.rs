@@ -1,13 +0,0 @@
-// Symlink to node/src/crypto.rs or copy the implementation
-//! Post-Quantum Signature Support for Ionova SDK
-//!
-//! This module reuses the implementation from the node's crypto.rs
-//! To use: Copy ../../node/src/crypto.rs here or create a workspace
-
-pub use crate::node_crypto::*;
-
-// For now, placeholder that will be replaced with actual node crypto
-mod node_crypto {
-    // This would be the actual crypto.rs from node
-    // Users should copy it or use a workspace setup
-}
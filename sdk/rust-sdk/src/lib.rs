@@ -1,11 +1,15 @@
-// Re-export from node's crypto module
+// Re-export from node's crypto and transaction modules (single source of truth)
+#[path = "../../../node/src/crypto.rs"]
 mod crypto;
+#[path = "../../../node/src/transaction.rs"]
 mod transaction;
+#[path = "../../../node/src/eth_tx.rs"]
+mod eth_tx;
 mod wallet;
 mod rpc_client;
 
-pub use crypto::{SignatureAlgorithm, Signature, PublicKeyData, Address};
-pub use transaction::Transaction;
+pub use crypto::{SignatureAlgorithm, Signature, PublicKeyData, Address, KeyPair};
+pub use transaction::{Transaction, TransactionBuilder};
 pub use wallet::IonovaWallet;
 pub use rpc_client::RpcClient;
 
@@ -10,7 +10,7 @@ use rust_decimal::Decimal;
 pub struct RpcClient {
     client: Client,
     url: String,
-    chain_id: u64,
+    shard_id: u8,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,26 +38,48 @@ impl RpcClient {
         Self {
             client: Client::new(),
             url,
-            chain_id: 31337, // Default testnet
+            shard_id: 0,
         }
     }
 
+    /// Target a specific shard (its sequencer's RPC endpoint)
+    pub fn with_shard(mut self, shard_id: u8) -> Self {
+        self.shard_id = shard_id;
+        self
+    }
+
+    pub fn shard_id(&self) -> u8 {
+        self.shard_id
+    }
+
+    /// Chain ID of the target shard, as its eth_chainId reports it
+    pub async fn chain_id(&self) -> Result<u64> {
+        let response = self.call("eth_chainId", vec![]).await?;
+        u64::try_from(parse_quantity(&response)?)
+            .map_err(|_| anyhow::anyhow!("Chain ID out of range"))
+    }
+
     pub async fn get_balance(&self, address: &Address) -> Result<Decimal> {
         let response = self
             .call("eth_getBalance", vec![
-                json!(format!("{:?}", address)),
+                json!(address.to_string()),
                 json!("latest")
             ])
             .await?;
         
-        // Parse balance from hex
-        Ok(Decimal::new(0, 0)) // Placeholder
+        // Balance is returned in wei
+        let wei = parse_quantity(&response)?;
+        let wei = i128::try_from(wei).map_err(|_| anyhow::anyhow!("Balance out of range"))?;
+        Decimal::try_from_i128_with_scale(wei, 18)
+            .map(|balance| balance.normalize())
+            .map_err(|_| anyhow::anyhow!("Balance out of range"))
     }
 
+    /// Submit a signed transaction in its canonical binary encoding
     pub async fn send_transaction(&self, tx: &Transaction) -> Result<String> {
-        let tx_json = serde_json::to_value(tx)?;
+        let raw_tx = format!("0x{}", hex::encode(tx.encode()));
         let response = self
-            .call("eth_sendRawTransaction", vec![tx_json])
+            .call("eth_sendRawTransaction", vec![json!(raw_tx)])
             .await?;
         
         Ok(response.as_str().unwrap_or("").to_string())
@@ -66,12 +88,13 @@ impl RpcClient {
     pub async fn get_transaction_count(&self, address: &Address) -> Result<u64> {
         let response = self
             .call("eth_getTransactionCount", vec![
-                json!(format!("{:?}", address)),
+                json!(address.to_string()),
                 json!("latest")
             ])
             .await?;
         
-        Ok(0) // Placeholder
+        u64::try_from(parse_quantity(&response)?)
+            .map_err(|_| anyhow::anyhow!("Nonce out of range"))
     }
 
     async fn call(&self, method: &str, params: Vec<Value>) -> Result<Value> {
@@ -98,3 +121,12 @@ impl RpcClient {
         response.result.ok_or_else(|| anyhow::anyhow!("No result in RPC response"))
     }
 }
+
+/// Parse a 0x-prefixed hex quantity from an RPC result
+fn parse_quantity(value: &Value) -> Result<u128> {
+    let raw = value
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Expected hex quantity, got {}", value))?;
+    let digits = raw.strip_prefix("0x").unwrap_or(raw);
+    u128::from_str_radix(digits, 16).map_err(|e| anyhow::anyhow!("Invalid quantity {}: {}", raw, e))
+}
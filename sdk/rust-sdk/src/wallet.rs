@@ -0,0 +1,68 @@
+use anyhow::Result;
+use rust_decimal::Decimal;
+
+use crate::{Address, KeyPair, RpcClient, SignatureAlgorithm, Transaction, TransactionBuilder};
+
+/// Wallet holding a single signing key
+pub struct IonovaWallet {
+    keypair: KeyPair,
+}
+
+impl IonovaWallet {
+    /// ECDSA (traditional)
+    pub fn create_ecdsa() -> Result<Self> {
+        Ok(Self::from_keypair(KeyPair::generate(SignatureAlgorithm::ECDSA)))
+    }
+
+    /// Dilithium (recommended for quantum safety)
+    pub fn create_dilithium() -> Result<Self> {
+        Ok(Self::from_keypair(KeyPair::generate(SignatureAlgorithm::Dilithium)))
+    }
+
+    /// SPHINCS+ (maximum security)
+    pub fn create_sphincs() -> Result<Self> {
+        Ok(Self::from_keypair(KeyPair::generate(SignatureAlgorithm::SPHINCSPlus)))
+    }
+
+    /// Hybrid (ECDSA + Dilithium)
+    pub fn create_hybrid() -> Result<Self> {
+        Ok(Self::from_keypair(KeyPair::generate(SignatureAlgorithm::Hybrid)))
+    }
+
+    pub fn from_keypair(keypair: KeyPair) -> Self {
+        Self { keypair }
+    }
+
+    pub fn address(&self) -> Address {
+        self.keypair.address()
+    }
+
+    pub fn keypair(&self) -> &KeyPair {
+        &self.keypair
+    }
+
+    /// Sign a transaction built from `builder` (sets the sender)
+    pub fn sign(&self, builder: TransactionBuilder) -> Result<Transaction> {
+        builder.sign(&self.keypair)
+    }
+
+    /// Transfer IONX, using the next nonce reported by the node
+    pub async fn send_transaction(
+        &self,
+        client: &RpcClient,
+        to: Address,
+        amount: Decimal,
+    ) -> Result<String> {
+        let nonce = client.get_transaction_count(&self.address()).await?;
+        let chain_id = client.chain_id().await?;
+        let tx = self.sign(
+            TransactionBuilder::new()
+                .shard(client.shard_id())
+                .chain_id(chain_id)
+                .nonce(nonce)
+                .to(to)
+                .value(amount)?,
+        )?;
+        client.send_transaction(&tx).await
+    }
+}